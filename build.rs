@@ -0,0 +1,46 @@
+// Copyright © 2019-2020 George Burton
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::env;
+
+/// The versions of nvtt which add APIs that are gated with a `cfg` attribute. A cfg
+/// named `nvtt_<major>_<minor>` is set for every version which is not newer than the
+/// nvtt being built.
+///
+/// * `nvtt_2_1`: `nvtt::Surface` and `nvtt::Context`, which back `Surface::load` and
+///   `Context::compress`.
+const KNOWN_VERSIONS: &[(u32, u32)] = &[(2, 1)];
+
+fn main() {
+    println!("cargo:rerun-if-env-changed=DEP_NVTT_VERSION");
+
+    let version = env::var("DEP_NVTT_VERSION")
+        .ok()
+        .and_then(|version| version.parse::<u32>().ok())
+        .unwrap_or(0);
+
+    for &(major, minor) in KNOWN_VERSIONS {
+        let cfg = format!("nvtt_{}_{}", major, minor);
+        println!("cargo:rustc-check-cfg=cfg({})", cfg);
+        if version >= major * 10000 + minor * 100 {
+            println!("cargo:rustc-cfg={}", cfg);
+        }
+    }
+}
@@ -0,0 +1,145 @@
+#ifndef NVTT_RS_H
+#define NVTT_RS_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include "nvtt/nvtt_wrapper.h"
+
+// The result of a function in the C api.
+typedef enum NvttRsStatus {
+  // The function succeeded.
+  NVTT_RS_STATUS_OK = 0,
+  // An argument was null, was not valid utf-8, or was not a valid enum value.
+  NVTT_RS_STATUS_INVALID_ARGUMENT = 1,
+  // A glob pattern was invalid.
+  NVTT_RS_STATUS_INVALID_PATTERN = 2,
+  // A texture could not be compressed.
+  NVTT_RS_STATUS_COMPRESSION_FAILED = 3,
+  // The library panicked. Any batch passed to the function should be freed.
+  NVTT_RS_STATUS_PANICKED = 4,
+  // Two inputs of a batch would be compressed into the same output file.
+  NVTT_RS_STATUS_OUTPUT_COLLISION = 5,
+} NvttRsStatus;
+
+// A set of compression settings which can be applied to a batch.
+typedef enum NvttRsPresetKind {
+  // `Format::Bc1` with `Quality::Normal`. These are the defaults of a [`Batch`].
+  //
+  // [`Batch`]: ../struct.Batch.html
+  NVTT_RS_PRESET_KIND_DEFAULT = 0,
+  // `Format::Bc7` srgb data, for color textures.
+  NVTT_RS_PRESET_KIND_ALBEDO = 1,
+  // `Format::Bc5` linear data, for tangent space normal maps.
+  NVTT_RS_PRESET_KIND_NORMAL = 2,
+  // `Format::Bc7` linear data, for textures such as roughness or occlusion maps.
+  NVTT_RS_PRESET_KIND_MASK = 3,
+} NvttRsPresetKind;
+
+// An opaque handle to a batch of files which are compressed with the same settings.
+typedef struct NvttRsBatch NvttRsBatch;
+
+// The settings used to compress textures. The `format`, `quality` and `container`
+// are the raw values of the nvtt `NvttFormat`, `NvttQuality` and `NvttContainer`
+// enums.
+typedef struct NvttRsPreset {
+  // The output format of the textures.
+  NvttFormat format;
+  // The compression quality.
+  NvttQuality quality;
+  // The output container. This is ignored unless `use_container` is set.
+  NvttContainer container;
+  // Whether the `container` is used instead of the default for the format.
+  bool use_container;
+  // Whether mipmaps are generated.
+  bool mipmaps;
+  // Whether the textures are marked as containing srgb data.
+  bool srgb;
+} NvttRsPreset;
+
+// The result of compressing a single file, which is passed to the callback of
+// [`nvtt_rs_batch_run`]. The strings are only valid during the callback.
+//
+// [`nvtt_rs_batch_run`]: fn.nvtt_rs_batch_run.html
+typedef struct NvttRsBatchResult {
+  // The path of the input image.
+  const char *input;
+  // The path of the compressed texture.
+  const char *output;
+  // `NvttRsStatus::Ok` if the file was compressed, or
+  // `NvttRsStatus::CompressionFailed` otherwise.
+  NvttRsStatus status;
+  // A description of the error if the file could not be compressed, or null.
+  const char *error_message;
+  // Whether the texture was taken from the cache.
+  bool cached;
+  // The time taken to compress the file, in seconds.
+  double elapsed_seconds;
+} NvttRsBatchResult;
+
+// A callback which receives the result of each file in a batch.
+typedef void (*NvttRsResultCallback)(const NvttRsBatchResult *result, void *user_data);
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Get a description of the last error which occurred on this thread, or null if no
+// error has occurred. The string is valid until the next call into this library on
+// the same thread.
+const char *nvtt_rs_last_error_message(void);
+
+// Initialize `preset` with the settings of the given `kind`, which is one of the
+// values of `NvttRsPresetKind`. Any other value returns
+// `NvttRsStatus::InvalidArgument`.
+NvttRsStatus nvtt_rs_preset_init(NvttRsPreset *preset, uint32_t kind);
+
+// Create a new batch which compresses files with the settings in `preset`, and
+// write it into `batch`. The batch must be freed with `nvtt_rs_batch_free`.
+NvttRsStatus nvtt_rs_batch_new(const NvttRsPreset *preset, NvttRsBatch **batch);
+
+// Free a batch created with `nvtt_rs_batch_new`. Passing null does nothing.
+void nvtt_rs_batch_free(NvttRsBatch *batch);
+
+// Add a single image file to the batch.
+NvttRsStatus nvtt_rs_batch_add_file(NvttRsBatch *batch, const char *path);
+
+// Add every file matching the glob `pattern` to the batch. The pattern is expanded
+// when the batch is run.
+NvttRsStatus nvtt_rs_batch_add_glob(NvttRsBatch *batch, const char *pattern);
+
+// Set the directory which the compressed textures are written to.
+NvttRsStatus nvtt_rs_batch_set_output_dir(NvttRsBatch *batch, const char *dir);
+
+// Enable caching of compressed textures in `dir`.
+NvttRsStatus nvtt_rs_batch_set_cache_dir(NvttRsBatch *batch, const char *dir);
+
+// Set the number of threads used to compress the batch. If `threads` is `0`, then
+// one thread is used for each available cpu.
+NvttRsStatus nvtt_rs_batch_set_threads(NvttRsBatch *batch, size_t threads);
+
+// Limit the estimated memory used by the files which are compressed at the same
+// time to `bytes`. If `bytes` is `0`, then the memory is not limited.
+NvttRsStatus nvtt_rs_batch_set_memory_budget(NvttRsBatch *batch, size_t bytes);
+
+// Compress every file in the batch. The `callback` is called with the result of
+// each file once every file has been compressed, and may be null.
+//
+// Returns `NvttRsStatus::CompressionFailed` if any file could not be compressed, and
+// `NvttRsStatus::OutputCollision` if two files would be compressed into the same
+// output, in which case no file is compressed.
+NvttRsStatus nvtt_rs_batch_run(NvttRsBatch *batch,
+                               NvttRsResultCallback callback,
+                               void *user_data);
+
+// Compress the image at `input` into the texture at `output`, using the settings in
+// `preset`.
+NvttRsStatus nvtt_rs_compress_file(const char *input,
+                                   const char *output,
+                                   const NvttRsPreset *preset);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif // NVTT_RS_H
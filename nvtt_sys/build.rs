@@ -20,9 +20,17 @@
 
 #![allow(unused)]
 
-use bindgen;
 use cfg_if::cfg_if;
-use std::{env, error::Error, path::PathBuf};
+use std::{
+    env,
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// The version of nvtt which the pregenerated bindings were generated from.
+const NVTT_BINDINGS_VERSION: &str = "2.1.2";
 
 #[inline(always)]
 fn e(
@@ -31,90 +39,688 @@ fn e(
     val.into()
 }
 
+/// The target which nvtt is being built for. A build script is compiled for the
+/// host, so the target must be read from the environment instead of using `cfg!`.
+struct Target {
+    triple: String,
+    host: String,
+    arch: String,
+    os: String,
+    env: String,
+}
+
+impl Target {
+    fn from_env() -> Result<Self, Box<dyn Error + Send + Sync + 'static>> {
+        Ok(Target {
+            triple: env::var("TARGET")?,
+            host: env::var("HOST")?,
+            arch: env::var("CARGO_CFG_TARGET_ARCH")?,
+            os: env::var("CARGO_CFG_TARGET_OS")?,
+            env: env::var("CARGO_CFG_TARGET_ENV").unwrap_or_default(),
+        })
+    }
+
+    #[inline]
+    fn is_cross(&self) -> bool {
+        self.triple != self.host
+    }
+
+    #[inline]
+    fn is_msvc(&self) -> bool {
+        self.env == "msvc"
+    }
+
+    #[inline]
+    fn is_apple(&self) -> bool {
+        self.os == "macos" || self.os == "ios"
+    }
+
+    /// Get an environment variable which may be specialised for the target, in the same
+    /// way as the `cc` and `cmake` crates.
+    fn var(&self, name: &str) -> Option<String> {
+        let kind = if self.is_cross() { "TARGET" } else { "HOST" };
+        let names = [
+            format!("{}_{}", name, self.triple),
+            format!("{}_{}", name, self.triple.replace('-', "_")),
+            format!("{}_{}", kind, name),
+            name.to_string(),
+        ];
+
+        names.iter().find_map(|name| {
+            println!("cargo:rerun-if-env-changed={}", name);
+            env::var(name).ok()
+        })
+    }
+}
+
 cfg_if! {
     if #[cfg(target_os = "windows")] {
-        use semver::Version;
-        use std::process::Command;
-        use vswhere::{Config, FourPointVersion, InstallInfo};
+        use vswhere::{Config, InstallInfo};
 
-        fn build_nvtt() -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
-            // let min_version = FourPoint
-            let vs_path = Config::new()
+        /// Configure the cmake build to use the newest supported installation of
+        /// Visual Studio, and the same C runtime as rustc.
+        fn configure_msvc(
+            config: &mut cmake::Config,
+        ) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+            // nvtt 2.1 supports Visual Studio 2013 (12.0) to Visual Studio 2022 (17.0).
+            let vs_major = Config::new()
                 .run_default_path()?
                 .iter()
-                .filter(|&info| {
-                    let maj = info.installation_version().major();
-                    maj >= 2013 && maj <= 2017
-                })
-                .nth(0)
-                .map(InstallInfo::installation_path)
-                .map(PathBuf::from)
-                .ok_or_else(|| e("Could not find Visual Studio installation info"))?;
+                .map(InstallInfo::installation_version)
+                .map(|version| version.major())
+                .filter(|&major| major >= 12 && major <= 17)
+                .max()
+                .ok_or_else(|| e("Could not find a supported Visual Studio installation"))?;
+
+            let generator = match vs_major {
+                12 => "Visual Studio 12 2013",
+                14 => "Visual Studio 14 2015",
+                15 => "Visual Studio 15 2017",
+                16 => "Visual Studio 16 2019",
+                _ => "Visual Studio 17 2022",
+            };
+            let is_64_bit = env::var("CARGO_CFG_TARGET_POINTER_WIDTH")? == "64";
+            if vs_major >= 16 {
+                // The generators for Visual Studio 2019 and newer do not accept the
+                // `Win64` suffix, and select the architecture with a platform instead.
+                let platform = if is_64_bit { "x64" } else { "Win32" };
+                config
+                    .generator(generator)
+                    .define("CMAKE_GENERATOR_PLATFORM", platform);
+            } else if is_64_bit {
+                config.generator(format!("{} Win64", generator));
+            } else {
+                config.generator(generator);
+            }
+
+            // Rust always links to the release C runtime, so the debug configuration
+            // of nvtt would link to a mismatched runtime.
+            let crt_static = env::var("CARGO_CFG_TARGET_FEATURE")
+                .map(|features| features.split(',').any(|f| f == "crt-static"))
+                .unwrap_or(false);
+            config.profile("Release").static_crt(crt_static);
 
             Ok(())
         }
     } else {
-        use cmake;
-        fn build_nvtt() -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
-            let dst = cmake::build("./nvidia-texture-tools");
-
-            println!("cargo:rustc-link-search={}", dst.join("lib").join("static").display());
-
-            // @TODO(burtonageo): Is this necessary???
-            let src_dir = dst.join("build").join("src");
-            let extern_dir = dst.join("build").join("extern");
-
-            println!("cargo:rustc-link-search={}", src_dir.join("bc7").display());
-            println!("cargo:rustc-link-search={}", src_dir.join("bc6h").display());
-            println!("cargo:rustc-link-search={}", extern_dir.join("rg_etc1_v104").display());
-
-            let libs = &[
-                "nvcore",
-                "nvimage",
-                "nvmath",
-                "nvthread",
-                "nvtt",
-
-                // @TODO(burtonageo): Is this necessary???
-                "bc7",
-                "bc6h",
-                "rg_etc1",
-            ];
-
-            for lib in &libs[..] {
-                println!("cargo:rustc-link-lib=static={}", lib);
-            }
+        fn configure_msvc(
+            _config: &mut cmake::Config,
+        ) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+            Ok(())
+        }
+    }
+}
+
+/// Find the root directory of the CUDA toolkit.
+fn find_cuda_toolkit() -> Option<PathBuf> {
+    println!("cargo:rerun-if-env-changed=CUDA_PATH");
+    println!("cargo:rerun-if-env-changed=CUDA_HOME");
+
+    env::var_os("CUDA_PATH")
+        .or_else(|| env::var_os("CUDA_HOME"))
+        .map(PathBuf::from)
+        .or_else(|| Some(PathBuf::from("/usr/local/cuda")))
+        .filter(|path| path.join("include").join("cuda_runtime.h").is_file())
+}
+
+/// Configure whether nvtt is built with CUDA support. nvtt searches for CUDA by
+/// itself, so it is explicitly disabled unless the `cuda` feature is enabled, and
+/// on targets which CUDA does not support.
+fn configure_cuda(
+    config: &mut cmake::Config,
+    target: &Target,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    let cuda_target = target.arch == "x86_64" && (target.os == "linux" || target.os == "windows");
+    if cfg!(feature = "cuda") && !cuda_target {
+        println!(
+            "cargo:warning=CUDA is not supported on {}, so nvtt will be built without it",
+            target.triple
+        );
+    }
+
+    if !cfg!(feature = "cuda") || !cuda_target {
+        config.define("CMAKE_DISABLE_FIND_PACKAGE_CUDA", "TRUE");
+        return Ok(());
+    }
+
+    let cuda_root = find_cuda_toolkit().ok_or_else(|| {
+        e(
+            "The `cuda` feature is enabled, but the CUDA toolkit could not be found. \
+           Set the CUDA_PATH environment variable to the root of the toolkit.",
+        )
+    })?;
+    config.define("CUDA_TOOLKIT_ROOT_DIR", &cuda_root);
+
+    let lib_dir = if target.is_msvc() {
+        cuda_root.join("lib").join("x64")
+    } else {
+        cuda_root.join("lib64")
+    };
+    println!("cargo:rustc-link-search=native={}", lib_dir.display());
+    println!("cargo:rustc-link-lib=dylib=cudart");
+
+    Ok(())
+}
+
+/// Parse a version string of the form `major.minor.patch` into the integer
+/// encoding used by the `NVTT_VERSION` macro.
+fn encode_version(version: &str) -> Option<u32> {
+    let mut parts = version.split('.').map(str::parse::<u32>);
+    let major = parts.next()?.ok()?;
+    let minor = parts.next().unwrap_or(Ok(0)).ok()?;
+    let patch = parts.next().unwrap_or(Ok(0)).ok()?;
+    Some(major * 10000 + minor * 100 + patch)
+}
+
+/// Detect the version of nvtt which is being built by reading the `NVTT_VERSION`
+/// macro from its public header. If the header cannot be read, the version of the
+/// pregenerated bindings is assumed.
+fn detect_nvtt_version(include_dir: &Path) -> u32 {
+    let header = include_dir.join("nvtt").join("nvtt.h");
+
+    fs::read_to_string(&header)
+        .ok()
+        .and_then(|contents| {
+            contents.lines().find_map(|line| {
+                let mut tokens = line.split_whitespace();
+                match (tokens.next(), tokens.next(), tokens.next()) {
+                    (Some("#define"), Some("NVTT_VERSION"), Some(version)) => version.parse().ok(),
+                    _ => None,
+                }
+            })
+        })
+        .unwrap_or_else(|| {
+            println!(
+                "cargo:warning=Could not read NVTT_VERSION from {}, assuming nvtt {}",
+                header.display(),
+                NVTT_BINDINGS_VERSION
+            );
+            encode_version(NVTT_BINDINGS_VERSION).unwrap_or(0)
+        })
+}
+
+/// Configure cmake to build nvtt for a target which is different to the host. If a
+/// toolchain file is given with the `CMAKE_TOOLCHAIN_FILE` environment variable, then
+/// it is used by cmake instead.
+fn configure_cross(
+    config: &mut cmake::Config,
+    target: &Target,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    if !target.is_cross() || target.var("CMAKE_TOOLCHAIN_FILE").is_some() {
+        return Ok(());
+    }
+
+    match &target.os[..] {
+        "android" => {
+            let ndk = target
+                .var("ANDROID_NDK_HOME")
+                .or_else(|| target.var("ANDROID_NDK_ROOT"))
+                .map(PathBuf::from)
+                .ok_or_else(|| {
+                    e(
+                        "Could not find the Android NDK. Set the ANDROID_NDK_HOME environment \
+                       variable to the root of the NDK.",
+                    )
+                })?;
 
-            // Need to link to the c++ stdlib
-            if cfg!(target_os = "macos") {
-                println!("cargo:rustc-link-lib=dylib=c++");
+            let abi = match &target.arch[..] {
+                "aarch64" => "arm64-v8a",
+                "arm" => "armeabi-v7a",
+                "x86" => "x86",
+                "x86_64" => "x86_64",
+                arch => return Err(e(format!("Unsupported Android architecture {}", arch))),
+            };
+
+            let stl = if cfg!(feature = "static-stdlib") {
+                "c++_static"
             } else {
-                println!("cargo:rustc-link-lib=dylib=stdc++");
+                "c++_shared"
+            };
+
+            config
+                .define(
+                    "CMAKE_TOOLCHAIN_FILE",
+                    ndk.join("build")
+                        .join("cmake")
+                        .join("android.toolchain.cmake"),
+                )
+                .define("ANDROID_ABI", abi)
+                .define(
+                    "ANDROID_PLATFORM",
+                    target
+                        .var("ANDROID_PLATFORM")
+                        .unwrap_or_else(|| "android-21".to_string()),
+                )
+                .define("ANDROID_STL", stl);
+        }
+        "ios" => {
+            let (arch, sysroot) = match &target.arch[..] {
+                "aarch64" if !target.triple.ends_with("-sim") => ("arm64", "iphoneos"),
+                "aarch64" => ("arm64", "iphonesimulator"),
+                "x86_64" => ("x86_64", "iphonesimulator"),
+                arch => return Err(e(format!("Unsupported iOS architecture {}", arch))),
+            };
+
+            config
+                .define("CMAKE_SYSTEM_NAME", "iOS")
+                .define("CMAKE_OSX_ARCHITECTURES", arch)
+                .define("CMAKE_OSX_SYSROOT", sysroot);
+        }
+        os => {
+            let system_name = match os {
+                "linux" => "Linux",
+                "macos" => "Darwin",
+                "windows" => "Windows",
+                "freebsd" => "FreeBSD",
+                _ => return Ok(()),
+            };
+
+            // nvtt selects its SIMD code paths from the processor name.
+            config
+                .define("CMAKE_SYSTEM_NAME", system_name)
+                .define("CMAKE_SYSTEM_PROCESSOR", &target.arch);
+        }
+    }
+
+    Ok(())
+}
+
+/// Ask the c++ compiler for the location of one of its runtime libraries.
+fn find_compiler_lib(
+    target: &Target,
+    lib: &str,
+) -> Result<PathBuf, Box<dyn Error + Send + Sync + 'static>> {
+    let compiler = target.var("CXX").unwrap_or_else(|| "c++".to_string());
+
+    let output = Command::new(&compiler)
+        .arg(format!("-print-file-name={}", lib))
+        .output()
+        .map_err(|err| {
+            e(format!(
+                "Could not run the c++ compiler {}: {}",
+                compiler, err
+            ))
+        })?;
+
+    // If the compiler cannot find the library, it prints the file name unchanged.
+    let path = PathBuf::from(String::from_utf8(output.stdout)?.trim());
+    if path.is_absolute() && path.is_file() {
+        Ok(path)
+    } else {
+        Err(e(format!(
+            "Could not find {} to link the c++ stdlib statically",
+            lib
+        )))
+    }
+}
+
+/// Link to the c++ stdlib which nvtt was compiled with. MSVC links to its c++ stdlib
+/// automatically.
+fn link_cpp_stdlib(target: &Target) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    if target.is_msvc() {
+        return Ok(());
+    }
+
+    // The NDK linker already knows where its c++ stdlib is, so it is linked by name.
+    if target.os == "android" {
+        if cfg!(feature = "static-stdlib") {
+            println!("cargo:rustc-link-lib=c++_static");
+            println!("cargo:rustc-link-lib=c++abi");
+        } else {
+            println!("cargo:rustc-link-lib=c++_shared");
+        }
+        return Ok(());
+    }
+
+    let libs: &[&str] = if target.is_apple() {
+        &["c++", "c++abi"]
+    } else {
+        &["stdc++"]
+    };
+
+    for lib in libs {
+        if cfg!(feature = "static-stdlib") {
+            let path = find_compiler_lib(target, &format!("lib{}.a", lib))?;
+            if let Some(dir) = path.parent() {
+                println!("cargo:rustc-link-search=native={}", dir.display());
+            }
+            println!("cargo:rustc-link-lib=static={}", lib);
+        } else if *lib != "c++abi" {
+            println!("cargo:rustc-link-lib=dylib={}", lib);
+        }
+    }
+
+    Ok(())
+}
+
+/// Compile the C shims over the `Surface` and `Context` C++ API. This must be called
+/// before nvtt is linked, so that the shims are placed before nvtt on the linker
+/// command line.
+fn build_surface_wrapper(target: &Target, include_dir: &Path) {
+    let mut build = cc::Build::new();
+    build
+        .cpp(true)
+        .file("./surface_wrapper.cpp")
+        .include(include_dir)
+        // The c++ stdlib is linked by `link_cpp_stdlib`.
+        .cpp_link_stdlib(None)
+        .warnings(false);
+
+    if cfg!(feature = "dylib") {
+        build.define("NVTT_SHARED", "1");
+    }
+    if target.is_msvc() {
+        build.flag("/EHsc");
+    }
+
+    build.compile("nvtt_surface_wrapper");
+}
+
+/// An nvtt which has been found with vcpkg.
+struct VcpkgNvtt {
+    include_dir: PathBuf,
+    cargo_metadata: Vec<String>,
+}
+
+cfg_if! {
+    if #[cfg(all(target_os = "windows", feature = "vcpkg"))] {
+        /// Find nvtt and its dependencies with vcpkg. The link metadata is printed
+        /// later, so that the shims can be linked before nvtt.
+        fn find_vcpkg(
+            target: &Target,
+        ) -> Result<Option<VcpkgNvtt>, Box<dyn Error + Send + Sync + 'static>> {
+            if !target.is_msvc() {
+                return Ok(None);
             }
 
+            let library = vcpkg::Config::new()
+                .cargo_metadata(false)
+                .find_package("nvtt")
+                .map_err(|err| e(format!("Could not find nvtt with vcpkg: {}", err)))?;
+
+            let include_dir = library
+                .include_paths
+                .first()
+                .cloned()
+                .ok_or_else(|| e("vcpkg did not provide an include directory for nvtt"))?;
+
+            Ok(Some(VcpkgNvtt {
+                include_dir,
+                cargo_metadata: library.cargo_metadata,
+            }))
+        }
+    } else {
+        fn find_vcpkg(
+            _target: &Target,
+        ) -> Result<Option<VcpkgNvtt>, Box<dyn Error + Send + Sync + 'static>> {
+            if cfg!(feature = "vcpkg") {
+                println!("cargo:warning=vcpkg is only supported when building on Windows");
+            }
+            Ok(None)
+        }
+    }
+}
+
+/// The location of the nvtt libraries which are linked.
+enum NvttSource {
+    /// Build the vendored nvtt source tree with cmake.
+    Vendored,
+    /// Link to the prebuilt libraries in `NVTT_LIB_DIR`.
+    Prebuilt(PathBuf),
+    /// Link to the nvtt installed by vcpkg.
+    Vcpkg(VcpkgNvtt),
+}
+
+impl NvttSource {
+    fn find(target: &Target) -> Result<Self, Box<dyn Error + Send + Sync + 'static>> {
+        if let Some(lib_dir) = target.var("NVTT_LIB_DIR") {
+            return Ok(NvttSource::Prebuilt(PathBuf::from(lib_dir)));
+        }
+
+        Ok(match find_vcpkg(target)? {
+            Some(nvtt) => NvttSource::Vcpkg(nvtt),
+            None => NvttSource::Vendored,
+        })
+    }
+
+    /// Get the directory containing the nvtt headers.
+    fn include_dir(
+        &self,
+        target: &Target,
+    ) -> Result<PathBuf, Box<dyn Error + Send + Sync + 'static>> {
+        match *self {
+            NvttSource::Vendored => Ok(PathBuf::from("./nvidia-texture-tools/src")),
+            NvttSource::Prebuilt(ref lib_dir) => prebuilt_include_dir(lib_dir, target),
+            NvttSource::Vcpkg(ref nvtt) => Ok(nvtt.include_dir.clone()),
+        }
+    }
+
+    fn link(&self, target: &Target) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+        match *self {
+            NvttSource::Vendored => build_nvtt(target),
+            NvttSource::Prebuilt(ref lib_dir) => link_prebuilt(lib_dir, target),
+            NvttSource::Vcpkg(ref nvtt) => {
+                for line in &nvtt.cargo_metadata {
+                    println!("{}", line);
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Get the directory containing the headers of the prebuilt nvtt in `lib_dir`.
+fn prebuilt_include_dir(
+    lib_dir: &Path,
+    target: &Target,
+) -> Result<PathBuf, Box<dyn Error + Send + Sync + 'static>> {
+    let include_dir = target
+        .var("NVTT_INCLUDE_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| lib_dir.join("..").join("include"));
+
+    if include_dir.join("nvtt").join("nvtt_wrapper.h").is_file() {
+        Ok(include_dir)
+    } else {
+        Err(e(format!(
+            "Could not find nvtt/nvtt_wrapper.h in {}. Set the NVTT_INCLUDE_DIR \
+             environment variable to the include directory of the prebuilt nvtt.",
+            include_dir.display()
+        )))
+    }
+}
+
+/// Link to a prebuilt nvtt in `lib_dir` instead of building the vendored source tree.
+/// The helper libraries are only linked if they are present, as they are merged
+/// into nvtt by some builds.
+fn link_prebuilt(
+    lib_dir: &Path,
+    target: &Target,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    let (kind, file_names): (_, &[(&str, &str)]) = if cfg!(feature = "dylib") {
+        ("dylib", &[("lib", ".so"), ("lib", ".dylib"), ("", ".lib")])
+    } else {
+        ("static", &[("lib", ".a"), ("", ".lib")])
+    };
+    let exists = |lib: &str| {
+        file_names
+            .iter()
+            .any(|(prefix, ext)| lib_dir.join(format!("{}{}{}", prefix, lib, ext)).is_file())
+    };
+
+    if !exists("nvtt") {
+        return Err(e(format!(
+            "Could not find the nvtt library in NVTT_LIB_DIR ({})",
+            lib_dir.display()
+        )));
+    }
+
+    println!("cargo:rustc-link-search=native={}", lib_dir.display());
+    let libs = &[
+        "nvtt", "bc7", "bc6h", "rg_etc1", "nvimage", "nvthread", "nvmath", "nvcore",
+    ];
+    for lib in libs.iter().filter(|lib| exists(lib)) {
+        println!("cargo:rustc-link-lib={}={}", kind, lib);
+    }
+
+    // The shared libraries link to the c++ stdlib themselves.
+    if cfg!(feature = "dylib") {
+        Ok(())
+    } else {
+        link_cpp_stdlib(target)
+    }
+}
+
+fn build_nvtt(target: &Target) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    let is_msvc = target.is_msvc();
+
+    let mut config = cmake::Config::new("./nvidia-texture-tools");
+    if cfg!(feature = "dylib") {
+        config.define("NVTT_SHARED", "1");
+        if cfg!(feature = "static-stdlib") && target.os == "linux" {
+            config.define("CMAKE_SHARED_LINKER_FLAGS", "-static-libstdc++");
+        }
+    }
+    if is_msvc {
+        configure_msvc(&mut config)?;
+    }
+    configure_cross(&mut config, target)?;
+    configure_cuda(&mut config, target)?;
+    let dst = config.build();
+
+    if cfg!(feature = "dylib") {
+        // The shared libraries link to their own dependencies and the c++ stdlib.
+        println!(
+            "cargo:rustc-link-search=native={}",
+            dst.join("lib").display()
+        );
+        for lib in &["nvcore", "nvimage", "nvmath", "nvthread", "nvtt"] {
+            println!("cargo:rustc-link-lib=dylib={}", lib);
+        }
+        return Ok(());
+    }
+
+    println!(
+        "cargo:rustc-link-search={}",
+        dst.join("lib").join("static").display()
+    );
+
+    // @TODO(burtonageo): Is this necessary???
+    let src_dir = dst.join("build").join("src");
+    let extern_dir = dst.join("build").join("extern");
+
+    // Visual Studio is a multi-config generator, so the libraries which are not
+    // installed are placed in a directory named after the configuration.
+    let config_dir = if is_msvc { "Release" } else { "" };
+
+    println!(
+        "cargo:rustc-link-search={}",
+        src_dir.join("bc7").join(config_dir).display()
+    );
+    println!(
+        "cargo:rustc-link-search={}",
+        src_dir.join("bc6h").join(config_dir).display()
+    );
+    println!(
+        "cargo:rustc-link-search={}",
+        extern_dir.join("rg_etc1_v104").join(config_dir).display()
+    );
+
+    let libs = &[
+        "nvcore", "nvimage", "nvmath", "nvthread", "nvtt",
+        // @TODO(burtonageo): Is this necessary???
+        "bc7", "bc6h", "rg_etc1",
+    ];
+
+    for lib in &libs[..] {
+        println!("cargo:rustc-link-lib=static={}", lib);
+    }
+
+    link_cpp_stdlib(target)
+}
+
+/// Get the path of the pregenerated bindings which are shipped with this crate.
+fn pregenerated_bindings_path() -> Result<PathBuf, Box<dyn Error + Send + Sync + 'static>> {
+    let file_name = format!("nvtt_{}.rs", NVTT_BINDINGS_VERSION.replace('.', "_"));
+    let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR")?);
+    Ok(manifest_dir.join("bindings").join(file_name))
+}
+
+fn copy_bindings(
+    bindings_path: &Path,
+    out_file: &Path,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    println!("cargo:rerun-if-changed={}", bindings_path.display());
+    fs::copy(bindings_path, out_file).map_err(|err| {
+        e(format!(
+            "Could not copy the bindings from {}: {}",
+            bindings_path.display(),
+            err
+        ))
+    })?;
+    Ok(())
+}
+
+cfg_if! {
+    if #[cfg(feature = "bindgen")] {
+        use bindgen;
+
+        fn write_bindings(
+            out_file: &Path,
+            include_dir: &Path,
+        ) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+            let bindings = bindgen::builder()
+                .header("./wrapper.h")
+                .clang_arg(format!("-I{}", include_dir.display()))
+                // Only generate bindings for the nvtt API, and not for the system
+                // headers which it includes.
+                .whitelist_function("nvtt.*")
+                .whitelist_type("Nvtt.*")
+                .whitelist_var("NVTT_.*")
+                .layout_tests(true)
+                .ctypes_prefix("libc")
+                .rustified_enum("NvttBoolean")
+                .use_core()
+                .generate()
+                .map_err(|_| e("Could not generate bindings"))?;
+
+            bindings.write_to_file(out_file)?;
             Ok(())
         }
+    } else {
+        fn write_bindings(
+            out_file: &Path,
+            _include_dir: &Path,
+        ) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+            copy_bindings(&pregenerated_bindings_path()?, out_file)
+        }
     }
 }
 
 fn main() -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
     println!("cargo:rerun-if-changed=./nvidia-texture-tools");
     println!("cargo:rerun-if-changed=./wrapper.h");
+    println!("cargo:rerun-if-changed=./surface_wrapper.h");
+    println!("cargo:rerun-if-changed=./surface_wrapper.cpp");
+    println!("cargo:rerun-if-env-changed=NVTT_SYS_BINDINGS");
 
-    if !cfg!(target_os = "windows") {
-        build_nvtt()?;
-    }
+    let target = Target::from_env()?;
+    let source = NvttSource::find(&target)?;
+    let include_dir = source.include_dir(&target)?;
 
-    let bindings = bindgen::builder()
-        .header("./wrapper.h")
-        .ctypes_prefix("libc")
-        .rustified_enum("NvttBoolean")
-        .use_core()
-        .generate()
-        .map_err(|_| e("Could not generate bindings"))?;
+    // Exposed to dependent crates as the `DEP_NVTT_VERSION` and `DEP_NVTT_INCLUDE`
+    // environment variables.
+    println!("cargo:version={}", detect_nvtt_version(&include_dir));
+    println!("cargo:include={}", include_dir.display());
 
-    let out_path = PathBuf::from(env::var("OUT_DIR")?);
-    bindings.write_to_file(out_path.join("nvtt_bindings.rs"))?;
+    build_surface_wrapper(&target, &include_dir);
+    source.link(&target)?;
+
+    let out_file = PathBuf::from(env::var("OUT_DIR")?).join("nvtt_bindings.rs");
+    match env::var_os("NVTT_SYS_BINDINGS") {
+        Some(bindings_path) => copy_bindings(Path::new(&bindings_path), &out_file)?,
+        None => write_bindings(&out_file, &include_dir)?,
+    }
 
     Ok(())
 }
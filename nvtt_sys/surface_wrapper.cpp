@@ -0,0 +1,139 @@
+// Copyright © 2019-2020 George Burton
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+#include <nvtt/nvtt.h>
+#include "./surface_wrapper.h"
+
+static inline NvttBoolean toNvttBoolean(bool b)
+{
+    return b ? NVTT_True : NVTT_False;
+}
+
+// Surface functions.
+NvttSurface * nvttCreateSurface()
+{
+    return new nvtt::Surface();
+}
+
+void nvttDestroySurface(NvttSurface * surface)
+{
+    delete surface;
+}
+
+int nvttSurfaceWidth(const NvttSurface * surface)
+{
+    return surface->width();
+}
+
+int nvttSurfaceHeight(const NvttSurface * surface)
+{
+    return surface->height();
+}
+
+int nvttSurfaceDepth(const NvttSurface * surface)
+{
+    return surface->depth();
+}
+
+void nvttSurfaceSetWrapMode(NvttSurface * surface, NvttWrapMode mode)
+{
+    surface->setWrapMode((nvtt::WrapMode)mode);
+}
+
+NvttWrapMode nvttSurfaceWrapMode(const NvttSurface * surface)
+{
+    return (NvttWrapMode)surface->wrapMode();
+}
+
+void nvttSurfaceSetAlphaMode(NvttSurface * surface, NvttAlphaMode mode)
+{
+    surface->setAlphaMode((nvtt::AlphaMode)mode);
+}
+
+NvttAlphaMode nvttSurfaceAlphaMode(const NvttSurface * surface)
+{
+    return (NvttAlphaMode)surface->alphaMode();
+}
+
+void nvttSurfaceSetNormalMap(NvttSurface * surface, NvttBoolean isNormalMap)
+{
+    surface->setNormalMap(isNormalMap != NVTT_False);
+}
+
+NvttBoolean nvttSurfaceIsNormalMap(const NvttSurface * surface)
+{
+    return toNvttBoolean(surface->isNormalMap());
+}
+
+NvttBoolean nvttSurfaceSetImageChannels(NvttSurface * surface, NvttInputFormat format, int w, int h, int d, const void * r, const void * g, const void * b, const void * a)
+{
+    return toNvttBoolean(surface->setImage((nvtt::InputFormat)format, w, h, d, r, g, b, a));
+}
+
+float * nvttSurfaceChannel(NvttSurface * surface, int channel)
+{
+    return surface->channel(channel);
+}
+
+NvttBoolean nvttSurfaceLoad(NvttSurface * surface, const char * fileName)
+{
+    return toNvttBoolean(surface->load(fileName));
+}
+
+// Context functions.
+NvttContext * nvttCreateContext()
+{
+    return new nvtt::Context();
+}
+
+void nvttDestroyContext(NvttContext * context)
+{
+    delete context;
+}
+
+void nvttContextEnableCudaAcceleration(NvttContext * context, NvttBoolean enable)
+{
+    context->enableCudaAcceleration(enable != NVTT_False);
+}
+
+NvttBoolean nvttContextIsCudaAccelerationEnabled(const NvttContext * context)
+{
+    return toNvttBoolean(context->isCudaAccelerationEnabled());
+}
+
+NvttBoolean nvttContextOutputHeader(const NvttContext * context, const NvttSurface * surface, int mipmapCount, const NvttCompressionOptions * compressionOptions, const NvttOutputOptions * outputOptions)
+{
+    return toNvttBoolean(context->outputHeader(*surface, mipmapCount, *compressionOptions, *outputOptions));
+}
+
+NvttBoolean nvttContextCompress(const NvttContext * context, const NvttSurface * surface, int face, int mipmap, const NvttCompressionOptions * compressionOptions, const NvttOutputOptions * outputOptions)
+{
+    return toNvttBoolean(context->compress(*surface, face, mipmap, *compressionOptions, *outputOptions));
+}
+
+int nvttContextEstimateSize(const NvttContext * context, const NvttSurface * surface, int mipmapCount, const NvttCompressionOptions * compressionOptions)
+{
+    return context->estimateSize(*surface, mipmapCount, *compressionOptions);
+}
+
+void nvttContextQuantize(const NvttContext * context, NvttSurface * surface, const NvttCompressionOptions * compressionOptions)
+{
+    context->quantize(*surface, *compressionOptions);
+}
@@ -0,0 +1,78 @@
+// Copyright © 2019-2020 George Burton
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+// C bindings to the `nvtt::Surface` and `nvtt::Context` types, which are only
+// available through the C++ API in nvtt 2.1.
+
+#pragma once
+#include <nvtt/nvtt_wrapper.h>
+
+#ifdef __cplusplus
+namespace nvtt {
+    struct Surface;
+    struct Context;
+}
+
+typedef struct nvtt::Surface NvttSurface;
+typedef struct nvtt::Context NvttContext;
+#else
+typedef struct NvttSurface NvttSurface;
+typedef struct NvttContext NvttContext;
+#endif
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+// Surface functions.
+NvttSurface * nvttCreateSurface();
+void nvttDestroySurface(NvttSurface * surface);
+
+int nvttSurfaceWidth(const NvttSurface * surface);
+int nvttSurfaceHeight(const NvttSurface * surface);
+int nvttSurfaceDepth(const NvttSurface * surface);
+
+void nvttSurfaceSetWrapMode(NvttSurface * surface, NvttWrapMode mode);
+NvttWrapMode nvttSurfaceWrapMode(const NvttSurface * surface);
+void nvttSurfaceSetAlphaMode(NvttSurface * surface, NvttAlphaMode mode);
+NvttAlphaMode nvttSurfaceAlphaMode(const NvttSurface * surface);
+void nvttSurfaceSetNormalMap(NvttSurface * surface, NvttBoolean isNormalMap);
+NvttBoolean nvttSurfaceIsNormalMap(const NvttSurface * surface);
+
+NvttBoolean nvttSurfaceSetImageChannels(NvttSurface * surface, NvttInputFormat format, int w, int h, int d, const void * r, const void * g, const void * b, const void * a);
+float * nvttSurfaceChannel(NvttSurface * surface, int channel);
+
+NvttBoolean nvttSurfaceLoad(NvttSurface * surface, const char * fileName);
+
+// Context functions.
+NvttContext * nvttCreateContext();
+void nvttDestroyContext(NvttContext * context);
+
+void nvttContextEnableCudaAcceleration(NvttContext * context, NvttBoolean enable);
+NvttBoolean nvttContextIsCudaAccelerationEnabled(const NvttContext * context);
+
+NvttBoolean nvttContextOutputHeader(const NvttContext * context, const NvttSurface * surface, int mipmapCount, const NvttCompressionOptions * compressionOptions, const NvttOutputOptions * outputOptions);
+NvttBoolean nvttContextCompress(const NvttContext * context, const NvttSurface * surface, int face, int mipmap, const NvttCompressionOptions * compressionOptions, const NvttOutputOptions * outputOptions);
+int nvttContextEstimateSize(const NvttContext * context, const NvttSurface * surface, int mipmapCount, const NvttCompressionOptions * compressionOptions);
+void nvttContextQuantize(const NvttContext * context, NvttSurface * surface, const NvttCompressionOptions * compressionOptions);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif
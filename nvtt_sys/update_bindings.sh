@@ -0,0 +1,24 @@
+#!/bin/sh
+# Refresh the pregenerated bindings in `bindings/` from the bindings which the build
+# script generates with `bindgen`. The build script only writes into its `OUT_DIR`,
+# so they are copied from there.
+set -eu
+
+cd "$(dirname "$0")"
+
+version=$(sed -n 's/^const NVTT_BINDINGS_VERSION: &str = "\(.*\)";$/\1/p' build.rs | tr . _)
+out_dir=$(
+    cargo build --features bindgen --message-format=json |
+        grep '"reason":"build-script-executed"' |
+        grep '"package_id":"[^"]*nvtt_sys' |
+        sed 's/.*"out_dir":"\([^"]*\)".*/\1/' |
+        tail -n 1
+)
+
+if [ -z "$out_dir" ] || [ ! -f "$out_dir/nvtt_bindings.rs" ]; then
+    echo "Could not find the bindings generated by the build script" >&2
+    exit 1
+fi
+
+cp "$out_dir/nvtt_bindings.rs" "bindings/nvtt_$version.rs"
+echo "Updated bindings/nvtt_$version.rs"
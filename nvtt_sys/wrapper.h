@@ -20,4 +20,5 @@
 
 #pragma once
 #include <stdbool.h>
-#include "./nvidia-texture-tools/src/nvtt/nvtt_wrapper.h"
+#include <nvtt/nvtt_wrapper.h>
+#include "./surface_wrapper.h"
@@ -0,0 +1,781 @@
+// Copyright © 2019-2020 George Burton
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::{AlphaMode, Channel, Error, Format, Surface};
+#[cfg(feature = "nvtt")]
+use crate::{
+    CompressError, CompressionOptions, CompressionStage, Container, Context, CubeFace,
+    OutputOptions, Quality,
+};
+#[cfg(feature = "serde-serialize")]
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "nvtt")]
+use std::{
+    env, fs, iter, process,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+use std::{fmt, path::Path};
+
+/// The error between a single channel of two images.
+#[cfg_attr(feature = "serde-serialize", derive(Deserialize, Serialize))]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ChannelMetrics {
+    /// The root mean squared error.
+    pub rmse: f32,
+    /// The peak signal to noise ratio in decibels, relative to a peak value of `1.0`.
+    /// This is infinite if the images are identical.
+    pub psnr: f32,
+    /// The largest absolute difference between two pixels.
+    pub max_error: f32,
+}
+
+impl ChannelMetrics {
+    fn new(squared_error: f64, max_error: f32, count: usize) -> Self {
+        let mse = if count == 0 {
+            0.0
+        } else {
+            squared_error / count as f64
+        };
+        let psnr = if mse == 0.0 {
+            f32::INFINITY
+        } else {
+            (-10.0 * mse.log10()) as f32
+        };
+
+        Self {
+            rmse: mse.sqrt() as f32,
+            psnr,
+            max_error,
+        }
+    }
+}
+
+/// Measures the error between a reference image and a compressed copy of it.
+///
+/// # Example
+///
+/// ```no_run
+/// # #[cfg(not(feature = "nvtt"))]
+/// # fn main() {}
+/// # #[cfg(feature = "nvtt")]
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use nvtt_rs::{CompressionOptions, Context, Format, QualityMetrics, Surface};
+///
+/// let surface = Surface::load("albedo.png")?;
+/// let mut compression_options = CompressionOptions::new()?;
+/// compression_options.set_format(Format::Bc1);
+///
+/// let metrics = QualityMetrics::measure(&Context::new()?, &surface, &compression_options)?;
+/// assert!(metrics.rgb.psnr > 35.0);
+/// # Ok(())
+/// # }
+/// ```
+#[cfg_attr(feature = "serde-serialize", derive(Deserialize, Serialize))]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct QualityMetrics {
+    /// The error in the red channel.
+    pub red: ChannelMetrics,
+    /// The error in the green channel.
+    pub green: ChannelMetrics,
+    /// The error in the blue channel.
+    pub blue: ChannelMetrics,
+    /// The error in the alpha channel.
+    pub alpha: ChannelMetrics,
+    /// The combined error of the red, green and blue channels.
+    pub rgb: ChannelMetrics,
+}
+
+impl QualityMetrics {
+    /// Compare `image` against the `reference` image.
+    ///
+    /// # Errors
+    ///
+    /// If the dimensions of the two surfaces differ, then this method will fail with
+    /// [`Error::InvalidInput`].
+    ///
+    /// [`Error::InvalidInput`]: enum.Error.html#variant.InvalidInput
+    pub fn compare(reference: &Surface, image: &Surface) -> Result<Self, Error> {
+        let dimensions = |s: &Surface| (s.width(), s.height(), s.depth());
+        if dimensions(reference) != dimensions(image) {
+            return Err(Error::InvalidInput);
+        }
+
+        let mut channels = [(0.0f64, 0.0f32); 4];
+        for (&channel, (squared_error, max_error)) in Channel::ALL.iter().zip(&mut channels) {
+            for (&a, &b) in reference
+                .channel(channel)
+                .iter()
+                .zip(image.channel(channel))
+            {
+                let diff = (a - b).abs();
+                *squared_error += f64::from(diff) * f64::from(diff);
+                *max_error = max_error.max(diff);
+            }
+        }
+
+        let count = reference.channel(Channel::Red).len();
+        let metrics =
+            |(squared_error, max_error)| ChannelMetrics::new(squared_error, max_error, count);
+        let rgb_squared_error = channels[..3].iter().map(|&(e, _)| e).sum();
+        let rgb_max_error = channels[..3].iter().fold(0.0f32, |m, &(_, e)| m.max(e));
+
+        Ok(Self {
+            red: metrics(channels[0]),
+            green: metrics(channels[1]),
+            blue: metrics(channels[2]),
+            alpha: metrics(channels[3]),
+            rgb: ChannelMetrics::new(rgb_squared_error, rgb_max_error, 3 * count),
+        })
+    }
+
+    /// Load and decode the images at `reference` and `compressed`, and compare them.
+    /// Any file which can be read by [`Surface::load`], such as a dds file, can be
+    /// used.
+    ///
+    /// # Errors
+    ///
+    /// If either file cannot be loaded, then this method will fail with
+    /// [`Error::FileOpen`]. If the dimensions of the images differ, then this method
+    /// will fail with [`Error::InvalidInput`].
+    ///
+    /// [`Surface::load`]: struct.Surface.html#method.load
+    /// [`Error::FileOpen`]: enum.Error.html#variant.FileOpen
+    /// [`Error::InvalidInput`]: enum.Error.html#variant.InvalidInput
+    pub fn compare_files<P, Q>(reference: P, compressed: Q) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let reference = Surface::load(reference)?;
+        let compressed = Surface::load(compressed)?;
+        Self::compare(&reference, &compressed)
+    }
+
+    /// Compress `surface` with the `compression_options`, then decode the result and
+    /// compare it against the original `surface`.
+    ///
+    /// The compressed texture is written to a temporary dds file, which is removed
+    /// once it has been decoded.
+    ///
+    /// # Errors
+    ///
+    /// If the texture cannot be compressed, then the error from [`Context::compress`]
+    /// is returned. If the compressed texture cannot be decoded, which is the case
+    /// for formats which cannot be stored in a dds file, then this method will fail
+    /// with an error in the [`CompressionStage::Decoding`] stage.
+    ///
+    /// [`Context::compress`]: struct.Context.html#method.compress
+    /// [`CompressionStage::Decoding`]: enum.CompressionStage.html#variant.Decoding
+    #[cfg(feature = "nvtt")]
+    pub fn measure(
+        context: &Context,
+        surface: &Surface,
+        compression_options: &CompressionOptions,
+    ) -> Result<Self, CompressError> {
+        measure_compression(context, surface, compression_options).map(|(metrics, ..)| metrics)
+    }
+
+    /// Get the error of a single `channel`.
+    #[inline]
+    pub fn channel(&self, channel: Channel) -> &ChannelMetrics {
+        match channel {
+            Channel::Red => &self.red,
+            Channel::Green => &self.green,
+            Channel::Blue => &self.blue,
+            Channel::Alpha => &self.alpha,
+        }
+    }
+}
+
+/// How the alpha channel of an image is used. This is part of an [`AlphaAnalysis`].
+///
+/// [`AlphaAnalysis`]: struct.AlphaAnalysis.html
+#[cfg_attr(feature = "serde-serialize", derive(Deserialize, Serialize))]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AlphaUsage {
+    /// Every pixel has the same alpha value.
+    Constant(f32),
+    /// Every pixel is either fully transparent or fully opaque.
+    Binary,
+    /// Some pixels are partially transparent.
+    Smooth,
+}
+
+/// Describes how the alpha channel of an image is used, so that a suitable
+/// [`AlphaMode`] and format can be chosen for it.
+///
+/// # Example
+///
+/// ```no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use nvtt_rs::{AlphaAnalysis, Surface};
+///
+/// let surface = Surface::load("foliage.png")?;
+/// let analysis = AlphaAnalysis::analyze(&surface);
+/// println!("{:?}: {:.1}% opaque", analysis.usage, analysis.opaque * 100.0);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`AlphaMode`]: enum.AlphaMode.html
+#[cfg_attr(feature = "serde-serialize", derive(Deserialize, Serialize))]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AlphaAnalysis {
+    /// How the alpha channel is used.
+    pub usage: AlphaUsage,
+    /// The smallest alpha value.
+    pub min: f32,
+    /// The largest alpha value.
+    pub max: f32,
+    /// The mean alpha value, which is the fraction of the image which is covered.
+    pub mean: f32,
+    /// The fraction of pixels which are fully opaque.
+    pub opaque: f32,
+    /// The fraction of pixels which are fully transparent.
+    pub transparent: f32,
+}
+
+impl AlphaAnalysis {
+    /// The largest difference from `0.0` or `1.0` which is treated as fully transparent
+    /// or fully opaque. This is half of the step of an 8 bit channel.
+    pub const TOLERANCE: f32 = 0.5 / 255.0;
+
+    /// Analyze the alpha channel of `surface`. A null surface is reported as
+    /// constant and opaque.
+    pub fn analyze(surface: &Surface) -> Self {
+        let alpha = surface.channel(Channel::Alpha);
+        if alpha.is_empty() {
+            return Self {
+                usage: AlphaUsage::Constant(1.0),
+                min: 1.0,
+                max: 1.0,
+                mean: 1.0,
+                opaque: 1.0,
+                transparent: 0.0,
+            };
+        }
+
+        let (mut min, mut max, mut sum) = (f32::INFINITY, f32::NEG_INFINITY, 0.0f64);
+        let (mut opaque, mut transparent) = (0usize, 0usize);
+        for &a in alpha {
+            min = min.min(a);
+            max = max.max(a);
+            sum += f64::from(a);
+            if a >= 1.0 - Self::TOLERANCE {
+                opaque += 1;
+            } else if a <= Self::TOLERANCE {
+                transparent += 1;
+            }
+        }
+
+        let count = alpha.len();
+        let usage = if max - min <= Self::TOLERANCE {
+            AlphaUsage::Constant(min)
+        } else if opaque + transparent == count {
+            AlphaUsage::Binary
+        } else {
+            AlphaUsage::Smooth
+        };
+
+        Self {
+            usage,
+            min,
+            max,
+            mean: (sum / count as f64) as f32,
+            opaque: opaque as f32 / count as f32,
+            transparent: transparent as f32 / count as f32,
+        }
+    }
+
+    /// Returns `true` if every pixel is fully opaque.
+    #[inline]
+    pub fn is_opaque(&self) -> bool {
+        self.min >= 1.0 - Self::TOLERANCE
+    }
+
+    /// Get the `AlphaMode` which the image should be compressed with. This is
+    /// `AlphaMode::None` if the image is opaque, and `AlphaMode::Transparency`
+    /// otherwise.
+    #[inline]
+    pub fn alpha_mode(&self) -> AlphaMode {
+        if self.is_opaque() {
+            AlphaMode::None
+        } else {
+            AlphaMode::Transparency
+        }
+    }
+
+    /// Get the smallest block compressed format which can store the alpha channel.
+    /// This is `Format::Bc1` for opaque images, `Format::Bc1a` for images with binary
+    /// alpha, and `Format::Bc3` otherwise.
+    #[inline]
+    pub fn format(&self) -> Format {
+        match self.usage {
+            _ if self.is_opaque() => Format::Bc1,
+            AlphaUsage::Binary => Format::Bc1a,
+            AlphaUsage::Constant(_) | AlphaUsage::Smooth => Format::Bc3,
+        }
+    }
+}
+
+/// Describes how closely an image resembles a tangent space normal map, so that
+/// normal maps which are about to be compressed with color settings can be caught.
+///
+/// Each pixel is decoded from the `0..1` range to a vector in `-1..1`. The pixels of
+/// a tangent space normal map decode to vectors of unit length, which point away
+/// from the surface along `+z`.
+///
+/// # Example
+///
+/// ```no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use nvtt_rs::{Format, NormalMapAnalysis, Surface};
+///
+/// let surface = Surface::load("bricks_n.png")?;
+/// let analysis = NormalMapAnalysis::analyze(&surface);
+/// if analysis.is_normal_map() {
+///     assert!(NormalMapAnalysis::preserves_normals(Format::Bc5, false));
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[cfg_attr(feature = "serde-serialize", derive(Deserialize, Serialize))]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NormalMapAnalysis {
+    /// The fraction of pixels which decode to a vector of unit length.
+    pub unit_length: f32,
+    /// The mean of the decoded vectors. This is close to `[0.0, 0.0, 1.0]` for
+    /// normal maps.
+    pub mean: [f32; 3],
+}
+
+impl NormalMapAnalysis {
+    /// The largest difference from `1.0` in the length of a decoded vector which is
+    /// treated as unit length. This allows for quantization, and for normal maps
+    /// which were not renormalized after they were filtered.
+    pub const LENGTH_TOLERANCE: f32 = 0.1;
+    /// The smallest fraction of pixels which must decode to a vector of unit length
+    /// for the image to be treated as a normal map.
+    pub const MIN_UNIT_LENGTH: f32 = 0.9;
+    /// The smallest `z` component of the mean decoded vector for the image to be
+    /// treated as a normal map.
+    pub const MIN_MEAN_Z: f32 = 0.7;
+
+    /// Analyze the rgb channels of `surface`. A null surface is not a normal map.
+    pub fn analyze(surface: &Surface) -> Self {
+        let (r, g, b) = (
+            surface.channel(Channel::Red),
+            surface.channel(Channel::Green),
+            surface.channel(Channel::Blue),
+        );
+        let count = r.len().min(g.len()).min(b.len());
+        if count == 0 {
+            return Self {
+                unit_length: 0.0,
+                mean: [0.0; 3],
+            };
+        }
+
+        let mut sum = [0.0f64; 3];
+        let mut unit_length = 0usize;
+        for ((&r, &g), &b) in r.iter().zip(g).zip(b) {
+            let n = [r * 2.0 - 1.0, g * 2.0 - 1.0, b * 2.0 - 1.0];
+            let length = (n[0] * n[0] + n[1] * n[1] + n[2] * n[2]).sqrt();
+            if (length - 1.0).abs() <= Self::LENGTH_TOLERANCE {
+                unit_length += 1;
+            }
+            for (sum, &n) in sum.iter_mut().zip(&n) {
+                *sum += f64::from(n);
+            }
+        }
+
+        let mean = |i: usize| (sum[i] / count as f64) as f32;
+        Self {
+            unit_length: unit_length as f32 / count as f32,
+            mean: [mean(0), mean(1), mean(2)],
+        }
+    }
+
+    /// Returns `true` if the image looks like a tangent space normal map.
+    #[inline]
+    pub fn is_normal_map(&self) -> bool {
+        self.unit_length >= Self::MIN_UNIT_LENGTH && self.mean[2] >= Self::MIN_MEAN_Z
+    }
+
+    /// Returns `true` if normal maps can be compressed to `format`, with the srgb flag
+    /// set to `srgb`, without being damaged. This is `false` for srgb data, and for
+    /// color formats such as `Format::Bc1` which compress the channels of a normal
+    /// together.
+    #[inline]
+    pub fn preserves_normals(format: Format, srgb: bool) -> bool {
+        !srgb
+            && (format.normal_decode().is_some()
+                || matches!(
+                    format,
+                    Format::Bc7
+                        | Format::Ctx1
+                        | Format::Etc2Rg
+                        | Format::Rgb
+                        | Format::Rgba
+                        | Format::Other(_)
+                ))
+    }
+}
+
+/// Log a warning if `surface` is marked as, or looks like, a normal map, but is
+/// about to be compressed to `format` with settings which are meant for colors.
+/// `name` identifies the texture in the warning.
+pub(crate) fn warn_color_normal_map(
+    surface: &Surface,
+    format: Format,
+    srgb: bool,
+    name: &dyn fmt::Display,
+) {
+    if NormalMapAnalysis::preserves_normals(format, srgb) {
+        return;
+    }
+    if surface.is_normal_map() || NormalMapAnalysis::analyze(surface).is_normal_map() {
+        log::warn!(
+            "{} looks like a normal map, but is being compressed to {:?}{}. \
+             Use Format::Bc5 without the srgb flag to store normal maps",
+            name,
+            format,
+            if srgb { " as srgb data" } else { "" },
+        );
+    }
+}
+
+/// Compares the size, quality and compression time of one image compressed with
+/// several candidate formats and qualities. This can be used to pick the best format
+/// for each texture.
+///
+/// # Example
+///
+/// ```no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use nvtt_rs::{Context, Format, FormatComparison, Quality, Surface};
+///
+/// let surface = Surface::load("albedo.png")?;
+/// let comparison = FormatComparison::run(
+///     &Context::new()?,
+///     &surface,
+///     &[Format::Bc1, Format::Bc3, Format::Bc7],
+///     &[Quality::Production],
+/// )?;
+///
+/// println!("{}", comparison);
+/// if let Some(best) = comparison.best(40.0) {
+///     println!("Use {:?}", best.format());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "nvtt")]
+#[derive(Clone, Debug)]
+pub struct FormatComparison {
+    candidates: Vec<FormatCandidate>,
+}
+
+#[cfg(feature = "nvtt")]
+impl FormatComparison {
+    /// Compress `surface` with every combination of `formats` and `qualities`, and
+    /// measure each result with [`QualityMetrics::measure`].
+    ///
+    /// Candidates which cannot be compressed or decoded are still included in the
+    /// comparison, with their error.
+    ///
+    /// # Errors
+    ///
+    /// If the `CompressionOptions` cannot be created, then this method will fail with
+    /// `Error::Unknown`.
+    ///
+    /// [`QualityMetrics::measure`]: struct.QualityMetrics.html#method.measure
+    pub fn run(
+        context: &Context,
+        surface: &Surface,
+        formats: &[Format],
+        qualities: &[Quality],
+    ) -> Result<Self, Error> {
+        let mut compression_options = CompressionOptions::new()?;
+        let mut candidates = Vec::with_capacity(formats.len() * qualities.len());
+        for &format in formats {
+            for &quality in qualities {
+                compression_options.set_format(format).set_quality(quality);
+                let result = measure_compression(context, surface, &compression_options);
+
+                candidates.push(FormatCandidate {
+                    format,
+                    quality,
+                    result,
+                });
+            }
+        }
+
+        Ok(Self { candidates })
+    }
+
+    /// The candidates, in the order they were compressed.
+    #[inline]
+    pub fn candidates(&self) -> &[FormatCandidate] {
+        &self.candidates
+    }
+
+    /// Get the smallest candidate whose combined rgb PSNR is at least `min_psnr`. If
+    /// several candidates have the same size, then the one with the highest PSNR is
+    /// chosen.
+    pub fn best(&self, min_psnr: f32) -> Option<&FormatCandidate> {
+        self.candidates
+            .iter()
+            .filter(|c| c.metrics().map(|m| m.rgb.psnr >= min_psnr) == Some(true))
+            .min_by(|a, b| {
+                let psnr = |c: &FormatCandidate| c.metrics().map_or(0.0, |m| m.rgb.psnr);
+                a.output_size().cmp(&b.output_size()).then_with(|| {
+                    psnr(b)
+                        .partial_cmp(&psnr(a))
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                })
+            })
+    }
+}
+
+#[cfg(feature = "nvtt")]
+impl fmt::Display for FormatComparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<14} {:<11} {:>12} {:>10} {:>10} {:>10}",
+            "format", "quality", "size (bytes)", "psnr (dB)", "rmse", "time (ms)"
+        )?;
+        for candidate in &self.candidates {
+            let format = format!("{:?}", candidate.format);
+            let quality = format!("{:?}", candidate.quality);
+            write!(f, "{:<14} {:<11} ", format, quality)?;
+            match candidate.result {
+                Ok((ref metrics, size, elapsed)) => writeln!(
+                    f,
+                    "{:>12} {:>10.2} {:>10.5} {:>10.2}",
+                    size,
+                    metrics.rgb.psnr,
+                    metrics.rgb.rmse,
+                    elapsed.as_secs_f64() * 1000.0
+                )?,
+                Err(ref e) => writeln!(f, "failed: {}", e.error())?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A single format and quality in a [`FormatComparison`].
+///
+/// [`FormatComparison`]: struct.FormatComparison.html
+#[cfg(feature = "nvtt")]
+#[derive(Clone, Debug)]
+pub struct FormatCandidate {
+    format: Format,
+    quality: Quality,
+    result: Result<(QualityMetrics, u64, Duration), CompressError>,
+}
+
+#[cfg(feature = "nvtt")]
+impl FormatCandidate {
+    /// The format of the candidate.
+    #[inline]
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// The quality of the candidate.
+    #[inline]
+    pub fn quality(&self) -> Quality {
+        self.quality
+    }
+
+    /// The error of the decoded texture, or `None` if the candidate failed.
+    #[inline]
+    pub fn metrics(&self) -> Option<&QualityMetrics> {
+        self.result.as_ref().ok().map(|(metrics, ..)| metrics)
+    }
+
+    /// The size of the compressed dds file in bytes, or `None` if the candidate failed.
+    #[inline]
+    pub fn output_size(&self) -> Option<u64> {
+        self.result.as_ref().ok().map(|&(_, size, _)| size)
+    }
+
+    /// The time taken to compress the texture, or `None` if the candidate failed.
+    #[inline]
+    pub fn elapsed(&self) -> Option<Duration> {
+        self.result.as_ref().ok().map(|&(.., elapsed)| elapsed)
+    }
+
+    /// Whether the candidate was compressed and measured successfully.
+    #[inline]
+    pub fn result(&self) -> Result<(), &CompressError> {
+        self.result.as_ref().map(|_| ())
+    }
+}
+
+/// Compress `surface` into a temporary file, and compare the decoded texture against
+/// it. Returns the metrics, the size of the compressed file and the time taken to
+/// compress it.
+#[cfg(feature = "nvtt")]
+fn measure_compression(
+    context: &Context,
+    surface: &Surface,
+    compression_options: &CompressionOptions,
+) -> Result<(QualityMetrics, u64, Duration), CompressError> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let container = match compression_options.format() {
+        Format::Bc6 | Format::Bc7 => Container::Dds10,
+        _ => Container::Dds,
+    };
+    let path = env::temp_dir().join(format!(
+        "nvtt_rs-{}-{}.dds",
+        process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    let mut output_options =
+        OutputOptions::new().map_err(|e| measure_error(e, CompressionStage::Output, &path))?;
+    output_options
+        .set_output_location(&path)
+        .map_err(|_| measure_error(Error::FileOpen, CompressionStage::Output, &path))?
+        .set_container(container);
+
+    let start = Instant::now();
+    let decoded = context
+        .compress(
+            surface,
+            CubeFace::default(),
+            0,
+            compression_options,
+            &output_options,
+        )
+        .and_then(|_| {
+            let elapsed = start.elapsed();
+            let size = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+            Surface::load(&path)
+                .map(|decoded| (decoded, size, elapsed))
+                .map_err(|e| measure_error(e, CompressionStage::Decoding, &path))
+        });
+    let _ = fs::remove_file(&path);
+
+    let (decoded, size, elapsed) = decoded?;
+    let metrics = QualityMetrics::compare(surface, &decoded)
+        .map_err(|e| measure_error(e, CompressionStage::Decoding, &path))?;
+    Ok((metrics, size, elapsed))
+}
+
+/// Get a copy of the `compression_options` with the format and quality which reach
+/// their target PSNR on `surface`, without a target PSNR. See
+/// `CompressionOptions::set_target_psnr`.
+#[cfg(feature = "nvtt")]
+pub(crate) fn select_for_target_psnr(
+    context: &Context,
+    surface: &Surface,
+    compression_options: &CompressionOptions,
+    container: Container,
+) -> CompressionOptions {
+    let mut selected = compression_options.clone();
+    selected.clear_target_psnr();
+    let target = match compression_options.target_psnr() {
+        Some(target) => target,
+        None => return selected,
+    };
+
+    let format = compression_options.format();
+    let alternatives = compression_options.target_formats().iter().copied();
+    let formats = iter::once(format)
+        .chain(alternatives.filter(|&alternative| alternative != format))
+        .filter(|&format| {
+            container != Container::Dds || !matches!(format, Format::Bc6 | Format::Bc7)
+        });
+
+    // The smallest and then fastest configuration which reaches the target, and the
+    // configuration with the highest PSNR in case none do.
+    let mut cheapest: Option<(u64, usize, Format, Quality)> = None;
+    let mut closest: Option<(f32, Format, Quality)> = None;
+    let mut candidate = selected.clone();
+    for format in formats {
+        let qualities = [
+            Quality::Fastest,
+            Quality::Normal,
+            Quality::Production,
+            Quality::Highest,
+        ];
+        for (rank, &quality) in qualities.iter().enumerate() {
+            candidate.set_format(format).set_quality(quality);
+            let (metrics, size, _) = match measure_compression(context, surface, &candidate) {
+                Ok(result) => result,
+                // The format cannot be compressed or decoded, so no quality will work.
+                Err(_) => break,
+            };
+
+            let psnr = metrics.rgb.psnr;
+            if psnr >= target {
+                if !matches!(cheapest, Some((s, r, ..)) if (s, r) <= (size, rank)) {
+                    cheapest = Some((size, rank, format, quality));
+                }
+                // Higher qualities of this format are slower, and no smaller.
+                break;
+            }
+            if !matches!(closest, Some((p, ..)) if p >= psnr) {
+                closest = Some((psnr, format, quality));
+            }
+        }
+    }
+
+    match (cheapest, closest) {
+        (Some((.., format, quality)), _) => {
+            selected.set_format(format).set_quality(quality);
+        }
+        (None, Some((psnr, format, quality))) => {
+            log::warn!(
+                "No configuration reaches the target PSNR of {t} dB, using {f:?} at {p:.2} dB",
+                t = target,
+                f = format,
+                p = psnr
+            );
+            selected.set_format(format).set_quality(quality);
+        }
+        (None, None) => {
+            log::warn!(
+                "Could not measure any configuration for the target PSNR of {t} dB",
+                t = target
+            );
+        }
+    }
+    selected
+}
+
+/// Create an error for a step of `QualityMetrics::measure` which is not run by nvtt.
+#[cfg(feature = "nvtt")]
+fn measure_error(error: Error, stage: CompressionStage, path: &Path) -> CompressError {
+    CompressError {
+        error,
+        stage,
+        path: Some(path.to_path_buf()),
+        image: None,
+        diagnosis: None,
+    }
+}
@@ -0,0 +1,385 @@
+// Copyright © 2019-2020 George Burton
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::{Error, Quality, Surface};
+use basis_universal::{
+    BasisTextureFormat, ColorSpace, Compressor as BasisCompressor, CompressorParams,
+    BASISU_DEFAULT_COMPRESSION_LEVEL, BASISU_MAX_COMPRESSION_LEVEL, UASTC_QUALITY_DEFAULT,
+    UASTC_QUALITY_MAX, UASTC_QUALITY_MIN, UASTC_QUALITY_SLOWER,
+};
+#[cfg(feature = "serde-serialize")]
+use serde::{Deserialize, Serialize};
+use std::{convert::TryFrom, thread};
+
+/// The format of the data in a `.basis` file.
+#[cfg_attr(feature = "serde-serialize", derive(Deserialize, Serialize))]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum BasisFormat {
+    /// The ETC1S format, which produces small files of lower quality.
+    Etc1s,
+    /// The UASTC format, which produces larger files of higher quality.
+    Uastc,
+}
+
+impl From<BasisFormat> for BasisTextureFormat {
+    #[inline]
+    fn from(format: BasisFormat) -> Self {
+        match format {
+            BasisFormat::Etc1s => BasisTextureFormat::ETC1S,
+            BasisFormat::Uastc => BasisTextureFormat::UASTC4x4,
+        }
+    }
+}
+
+/// Compresses [`Surface`]s into Basis Universal `.basis` files, which can be
+/// transcoded to a gpu format supported by the target device at runtime.
+///
+/// # Example
+///
+/// ```no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use nvtt_rs::{BasisFormat, BasisOptions, Surface};
+///
+/// let surface = Surface::load("albedo.png")?;
+/// let data = BasisOptions::new(BasisFormat::Uastc)
+///     .mipmaps(true)
+///     .srgb(true)
+///     .compress(&surface)?;
+///
+/// std::fs::write("albedo.basis", data)?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Notes
+///
+/// This type requires the [`basis`] feature.
+///
+/// [`Surface`]: struct.Surface.html
+/// [`basis`]: index.html#basis
+#[cfg_attr(feature = "serde-serialize", derive(Deserialize, Serialize))]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct BasisOptions {
+    format: BasisFormat,
+    quality: Quality,
+    mipmaps: bool,
+    srgb: bool,
+    threads: Option<usize>,
+}
+
+impl BasisOptions {
+    /// Create a new `BasisOptions` which compresses to `format` with
+    /// `Quality::Normal`, without mipmaps.
+    #[inline]
+    pub fn new(format: BasisFormat) -> Self {
+        Self {
+            format,
+            quality: Quality::Normal,
+            mipmaps: false,
+            srgb: false,
+            threads: None,
+        }
+    }
+
+    /// Set the format of the compressed data.
+    #[inline]
+    pub fn format(&mut self, format: BasisFormat) -> &mut Self {
+        self.format = format;
+        self
+    }
+
+    /// Set the compression quality. Higher qualities spend more time searching for
+    /// a better encoding, in the same way as they do for nvtt.
+    #[inline]
+    pub fn quality(&mut self, quality: Quality) -> &mut Self {
+        self.quality = quality;
+        self
+    }
+
+    /// Set whether mipmaps are generated.
+    #[inline]
+    pub fn mipmaps(&mut self, generate_mipmaps: bool) -> &mut Self {
+        self.mipmaps = generate_mipmaps;
+        self
+    }
+
+    /// Set whether the image contains srgb data.
+    #[inline]
+    pub fn srgb(&mut self, srgb: bool) -> &mut Self {
+        self.srgb = srgb;
+        self
+    }
+
+    /// Set the number of threads used to compress each image. By default, this is
+    /// the number of available cpus.
+    #[inline]
+    pub fn threads(&mut self, threads: usize) -> &mut Self {
+        self.threads = Some(threads.max(1));
+        self
+    }
+
+    /// Compress the `surface` into the contents of a `.basis` file. The values of
+    /// the surface are clamped to the `0..1` range, and stored as 8 bit values.
+    ///
+    /// # Errors
+    ///
+    /// If the surface is empty or too large, then this method will fail with
+    /// [`Error::InvalidInput`]. If the encoder fails, then this method will fail with
+    /// [`Error::Unknown`].
+    ///
+    /// [`Error::InvalidInput`]: enum.Error.html#variant.InvalidInput
+    /// [`Error::Unknown`]: enum.Error.html#variant.Unknown
+    pub fn compress(&self, surface: &Surface) -> Result<Vec<u8>, Error> {
+        let width = u32::try_from(surface.width()).map_err(|_| Error::InvalidInput)?;
+        let height = u32::try_from(surface.height()).map_err(|_| Error::InvalidInput)?;
+        if width == 0 || height == 0 || surface.depth() != 1 {
+            return Err(Error::InvalidInput);
+        }
+
+        let pixels = surface
+            .to_rgba()
+            .iter()
+            .map(|&x| (x.clamp(0.0, 1.0) * 255.0).round() as u8)
+            .collect::<Vec<_>>();
+
+        let mut params = CompressorParams::new();
+        params.set_basis_format(self.format.into());
+        params.set_generate_mipmaps(self.mipmaps);
+        params.set_print_status_to_stdout(false);
+        params.set_color_space(if self.srgb {
+            ColorSpace::Srgb
+        } else {
+            ColorSpace::Linear
+        });
+        match self.format {
+            BasisFormat::Uastc => params.set_uastc_quality_level(match self.quality {
+                Quality::Fastest => UASTC_QUALITY_MIN,
+                Quality::Production => UASTC_QUALITY_SLOWER,
+                Quality::Highest => UASTC_QUALITY_MAX,
+                _ => UASTC_QUALITY_DEFAULT,
+            }),
+            BasisFormat::Etc1s => params.set_compression_level(match self.quality {
+                Quality::Fastest => 0,
+                Quality::Production => BASISU_MAX_COMPRESSION_LEVEL - 1,
+                Quality::Highest => BASISU_MAX_COMPRESSION_LEVEL,
+                _ => BASISU_DEFAULT_COMPRESSION_LEVEL,
+            }),
+        }
+        params.source_image_mut(0).init(&pixels, width, height, 4);
+
+        let threads = self
+            .threads
+            .or_else(|| thread::available_parallelism().ok().map(usize::from))
+            .unwrap_or(1);
+        let mut compressor = BasisCompressor::new(u32::try_from(threads).unwrap_or(1));
+
+        // @SAFETY: The parameters are fully initialized above, and the source image
+        // has the dimensions it was initialized with.
+        unsafe {
+            if !compressor.init(&params) {
+                return Err(Error::InvalidInput);
+            }
+            compressor.process().map_err(|e| {
+                log::error!("Basis compression failed: {:?}", e);
+                Error::Unknown
+            })?;
+        }
+
+        Ok(compressor.basis_file().to_vec())
+    }
+
+    /// Compress the `surface` into the contents of a `.ktx2` file, as used by the glTF
+    /// `KHR_texture_basisu` extension. Only `BasisFormat::Uastc` can be stored in a
+    /// ktx2 file by this method.
+    ///
+    /// # Errors
+    ///
+    /// If the format is `BasisFormat::Etc1s`, then this method will fail with
+    /// [`Error::UnsupportedFeature`]. Otherwise, this fails in the same way as
+    /// [`compress`].
+    ///
+    /// [`Error::UnsupportedFeature`]: enum.Error.html#variant.UnsupportedFeature
+    /// [`compress`]: struct.BasisOptions.html#method.compress
+    pub fn compress_ktx2(&self, surface: &Surface) -> Result<Vec<u8>, Error> {
+        // Etc1s data must be stored with the BasisLZ supercompression scheme, which
+        // is not produced by the basis encoder.
+        if self.format != BasisFormat::Uastc {
+            return Err(Error::UnsupportedFeature);
+        }
+
+        let basis = self.compress(surface)?;
+        let levels = basis_levels(&basis).ok_or(Error::Unknown)?;
+        Ok(write_uastc_ktx2(&levels, self.srgb))
+    }
+}
+
+/// A mipmap level read from a `.basis` file.
+struct BasisLevel<'a> {
+    width: u32,
+    height: u32,
+    has_alpha: bool,
+    data: &'a [u8],
+}
+
+/// Read a little endian integer of `len` bytes at `offset`.
+fn read_packed(bytes: &[u8], offset: usize, len: usize) -> Option<u32> {
+    let bytes = bytes.get(offset..offset + len)?;
+    Some(
+        bytes
+            .iter()
+            .rev()
+            .fold(0, |value, &byte| (value << 8) | u32::from(byte)),
+    )
+}
+
+/// Get the mipmap levels of the first image in the `.basis` file `basis`, ordered
+/// from the largest level.
+fn basis_levels(basis: &[u8]) -> Option<Vec<BasisLevel<'_>>> {
+    // The offsets are those of the packed `basis_file_header` and `basis_slice_desc`
+    // structures of the basis file format.
+    const BASIS_SIGNATURE: u32 = 0x4273;
+    const SLICE_DESC_SIZE: usize = 23;
+    const SLICE_HAS_ALPHA: u32 = 0x1;
+
+    if read_packed(basis, 0, 2)? != BASIS_SIGNATURE {
+        return None;
+    }
+    let total_slices = read_packed(basis, 14, 3)? as usize;
+    let slice_desc_offset = read_packed(basis, 65, 4)? as usize;
+
+    let mut levels = vec![];
+    for slice in 0..total_slices {
+        let desc = slice_desc_offset + slice * SLICE_DESC_SIZE;
+        if read_packed(basis, desc, 3)? != 0 {
+            continue;
+        }
+        let level = read_packed(basis, desc + 3, 1)? as usize;
+        let offset = read_packed(basis, desc + 13, 4)? as usize;
+        let len = read_packed(basis, desc + 17, 4)? as usize;
+        levels.push((
+            level,
+            BasisLevel {
+                width: read_packed(basis, desc + 5, 2)?,
+                height: read_packed(basis, desc + 7, 2)?,
+                has_alpha: read_packed(basis, desc + 4, 1)? & SLICE_HAS_ALPHA != 0,
+                data: basis.get(offset..offset + len)?,
+            },
+        ));
+    }
+
+    levels.sort_by_key(|&(level, _)| level);
+    if levels.is_empty() {
+        return None;
+    }
+    Some(levels.into_iter().map(|(_, level)| level).collect())
+}
+
+/// Write the uastc mipmap `levels` into a ktx2 file.
+fn write_uastc_ktx2(levels: &[BasisLevel<'_>], srgb: bool) -> Vec<u8> {
+    const KTX2_IDENTIFIER: [u8; 12] = [
+        0xAB, b'K', b'T', b'X', b' ', b'2', b'0', 0xBB, b'\r', b'\n', 0x1A, b'\n',
+    ];
+    const HEADER_SIZE: usize = 80;
+    const LEVEL_INDEX_SIZE: usize = 24;
+    const DFD_SIZE: u32 = 44;
+    const KHR_DF_MODEL_UASTC: u8 = 166;
+    const KHR_DF_PRIMARIES_BT709: u8 = 1;
+    const KHR_DF_TRANSFER_LINEAR: u8 = 1;
+    const KHR_DF_TRANSFER_SRGB: u8 = 2;
+    const KHR_DF_CHANNEL_UASTC_RGB: u8 = 0;
+    const KHR_DF_CHANNEL_UASTC_RGBA: u8 = 3;
+    const UASTC_BLOCK_SIZE: usize = 16;
+
+    let top = &levels[0];
+    let dfd_offset = HEADER_SIZE + LEVEL_INDEX_SIZE * levels.len();
+
+    let mut out = Vec::new();
+    let u32_le = |out: &mut Vec<u8>, value: u32| out.extend_from_slice(&value.to_le_bytes());
+    let u64_le = |out: &mut Vec<u8>, value: u64| out.extend_from_slice(&value.to_le_bytes());
+
+    out.extend_from_slice(&KTX2_IDENTIFIER);
+    // vkFormat is VK_FORMAT_UNDEFINED for basis data, with a type size of 1.
+    u32_le(&mut out, 0);
+    u32_le(&mut out, 1);
+    u32_le(&mut out, top.width);
+    u32_le(&mut out, top.height);
+    // The depth and layer count are 0 for a 2d texture which is not an array.
+    u32_le(&mut out, 0);
+    u32_le(&mut out, 0);
+    u32_le(&mut out, 1);
+    u32_le(&mut out, levels.len() as u32);
+    // No supercompression.
+    u32_le(&mut out, 0);
+
+    u32_le(&mut out, dfd_offset as u32);
+    u32_le(&mut out, DFD_SIZE);
+    // There is no key/value or supercompression global data.
+    u32_le(&mut out, 0);
+    u32_le(&mut out, 0);
+    u64_le(&mut out, 0);
+    u64_le(&mut out, 0);
+
+    // The levels are stored from the smallest to the largest, each aligned to the
+    // size of a block.
+    let mut offsets = vec![0; levels.len()];
+    let mut offset = dfd_offset + DFD_SIZE as usize;
+    for (index, level) in levels.iter().enumerate().rev() {
+        offset = offset.div_ceil(UASTC_BLOCK_SIZE) * UASTC_BLOCK_SIZE;
+        offsets[index] = offset;
+        offset += level.data.len();
+    }
+    for (level, &offset) in levels.iter().zip(&offsets) {
+        u64_le(&mut out, offset as u64);
+        u64_le(&mut out, level.data.len() as u64);
+        u64_le(&mut out, level.data.len() as u64);
+    }
+
+    // The data format descriptor contains a single basic block with one sample.
+    u32_le(&mut out, DFD_SIZE);
+    u32_le(&mut out, 0);
+    out.extend_from_slice(&2u16.to_le_bytes());
+    out.extend_from_slice(&(DFD_SIZE as u16 - 4).to_le_bytes());
+    out.push(KHR_DF_MODEL_UASTC);
+    out.push(KHR_DF_PRIMARIES_BT709);
+    out.push(if srgb {
+        KHR_DF_TRANSFER_SRGB
+    } else {
+        KHR_DF_TRANSFER_LINEAR
+    });
+    out.push(0);
+    out.extend_from_slice(&[3, 3, 0, 0]);
+    out.extend_from_slice(&[UASTC_BLOCK_SIZE as u8, 0, 0, 0, 0, 0, 0, 0]);
+    out.extend_from_slice(&0u16.to_le_bytes());
+    out.push(127);
+    out.push(if top.has_alpha {
+        KHR_DF_CHANNEL_UASTC_RGBA
+    } else {
+        KHR_DF_CHANNEL_UASTC_RGB
+    });
+    out.extend_from_slice(&[0, 0, 0, 0]);
+    u32_le(&mut out, 0);
+    u32_le(&mut out, u32::MAX);
+
+    for (level, &offset) in levels.iter().zip(&offsets).rev() {
+        out.resize(offset, 0);
+        out.extend_from_slice(level.data);
+    }
+    out
+}
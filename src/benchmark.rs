@@ -0,0 +1,278 @@
+// Copyright © 2019-2020 George Burton
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::{
+    CompressError, CompressionOptions, CompressionOutput, Context, CubeFace, Error, Format,
+    OutputLocation, OutputOptions, Quality, Surface,
+};
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+/// Measures how quickly a set of images can be compressed to each combination of
+/// [`Format`] and [`Quality`], and the size of the compressed output.
+///
+/// This can be used to compare the speed of cpu and [`cuda`] compression, or the
+/// cost of each quality level, on the hardware the program is running on.
+///
+/// # Example
+///
+/// ```no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use nvtt_rs::{Benchmark, Format, Quality, Surface};
+///
+/// let inputs = [Surface::load("albedo.png")?];
+/// let report = Benchmark::new().iterations(3).run(
+///     &inputs,
+///     &[Format::Bc1, Format::Bc7],
+///     &[Quality::Fastest, Quality::Production],
+/// )?;
+///
+/// println!("{}", report);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Format`]: enum.Format.html
+/// [`Quality`]: enum.Quality.html
+/// [`cuda`]: index.html#cuda
+#[derive(Clone, Debug)]
+pub struct Benchmark {
+    cuda: bool,
+    iterations: usize,
+}
+
+impl Default for Benchmark {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Benchmark {
+    /// Create a new `Benchmark`, which compresses each image once on the cpu.
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            cuda: false,
+            iterations: 1,
+        }
+    }
+
+    /// Set whether the images are compressed using cuda acceleration.
+    #[inline]
+    pub fn cuda(&mut self, cuda: bool) -> &mut Self {
+        self.cuda = cuda;
+        self
+    }
+
+    /// Set the number of times each image is compressed. The reported time is the
+    /// average of all iterations. Values less than `1` are treated as `1`.
+    #[inline]
+    pub fn iterations(&mut self, iterations: usize) -> &mut Self {
+        self.iterations = iterations.max(1);
+        self
+    }
+
+    /// Compress each of the `inputs` with every combination of `formats` and
+    /// `qualities`. The textures are compressed into memory, without mipmaps.
+    ///
+    /// Failing to compress a single texture does not stop the benchmark. The error
+    /// is reported in the [`BenchmarkResult`] for that texture instead.
+    ///
+    /// # Errors
+    ///
+    /// If cuda acceleration was requested but is not available, then this method will
+    /// fail with [`Error::CudaUnavailable`].
+    ///
+    /// [`BenchmarkResult`]: struct.BenchmarkResult.html
+    /// [`Error::CudaUnavailable`]: enum.Error.html#variant.CudaUnavailable
+    pub fn run(
+        &self,
+        inputs: &[Surface],
+        formats: &[Format],
+        qualities: &[Quality],
+    ) -> Result<BenchmarkReport, Error> {
+        let mut context = Context::new()?;
+        if self.cuda {
+            context.compressor_mut().try_enable_cuda_acceleration()?;
+        }
+
+        let mut output_options = OutputOptions::new()?;
+        output_options
+            .set_output_location(OutputLocation::Buffer)
+            .map_err(|_| Error::InvalidInput)?;
+
+        let mut results = Vec::with_capacity(inputs.len() * formats.len() * qualities.len());
+        for (input, surface) in inputs.iter().enumerate() {
+            for &format in formats {
+                for &quality in qualities {
+                    let mut compression_options = CompressionOptions::new()?;
+                    compression_options.set_format(format).set_quality(quality);
+
+                    let mut elapsed = Duration::default();
+                    let mut output_size = Ok(0);
+                    for _ in 0..self.iterations {
+                        let start = Instant::now();
+                        let output = context.compress(
+                            surface,
+                            CubeFace::default(),
+                            0,
+                            &compression_options,
+                            &output_options,
+                        );
+                        elapsed += start.elapsed();
+
+                        output_size = output.map(|output| match output {
+                            CompressionOutput::Memory { data, .. } => data.len(),
+                            CompressionOutput::File => 0,
+                        });
+                        if output_size.is_err() {
+                            break;
+                        }
+                    }
+
+                    results.push(BenchmarkResult {
+                        input,
+                        format,
+                        quality,
+                        pixels: surface.width() * surface.height() * surface.depth(),
+                        elapsed: elapsed / self.iterations as u32,
+                        output_size,
+                    });
+                }
+            }
+        }
+
+        Ok(BenchmarkReport {
+            cuda: self.cuda,
+            results,
+        })
+    }
+}
+
+/// The time taken to compress a single input of a [`Benchmark`] to one format and
+/// quality.
+///
+/// [`Benchmark`]: struct.Benchmark.html
+#[derive(Clone, Debug)]
+pub struct BenchmarkResult {
+    input: usize,
+    format: Format,
+    quality: Quality,
+    pixels: usize,
+    elapsed: Duration,
+    output_size: Result<usize, CompressError>,
+}
+
+impl BenchmarkResult {
+    /// The index of the input `Surface`.
+    #[inline]
+    pub fn input(&self) -> usize {
+        self.input
+    }
+
+    /// The format the input was compressed to.
+    #[inline]
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// The quality the input was compressed with.
+    #[inline]
+    pub fn quality(&self) -> Quality {
+        self.quality
+    }
+
+    /// The average time taken to compress the input.
+    #[inline]
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// The number of megapixels compressed per second.
+    #[inline]
+    pub fn megapixels_per_second(&self) -> f64 {
+        let seconds = self.elapsed.as_secs_f64();
+        if seconds == 0.0 {
+            return 0.0;
+        }
+        self.pixels as f64 / 1_000_000.0 / seconds
+    }
+
+    /// The size of the compressed texture in bytes, including the container header,
+    /// or the error if the input could not be compressed.
+    #[inline]
+    pub fn output_size(&self) -> Result<usize, &CompressError> {
+        self.output_size.as_ref().map(|&size| size)
+    }
+}
+
+/// The results of running a [`Benchmark`]. The report can be printed as a table
+/// using its `Display` implementation.
+///
+/// [`Benchmark`]: struct.Benchmark.html
+#[derive(Clone, Debug)]
+pub struct BenchmarkReport {
+    cuda: bool,
+    results: Vec<BenchmarkResult>,
+}
+
+impl BenchmarkReport {
+    /// Whether the benchmark was run with cuda acceleration.
+    #[inline]
+    pub fn is_cuda_accelerated(&self) -> bool {
+        self.cuda
+    }
+
+    /// The result of each combination of input, format and quality, in the order
+    /// they were run.
+    #[inline]
+    pub fn results(&self) -> &[BenchmarkResult] {
+        &self.results
+    }
+}
+
+impl fmt::Display for BenchmarkReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<6} {:<14} {:<11} {:>10} {:>9} {:>12}",
+            "input", "format", "quality", "time (ms)", "MP/s", "size (bytes)"
+        )?;
+        for result in &self.results {
+            let format = format!("{:?}", result.format);
+            let quality = format!("{:?}", result.quality);
+            write!(f, "{:<6} {:<14} {:<11} ", result.input, format, quality)?;
+            match result.output_size {
+                Ok(size) => writeln!(
+                    f,
+                    "{:>10.2} {:>9.2} {:>12}",
+                    result.elapsed.as_secs_f64() * 1000.0,
+                    result.megapixels_per_second(),
+                    size
+                )?,
+                Err(ref e) => writeln!(f, "failed: {}", e.error())?,
+            }
+        }
+        Ok(())
+    }
+}
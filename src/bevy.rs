@@ -0,0 +1,403 @@
+// Copyright © 2019-2020 George Burton
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+#[cfg(feature = "basis")]
+use crate::{BasisFormat, BasisOptions};
+use crate::{
+    CompressError, CompressionOptions, CompressionOutput, Container, Context, Error, Format,
+    MipmapFilter, OutputOptions, Quality, Surface,
+};
+use bevy_asset::{
+    io::Writer,
+    saver::{AssetSaver, SavedAsset},
+    AsyncWriteExt,
+};
+use bevy_image::{Image, ImageFormat, ImageFormatSetting, ImageLoader, ImageLoaderSettings};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, error::Error as ErrorTrait, fmt, io};
+
+/// Describes how an image is used by a material, which selects the [`TexturePreset`]
+/// used to compress it.
+///
+/// [`TexturePreset`]: struct.TexturePreset.html
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum TextureLabel {
+    /// A color texture, such as a base color or emissive map. This is the default.
+    #[default]
+    Albedo,
+    /// A tangent space normal map.
+    Normal,
+    /// A texture containing linear data, such as an occlusion, roughness or
+    /// metallic map.
+    Mask,
+}
+
+/// The settings used to compress images with a [`TextureLabel`].
+///
+/// [`TextureLabel`]: enum.TextureLabel.html
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct TexturePreset {
+    format: Format,
+    quality: Quality,
+    mipmaps: bool,
+    srgb: bool,
+    normal_map: bool,
+    #[cfg(feature = "basis")]
+    ktx2: bool,
+}
+
+impl TexturePreset {
+    /// Create a new `TexturePreset` which compresses images to `format` in a dds file.
+    /// Mipmaps are generated, and the image is treated as linear data.
+    #[inline]
+    pub fn new(format: Format) -> Self {
+        Self {
+            format,
+            quality: Quality::default(),
+            mipmaps: true,
+            srgb: false,
+            normal_map: false,
+            #[cfg(feature = "basis")]
+            ktx2: false,
+        }
+    }
+
+    /// Get the default preset for images with the given `label`.
+    ///
+    /// Albedo textures are compressed to `Format::Bc7` as srgb data, normal maps to
+    /// `Format::Bc5`, and masks to `Format::Bc7` as linear data.
+    pub fn for_label(label: TextureLabel) -> Self {
+        let mut preset = match label {
+            TextureLabel::Albedo | TextureLabel::Mask => Self::new(Format::Bc7),
+            TextureLabel::Normal => Self::new(Format::Bc5),
+        };
+        preset
+            .srgb(label == TextureLabel::Albedo)
+            .normal_map(label == TextureLabel::Normal);
+        preset
+    }
+
+    /// Set the format which images are compressed to.
+    #[inline]
+    pub fn format(&mut self, format: Format) -> &mut Self {
+        self.format = format;
+        self
+    }
+
+    /// Set the quality of the compression.
+    #[inline]
+    pub fn quality(&mut self, quality: Quality) -> &mut Self {
+        self.quality = quality;
+        self
+    }
+
+    /// Set whether a full chain of mipmaps is generated.
+    #[inline]
+    pub fn mipmaps(&mut self, mipmaps: bool) -> &mut Self {
+        self.mipmaps = mipmaps;
+        self
+    }
+
+    /// Set whether the image contains srgb data. Mipmaps of srgb images are filtered
+    /// in linear space.
+    #[inline]
+    pub fn srgb(&mut self, srgb: bool) -> &mut Self {
+        self.srgb = srgb;
+        self
+    }
+
+    /// Set whether the image is a normal map. The normals in each mipmap level of a
+    /// normal map are renormalized.
+    #[inline]
+    pub fn normal_map(&mut self, normal_map: bool) -> &mut Self {
+        self.normal_map = normal_map;
+        self
+    }
+
+    /// Set whether images are compressed to UASTC and written into a ktx2 file with
+    /// [`BasisOptions::compress_ktx2`], instead of being compressed to the preset
+    /// format in a dds file.
+    ///
+    /// The image can only be loaded if bevy's `ktx2` and `basis-universal` features
+    /// are enabled.
+    ///
+    /// # Notes
+    ///
+    /// This method requires the [`basis`] feature.
+    ///
+    /// [`BasisOptions::compress_ktx2`]: struct.BasisOptions.html#method.compress_ktx2
+    /// [`basis`]: index.html#basis
+    #[cfg(feature = "basis")]
+    #[inline]
+    pub fn ktx2(&mut self, ktx2: bool) -> &mut Self {
+        self.ktx2 = ktx2;
+        self
+    }
+}
+
+/// The per-asset settings of a [`BevyImageSaver`], which are stored in the `.meta`
+/// file of each image.
+///
+/// [`BevyImageSaver`]: struct.BevyImageSaver.html
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct BevyImageSettings {
+    /// The label which selects the preset used to compress the image.
+    pub label: TextureLabel,
+}
+
+/// An `AssetSaver` which compresses bevy `Image`s with nvtt when assets are
+/// processed, so that compressed textures are loaded at runtime.
+///
+/// The [`TexturePreset`] used for each image is selected by the [`TextureLabel`] in
+/// its [`BevyImageSettings`].
+///
+/// # Example
+///
+/// ```ignore
+/// use bevy::{asset::processor::LoadTransformAndSave, image::ImageLoader, prelude::*};
+/// use bevy::asset::transformer::IdentityAssetTransformer;
+/// use nvtt_rs::{BevyImageSaver, Format, TextureLabel, TexturePreset};
+///
+/// type CompressImage =
+///     LoadTransformAndSave<ImageLoader, IdentityAssetTransformer<Image>, BevyImageSaver>;
+///
+/// let mut saver = BevyImageSaver::new();
+/// saver.preset(TextureLabel::Mask, TexturePreset::new(Format::Bc4));
+///
+/// App::new()
+///     .add_plugins(DefaultPlugins.set(AssetPlugin {
+///         mode: AssetMode::Processed,
+///         ..default()
+///     }))
+///     .register_asset_processor::<CompressImage>(CompressImage::new(
+///         IdentityAssetTransformer::new(),
+///         saver,
+///     ))
+///     .set_default_asset_processor::<CompressImage>("png");
+/// ```
+///
+/// # Notes
+///
+/// This type requires the [`bevy`] feature.
+///
+/// [`TexturePreset`]: struct.TexturePreset.html
+/// [`TextureLabel`]: enum.TextureLabel.html
+/// [`BevyImageSettings`]: struct.BevyImageSettings.html
+/// [`bevy`]: index.html#bevy
+#[derive(Clone, Debug, Default)]
+pub struct BevyImageSaver {
+    presets: HashMap<TextureLabel, TexturePreset>,
+}
+
+impl BevyImageSaver {
+    /// Create a new `BevyImageSaver`, which uses the default preset for each label.
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the `preset` used to compress images with the given `label`.
+    #[inline]
+    pub fn preset(&mut self, label: TextureLabel, preset: TexturePreset) -> &mut Self {
+        self.presets.insert(label, preset);
+        self
+    }
+
+    /// Get the preset used to compress images with the given `label`.
+    #[inline]
+    pub fn preset_for(&self, label: TextureLabel) -> TexturePreset {
+        self.presets
+            .get(&label)
+            .cloned()
+            .unwrap_or_else(|| TexturePreset::for_label(label))
+    }
+}
+
+impl AssetSaver for BevyImageSaver {
+    type Asset = Image;
+    type Settings = BevyImageSettings;
+    type OutputLoader = ImageLoader;
+    type Error = BevySaverError;
+
+    async fn save(
+        &self,
+        writer: &mut Writer,
+        image: SavedAsset<'_, Image>,
+        settings: &BevyImageSettings,
+    ) -> Result<ImageLoaderSettings, BevySaverError> {
+        let preset = self.preset_for(settings.label);
+        // The nvtt objects cannot be held across an await point, so the image is
+        // compressed before anything is written.
+        let (data, format) = compress_image(&image, &preset)?;
+        writer.write_all(&data).await?;
+
+        Ok(ImageLoaderSettings {
+            format: ImageFormatSetting::Format(format),
+            is_srgb: preset.srgb,
+            sampler: image.sampler.clone(),
+            asset_usage: image.asset_usage,
+            texture_format: None,
+        })
+    }
+}
+
+/// Compress `image` with the settings in `preset`, returning the contents of the
+/// compressed file and its format.
+fn compress_image(
+    image: &Image,
+    preset: &TexturePreset,
+) -> Result<(Vec<u8>, ImageFormat), BevySaverError> {
+    if image.data.is_none() {
+        return Err(BevySaverError::UninitializedImage);
+    }
+    if image.texture_descriptor.size.depth_or_array_layers != 1 {
+        return Err(BevySaverError::UnsupportedImage);
+    }
+
+    let size = image.size();
+    let pixels = image
+        .clone()
+        .try_into_dynamic()
+        .map_err(|_| BevySaverError::UnsupportedImage)?
+        .to_rgba8()
+        .into_raw()
+        .into_iter()
+        .map(|x| f32::from(x) / 255.0)
+        .collect::<Vec<_>>();
+    let mut surface = Surface::from_rgba(size.x as usize, size.y as usize, &pixels)?;
+    surface.set_normal_map(preset.normal_map);
+
+    #[cfg(feature = "basis")]
+    {
+        if preset.ktx2 {
+            let data = BasisOptions::new(BasisFormat::Uastc)
+                .quality(preset.quality)
+                .mipmaps(preset.mipmaps)
+                .srgb(preset.srgb)
+                .compress_ktx2(&surface)?;
+            return Ok((data, ImageFormat::Ktx2));
+        }
+    }
+
+    let mut mipmaps = vec![surface];
+    if preset.mipmaps {
+        let mut level = mipmaps[0].clone();
+        if preset.srgb {
+            level.to_linear_from_srgb();
+        }
+        while level.build_next_mipmap(MipmapFilter::Box) {
+            let mut mipmap = level.clone();
+            if preset.srgb {
+                mipmap.to_srgb();
+            }
+            if preset.normal_map {
+                mipmap.normalize();
+            }
+            mipmaps.push(mipmap);
+        }
+    }
+
+    let mut compression_options = CompressionOptions::new()?;
+    compression_options
+        .set_format(preset.format)
+        .set_quality(preset.quality);
+
+    // The output is written into memory by default.
+    let mut output_options = OutputOptions::new()?;
+    output_options
+        .set_container(match preset.format {
+            Format::Bc6 | Format::Bc7 => Container::Dds10,
+            _ => Container::Dds,
+        })
+        .set_srgb_flag(preset.srgb);
+
+    let context = Context::new()?;
+    match context.compress_mipmaps(&mipmaps, &compression_options, &output_options)? {
+        CompressionOutput::Memory { data, .. } => Ok((data, ImageFormat::Dds)),
+        CompressionOutput::File => Err(BevySaverError::Nvtt(Error::FileWrite)),
+    }
+}
+
+/// An error which may occur while saving an image with a [`BevyImageSaver`].
+///
+/// [`BevyImageSaver`]: struct.BevyImageSaver.html
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum BevySaverError {
+    /// The compressed image could not be written.
+    Io(io::Error),
+    /// The image has no data.
+    UninitializedImage,
+    /// The image is not a single 2D image in an uncompressed format.
+    UnsupportedImage,
+    /// The nvtt options could not be created, or the image could not be converted.
+    Nvtt(Error),
+    /// The image could not be compressed.
+    Compress(CompressError),
+}
+
+impl fmt::Display for BevySaverError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            BevySaverError::Io(ref e) => write!(f, "Could not write the image: {}", e),
+            BevySaverError::UninitializedImage => {
+                f.write_str("Cannot compress an uninitialized image")
+            }
+            BevySaverError::UnsupportedImage => {
+                f.write_str("Only uncompressed 2D images can be compressed")
+            }
+            BevySaverError::Nvtt(ref e) => write!(f, "Could not compress the image: {}", e),
+            BevySaverError::Compress(ref e) => write!(f, "Could not compress the image: {}", e),
+        }
+    }
+}
+
+impl ErrorTrait for BevySaverError {
+    #[inline]
+    fn source(&self) -> Option<&(dyn ErrorTrait + 'static)> {
+        match *self {
+            BevySaverError::Io(ref e) => Some(e),
+            BevySaverError::Nvtt(ref e) => Some(e),
+            BevySaverError::Compress(ref e) => Some(e),
+            BevySaverError::UninitializedImage | BevySaverError::UnsupportedImage => None,
+        }
+    }
+}
+
+impl From<io::Error> for BevySaverError {
+    #[inline]
+    fn from(e: io::Error) -> Self {
+        BevySaverError::Io(e)
+    }
+}
+
+impl From<CompressError> for BevySaverError {
+    #[inline]
+    fn from(e: CompressError) -> Self {
+        BevySaverError::Compress(e)
+    }
+}
+
+impl From<Error> for BevySaverError {
+    #[inline]
+    fn from(e: Error) -> Self {
+        BevySaverError::Nvtt(e)
+    }
+}
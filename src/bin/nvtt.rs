@@ -0,0 +1,255 @@
+// Copyright © 2019-2020 George Burton
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! A command line interface to nvtt, similar to the `nvcompress` tool.
+
+use nvtt_rs::{
+    Channel, CompressionOptions, Compressor, Container, CubeFace, Format, InputOptions, MipLevel,
+    OutputOptions, Quality, Surface,
+};
+use std::{
+    env,
+    error::Error,
+    ffi::OsString,
+    path::{Path, PathBuf},
+    process,
+};
+
+const USAGE: &str = "\
+Usage:
+    nvtt compress <input> -o <output> [options]
+    nvtt decompress <input> -o <output>
+    nvtt info <input>
+
+Compress options:
+    -f, --format <format>        The output format (default: bc1). One of:
+                                     bc1, bc1a, bc2, bc3, bc3n, bc3-rgbm, bc4, bc5, bc6, bc7,
+                                     etc1, etc2-r, etc2-rg, etc2-rgb, etc2-rgba, etc2-rgbm,
+                                     etc2-rgb-a1, pvr-2bpp-rgb, pvr-2bpp-rgba, pvr-4bpp-rgb,
+                                     pvr-4bpp-rgba, rgb, rgba
+    -q, --quality <quality>      The compression quality (default: normal). One of:
+                                     fastest, normal, production, highest
+    -c, --container <container>  The output container: dds, dds10 or ktx. This is chosen
+                                 from the output extension by default.
+    -m, --mips                   Generate mipmaps.
+        --srgb                   Mark the output as containing srgb data.
+    -h, --help                   Print this message.";
+
+type BoxError = Box<dyn Error + Send + Sync + 'static>;
+
+/// Get the value following a command line flag.
+fn value(args: &mut impl Iterator<Item = OsString>, flag: &str) -> Result<String, BoxError> {
+    args.next()
+        .and_then(|v| v.into_string().ok())
+        .ok_or_else(|| format!("Expected a value after `{}`", flag).into())
+}
+
+/// The parsed command line arguments.
+#[derive(Debug, Default)]
+struct Args {
+    command: String,
+    input: Option<PathBuf>,
+    output: Option<PathBuf>,
+    format: Option<Format>,
+    quality: Option<Quality>,
+    container: Option<Container>,
+    mips: bool,
+    srgb: bool,
+}
+
+impl Args {
+    fn parse<I: IntoIterator<Item = OsString>>(args: I) -> Result<Self, BoxError> {
+        let mut args = args.into_iter();
+        let mut parsed = Args::default();
+
+        while let Some(arg) = args.next() {
+            let flag = arg.to_string_lossy().into_owned();
+            match &flag[..] {
+                "-h" | "--help" => {
+                    parsed.command = "help".to_string();
+                    return Ok(parsed);
+                }
+                "-o" | "--output" => {
+                    parsed.output = args.next().map(PathBuf::from);
+                    if parsed.output.is_none() {
+                        return Err(format!("Expected a path after `{}`", flag).into());
+                    }
+                }
+                "-f" | "--format" => {
+                    let v = value(&mut args, &flag)?;
+                    parsed.format = Some(v.parse()?);
+                }
+                "-q" | "--quality" => {
+                    let v = value(&mut args, &flag)?;
+                    parsed.quality = Some(v.parse()?);
+                }
+                "-c" | "--container" => {
+                    let v = value(&mut args, &flag)?;
+                    parsed.container = Some(v.parse()?);
+                }
+                "-m" | "--mips" => parsed.mips = true,
+                "--srgb" => parsed.srgb = true,
+                _ if flag.starts_with('-') => {
+                    return Err(format!("Unknown option `{}`", flag).into());
+                }
+                _ if parsed.command.is_empty() => parsed.command = flag,
+                _ if parsed.input.is_none() => parsed.input = Some(PathBuf::from(arg)),
+                _ => return Err(format!("Unexpected argument `{}`", flag).into()),
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    fn input(&self) -> Result<&Path, BoxError> {
+        self.input
+            .as_deref()
+            .ok_or_else(|| "No input file was given".into())
+    }
+
+    fn output(&self) -> Result<&Path, BoxError> {
+        self.output
+            .as_deref()
+            .ok_or_else(|| "No output file was given. Use `-o <output>`".into())
+    }
+}
+
+fn compress(args: &Args) -> Result<(), BoxError> {
+    let (input, output) = (args.input()?, args.output()?);
+    let format = args.format.unwrap_or(Format::Bc1);
+
+    let image =
+        image::open(input).map_err(|e| format!("Could not open {}: {}", input.display(), e))?;
+
+    let mut input_options = InputOptions::new()?;
+    input_options
+        .set_image(&image, CubeFace::default(), MipLevel(0))?
+        .set_mipmap_generation(args.mips, None);
+
+    let mut compression_options = CompressionOptions::new()?;
+    compression_options
+        .set_format(format)
+        .set_quality(args.quality.unwrap_or_default());
+
+    // Bc6 and Bc7 can only be described by the DX10 extension of the dds header.
+    let container = args.container.unwrap_or_else(|| {
+        let is_ktx = output
+            .extension()
+            .map(|ext| ext.eq_ignore_ascii_case("ktx"))
+            == Some(true);
+        match format {
+            _ if is_ktx => Container::Ktx,
+            Format::Bc6 | Format::Bc7 => Container::Dds10,
+            _ => Container::Dds,
+        }
+    });
+
+    let mut output_options = OutputOptions::new()?;
+    output_options
+        .set_output_location(output)?
+        .set_container(container)
+        .set_srgb_flag(args.srgb);
+
+    let compressor = Compressor::new()?;
+    compressor.compress(&compression_options, &input_options, &output_options)?;
+    Ok(())
+}
+
+fn decompress(args: &Args) -> Result<(), BoxError> {
+    let (input, output) = (args.input()?, args.output()?);
+    Surface::load(input)?.save(output)?;
+    Ok(())
+}
+
+fn info(args: &Args) -> Result<(), BoxError> {
+    let input = args.input()?;
+    println!("{}", input.display());
+
+    // Compressed textures are described from their header, and are only decoded
+    // if nvtt is able to read them.
+    let header = nvtt_rs::inspect(input).ok();
+    if let Some(header) = &header {
+        println!("  Container: {:?}", header.container);
+        match header.format {
+            Some(format) => println!("  Format: {:?}", format),
+            None => println!("  Format: unknown"),
+        }
+        if let Some(dxgi_format) = header.dxgi_format {
+            println!("  DXGI format: {:?}", dxgi_format);
+        }
+        println!("  Texture type: {:?}", header.texture_type);
+        println!(
+            "  Dimensions: {} x {} x {}",
+            header.width, header.height, header.depth
+        );
+        println!("  Array length: {}", header.array_length);
+        println!("  Mipmaps: {}", header.mip_count);
+        println!("  sRGB: {}", header.srgb);
+    }
+
+    let surface = match Surface::load(input) {
+        Ok(surface) => surface,
+        Err(_) if header.is_some() => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+
+    if header.is_none() {
+        println!(
+            "  Dimensions: {} x {} x {}",
+            surface.width(),
+            surface.height(),
+            surface.depth()
+        );
+    }
+    println!("  Alpha mode: {:?}", surface.alpha_mode());
+    println!("  Normal map: {}", surface.is_normal_map());
+    for &channel in &Channel::ALL {
+        if let Some((min, max)) = surface.range(channel) {
+            println!(
+                "  {:?}: min = {:.4}, max = {:.4}, average = {:.4}",
+                channel,
+                min,
+                max,
+                surface.average(channel)
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn main() {
+    let result = Args::parse(env::args_os().skip(1)).and_then(|args| match &args.command[..] {
+        "compress" => compress(&args),
+        "decompress" => decompress(&args),
+        "info" => info(&args),
+        "help" => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        "" => Err(USAGE.into()),
+        command => Err(format!("Unknown command `{}`\n\n{}", command, USAGE).into()),
+    });
+
+    if let Err(e) = result {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
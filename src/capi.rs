@@ -0,0 +1,551 @@
+// Copyright © 2019-2020 George Burton
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! A C ABI over the [`Batch`] pipeline, for tools which are not written in Rust.
+//!
+//! The declarations of this module are in the `include/nvtt_rs.h` header, which can
+//! be regenerated with [`cbindgen`] using the `cbindgen.toml` configuration in the
+//! root of the repository. The library is built with:
+//!
+//! ```sh
+//! cargo rustc --release --features capi --crate-type cdylib
+//! ```
+//!
+//! Every function returns an [`NvttRsStatus`]. If a function fails, then a message
+//! describing the failure can be read with [`nvtt_rs_last_error_message`]. Panics are
+//! caught at the boundary and reported as `NvttRsStatus::Panicked`, rather than
+//! unwinding into the caller.
+//!
+//! # Notes
+//!
+//! This module requires the [`capi`] feature.
+//!
+//! [`Batch`]: ../struct.Batch.html
+//! [`cbindgen`]: https://github.com/mozilla/cbindgen
+//! [`NvttRsStatus`]: enum.NvttRsStatus.html
+//! [`nvtt_rs_last_error_message`]: fn.nvtt_rs_last_error_message.html
+//! [`capi`]: ../index.html#capi
+
+use crate::{
+    Batch, BatchError, BatchResult, Container, Format, MemoryBudget, NvttContainer, NvttFormat,
+    NvttQuality, Quality,
+};
+use std::{
+    cell::RefCell,
+    convert::TryFrom,
+    ffi::{CStr, CString},
+    fmt::Display,
+    os::raw::{c_char, c_void},
+    panic::{self, AssertUnwindSafe},
+    path::Path,
+    ptr,
+};
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// The result of a function in the C api.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum NvttRsStatus {
+    /// The function succeeded.
+    Ok = 0,
+    /// An argument was null, was not valid utf-8, or was not a valid enum value.
+    InvalidArgument = 1,
+    /// A glob pattern was invalid.
+    InvalidPattern = 2,
+    /// A texture could not be compressed.
+    CompressionFailed = 3,
+    /// The library panicked. Any batch passed to the function should be freed.
+    Panicked = 4,
+    /// Two inputs of a batch would be compressed into the same output file.
+    OutputCollision = 5,
+}
+
+/// A set of compression settings which can be applied to a batch.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum NvttRsPresetKind {
+    /// `Format::Bc1` with `Quality::Normal`. These are the defaults of a [`Batch`].
+    ///
+    /// [`Batch`]: ../struct.Batch.html
+    Default = 0,
+    /// `Format::Bc7` srgb data, for color textures.
+    Albedo = 1,
+    /// `Format::Bc5` linear data, for tangent space normal maps.
+    Normal = 2,
+    /// `Format::Bc7` linear data, for textures such as roughness or occlusion maps.
+    Mask = 3,
+}
+
+impl TryFrom<u32> for NvttRsPresetKind {
+    type Error = NvttRsStatus;
+
+    fn try_from(kind: u32) -> Result<Self, Self::Error> {
+        match kind {
+            0 => Ok(NvttRsPresetKind::Default),
+            1 => Ok(NvttRsPresetKind::Albedo),
+            2 => Ok(NvttRsPresetKind::Normal),
+            3 => Ok(NvttRsPresetKind::Mask),
+            _ => Err(invalid_argument(&format_args!(
+                "{} is not a valid NvttRsPresetKind",
+                kind
+            ))),
+        }
+    }
+}
+
+/// The settings used to compress textures. The `format`, `quality` and `container`
+/// are the raw values of the nvtt `NvttFormat`, `NvttQuality` and `NvttContainer`
+/// enums.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct NvttRsPreset {
+    /// The output format of the textures.
+    pub format: NvttFormat,
+    /// The compression quality.
+    pub quality: NvttQuality,
+    /// The output container. This is ignored unless `use_container` is set.
+    pub container: NvttContainer,
+    /// Whether the `container` is used instead of the default for the format.
+    pub use_container: bool,
+    /// Whether mipmaps are generated.
+    pub mipmaps: bool,
+    /// Whether the textures are marked as containing srgb data.
+    pub srgb: bool,
+}
+
+impl NvttRsPreset {
+    fn new(kind: NvttRsPresetKind) -> Self {
+        let (format, srgb) = match kind {
+            NvttRsPresetKind::Default => (Format::Bc1, false),
+            NvttRsPresetKind::Albedo => (Format::Bc7, true),
+            NvttRsPresetKind::Normal => (Format::Bc5, false),
+            NvttRsPresetKind::Mask => (Format::Bc7, false),
+        };
+        Self {
+            format: format.into(),
+            quality: Quality::Normal.into(),
+            container: Container::Dds.into(),
+            use_container: false,
+            mipmaps: true,
+            srgb,
+        }
+    }
+
+    fn to_batch(self) -> Result<Batch, NvttRsStatus> {
+        // `Format` and `Container` keep unknown values in their `Other` variants, which
+        // nvtt would reject much later, so they are checked here instead.
+        let format = match Format::from(self.format) {
+            Format::Other(raw) => {
+                return Err(invalid_argument(&format_args!(
+                    "{} is not a valid NvttFormat",
+                    raw
+                )))
+            }
+            format => format,
+        };
+        let container = match Container::from(self.container) {
+            Container::Other(raw) if self.use_container => {
+                return Err(invalid_argument(&format_args!(
+                    "{} is not a valid NvttContainer",
+                    raw
+                )))
+            }
+            container => container,
+        };
+        let quality = Quality::try_from(self.quality).map_err(|e| invalid_argument(&e))?;
+
+        let mut batch = Batch::new();
+        batch
+            .format(format)
+            .quality(quality)
+            .mipmaps(self.mipmaps)
+            .srgb(self.srgb);
+        if self.use_container {
+            batch.container(container);
+        }
+        Ok(batch)
+    }
+}
+
+/// The result of compressing a single file, which is passed to the callback of
+/// [`nvtt_rs_batch_run`]. The strings are only valid during the callback.
+///
+/// [`nvtt_rs_batch_run`]: fn.nvtt_rs_batch_run.html
+#[repr(C)]
+#[derive(Debug)]
+pub struct NvttRsBatchResult {
+    /// The path of the input image.
+    pub input: *const c_char,
+    /// The path of the compressed texture.
+    pub output: *const c_char,
+    /// `NvttRsStatus::Ok` if the file was compressed, or
+    /// `NvttRsStatus::CompressionFailed` otherwise.
+    pub status: NvttRsStatus,
+    /// A description of the error if the file could not be compressed, or null.
+    pub error_message: *const c_char,
+    /// Whether the texture was taken from the cache.
+    pub cached: bool,
+    /// The time taken to compress the file, in seconds.
+    pub elapsed_seconds: f64,
+}
+
+/// A callback which receives the result of each file in a batch.
+pub type NvttRsResultCallback =
+    Option<unsafe extern "C" fn(result: *const NvttRsBatchResult, user_data: *mut c_void)>;
+
+/// An opaque handle to a batch of files which are compressed with the same settings.
+#[derive(Debug)]
+pub struct NvttRsBatch {
+    batch: Batch,
+}
+
+fn set_last_error(message: &dyn Display) {
+    let message = CString::new(message.to_string().replace('\0', " ")).ok();
+    LAST_ERROR.with(|e| *e.borrow_mut() = message);
+}
+
+fn invalid_argument(message: &dyn Display) -> NvttRsStatus {
+    set_last_error(message);
+    NvttRsStatus::InvalidArgument
+}
+
+/// Convert a nul terminated `string` into a `str`.
+///
+/// # Safety
+///
+/// The `string` must be null, or point to a nul terminated string.
+unsafe fn to_str<'a>(string: *const c_char) -> Result<&'a str, NvttRsStatus> {
+    if string.is_null() {
+        return Err(invalid_argument(&"A string argument was null"));
+    }
+    CStr::from_ptr(string)
+        .to_str()
+        .map_err(|e| invalid_argument(&e))
+}
+
+/// Run `f`, converting a panic into `NvttRsStatus::Panicked`.
+fn catch_panic(f: impl FnOnce() -> NvttRsStatus) -> NvttRsStatus {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|payload| {
+        let message = payload
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("unknown panic");
+        set_last_error(&format_args!("nvtt_rs panicked: {}", message));
+        NvttRsStatus::Panicked
+    })
+}
+
+/// Run `f` with the batch behind the `batch` pointer.
+///
+/// # Safety
+///
+/// The `batch` must be null, or a pointer returned by `nvtt_rs_batch_new`.
+unsafe fn with_batch(
+    batch: *mut NvttRsBatch,
+    f: impl FnOnce(&mut Batch) -> Result<(), NvttRsStatus>,
+) -> NvttRsStatus {
+    catch_panic(|| match batch.as_mut() {
+        Some(batch) => f(&mut batch.batch).err().unwrap_or(NvttRsStatus::Ok),
+        None => invalid_argument(&"The batch was null"),
+    })
+}
+
+/// Get a description of the last error which occurred on this thread, or null if no
+/// error has occurred. The string is valid until the next call into this library on
+/// the same thread.
+#[no_mangle]
+pub extern "C" fn nvtt_rs_last_error_message() -> *const c_char {
+    panic::catch_unwind(|| {
+        LAST_ERROR.with(|e| e.borrow().as_ref().map_or(ptr::null(), |e| e.as_ptr()))
+    })
+    .unwrap_or(ptr::null())
+}
+
+/// Initialize `preset` with the settings of the given `kind`, which is one of the
+/// values of `NvttRsPresetKind`. Any other value returns
+/// `NvttRsStatus::InvalidArgument`.
+///
+/// # Safety
+///
+/// The `preset` must be null, or point to a writable `NvttRsPreset`.
+#[no_mangle]
+pub unsafe extern "C" fn nvtt_rs_preset_init(preset: *mut NvttRsPreset, kind: u32) -> NvttRsStatus {
+    catch_panic(|| {
+        if preset.is_null() {
+            return invalid_argument(&"The preset was null");
+        }
+        match NvttRsPresetKind::try_from(kind) {
+            Ok(kind) => {
+                preset.write(NvttRsPreset::new(kind));
+                NvttRsStatus::Ok
+            }
+            Err(status) => status,
+        }
+    })
+}
+
+/// Create a new batch which compresses files with the settings in `preset`, and
+/// write it into `batch`. The batch must be freed with `nvtt_rs_batch_free`.
+///
+/// # Safety
+///
+/// The `preset` must be null, or point to a valid `NvttRsPreset`. The `batch` must
+/// be null, or point to a writable pointer.
+#[no_mangle]
+pub unsafe extern "C" fn nvtt_rs_batch_new(
+    preset: *const NvttRsPreset,
+    batch: *mut *mut NvttRsBatch,
+) -> NvttRsStatus {
+    catch_panic(|| {
+        let (preset, out) = match (preset.as_ref(), batch.as_mut()) {
+            (Some(preset), Some(out)) => (preset, out),
+            _ => return invalid_argument(&"The preset or batch was null"),
+        };
+        match preset.to_batch() {
+            Ok(batch) => {
+                *out = Box::into_raw(Box::new(NvttRsBatch { batch }));
+                NvttRsStatus::Ok
+            }
+            Err(status) => status,
+        }
+    })
+}
+
+/// Free a batch created with `nvtt_rs_batch_new`. Passing null does nothing.
+///
+/// # Safety
+///
+/// The `batch` must be null, or a pointer returned by `nvtt_rs_batch_new` which has
+/// not already been freed.
+#[no_mangle]
+pub unsafe extern "C" fn nvtt_rs_batch_free(batch: *mut NvttRsBatch) {
+    if !batch.is_null() {
+        // A panic while dropping the batch cannot be reported, so it is only stopped
+        // from unwinding into the caller.
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| drop(Box::from_raw(batch))));
+    }
+}
+
+/// Add a single image file to the batch.
+///
+/// # Safety
+///
+/// The `batch` must be null, or a pointer returned by `nvtt_rs_batch_new`. The `path`
+/// must be null, or point to a nul terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn nvtt_rs_batch_add_file(
+    batch: *mut NvttRsBatch,
+    path: *const c_char,
+) -> NvttRsStatus {
+    with_batch(batch, |batch| {
+        batch.add_file(to_str(path)?);
+        Ok(())
+    })
+}
+
+/// Add every file matching the glob `pattern` to the batch. The pattern is expanded
+/// when the batch is run.
+///
+/// # Safety
+///
+/// The `batch` must be null, or a pointer returned by `nvtt_rs_batch_new`. The
+/// `pattern` must be null, or point to a nul terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn nvtt_rs_batch_add_glob(
+    batch: *mut NvttRsBatch,
+    pattern: *const c_char,
+) -> NvttRsStatus {
+    with_batch(batch, |batch| {
+        batch.add_glob(to_str(pattern)?);
+        Ok(())
+    })
+}
+
+/// Set the directory which the compressed textures are written to.
+///
+/// # Safety
+///
+/// The `batch` must be null, or a pointer returned by `nvtt_rs_batch_new`. The `dir`
+/// must be null, or point to a nul terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn nvtt_rs_batch_set_output_dir(
+    batch: *mut NvttRsBatch,
+    dir: *const c_char,
+) -> NvttRsStatus {
+    with_batch(batch, |batch| {
+        batch.output_dir(to_str(dir)?);
+        Ok(())
+    })
+}
+
+/// Enable caching of compressed textures in `dir`.
+///
+/// # Safety
+///
+/// The `batch` must be null, or a pointer returned by `nvtt_rs_batch_new`. The `dir`
+/// must be null, or point to a nul terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn nvtt_rs_batch_set_cache_dir(
+    batch: *mut NvttRsBatch,
+    dir: *const c_char,
+) -> NvttRsStatus {
+    with_batch(batch, |batch| {
+        batch.cache_dir(to_str(dir)?);
+        Ok(())
+    })
+}
+
+/// Set the number of threads used to compress the batch. If `threads` is `0`, then
+/// one thread is used for each available cpu.
+///
+/// # Safety
+///
+/// The `batch` must be null, or a pointer returned by `nvtt_rs_batch_new`.
+#[no_mangle]
+pub unsafe extern "C" fn nvtt_rs_batch_set_threads(
+    batch: *mut NvttRsBatch,
+    threads: usize,
+) -> NvttRsStatus {
+    with_batch(batch, |batch| {
+        if threads > 0 {
+            batch.threads(threads);
+        }
+        Ok(())
+    })
+}
+
+/// Limit the estimated memory used by the files which are compressed at the same
+/// time to `bytes`. If `bytes` is `0`, then the memory is not limited.
+///
+/// # Safety
+///
+/// The `batch` must be null, or a pointer returned by `nvtt_rs_batch_new`.
+#[no_mangle]
+pub unsafe extern "C" fn nvtt_rs_batch_set_memory_budget(
+    batch: *mut NvttRsBatch,
+    bytes: usize,
+) -> NvttRsStatus {
+    with_batch(batch, |batch| {
+        batch.memory_budget(match bytes {
+            0 => MemoryBudget::Unlimited,
+            bytes => MemoryBudget::Bytes(bytes),
+        });
+        Ok(())
+    })
+}
+
+/// Compress every file in the batch. The `callback` is called with the result of
+/// each file once every file has been compressed, and may be null.
+///
+/// Returns `NvttRsStatus::CompressionFailed` if any file could not be compressed, and
+/// `NvttRsStatus::OutputCollision` if two files would be compressed into the same
+/// output, in which case no file is compressed.
+///
+/// # Safety
+///
+/// The `batch` must be null, or a pointer returned by `nvtt_rs_batch_new`. The
+/// `callback` must be safe to call with the `user_data`.
+#[no_mangle]
+pub unsafe extern "C" fn nvtt_rs_batch_run(
+    batch: *mut NvttRsBatch,
+    callback: NvttRsResultCallback,
+    user_data: *mut c_void,
+) -> NvttRsStatus {
+    with_batch(batch, |batch| {
+        let results = batch.run().map_err(|e| {
+            set_last_error(&e);
+            match e {
+                BatchError::Pattern(_) => NvttRsStatus::InvalidPattern,
+                _ => NvttRsStatus::OutputCollision,
+            }
+        })?;
+
+        let mut status = Ok(());
+        for result in &results {
+            if let Err(e) = result.result() {
+                set_last_error(e);
+                status = Err(NvttRsStatus::CompressionFailed);
+            }
+            if let Some(callback) = callback {
+                report_result(result, |result| callback(result, user_data));
+            }
+        }
+        status
+    })
+}
+
+/// Compress the image at `input` into the texture at `output`, using the settings in
+/// `preset`.
+///
+/// # Safety
+///
+/// The `input` and `output` must be null, or point to nul terminated strings. The
+/// `preset` must be null, or point to a valid `NvttRsPreset`.
+#[no_mangle]
+pub unsafe extern "C" fn nvtt_rs_compress_file(
+    input: *const c_char,
+    output: *const c_char,
+    preset: *const NvttRsPreset,
+) -> NvttRsStatus {
+    let compress = || -> Result<(), NvttRsStatus> {
+        let (input, output) = (to_str(input)?, to_str(output)?);
+        let preset = preset
+            .as_ref()
+            .ok_or_else(|| invalid_argument(&"The preset was null"))?;
+
+        let batch = preset.to_batch()?;
+        let result = batch.run_job(&batch.worker(), Path::new(input), Path::new(output));
+        result.result().map_err(|e| {
+            set_last_error(e);
+            NvttRsStatus::CompressionFailed
+        })
+    };
+    catch_panic(|| compress().err().unwrap_or(NvttRsStatus::Ok))
+}
+
+/// Convert `result` into an `NvttRsBatchResult`, and pass it to `f`.
+fn report_result(result: &BatchResult, f: impl FnOnce(*const NvttRsBatchResult)) {
+    // Paths cannot contain nul bytes, so the conversion only fails on invalid input.
+    let c_path =
+        |path: &Path| CString::new(path.to_string_lossy().into_owned()).unwrap_or_default();
+    let input = c_path(result.input());
+    let output = c_path(result.output());
+    let error_message = result
+        .result()
+        .err()
+        .and_then(|e| CString::new(e.to_string()).ok());
+
+    let c_result = NvttRsBatchResult {
+        input: input.as_ptr(),
+        output: output.as_ptr(),
+        status: match result.result() {
+            Ok(()) => NvttRsStatus::Ok,
+            Err(_) => NvttRsStatus::CompressionFailed,
+        },
+        error_message: error_message.as_ref().map_or(ptr::null(), |e| e.as_ptr()),
+        cached: result.is_cached(),
+        elapsed_seconds: result.elapsed().as_secs_f64(),
+    };
+    f(&c_result);
+}
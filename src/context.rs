@@ -0,0 +1,477 @@
+// Copyright © 2019-2020 George Burton
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::surface::NativeSurface;
+use crate::{
+    analysis::select_for_target_psnr, transcode::decode_surface, Channel, CompressError,
+    CompressionOptions, CompressionOutput, CompressionStage, Compressor, Container, CubeFace,
+    Dither, Encode, Error, Format, InputOptions, MipLevel, MipmapFilter, OutputOptions,
+    QualityMetrics, Surface, REPORT_IMAGES,
+};
+use cfg_if::cfg_if;
+use nvtt_sys::*;
+use std::{borrow::Cow, convert::TryFrom, os::raw::c_int};
+
+/// Compresses [`Surface`]s directly, rather than compressing the images set on an
+/// [`InputOptions`]. This mirrors the [`nvtt::Context`] type.
+///
+/// Because each `Surface` is compressed as it is, any processing can be performed
+/// on each mipmap level before it is encoded.
+///
+/// # Notes
+///
+/// Single surfaces are compressed with `nvtt::Context`, through the C bindings in
+/// `nvtt_sys`. Mip chains are compressed with the [`Compressor`] of the `Context`.
+///
+/// [`Surface`]: struct.Surface.html
+/// [`InputOptions`]: struct.InputOptions.html
+/// [`Compressor`]: struct.Compressor.html
+/// [`nvtt::Context`]: https://github.com/castano/nvidia-texture-tools/wiki/ApiDocumentation
+#[derive(Debug)]
+pub struct Context {
+    compressor: Compressor,
+}
+
+impl Context {
+    /// Create a new `Context`. If the underlying `Compressor` cannot be created, returns
+    /// `Error::Unknown`, or `Error::UnsupportedFeature` if this crate was built without
+    /// the [`nvtt`] feature.
+    ///
+    /// [`nvtt`]: index.html#nvtt
+    #[inline]
+    pub fn new() -> Result<Self, Error> {
+        Compressor::new().map(Self::from)
+    }
+
+    /// Get a reference to the `Compressor` used by this `Context`.
+    #[inline]
+    pub fn compressor(&self) -> &Compressor {
+        &self.compressor
+    }
+
+    /// Get a mutable reference to the `Compressor` used by this `Context`. This can
+    /// be used to enable cuda acceleration.
+    #[inline]
+    pub fn compressor_mut(&mut self) -> &mut Compressor {
+        &mut self.compressor
+    }
+
+    /// Compress a single `surface`, which is the given `face` and `mipmap` level of
+    /// a texture. No mipmaps are generated. For 2D textures, the `face` is
+    /// `CubeFace::default()`.
+    ///
+    /// If the texture is written into memory, then the `face` and `mipmap` are
+    /// reported in the returned [`CompressionOutput`]. When compressing each mipmap
+    /// level of a texture separately, the header should be disabled with
+    /// [`OutputOptions::set_write_header`]. Without a header, the image is appended to
+    /// the output file rather than replacing it, so each level of the texture is kept.
+    ///
+    /// # Errors
+    ///
+    /// If the `mipmap` level does not fit in a `c_int`, then this method will fail
+    /// with [`Error::InvalidMipLevel`]. `nvtt::Context` was added in nvtt 2.1, so if
+    /// this crate was built against an older nvtt, then this method will fail with
+    /// [`Error::UnsupportedFeature`].
+    ///
+    /// [`CompressionOutput`]: enum.CompressionOutput.html
+    /// [`OutputOptions::set_write_header`]: struct.OutputOptions.html#method.set_write_header
+    /// [`Error::InvalidMipLevel`]: enum.Error.html#variant.InvalidMipLevel
+    /// [`Error::UnsupportedFeature`]: enum.Error.html#variant.UnsupportedFeature
+    pub fn compress(
+        &self,
+        surface: &Surface,
+        face: CubeFace,
+        mipmap: usize,
+        compression_options: &CompressionOptions,
+        output_options: &OutputOptions,
+    ) -> Result<CompressionOutput, CompressError> {
+        let selected = compression_options.target_psnr().map(|_| {
+            self.select_target_psnr(surface, compression_options, output_options.container())
+        });
+        let compression_options = selected.as_ref().unwrap_or(compression_options);
+
+        let reference = std::slice::from_ref(surface);
+        let surfaces = self.dither(reference, compression_options);
+        let invalid = |e| CompressError::new(e, CompressionStage::InputValidation, output_options);
+
+        let mipmap = c_int::try_from(mipmap).map_err(|_| invalid(Error::InvalidMipLevel))?;
+        let input_options = layout_options_for(surface, 0).map_err(invalid)?;
+        let context =
+            NativeContext::new(self.compressor.is_cuda_acceleration_enabled()).map_err(invalid)?;
+        let native = NativeSurface::from_surface(&surfaces[0]).map_err(invalid)?;
+
+        self.compress_measured(
+            reference,
+            compression_options,
+            &input_options,
+            output_options,
+            Encode::Surface {
+                context: &context,
+                surface: &native,
+                face,
+                mipmap,
+            },
+        )
+    }
+
+    /// Compress a chain of `mipmaps` into a single texture. The first `Surface` is
+    /// the top level of the texture, and the dimensions of each following `Surface`
+    /// must be half of the previous level.
+    ///
+    /// If either channel of the `compression_options` uses `Dither::Ordered`, then each
+    /// `Surface` is dithered with [`Context::quantize`] before it is compressed.
+    ///
+    /// # Errors
+    ///
+    /// If `mipmaps` is empty, then this method will fail with [`Error::InvalidInput`].
+    ///
+    /// [`Context::quantize`]: struct.Context.html#method.quantize
+    /// [`Error::InvalidInput`]: enum.Error.html#variant.InvalidInput
+    pub fn compress_mipmaps(
+        &self,
+        mipmaps: &[Surface],
+        compression_options: &CompressionOptions,
+        output_options: &OutputOptions,
+    ) -> Result<CompressionOutput, CompressError> {
+        let selected = mipmaps
+            .first()
+            .filter(|_| compression_options.target_psnr().is_some())
+            .map(|top| {
+                self.select_target_psnr(top, compression_options, output_options.container())
+            });
+        let compression_options = selected.as_ref().unwrap_or(compression_options);
+
+        let dithered = self.dither(mipmaps, compression_options);
+        let input_options = input_options_for(&dithered).map_err(|e| {
+            CompressError::new(e, CompressionStage::InputValidation, output_options)
+        })?;
+        self.compress_measured(
+            mipmaps,
+            compression_options,
+            &input_options,
+            output_options,
+            Encode::Input,
+        )
+    }
+
+    /// Build the mip chain of `surface` with [`Surface::build_mipmaps`], and compress
+    /// it into a single texture.
+    ///
+    /// The mipmaps are generated in Rust rather than by nvtt, and are resampled in
+    /// parallel when the `rayon` feature is enabled.
+    ///
+    /// # Errors
+    ///
+    /// If `surface` is null, then this method will fail with [`Error::InvalidInput`].
+    ///
+    /// [`Surface::build_mipmaps`]: struct.Surface.html#method.build_mipmaps
+    /// [`Error::InvalidInput`]: enum.Error.html#variant.InvalidInput
+    pub fn compress_with_mipmaps(
+        &self,
+        surface: &Surface,
+        filter: MipmapFilter,
+        compression_options: &CompressionOptions,
+        output_options: &OutputOptions,
+    ) -> Result<CompressionOutput, CompressError> {
+        let mipmaps = surface.build_mipmaps(filter);
+        self.compress_mipmaps(&mipmaps, compression_options, output_options)
+    }
+
+    /// Get a copy of the `compression_options` with the format and quality which reach
+    /// their target PSNR on `surface`, when stored in the `container`. See
+    /// [`CompressionOptions::set_target_psnr`].
+    ///
+    /// The returned options do not have a target PSNR. If no target PSNR is set, then
+    /// the options are copied as they are. This is called by the compression methods
+    /// of the `Context`, so it only needs to be called to inspect the selection.
+    ///
+    /// [`CompressionOptions::set_target_psnr`]: struct.CompressionOptions.html#method.set_target_psnr
+    pub fn select_target_psnr(
+        &self,
+        surface: &Surface,
+        compression_options: &CompressionOptions,
+        container: Container,
+    ) -> CompressionOptions {
+        select_for_target_psnr(self, surface, compression_options, container)
+    }
+
+    /// Quantize the `surface` to the precision of the format set on the
+    /// `compression_options`, so that the `surface` can be inspected as it will
+    /// appear after compression. Values are clamped to the `0..1` range, and are
+    /// dithered as set by [`CompressionOptions::set_dithering`].
+    ///
+    /// Surfaces which will be compressed to `Format::Bc6` are not modified.
+    ///
+    /// [`CompressionOptions::set_dithering`]: struct.CompressionOptions.html#method.set_dithering
+    pub fn quantize(&self, surface: &mut Surface, compression_options: &CompressionOptions) {
+        let bits = match compression_options.format() {
+            Format::Bc6 => return,
+            Format::Bc1
+            | Format::Bc3
+            | Format::Bc3n
+            | Format::Ctx1
+            | Format::Dxt1
+            | Format::Dxt1n
+            | Format::Dxt5
+            | Format::Dxt5n => [5, 6, 5, 8],
+            Format::Bc1a | Format::Dxt1a => [5, 6, 5, 1],
+            Format::Bc2 | Format::Dxt3 => [5, 6, 5, 4],
+            _ => [8, 8, 8, 8],
+        };
+
+        let (color_dither, alpha_dither) = compression_options.dithering();
+        for (&channel, &bits) in Channel::ALL.iter().zip(bits.iter()) {
+            let dither = match channel {
+                Channel::Alpha => alpha_dither,
+                _ => color_dither,
+            };
+            surface.quantize(channel, bits, dither);
+        }
+    }
+
+    /// Compress the texture created from the `reference` mipmaps, as selected by
+    /// `encode`. If the `output_options` record report metrics, then each compressed
+    /// image is decoded and compared against its mipmap.
+    fn compress_measured(
+        &self,
+        reference: &[Surface],
+        compression_options: &CompressionOptions,
+        input_options: &InputOptions,
+        output_options: &OutputOptions,
+        encode: Encode<'_>,
+    ) -> Result<CompressionOutput, CompressError> {
+        let measure = output_options.report_metrics();
+        if measure {
+            REPORT_IMAGES.with(|images| *images.borrow_mut() = Some(vec![]));
+        }
+
+        let output = self.compressor.compress_output(
+            compression_options,
+            input_options,
+            output_options,
+            encode,
+        );
+
+        let images = REPORT_IMAGES.with(|images| images.borrow_mut().take());
+        if let Some(images) = images.filter(|_| measure) {
+            let format = compression_options.format();
+            output_options.update_report(|report| {
+                for (mip, image) in report.mips.iter_mut().zip(&images) {
+                    // A single `Surface` is the only reference for its mipmap level.
+                    let level = match encode {
+                        Encode::Input => mip.miplevel,
+                        Encode::Surface { .. } => 0,
+                    };
+                    mip.metrics = reference
+                        .get(level)
+                        .filter(|reference| reference.depth() == 1)
+                        .and_then(|reference| {
+                            let decoded = decode_surface(format, image, mip.width, mip.height)?;
+                            QualityMetrics::compare(reference, &decoded).ok()
+                        });
+                }
+            });
+        }
+
+        output
+    }
+
+    /// Apply the ordered dithering which nvtt does not support to copies of the
+    /// `surfaces`. The `surfaces` are borrowed if no ordered dithering is needed.
+    fn dither<'a>(
+        &self,
+        surfaces: &'a [Surface],
+        compression_options: &CompressionOptions,
+    ) -> Cow<'a, [Surface]> {
+        let (color_dither, alpha_dither) = compression_options.dithering();
+        if color_dither != Dither::Ordered && alpha_dither != Dither::Ordered {
+            return Cow::Borrowed(surfaces);
+        }
+
+        let mut dithered = surfaces.to_vec();
+        for surface in &mut dithered {
+            self.quantize(surface, compression_options);
+        }
+        Cow::Owned(dithered)
+    }
+}
+
+impl Default for Context {
+    /// Create a new `Context`. See [`Context::new`].
+    ///
+    /// # Panics
+    ///
+    /// This will panic if nvtt fails to allocate the underlying `Compressor`.
+    ///
+    /// [`Context::new`]: struct.Context.html#method.new
+    #[inline]
+    fn default() -> Self {
+        Self::new().expect("could not create the Context")
+    }
+}
+
+impl From<Compressor> for Context {
+    #[inline]
+    fn from(compressor: Compressor) -> Self {
+        Self { compressor }
+    }
+}
+
+cfg_if! {
+    if #[cfg(nvtt_2_1)] {
+        use std::ptr::NonNull;
+
+        /// An owned `nvtt::Context`, which is destroyed when it is dropped.
+        pub(crate) struct NativeContext(NonNull<NvttContext>);
+
+        impl NativeContext {
+            fn new(cuda_acceleration: bool) -> Result<Self, Error> {
+                let context = NonNull::new(unsafe { nvttCreateContext() })
+                    .map(Self)
+                    .ok_or(Error::Unknown)?;
+                unsafe {
+                    nvttContextEnableCudaAcceleration(context.0.as_ptr(), cuda_acceleration.into());
+                }
+                Ok(context)
+            }
+
+            /// Estimate the size of `surface` when it is compressed with the
+            /// `compression_options`.
+            pub(crate) fn estimate_size(
+                &self,
+                surface: &NativeSurface,
+                compression_options: &CompressionOptions,
+            ) -> usize {
+                unsafe {
+                    nvttContextEstimateSize(
+                        self.0.as_ptr(),
+                        surface.as_ptr(),
+                        1,
+                        compression_options.opts.as_ptr(),
+                    ) as usize
+                }
+            }
+
+            /// Compress `surface` as the `face` and `mipmap` level of a texture into
+            /// `out_opts`, after the header of the texture if `write_header` is `true`.
+            ///
+            /// # Safety
+            ///
+            /// `out_opts` must point to valid nvtt output options.
+            pub(crate) unsafe fn compress(
+                &self,
+                surface: &NativeSurface,
+                face: CubeFace,
+                mipmap: c_int,
+                compression_options: &CompressionOptions,
+                out_opts: *mut NvttOutputOptions,
+                write_header: bool,
+            ) -> NvttBoolean {
+                let compression_options = compression_options.opts.as_ptr();
+                if write_header {
+                    let header = nvttContextOutputHeader(
+                        self.0.as_ptr(),
+                        surface.as_ptr(),
+                        1,
+                        compression_options,
+                        out_opts,
+                    );
+                    if header != NvttBoolean::NVTT_True {
+                        return header;
+                    }
+                }
+
+                nvttContextCompress(
+                    self.0.as_ptr(),
+                    surface.as_ptr(),
+                    face.index() as c_int,
+                    mipmap,
+                    compression_options,
+                    out_opts,
+                )
+            }
+        }
+
+        impl Drop for NativeContext {
+            fn drop(&mut self) {
+                unsafe { nvttDestroyContext(self.0.as_ptr()) }
+            }
+        }
+    } else {
+        /// `nvtt::Context` was added in nvtt 2.1, so a `NativeContext` cannot be
+        /// created when this crate is built against an older nvtt.
+        pub(crate) enum NativeContext {}
+
+        impl NativeContext {
+            fn new(_cuda_acceleration: bool) -> Result<Self, Error> {
+                Err(Error::UnsupportedFeature)
+            }
+
+            pub(crate) fn estimate_size(
+                &self,
+                _surface: &NativeSurface,
+                _compression_options: &CompressionOptions,
+            ) -> usize {
+                match *self {}
+            }
+
+            pub(crate) unsafe fn compress(
+                &self,
+                _surface: &NativeSurface,
+                _face: CubeFace,
+                _mipmap: c_int,
+                _compression_options: &CompressionOptions,
+                _out_opts: *mut NvttOutputOptions,
+                _write_header: bool,
+            ) -> NvttBoolean {
+                match *self {}
+            }
+        }
+    }
+}
+
+/// Create the `InputOptions` used to compress a chain of `mipmaps`.
+fn input_options_for(mipmaps: &[Surface]) -> Result<InputOptions, Error> {
+    let top = mipmaps.first().ok_or(Error::InvalidInput)?;
+    let max_level = u32::try_from(mipmaps.len() - 1).map_err(|_| Error::InvalidInput)?;
+    let mut input_options = layout_options_for(top, max_level)?;
+
+    for (level, surface) in mipmaps.iter().enumerate() {
+        let level = u32::try_from(level).map_err(|_| Error::InvalidMipLevel)?;
+        input_options.set_surface(surface, CubeFace::default(), MipLevel(level))?;
+    }
+
+    Ok(input_options)
+}
+
+/// Create the `InputOptions` which describe a texture with the layout of `top`, and
+/// `max_level` mipmaps below it. No images are set.
+fn layout_options_for(top: &Surface, max_level: u32) -> Result<InputOptions, Error> {
+    let mut input_options = InputOptions::new()?;
+    input_options
+        // The `Surface` is compressed as it is, so no gamma correction is applied.
+        .set_gamma(1.0, 1.0)?
+        .set_texture_layout(top.texture_layout())?
+        .set_mipmap_generation(max_level > 0, Some(max_level))
+        .set_alpha_mode(top.alpha_mode())
+        .set_wrap_mode(top.wrap_mode())
+        .set_normal_map(top.is_normal_map());
+    Ok(input_options)
+}
@@ -0,0 +1,465 @@
+// Copyright © 2019-2020 George Burton
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+#[cfg(feature = "nvtt")]
+use crate::{CubeFace, InputOptions, MipLevel};
+use crate::{Error, Surface, TextureLayout};
+#[cfg(feature = "serde-serialize")]
+use serde::{Deserialize, Serialize};
+
+/// The number of faces in a cube texture.
+const FACE_COUNT: usize = 6;
+
+/// Describes how the faces of a cube map are arranged in a single 2D image.
+///
+/// The faces are stored in the order `+X`, `-X`, `+Y`, `-Y`, `+Z`, `-Z`.
+#[cfg_attr(feature = "serde-serialize", derive(Deserialize, Serialize))]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum CubeLayout {
+    /// The faces are arranged in a cross which is 3 faces wide and 4 faces high. The
+    /// `-Z` face is stored upside down below the `-Y` face.
+    VerticalCross,
+    /// The faces are arranged in a cross which is 4 faces wide and 3 faces high.
+    HorizontalCross,
+    /// The faces are stacked in a single column.
+    Column,
+    /// The faces are placed next to each other in a single row.
+    Row,
+}
+
+impl CubeLayout {
+    /// The size of the image for this layout in faces.
+    #[inline]
+    fn size_in_faces(self) -> (usize, usize) {
+        match self {
+            CubeLayout::VerticalCross => (3, 4),
+            CubeLayout::HorizontalCross => (4, 3),
+            CubeLayout::Column => (1, 6),
+            CubeLayout::Row => (6, 1),
+        }
+    }
+
+    /// The position of `face` in the image in faces, and whether the face is
+    /// rotated by 180 degrees.
+    #[inline]
+    fn face_position(self, face: usize) -> (usize, usize, bool) {
+        match self {
+            CubeLayout::VerticalCross => match face {
+                0 => (2, 1, false),
+                1 => (0, 1, false),
+                2 => (1, 0, false),
+                3 => (1, 2, false),
+                4 => (1, 1, false),
+                _ => (1, 3, true),
+            },
+            CubeLayout::HorizontalCross => match face {
+                0 => (2, 1, false),
+                1 => (0, 1, false),
+                2 => (1, 0, false),
+                3 => (1, 2, false),
+                4 => (1, 1, false),
+                _ => (3, 1, false),
+            },
+            CubeLayout::Column => (0, face, false),
+            CubeLayout::Row => (face, 0, false),
+        }
+    }
+}
+
+/// A cube map made up of six square [`Surface`]s. This mirrors the
+/// [`nvtt::CubeSurface`] type.
+///
+/// The faces are stored in the order `+X`, `-X`, `+Y`, `-Y`, `+Z`, `-Z`.
+///
+/// [`Surface`]: struct.Surface.html
+/// [`nvtt::CubeSurface`]: https://github.com/castano/nvidia-texture-tools/wiki/ApiDocumentation
+#[derive(Clone, Debug, PartialEq)]
+pub struct CubeSurface {
+    edge_length: usize,
+    faces: Vec<Surface>,
+}
+
+impl CubeSurface {
+    /// Create a new `CubeSurface` where each face is `edge_length` pixels wide and
+    /// high. All channels are initialised to `0.0`.
+    #[inline]
+    pub fn new(edge_length: usize) -> Self {
+        Self {
+            edge_length,
+            faces: vec![Surface::new(edge_length, edge_length); FACE_COUNT],
+        }
+    }
+
+    /// Create a new `CubeSurface` from six faces.
+    ///
+    /// # Errors
+    ///
+    /// If any of the faces are not square 2D surfaces of the same size, then this
+    /// method will fail with [`Error::InvalidInput`].
+    ///
+    /// [`Error::InvalidInput`]: enum.Error.html#variant.InvalidInput
+    pub fn from_faces(faces: [Surface; 6]) -> Result<Self, Error> {
+        let edge_length = faces[0].width();
+        let valid = faces
+            .iter()
+            .all(|f| f.width() == edge_length && f.height() == edge_length && f.depth() == 1);
+
+        if !valid || edge_length == 0 {
+            return Err(Error::InvalidInput);
+        }
+
+        Ok(Self {
+            edge_length,
+            faces: faces.to_vec(),
+        })
+    }
+
+    /// Create a new `CubeSurface` by extracting the faces from `surface`, which
+    /// contains the faces arranged according to `layout`.
+    ///
+    /// # Errors
+    ///
+    /// If the dimensions of `surface` do not match the `layout`, then this method will
+    /// fail with [`Error::InvalidInput`].
+    ///
+    /// [`Error::InvalidInput`]: enum.Error.html#variant.InvalidInput
+    pub fn fold(surface: &Surface, layout: CubeLayout) -> Result<Self, Error> {
+        let (faces_w, faces_h) = layout.size_in_faces();
+        let edge_length = surface.width() / faces_w;
+        if edge_length == 0
+            || surface.depth() != 1
+            || surface.width() != edge_length * faces_w
+            || surface.height() != edge_length * faces_h
+        {
+            return Err(Error::InvalidInput);
+        }
+
+        let mut cube = Self::new(edge_length);
+        cube.set_options_from(surface);
+        for (i, face) in cube.faces.iter_mut().enumerate() {
+            let (fx, fy, rotated) = layout.face_position(i);
+            for y in 0..edge_length {
+                for x in 0..edge_length {
+                    let (sx, sy) = if rotated {
+                        (edge_length - 1 - x, edge_length - 1 - y)
+                    } else {
+                        (x, y)
+                    };
+                    let src = (fx * edge_length + sx) + (fy * edge_length + sy) * surface.width();
+                    face.set_pixel(x + y * edge_length, surface.pixel(src));
+                }
+            }
+        }
+
+        Ok(cube)
+    }
+
+    /// Arrange the faces of the `CubeSurface` into a single `Surface` using the given
+    /// `layout`. Areas of the image which are not covered by a face are set to `0.0`.
+    pub fn unfold(&self, layout: CubeLayout) -> Surface {
+        let (faces_w, faces_h) = layout.size_in_faces();
+        let edge_length = self.edge_length;
+        let width = faces_w * edge_length;
+        let mut surface = Surface::new(width, faces_h * edge_length);
+        surface
+            .set_wrap_mode(self.faces[0].wrap_mode())
+            .set_alpha_mode(self.faces[0].alpha_mode())
+            .set_normal_map(self.faces[0].is_normal_map());
+
+        for (i, face) in self.faces.iter().enumerate() {
+            let (fx, fy, rotated) = layout.face_position(i);
+            for y in 0..edge_length {
+                for x in 0..edge_length {
+                    let (dx, dy) = if rotated {
+                        (edge_length - 1 - x, edge_length - 1 - y)
+                    } else {
+                        (x, y)
+                    };
+                    let dst = (fx * edge_length + dx) + (fy * edge_length + dy) * width;
+                    surface.set_pixel(dst, face.pixel(x + y * edge_length));
+                }
+            }
+        }
+
+        surface
+    }
+
+    /// The width and height of each face in pixels.
+    #[inline]
+    pub fn edge_length(&self) -> usize {
+        self.edge_length
+    }
+
+    /// Get the face at `index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is greater than `5`.
+    #[inline]
+    pub fn face(&self, index: usize) -> &Surface {
+        &self.faces[index]
+    }
+
+    /// Get the face at `index` mutably. Changing the size of the face will cause
+    /// the `CubeSurface` to be rejected when it is compressed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is greater than `5`.
+    #[inline]
+    pub fn face_mut(&mut self, index: usize) -> &mut Surface {
+        &mut self.faces[index]
+    }
+
+    /// Get all of the faces of the `CubeSurface`.
+    #[inline]
+    pub fn faces(&self) -> &[Surface] {
+        &self.faces
+    }
+
+    /// Get the `TextureLayout` describing this `CubeSurface`.
+    #[inline]
+    pub fn texture_layout(&self) -> TextureLayout {
+        TextureLayout::cube(self.edge_length, self.edge_length)
+    }
+
+    /// Create a new `CubeSurface` with faces of `size` pixels, where each texel is the
+    /// average of the input cube map weighted by the cosine of the angle between the
+    /// texel directions raised to `cosine_power`. This can be used to generate glossy
+    /// reflection and irradiance maps.
+    ///
+    /// This is an expensive operation, as every output texel samples every input texel.
+    pub fn cosine_power_filter(&self, size: usize, cosine_power: f32) -> CubeSurface {
+        self.convolve(size, |cos| cos.powf(cosine_power))
+    }
+
+    /// Create a new `CubeSurface` with faces of `size` pixels, which is the diffuse
+    /// irradiance of this cube map. Each texel is the average of the input weighted by
+    /// the cosine of the angle between the texel directions, so it must be multiplied
+    /// by `PI` to give the irradiance.
+    ///
+    /// Irradiance maps have no high frequency detail, so `size` can be very small.
+    #[inline]
+    pub fn irradiance_filter(&self, size: usize) -> CubeSurface {
+        self.cosine_power_filter(size, 1.0)
+    }
+
+    /// Create a new `CubeSurface` with faces of `size` pixels, which is this cube map
+    /// convolved with the GGX distribution of the given perceptual `roughness`. The
+    /// view direction is assumed to be the same as the normal, as in the split sum
+    /// approximation used by most engines.
+    ///
+    /// This is an expensive operation, as every output texel samples every input texel.
+    pub fn ggx_filter(&self, size: usize, roughness: f32) -> CubeSurface {
+        let alpha = roughness.clamp(0.0, 1.0).powi(2).max(1.0e-3);
+        let alpha_sq = alpha * alpha;
+        self.convolve(size, |cos| {
+            // With the view direction equal to the normal, the half vector is halfway
+            // between the output and input directions.
+            let n_dot_h_sq = (1.0 + cos) * 0.5;
+            let d = n_dot_h_sq * (alpha_sq - 1.0) + 1.0;
+            alpha_sq / (std::f32::consts::PI * d * d) * cos
+        })
+    }
+
+    /// Build a prefiltered specular cube map, where each mipmap level is filtered with
+    /// [`ggx_filter`] for a roughness which increases linearly from `0.0` at the top
+    /// level to `1.0` at the last level. The top level has faces of `size` pixels, and
+    /// there are `mip_count` levels, or the full mip chain if `mip_count` is `None`.
+    ///
+    /// Engines select the level to sample with `roughness * (mip_count - 1)`. The
+    /// returned levels can be passed to [`InputOptions::set_cube_mipmaps`].
+    ///
+    /// [`ggx_filter`]: struct.CubeSurface.html#method.ggx_filter
+    /// [`InputOptions::set_cube_mipmaps`]: struct.InputOptions.html#method.set_cube_mipmaps
+    pub fn prefilter_specular(&self, size: usize, mip_count: Option<u32>) -> Vec<CubeSurface> {
+        let full_count = TextureLayout::cube(size, size).mip_count();
+        let mip_count = mip_count.map_or(full_count, |count| count.clamp(1, full_count));
+        (0..mip_count)
+            .map(|level| {
+                let level_size = (size >> level).max(1);
+                if level == 0 && level_size == self.edge_length {
+                    return self.clone();
+                }
+                let roughness = level as f32 / (mip_count - 1).max(1) as f32;
+                self.ggx_filter(level_size, roughness)
+            })
+            .collect()
+    }
+
+    /// Create a new `CubeSurface` with faces of `size` pixels, where each texel is the
+    /// average of the input weighted by `kernel` of the cosine of the angle between the
+    /// texel directions and the solid angle of each input texel.
+    fn convolve(&self, size: usize, kernel: impl Fn(f32) -> f32) -> CubeSurface {
+        let input = self.texel_table();
+        let mut filtered = CubeSurface::new(size);
+        filtered.set_options_from(&self.faces[0]);
+
+        for (face_index, face) in filtered.faces.iter_mut().enumerate() {
+            for y in 0..size {
+                for x in 0..size {
+                    let dir = texel_direction(face_index, x, y, size);
+                    let mut sum = [0.0f32; 4];
+                    let mut total_weight = 0.0;
+
+                    for texel in input.iter() {
+                        let cos = dot(dir, texel.dir);
+                        if cos <= 0.0 {
+                            continue;
+                        }
+                        let weight = kernel(cos) * texel.solid_angle;
+                        let color = self.faces[texel.face].pixel(texel.index);
+                        for (s, c) in sum.iter_mut().zip(color.iter()) {
+                            *s += c * weight;
+                        }
+                        total_weight += weight;
+                    }
+
+                    if total_weight > 0.0 {
+                        sum.iter_mut().for_each(|s| *s /= total_weight);
+                    }
+                    face.set_pixel(x + y * size, sum);
+                }
+            }
+        }
+
+        filtered
+    }
+
+    /// Precompute the direction and solid angle of every texel of the cube map.
+    fn texel_table(&self) -> Vec<Texel> {
+        let edge = self.edge_length;
+        let mut table = Vec::with_capacity(FACE_COUNT * edge * edge);
+        for face in 0..FACE_COUNT {
+            for y in 0..edge {
+                for x in 0..edge {
+                    table.push(Texel {
+                        face,
+                        index: x + y * edge,
+                        dir: texel_direction(face, x, y, edge),
+                        solid_angle: texel_solid_angle(x, y, edge),
+                    });
+                }
+            }
+        }
+        table
+    }
+
+    /// Copy the wrap mode, alpha mode and normal map flag from `surface` to each face.
+    fn set_options_from(&mut self, surface: &Surface) {
+        for face in self.faces.iter_mut() {
+            face.set_wrap_mode(surface.wrap_mode())
+                .set_alpha_mode(surface.alpha_mode())
+                .set_normal_map(surface.is_normal_map());
+        }
+    }
+}
+
+#[cfg(feature = "nvtt")]
+impl InputOptions {
+    /// Sets the data of all six faces at the given `mipmap` level to the contents
+    /// of `cube`. See [`InputOptions::set_surface`] for more information.
+    ///
+    /// [`InputOptions::set_surface`]: struct.InputOptions.html#method.set_surface
+    pub fn set_cube_surface(
+        &mut self,
+        cube: &CubeSurface,
+        mipmap: MipLevel,
+    ) -> Result<&mut Self, Error> {
+        for (face, &cube_face) in cube.faces().iter().zip(CubeFace::ALL.iter()) {
+            self.set_surface(face, cube_face, mipmap)?;
+        }
+        Ok(self)
+    }
+
+    /// Sets the texture layout to a cube map of the size of the first level of
+    /// `mipmaps`, and sets the data of every face of each mipmap level, such as the
+    /// levels built by [`CubeSurface::prefilter_specular`]. Mipmap generation is
+    /// limited to the given levels, so they are not overwritten.
+    ///
+    /// # Errors
+    ///
+    /// If `mipmaps` is empty, then this method will fail with [`Error::InvalidInput`].
+    /// Otherwise, see [`InputOptions::set_cube_surface`].
+    ///
+    /// [`CubeSurface::prefilter_specular`]: struct.CubeSurface.html#method.prefilter_specular
+    /// [`Error::InvalidInput`]: enum.Error.html#variant.InvalidInput
+    /// [`InputOptions::set_cube_surface`]: struct.InputOptions.html#method.set_cube_surface
+    pub fn set_cube_mipmaps(&mut self, mipmaps: &[CubeSurface]) -> Result<&mut Self, Error> {
+        let top = mipmaps.first().ok_or(Error::InvalidInput)?;
+        let max_level = mipmaps.len() as u32 - 1;
+        self.set_texture_layout(top.texture_layout())?
+            .set_mipmap_generation(max_level > 0, Some(max_level));
+        for (level, cube) in mipmaps.iter().enumerate() {
+            self.set_cube_surface(cube, MipLevel(level as u32))?;
+        }
+        Ok(self)
+    }
+}
+
+/// A single texel of a cube map, used for filtering.
+struct Texel {
+    face: usize,
+    index: usize,
+    dir: [f32; 3],
+    solid_angle: f32,
+}
+
+/// Get the normalized direction of the center of texel `x`, `y` on `face`.
+pub(crate) fn texel_direction(face: usize, x: usize, y: usize, edge: usize) -> [f32; 3] {
+    let u = 2.0 * (x as f32 + 0.5) / edge as f32 - 1.0;
+    let v = 2.0 * (y as f32 + 0.5) / edge as f32 - 1.0;
+    let dir = match face {
+        0 => [1.0, -v, -u],
+        1 => [-1.0, -v, u],
+        2 => [u, 1.0, v],
+        3 => [u, -1.0, -v],
+        4 => [u, -v, 1.0],
+        _ => [-u, -v, -1.0],
+    };
+    normalize(dir)
+}
+
+/// Get the solid angle covered by texel `x`, `y` of a cube map face.
+pub(crate) fn texel_solid_angle(x: usize, y: usize, edge: usize) -> f32 {
+    #[inline]
+    fn area_element(x: f32, y: f32) -> f32 {
+        (x * y).atan2((x * x + y * y + 1.0).sqrt())
+    }
+
+    let inv_edge = 1.0 / edge as f32;
+    let u = 2.0 * (x as f32 + 0.5) * inv_edge - 1.0;
+    let v = 2.0 * (y as f32 + 0.5) * inv_edge - 1.0;
+    let (x0, x1) = (u - inv_edge, u + inv_edge);
+    let (y0, y1) = (v - inv_edge, v + inv_edge);
+
+    area_element(x0, y0) - area_element(x0, y1) - area_element(x1, y0) + area_element(x1, y1)
+}
+
+#[inline]
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+#[inline]
+fn normalize(v: [f32; 3]) -> [f32; 3] {
+    let len = dot(v, v).sqrt();
+    [v[0] / len, v[1] / len, v[2] / len]
+}
@@ -0,0 +1,910 @@
+// Copyright © 2019-2020 George Burton
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::{Container, Error, Format, Surface};
+use std::{array, convert::TryFrom};
+
+/// The largest finite half precision float, which is the largest value `Bc6` can store.
+const HALF_MAX: f32 = 65504.0;
+
+/// The weights of the end point at each index of a block with 4 bit indices, out of 64.
+const WEIGHTS_4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+/// The block compressed formats which can be encoded without nvtt.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum BlockKind {
+    Bc1 {
+        alpha: bool,
+    },
+    Bc2,
+    Bc3,
+    /// `Bc3` with the normal map swizzle of [`Surface::swizzle_dxt5n`] applied.
+    ///
+    /// [`Surface::swizzle_dxt5n`]: struct.Surface.html#method.swizzle_dxt5n
+    Bc3n,
+    Bc4,
+    Bc5,
+    /// Unsigned `Bc6` blocks, which are encoded in mode 11 with a single pair of 10 bit
+    /// endpoints.
+    Bc6,
+    /// `Bc7` blocks, which are encoded in mode 6 with a single pair of rgba endpoints.
+    Bc7,
+}
+
+impl BlockKind {
+    fn for_format(format: Format) -> Option<Self> {
+        match format {
+            Format::Bc1 | Format::Dxt1 => Some(Self::Bc1 { alpha: false }),
+            Format::Bc1a | Format::Dxt1a => Some(Self::Bc1 { alpha: true }),
+            Format::Bc2 | Format::Dxt3 => Some(Self::Bc2),
+            Format::Bc3 | Format::Dxt5 => Some(Self::Bc3),
+            Format::Bc3n | Format::Dxt5n => Some(Self::Bc3n),
+            Format::Bc4 => Some(Self::Bc4),
+            Format::Bc5 => Some(Self::Bc5),
+            Format::Bc6 => Some(Self::Bc6),
+            Format::Bc7 => Some(Self::Bc7),
+            _ => None,
+        }
+    }
+
+    /// The size of a `4x4` block in bytes.
+    fn block_size(self) -> usize {
+        match self {
+            Self::Bc1 { .. } | Self::Bc4 => 8,
+            Self::Bc2 | Self::Bc3 | Self::Bc3n | Self::Bc5 | Self::Bc6 | Self::Bc7 => 16,
+        }
+    }
+
+    /// Clamp a channel `value` to the range which can be stored in the block.
+    fn clamp(self, value: f32) -> f32 {
+        match self {
+            Self::Bc6 => value.clamp(0.0, HALF_MAX),
+            _ => value.clamp(0.0, 1.0),
+        }
+    }
+
+    /// The container of the dds files written by [`fallback_compress`]. Formats which
+    /// have no four character code are written with a dx10 header.
+    ///
+    /// [`fallback_compress`]: fn.fallback_compress.html
+    fn container(self) -> Container {
+        match self {
+            Self::Bc6 | Self::Bc7 => Container::Dds10,
+            _ => Container::Dds,
+        }
+    }
+
+    /// The four character code which identifies the format in a dds header.
+    fn four_cc(self) -> &'static [u8; 4] {
+        match self {
+            Self::Bc1 { .. } => b"DXT1",
+            Self::Bc2 => b"DXT3",
+            Self::Bc3 | Self::Bc3n => b"DXT5",
+            Self::Bc4 => b"ATI1",
+            Self::Bc5 => b"ATI2",
+            Self::Bc6 | Self::Bc7 => b"DX10",
+        }
+    }
+
+    /// Encode a block of `texels`, and append it to `out`.
+    fn encode(self, texels: &[[f32; 4]; 16], out: &mut Vec<u8>) {
+        let channel = |c: usize| {
+            let mut values = [0.0; 16];
+            for (value, texel) in values.iter_mut().zip(texels.iter()) {
+                *value = texel[c];
+            }
+            values
+        };
+
+        match self {
+            Self::Bc1 { alpha } => encode_color(texels, alpha, out),
+            Self::Bc2 => {
+                let alpha = channel(3).iter().enumerate().fold(0u64, |bits, (i, &a)| {
+                    bits | (u64::from(quantize(a, 15)) << (4 * i))
+                });
+                out.extend_from_slice(&alpha.to_le_bytes());
+                encode_color(texels, false, out);
+            }
+            Self::Bc3 => {
+                encode_alpha(&channel(3), out);
+                encode_color(texels, false, out);
+            }
+            Self::Bc3n => {
+                let swizzled = texels.map(|[x, y, _, _]| [1.0, y, 0.0, x]);
+                Self::Bc3.encode(&swizzled, out);
+            }
+            Self::Bc4 => encode_alpha(&channel(0), out),
+            Self::Bc5 => {
+                encode_alpha(&channel(0), out);
+                encode_alpha(&channel(1), out);
+            }
+            Self::Bc6 => encode_bc6(texels, out),
+            Self::Bc7 => encode_bc7(texels, out),
+        }
+    }
+}
+
+impl Format {
+    /// Returns `true` if [`fallback_encode`] can compress a [`Surface`] to this format.
+    /// These are the `Bc1` to `Bc7` formats, `Bc3n`, and their `Dxt` aliases.
+    ///
+    /// # Notes
+    ///
+    /// This method requires the [`fallback-encoders`] feature.
+    ///
+    /// [`fallback_encode`]: fn.fallback_encode.html
+    /// [`Surface`]: struct.Surface.html
+    /// [`fallback-encoders`]: index.html#fallback-encoders
+    #[inline]
+    pub fn has_fallback_encoder(self) -> bool {
+        BlockKind::for_format(self).is_some()
+    }
+}
+
+/// Get the container of the dds file which [`fallback_compress`] writes for `format`,
+/// or `None` if the format has no fallback encoder.
+///
+/// [`fallback_compress`]: fn.fallback_compress.html
+pub(crate) fn fallback_container(format: Format) -> Option<Container> {
+    BlockKind::for_format(format).map(BlockKind::container)
+}
+
+/// Compress the `surface` to `format` with the pure Rust block encoders, without
+/// using nvtt. The blocks of each depth slice are returned in row major order, with
+/// no header.
+///
+/// The encoders fit the endpoints of each block to the principal axis of its colors,
+/// so they are fast, but do not match the quality of nvtt. `Bc6` and `Bc7` blocks only
+/// use a single pair of endpoints. Values are clamped to the `0..1` range before they
+/// are encoded, except for `Bc6`, which keeps values up to the largest half precision
+/// float.
+///
+/// # Errors
+///
+/// If the `format` is not supported by the fallback encoders, then this function
+/// will fail with [`Error::UnsupportedOutputFormat`]. If the `surface` is null, then
+/// it will fail with [`Error::InvalidInput`].
+///
+/// [`Error::UnsupportedOutputFormat`]: enum.Error.html#variant.UnsupportedOutputFormat
+/// [`Error::InvalidInput`]: enum.Error.html#variant.InvalidInput
+pub fn fallback_encode(surface: &Surface, format: Format) -> Result<Vec<u8>, Error> {
+    let kind = BlockKind::for_format(format).ok_or(Error::UnsupportedOutputFormat)?;
+    if surface.is_null() {
+        return Err(Error::InvalidInput);
+    }
+
+    let (width, height, depth) = (surface.width(), surface.height(), surface.depth());
+    let (blocks_x, blocks_y) = (width.div_ceil(4), height.div_ceil(4));
+    let mut out = Vec::with_capacity(blocks_x * blocks_y * depth * kind.block_size());
+    let mut texels = [[0.0; 4]; 16];
+
+    for z in 0..depth {
+        for block_y in 0..blocks_y {
+            for block_x in 0..blocks_x {
+                // Blocks which extend past the edge of the surface repeat the edge texels.
+                for (i, texel) in texels.iter_mut().enumerate() {
+                    let x = (block_x * 4 + i % 4).min(width - 1);
+                    let y = (block_y * 4 + i / 4).min(height - 1);
+                    let rgba = surface.pixel(x + y * width + z * width * height);
+                    *texel = rgba.map(|value| kind.clamp(value));
+                }
+                kind.encode(&texels, &mut out);
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+/// Compress a chain of `mipmaps` to `format` with the pure Rust block encoders, and
+/// return the contents of a dds file. The first `Surface` is the top level of the
+/// texture, and the dimensions of each following `Surface` must be half of the
+/// previous level. See [`fallback_encode`] for more information. `Bc6` and `Bc7`
+/// textures are written with a dx10 header.
+///
+/// This can be used where nvtt cannot compress the texture, for example when this
+/// crate is built without the [`nvtt`] feature, so there is no [`Compressor`].
+///
+/// # Errors
+///
+/// If `mipmaps` is empty, then this function will fail with [`Error::InvalidInput`].
+/// Otherwise, the errors of [`fallback_encode`] are returned.
+///
+/// [`fallback_encode`]: fn.fallback_encode.html
+/// [`nvtt`]: index.html#nvtt
+/// [`Compressor`]: struct.Compressor.html
+/// [`Error::InvalidInput`]: enum.Error.html#variant.InvalidInput
+pub fn fallback_compress(mipmaps: &[Surface], format: Format) -> Result<Vec<u8>, Error> {
+    const DDSD_CAPS: u32 = 0x1;
+    const DDSD_HEIGHT: u32 = 0x2;
+    const DDSD_WIDTH: u32 = 0x4;
+    const DDSD_PIXELFORMAT: u32 = 0x1000;
+    const DDSD_MIPMAPCOUNT: u32 = 0x0002_0000;
+    const DDSD_LINEARSIZE: u32 = 0x0008_0000;
+    const DDSD_DEPTH: u32 = 0x0080_0000;
+    const DDPF_ALPHAPIXELS: u32 = 0x1;
+    const DDPF_FOURCC: u32 = 0x4;
+    const DDSCAPS_COMPLEX: u32 = 0x8;
+    const DDSCAPS_TEXTURE: u32 = 0x1000;
+    const DDSCAPS_MIPMAP: u32 = 0x0040_0000;
+    const DDSCAPS2_VOLUME: u32 = 0x0020_0000;
+    const D3D10_RESOURCE_DIMENSION_TEXTURE2D: u32 = 3;
+    const D3D10_RESOURCE_DIMENSION_TEXTURE3D: u32 = 4;
+
+    let kind = BlockKind::for_format(format).ok_or(Error::UnsupportedOutputFormat)?;
+    let top = mipmaps.first().ok_or(Error::InvalidInput)?;
+    let levels = mipmaps
+        .iter()
+        .map(|surface| fallback_encode(surface, format))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let to_u32 = |value: usize| u32::try_from(value).map_err(|_| Error::InvalidInput);
+    let (width, height, depth) = (top.width(), top.height(), top.depth());
+    let linear_size = width.div_ceil(4) * height.div_ceil(4) * kind.block_size();
+
+    let mut flags = DDSD_CAPS | DDSD_HEIGHT | DDSD_WIDTH | DDSD_PIXELFORMAT | DDSD_LINEARSIZE;
+    let mut caps = DDSCAPS_TEXTURE;
+    let mut caps2 = 0;
+    if mipmaps.len() > 1 {
+        flags |= DDSD_MIPMAPCOUNT;
+        caps |= DDSCAPS_COMPLEX | DDSCAPS_MIPMAP;
+    }
+    if depth > 1 {
+        flags |= DDSD_DEPTH;
+        caps |= DDSCAPS_COMPLEX;
+        caps2 |= DDSCAPS2_VOLUME;
+    }
+
+    // Readers use the alpha flag to tell `Bc1a` data apart from opaque `Bc1`, as both
+    // have the same four character code.
+    let pixel_flags = match kind {
+        BlockKind::Bc1 { alpha: true } => DDPF_FOURCC | DDPF_ALPHAPIXELS,
+        _ => DDPF_FOURCC,
+    };
+
+    let header = [
+        124,
+        flags,
+        to_u32(height)?,
+        to_u32(width)?,
+        to_u32(linear_size)?,
+        to_u32(depth)?,
+        to_u32(mipmaps.len())?,
+    ];
+    let data_len = levels.iter().map(Vec::len).sum::<usize>();
+    let mut out = Vec::with_capacity(148 + data_len);
+    out.extend_from_slice(b"DDS ");
+    header
+        .iter()
+        .chain([0; 11].iter())
+        // The pixel format.
+        .chain([32, pixel_flags].iter())
+        .for_each(|value| out.extend_from_slice(&value.to_le_bytes()));
+    out.extend_from_slice(kind.four_cc());
+    [0, 0, 0, 0, 0, caps, caps2, 0, 0, 0]
+        .iter()
+        .for_each(|value: &u32| out.extend_from_slice(&value.to_le_bytes()));
+    if kind.container() == Container::Dds10 {
+        let dxgi_format = format
+            .to_dxgi(false)
+            .ok_or(Error::UnsupportedOutputFormat)?;
+        let dimension = if depth > 1 {
+            D3D10_RESOURCE_DIMENSION_TEXTURE3D
+        } else {
+            D3D10_RESOURCE_DIMENSION_TEXTURE2D
+        };
+        [u32::from(dxgi_format), dimension, 0, 1, 0]
+            .iter()
+            .for_each(|value| out.extend_from_slice(&value.to_le_bytes()));
+    }
+    levels.iter().for_each(|level| out.extend_from_slice(level));
+
+    Ok(out)
+}
+
+/// Quantize `value` in the `0..1` range to an integer in the `0..=max` range.
+#[inline]
+fn quantize(value: f32, max: u8) -> u8 {
+    (value * f32::from(max) + 0.5) as u8
+}
+
+/// Encode a single channel block, as used by the alpha of `Bc3` and by `Bc4` and `Bc5`.
+fn encode_alpha(values: &[f32; 16], out: &mut Vec<u8>) {
+    let min = values.iter().copied().fold(1.0, f32::min);
+    let max = values.iter().copied().fold(0.0, f32::max);
+    let (a0, a1) = (quantize(max, 255), quantize(min, 255));
+
+    // With `a0 > a1`, the palette interpolates six values between the endpoints.
+    let mut palette = [f32::from(a0), f32::from(a1), 0.0, 0.0, 0.0, 0.0, 0.0, 0.0];
+    for (i, entry) in palette.iter_mut().enumerate().skip(2) {
+        *entry = ((8 - i) as f32 * f32::from(a0) + (i - 1) as f32 * f32::from(a1)) / 7.0;
+    }
+
+    let indices = if a0 > a1 {
+        values.iter().enumerate().fold(0u64, |bits, (i, &value)| {
+            let index = nearest(palette.iter().map(|&entry| (entry - value * 255.0).abs()));
+            bits | ((index as u64) << (3 * i))
+        })
+    } else {
+        0
+    };
+
+    out.push(a0);
+    out.push(a1);
+    out.extend_from_slice(&indices.to_le_bytes()[..6]);
+}
+
+/// Encode the color part of a `Bc1`, `Bc2` or `Bc3` block. If `alpha` is `true`,
+/// texels with an alpha below `0.5` are encoded as transparent.
+fn encode_color(texels: &[[f32; 4]; 16], alpha: bool, out: &mut Vec<u8>) {
+    let transparent = |texel: &[f32; 4]| alpha && texel[3] < 0.5;
+    let opaque = texels
+        .iter()
+        .filter(|texel| !transparent(texel))
+        .map(|texel| [texel[0], texel[1], texel[2]])
+        .collect::<Vec<_>>();
+
+    if opaque.is_empty() {
+        // Every texel is transparent, which needs the three color mode.
+        out.extend_from_slice(&[0, 0, 0, 0, 0xff, 0xff, 0xff, 0xff]);
+        return;
+    }
+
+    let (low, high) = principal_endpoints(&opaque);
+    let (mut c0, mut c1) = (pack_565(high), pack_565(low));
+    // The order of the endpoints selects the mode of the block. Four colors are used
+    // when `c0 > c1`, and three colors and a transparent texel otherwise.
+    let three_color = opaque.len() < texels.len();
+    if (three_color && c0 > c1) || (!three_color && c0 < c1) {
+        std::mem::swap(&mut c0, &mut c1);
+    }
+
+    let (e0, e1) = (unpack_565(c0), unpack_565(c1));
+    let lerp = |t: f32| {
+        let mut color = [0.0; 3];
+        for (c, value) in color.iter_mut().enumerate() {
+            *value = e0[c] + (e1[c] - e0[c]) * t;
+        }
+        color
+    };
+    let palette = if three_color {
+        vec![e0, e1, lerp(0.5)]
+    } else {
+        vec![e0, e1, lerp(1.0 / 3.0), lerp(2.0 / 3.0)]
+    };
+
+    let indices = texels.iter().enumerate().fold(0u32, |bits, (i, texel)| {
+        let index = if transparent(texel) {
+            3
+        } else {
+            nearest(palette.iter().map(|entry| {
+                (0..3)
+                    .map(|c| (entry[c] - texel[c]) * (entry[c] - texel[c]))
+                    .sum::<f32>()
+            }))
+        };
+        bits | ((index as u32) << (2 * i))
+    });
+
+    out.extend_from_slice(&c0.to_le_bytes());
+    out.extend_from_slice(&c1.to_le_bytes());
+    out.extend_from_slice(&indices.to_le_bytes());
+}
+
+/// Encode a `Bc6` block in mode 11. The endpoints are fitted to the bits of the half
+/// precision values, which the decoder interpolates as integers.
+fn encode_bc6(texels: &[[f32; 4]; 16], out: &mut Vec<u8>) {
+    // The decoder scales the interpolated values by 31/64 to get the bits of a half
+    // float, so the colors are fitted in the unscaled `0..1` range.
+    let colors = texels.map(|[r, g, b, _]| {
+        [r, g, b].map(|value| f32::from(to_half(value)) * 64.0 / 31.0 / 65535.0)
+    });
+    let (low, high) = principal_endpoints(&colors);
+
+    let quantize_endpoint = |color: [f32; 3]| {
+        color.map(|v| ((v * 65535.0 - 32.0) / 64.0).round().clamp(0.0, 1023.0) as u32)
+    };
+    let unquantize = |value: u32| match value {
+        0 => 0,
+        1023 => 0xffff,
+        value => (value << 6) + 32,
+    };
+    let (mut e0, mut e1) = (quantize_endpoint(low), quantize_endpoint(high));
+    let palette = WEIGHTS_4.map(|w| {
+        array::from_fn(|c| {
+            let (a, b) = (unquantize(e0[c]), unquantize(e1[c]));
+            (((64 - w) * a + w * b + 32) >> 6) as f32 / 65535.0
+        })
+    });
+
+    let (indices, swap) = palette_indices(&colors, &palette);
+    if swap {
+        std::mem::swap(&mut e0, &mut e1);
+    }
+
+    let mut bits = BlockBits::default();
+    bits.push(0b00011, 5);
+    e0.iter().chain(e1.iter()).for_each(|&e| bits.push(e, 10));
+    bits.push_indices(&indices);
+    out.extend_from_slice(&bits.bits.to_le_bytes());
+}
+
+/// Encode a `Bc7` block in mode 6, which stores 7 bits of each channel of the
+/// endpoints, and a shared low bit for each endpoint.
+fn encode_bc7(texels: &[[f32; 4]; 16], out: &mut Vec<u8>) {
+    let quantize_endpoint = |color: [f32; 4]| {
+        let candidate = |p: u8| {
+            let channels = color.map(|value| {
+                ((value * 255.0 - f32::from(p)) / 2.0)
+                    .round()
+                    .clamp(0.0, 127.0) as u8
+            });
+            let error = (0..4)
+                .map(|c| {
+                    let d = f32::from((channels[c] << 1) | p) - color[c] * 255.0;
+                    d * d
+                })
+                .sum::<f32>();
+            (channels, p, error)
+        };
+        let (even, odd) = (candidate(0), candidate(1));
+        if odd.2 < even.2 {
+            (odd.0, odd.1)
+        } else {
+            (even.0, even.1)
+        }
+    };
+
+    let (low, high) = principal_endpoints(texels);
+    let (mut e0, mut e1) = (quantize_endpoint(low), quantize_endpoint(high));
+    let expand = |(channels, p): ([u8; 4], u8), c: usize| u32::from((channels[c] << 1) | p);
+    let palette = WEIGHTS_4.map(|w| {
+        array::from_fn(|c| {
+            let (a, b) = (expand(e0, c), expand(e1, c));
+            (((64 - w) * a + w * b + 32) >> 6) as f32 / 255.0
+        })
+    });
+
+    let (indices, swap) = palette_indices(texels, &palette);
+    if swap {
+        std::mem::swap(&mut e0, &mut e1);
+    }
+
+    let mut bits = BlockBits::default();
+    bits.push(1 << 6, 7);
+    for c in 0..4 {
+        bits.push(u32::from(e0.0[c]), 7);
+        bits.push(u32::from(e1.0[c]), 7);
+    }
+    bits.push(u32::from(e0.1), 1);
+    bits.push(u32::from(e1.1), 1);
+    bits.push_indices(&indices);
+    out.extend_from_slice(&bits.bits.to_le_bytes());
+}
+
+/// The bits of a 128 bit block, which are written from the least significant bit.
+#[derive(Default)]
+struct BlockBits {
+    bits: u128,
+    len: u32,
+}
+
+impl BlockBits {
+    /// Append the low `count` bits of `value`.
+    fn push(&mut self, value: u32, count: u32) {
+        let mask = (1 << count) - 1;
+        self.bits |= u128::from(value & mask) << self.len;
+        self.len += count;
+    }
+
+    /// Append 4 bit `indices`. The high bit of the first index is always zero, so it
+    /// is not stored.
+    fn push_indices(&mut self, indices: &[u32; 16]) {
+        self.push(indices[0], 3);
+        indices[1..].iter().for_each(|&index| self.push(index, 4));
+    }
+}
+
+/// Find the nearest entry of the `palette` to each of the `texels`. If the first
+/// index does not fit in 3 bits, then the indices are reversed, and `true` is
+/// returned to show that the endpoints must be swapped.
+fn palette_indices<const N: usize>(
+    texels: &[[f32; N]; 16],
+    palette: &[[f32; N]; 16],
+) -> ([u32; 16], bool) {
+    let mut indices = texels.map(|texel| {
+        nearest(palette.iter().map(|entry| {
+            (0..N)
+                .map(|c| (entry[c] - texel[c]) * (entry[c] - texel[c]))
+                .sum::<f32>()
+        })) as u32
+    });
+
+    let swap = indices[0] >= 8;
+    if swap {
+        indices.iter_mut().for_each(|index| *index = 15 - *index);
+    }
+    (indices, swap)
+}
+
+/// Convert a `value` in the `0..=HALF_MAX` range into the bits of a half precision
+/// float, rounding to the nearest value.
+fn to_half(value: f32) -> u16 {
+    // Values below the smallest normal half are stored as multiples of `2^-24`.
+    const MIN_NORMAL: f32 = 6.103_515_6e-5;
+    if value.is_nan() || value <= 0.0 {
+        return 0;
+    } else if value < MIN_NORMAL {
+        return (value * 16_777_216.0).round() as u16;
+    }
+
+    let bits = value.min(HALF_MAX).to_bits();
+    // Rebias the exponent from the 127 of an `f32` to the 15 of a half.
+    let exponent = (bits >> 23) - 112;
+    let mantissa = bits & 0x007f_ffff;
+    // Rounding may carry into the exponent, which gives the next power of two.
+    (((exponent << 10) | (mantissa >> 13)) + ((mantissa >> 12) & 1)) as u16
+}
+
+/// Find the endpoints of the line through `colors` along their principal axis, so
+/// that every color projects onto the segment between them. Each channel of the
+/// endpoints is clamped to the `0..1` range.
+fn principal_endpoints<const N: usize>(colors: &[[f32; N]]) -> ([f32; N], [f32; N]) {
+    let n = colors.len() as f32;
+    let mut mean = [0.0; N];
+    for color in colors {
+        (0..N).for_each(|c| mean[c] += color[c] / n);
+    }
+
+    let mut covariance = [[0.0; N]; N];
+    for color in colors {
+        let d: [f32; N] = array::from_fn(|c| color[c] - mean[c]);
+        for (i, row) in covariance.iter_mut().enumerate() {
+            (0..N).for_each(|j| row[j] += d[i] * d[j]);
+        }
+    }
+
+    // A few iterations of the power method converge on the principal axis.
+    let mut axis = [1.0; N];
+    for _ in 0..8 {
+        let next: [f32; N] =
+            array::from_fn(|i| (0..N).map(|j| covariance[i][j] * axis[j]).sum::<f32>());
+        let length = next.iter().map(|v| v * v).sum::<f32>().sqrt();
+        if length < 1e-8 {
+            return (mean, mean);
+        }
+        axis = next.map(|v| v / length);
+    }
+
+    let project = |color: &[f32; N]| (0..N).map(|c| (color[c] - mean[c]) * axis[c]).sum::<f32>();
+    let (min, max) = colors
+        .iter()
+        .map(project)
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), t| {
+            (min.min(t), max.max(t))
+        });
+    let point = |t: f32| array::from_fn(|c| (mean[c] + axis[c] * t).clamp(0.0, 1.0));
+    (point(min), point(max))
+}
+
+/// Get the index of the smallest of the `errors`.
+fn nearest<I: Iterator<Item = f32>>(errors: I) -> usize {
+    errors
+        .enumerate()
+        .fold((0, f32::INFINITY), |best, (i, error)| {
+            if error < best.1 {
+                (i, error)
+            } else {
+                best
+            }
+        })
+        .0
+}
+
+fn pack_565(color: [f32; 3]) -> u16 {
+    (u16::from(quantize(color[0], 31)) << 11)
+        | (u16::from(quantize(color[1], 63)) << 5)
+        | u16::from(quantize(color[2], 31))
+}
+
+fn unpack_565(color: u16) -> [f32; 3] {
+    let (r, g, b) = ((color >> 11) & 0x1f, (color >> 5) & 0x3f, color & 0x1f);
+    [
+        f32::from((r << 3) | (r >> 2)) / 255.0,
+        f32::from((g << 2) | (g >> 4)) / 255.0,
+        f32::from((b << 3) | (b >> 2)) / 255.0,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryInto;
+
+    /// Reads the bits of a 128 bit block from the least significant bit.
+    struct BitReader(u128);
+
+    impl BitReader {
+        fn new(block: &[u8]) -> Self {
+            Self(u128::from_le_bytes(block.try_into().unwrap()))
+        }
+
+        fn read(&mut self, count: u32) -> u32 {
+            let value = (self.0 & ((1 << count) - 1)) as u32;
+            self.0 >>= count;
+            value
+        }
+
+        /// Read the 4 bit indices of a block, where the first index has an implicit high
+        /// bit of zero.
+        fn read_indices(&mut self) -> [u32; 16] {
+            array::from_fn(|i| self.read(if i == 0 { 3 } else { 4 }))
+        }
+    }
+
+    /// Interpolate between two integer endpoints with one of the 4 bit index weights.
+    fn interpolate(a: u32, b: u32, index: u32) -> u32 {
+        const WEIGHTS: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+        let w = WEIGHTS[index as usize];
+        ((64 - w) * a + w * b + 32) >> 6
+    }
+
+    fn decode_bc1(block: &[u8]) -> [[f32; 4]; 16] {
+        let expand = |color: u16| {
+            let (r, g, b) = ((color >> 11) & 0x1f, (color >> 5) & 0x3f, color & 0x1f);
+            [
+                (r << 3) | (r >> 2),
+                (g << 2) | (g >> 4),
+                (b << 3) | (b >> 2),
+            ]
+            .map(f32::from)
+        };
+        let c0 = u16::from_le_bytes([block[0], block[1]]);
+        let c1 = u16::from_le_bytes([block[2], block[3]]);
+        let (e0, e1) = (expand(c0), expand(c1));
+        let mix = |w0: f32, w1: f32, d: f32| {
+            let [r, g, b]: [f32; 3] = array::from_fn(|c| (w0 * e0[c] + w1 * e1[c]) / d / 255.0);
+            [r, g, b, 1.0]
+        };
+        let palette = if c0 > c1 {
+            [
+                mix(1.0, 0.0, 1.0),
+                mix(0.0, 1.0, 1.0),
+                mix(2.0, 1.0, 3.0),
+                mix(1.0, 2.0, 3.0),
+            ]
+        } else {
+            [
+                mix(1.0, 0.0, 1.0),
+                mix(0.0, 1.0, 1.0),
+                mix(1.0, 1.0, 2.0),
+                [0.0; 4],
+            ]
+        };
+
+        let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+        array::from_fn(|i| palette[((indices >> (2 * i)) & 3) as usize])
+    }
+
+    fn decode_alpha(block: &[u8]) -> [f32; 16] {
+        let (a0, a1) = (f32::from(block[0]), f32::from(block[1]));
+        let mut palette = [a0, a1, 0.0, 0.0, 0.0, 0.0, 0.0, 255.0];
+        if a0 > a1 {
+            for i in 1..7 {
+                palette[i + 1] = ((7 - i) as f32 * a0 + i as f32 * a1) / 7.0;
+            }
+        } else {
+            for i in 1..5 {
+                palette[i + 1] = ((5 - i) as f32 * a0 + i as f32 * a1) / 5.0;
+            }
+        }
+
+        let mut bytes = [0; 8];
+        bytes[..6].copy_from_slice(&block[2..8]);
+        let indices = u64::from_le_bytes(bytes);
+        array::from_fn(|i| palette[((indices >> (3 * i)) & 7) as usize] / 255.0)
+    }
+
+    fn half_to_f32(half: u32) -> f32 {
+        let (exponent, mantissa) = ((half >> 10) & 0x1f, half & 0x3ff);
+        if exponent == 0 {
+            mantissa as f32 / 16_777_216.0
+        } else {
+            (1.0 + mantissa as f32 / 1024.0) * 2f32.powi(exponent as i32 - 15)
+        }
+    }
+
+    fn decode_bc6(block: &[u8]) -> [[f32; 3]; 16] {
+        let mut bits = BitReader::new(block);
+        assert_eq!(bits.read(5), 0b00011, "expected a mode 11 block");
+        let endpoints: [u32; 6] = array::from_fn(|_| match bits.read(10) {
+            0 => 0,
+            1023 => 0xffff,
+            value => (value << 6) + 32,
+        });
+        let indices = bits.read_indices();
+        indices.map(|index| {
+            array::from_fn(|c| {
+                let value = interpolate(endpoints[c], endpoints[c + 3], index);
+                half_to_f32((value * 31) >> 6)
+            })
+        })
+    }
+
+    fn decode_bc7(block: &[u8]) -> [[f32; 4]; 16] {
+        let mut bits = BitReader::new(block);
+        assert_eq!(bits.read(7), 1 << 6, "expected a mode 6 block");
+        let channels: [[u32; 2]; 4] = array::from_fn(|_| [bits.read(7), bits.read(7)]);
+        let p = [bits.read(1), bits.read(1)];
+        let indices = bits.read_indices();
+        indices.map(|index| {
+            array::from_fn(|c| {
+                let [a, b] = channels[c];
+                interpolate((a << 1) | p[0], (b << 1) | p[1], index) as f32 / 255.0
+            })
+        })
+    }
+
+    fn surface(texels: &[[f32; 4]; 16]) -> Surface {
+        let data = texels.iter().flatten().copied().collect::<Vec<_>>();
+        Surface::from_rgba(4, 4, &data).unwrap()
+    }
+
+    fn encode_block(texels: &[[f32; 4]; 16], format: Format) -> Vec<u8> {
+        fallback_encode(&surface(texels), format).unwrap()
+    }
+
+    fn max_error<const N: usize>(expected: &[[f32; N]; 16], actual: &[[f32; N]; 16]) -> f32 {
+        expected
+            .iter()
+            .flatten()
+            .zip(actual.iter().flatten())
+            .map(|(e, a)| (e - a).abs())
+            .fold(0.0, f32::max)
+    }
+
+    /// A block of colors along a line, with a different alpha at each texel.
+    fn gradient() -> [[f32; 4]; 16] {
+        array::from_fn(|i| {
+            let t = (i % 4) as f32 / 3.0;
+            let alpha = ((i % 4 + i / 4 * 4) % 8) as f32 / 7.0;
+            [0.1 + 0.8 * t, 0.7 - 0.5 * t, 0.3 + 0.2 * t, alpha]
+        })
+    }
+
+    fn opaque(texels: &[[f32; 4]; 16]) -> [[f32; 4]; 16] {
+        texels.map(|[r, g, b, _]| [r, g, b, 1.0])
+    }
+
+    #[test]
+    fn bc1_blocks_decode_to_the_input() {
+        let texels = opaque(&gradient());
+        let block = encode_block(&texels, Format::Bc1);
+        assert_eq!(block.len(), 8);
+        assert!(max_error(&texels, &decode_bc1(&block)) < 0.04);
+    }
+
+    #[test]
+    fn bc1a_blocks_decode_transparent_texels() {
+        // The three color mode has a single color between its endpoints.
+        let mut texels: [[f32; 4]; 16] = array::from_fn(|i| {
+            let t = (i % 3) as f32 / 2.0;
+            [0.1 + 0.8 * t, 0.7 - 0.5 * t, 0.3 + 0.2 * t, 1.0]
+        });
+        texels
+            .iter_mut()
+            .step_by(5)
+            .for_each(|texel| texel[3] = 0.0);
+        let decoded = decode_bc1(&encode_block(&texels, Format::Bc1a));
+
+        for (texel, decoded) in texels.iter().zip(decoded.iter()) {
+            if texel[3] == 0.0 {
+                assert_eq!(decoded[3], 0.0);
+            } else {
+                assert_eq!(decoded[3], 1.0);
+                assert!(max_error(&[*texel; 16], &[*decoded; 16]) < 0.04);
+            }
+        }
+    }
+
+    #[test]
+    fn bc3_blocks_decode_to_the_input() {
+        let texels = gradient();
+        let block = encode_block(&texels, Format::Bc3);
+        assert_eq!(block.len(), 16);
+
+        let alpha = decode_alpha(&block[..8]);
+        let mut decoded = decode_bc1(&block[8..]);
+        decoded
+            .iter_mut()
+            .zip(alpha.iter())
+            .for_each(|(texel, &a)| texel[3] = a);
+        assert!(max_error(&texels, &decoded) < 0.04);
+    }
+
+    #[test]
+    fn bc4_and_bc5_blocks_decode_to_the_input() {
+        // Values on the palette of an alpha block with endpoints of `0` and `1`.
+        let texels: [[f32; 4]; 16] =
+            array::from_fn(|i| [(i % 8) as f32 / 7.0, (i * 3 % 8) as f32 / 7.0, 0.0, 1.0]);
+
+        let block = encode_block(&texels, Format::Bc4);
+        assert_eq!(block.len(), 8);
+        let red = decode_alpha(&block).map(|r| [r]);
+        assert!(max_error(&texels.map(|texel| [texel[0]]), &red) < 0.01);
+
+        let block = encode_block(&texels, Format::Bc5);
+        assert_eq!(block.len(), 16);
+        let (red, green) = (decode_alpha(&block[..8]), decode_alpha(&block[8..]));
+        let decoded = array::from_fn(|i| [red[i], green[i]]);
+        assert!(max_error(&texels.map(|texel| [texel[0], texel[1]]), &decoded) < 0.01);
+    }
+
+    #[test]
+    fn bc6_blocks_decode_to_the_input() {
+        // The decoder interpolates the bits of the half floats, so the colors are spaced
+        // evenly in those bits rather than in their values.
+        let texels: [[f32; 4]; 16] = array::from_fn(|i| {
+            let bits = |low: u16, high: u16| {
+                half_to_f32(u32::from(low) + (u32::from(high - low) * i as u32 + 7) / 15)
+            };
+            let (r, g, b) = (
+                bits(0x3800, 0x4800),
+                bits(0x3400, 0x4000),
+                bits(0x3000, 0x3c00),
+            );
+            [r, g, b, 1.0]
+        });
+        let block = encode_block(&texels, Format::Bc6);
+        assert_eq!(block.len(), 16);
+
+        let decoded = decode_bc6(&block);
+        for (texel, decoded) in texels.iter().zip(decoded.iter()) {
+            for c in 0..3 {
+                let error = (texel[c] - decoded[c]).abs() / texel[c];
+                assert!(error < 0.03, "{} decoded as {}", texel[c], decoded[c]);
+            }
+        }
+    }
+
+    #[test]
+    fn bc7_blocks_decode_to_the_input() {
+        let texels: [[f32; 4]; 16] = array::from_fn(|i| {
+            let t = i as f32 / 15.0;
+            [0.9 - 0.8 * t, 0.2 + 0.6 * t, 0.5, 0.25 + 0.75 * t]
+        });
+        let block = encode_block(&texels, Format::Bc7);
+        assert_eq!(block.len(), 16);
+        assert!(max_error(&texels, &decode_bc7(&block)) < 0.01);
+    }
+
+    #[test]
+    fn bc1a_dds_files_set_the_alpha_flag() {
+        const DDPF_ALPHAPIXELS: u32 = 0x1;
+        let surface = surface(&gradient());
+        let pixel_flags = |format| {
+            let dds = fallback_compress(std::slice::from_ref(&surface), format).unwrap();
+            u32::from_le_bytes(dds[80..84].try_into().unwrap())
+        };
+
+        assert_ne!(pixel_flags(Format::Bc1a) & DDPF_ALPHAPIXELS, 0);
+        assert_eq!(pixel_flags(Format::Bc1) & DDPF_ALPHAPIXELS, 0);
+    }
+}
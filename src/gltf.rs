@@ -0,0 +1,269 @@
+// Copyright © 2019-2020 George Burton
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::{BasisOptions, Error, Surface};
+use serde_json::{json, Map, Value};
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error as ErrorTrait,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+const KHR_TEXTURE_BASISU: &str = "KHR_texture_basisu";
+
+/// Compress every image referenced by the glTF file at `input` into a ktx2 file, and
+/// write a copy of the glTF file to `output` which uses them through the
+/// `KHR_texture_basisu` extension.
+///
+/// The original images are kept as a fallback for viewers which do not support the
+/// extension. If `output` is in a different directory to `input`, then the original
+/// images and buffers are copied next to it. Images used as a base color or emissive
+/// texture are compressed as srgb data, and all other images as linear data.
+///
+/// # Example
+///
+/// ```no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use nvtt_rs::{BasisFormat, BasisOptions};
+///
+/// let mut options = BasisOptions::new(BasisFormat::Uastc);
+/// options.mipmaps(true);
+/// nvtt_rs::compress_gltf_textures("scene.gltf", "cooked/scene.gltf", &options)?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// If the glTF file cannot be read or parsed, or any file cannot be written, then
+/// this function will fail. Only `.gltf` files are supported, as the binary `.glb`
+/// format cannot be rewritten. Images embedded as data uris are not compressed.
+///
+/// # Notes
+///
+/// This function requires the [`gltf`] feature.
+///
+/// [`gltf`]: index.html#gltf
+pub fn compress_gltf_textures<P, Q>(
+    input: P,
+    output: Q,
+    options: &BasisOptions,
+) -> Result<(), GltfError>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let (input, output) = (input.as_ref(), output.as_ref());
+    let input_dir = parent_dir(input);
+    let output_dir = parent_dir(output);
+    fs::create_dir_all(&output_dir)?;
+    let copy_resources = input_dir.canonicalize()? != output_dir.canonicalize()?;
+
+    let mut document: Value = serde_json::from_slice(&fs::read(input)?)?;
+    let srgb_images = srgb_images(&document);
+
+    let mut images = document["images"].as_array().cloned().unwrap_or_default();
+    let mut ktx2_images = HashMap::new();
+    for index in 0..images.len() {
+        let uri = match external_uri(&images[index]) {
+            Some(uri) => uri.to_string(),
+            None => continue,
+        };
+
+        let image = image::open(input_dir.join(&uri)).map_err(|e| {
+            log::error!("Could not open image {u}\nCaused by: {e}", u = uri, e = e);
+            GltfError::Texture {
+                uri: uri.clone(),
+                error: Error::FileOpen,
+            }
+        })?;
+        let data = options
+            .clone()
+            .srgb(srgb_images.contains(&index))
+            .compress_ktx2(&Surface::from(&image))
+            .map_err(|error| GltfError::Texture {
+                uri: uri.clone(),
+                error,
+            })?;
+
+        let ktx2_uri = Path::new(&uri).with_extension("ktx2");
+        write_file(&output_dir.join(&ktx2_uri), &data)?;
+        if copy_resources {
+            copy_file(&input_dir.join(&uri), &output_dir.join(&uri))?;
+        }
+
+        ktx2_images.insert(index, images.len());
+        images.push(json!({
+            "uri": ktx2_uri.to_string_lossy(),
+            "mimeType": "image/ktx2",
+        }));
+    }
+
+    if copy_resources {
+        for buffer in document["buffers"].as_array().into_iter().flatten() {
+            if let Some(uri) = external_uri(buffer) {
+                copy_file(&input_dir.join(uri), &output_dir.join(uri))?;
+            }
+        }
+    }
+
+    if !ktx2_images.is_empty() {
+        document["images"] = Value::Array(images);
+        let textures = document["textures"].as_array_mut().into_iter().flatten();
+        for texture in textures {
+            let source = texture["source"].as_u64().map(|source| source as usize);
+            if let Some(&ktx2_source) = source.and_then(|source| ktx2_images.get(&source)) {
+                object_entry(texture, "extensions")
+                    .insert(KHR_TEXTURE_BASISU.into(), json!({ "source": ktx2_source }));
+            }
+        }
+
+        let used = document
+            .as_object_mut()
+            .ok_or(GltfError::InvalidDocument)?
+            .entry("extensionsUsed")
+            .or_insert_with(|| json!([]));
+        if let Some(used) = used.as_array_mut() {
+            if !used.iter().any(|e| e == KHR_TEXTURE_BASISU) {
+                used.push(KHR_TEXTURE_BASISU.into());
+            }
+        }
+    }
+
+    write_file(output, &serde_json::to_vec_pretty(&document)?)
+}
+
+/// Get the directory containing `path`.
+fn parent_dir(path: &Path) -> PathBuf {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+/// Get the uri of an image or buffer, unless it is embedded as a data uri.
+fn external_uri(value: &Value) -> Option<&str> {
+    value["uri"]
+        .as_str()
+        .filter(|uri| !uri.starts_with("data:"))
+}
+
+/// Get the indices of the images which are used as srgb textures. The glTF spec
+/// requires that base color and emissive textures contain srgb data.
+fn srgb_images(document: &Value) -> HashSet<usize> {
+    let materials = document["materials"].as_array().into_iter().flatten();
+    materials
+        .flat_map(|material| {
+            vec![
+                &material["pbrMetallicRoughness"]["baseColorTexture"]["index"],
+                &material["emissiveTexture"]["index"],
+            ]
+        })
+        .filter_map(Value::as_u64)
+        .filter_map(|texture| document["textures"][texture as usize]["source"].as_u64())
+        .map(|image| image as usize)
+        .collect()
+}
+
+/// Get the object stored under `key` in `value`, inserting an empty object if needed.
+fn object_entry<'a>(value: &'a mut Value, key: &str) -> &'a mut Map<String, Value> {
+    if !value[key].is_object() {
+        value[key] = json!({});
+    }
+    match value[key] {
+        Value::Object(ref mut map) => map,
+        _ => unreachable!(),
+    }
+}
+
+fn write_file(path: &Path, data: &[u8]) -> Result<(), GltfError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, data).map_err(GltfError::from)
+}
+
+fn copy_file(from: &Path, to: &Path) -> Result<(), GltfError> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::copy(from, to).map(|_| ()).map_err(GltfError::from)
+}
+
+/// An error which may occur in [`compress_gltf_textures`].
+///
+/// [`compress_gltf_textures`]: fn.compress_gltf_textures.html
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum GltfError {
+    /// A file could not be read or written.
+    Io(io::Error),
+    /// The glTF file is not valid json.
+    Json(serde_json::Error),
+    /// The glTF file is valid json, but not a valid glTF document.
+    InvalidDocument,
+    /// An image could not be compressed.
+    Texture {
+        /// The uri of the image.
+        uri: String,
+        /// The reason the image could not be compressed.
+        error: Error,
+    },
+}
+
+impl fmt::Display for GltfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            GltfError::Io(ref e) => write!(f, "Could not access a file: {}", e),
+            GltfError::Json(ref e) => write!(f, "Could not parse the glTF file: {}", e),
+            GltfError::InvalidDocument => f.write_str("The glTF file is not a json object"),
+            GltfError::Texture { ref uri, error } => {
+                write!(f, "Could not compress image {}: {}", uri, error)
+            }
+        }
+    }
+}
+
+impl ErrorTrait for GltfError {
+    #[inline]
+    fn source(&self) -> Option<&(dyn ErrorTrait + 'static)> {
+        match *self {
+            GltfError::Io(ref e) => Some(e),
+            GltfError::Json(ref e) => Some(e),
+            GltfError::InvalidDocument => None,
+            GltfError::Texture { ref error, .. } => Some(error),
+        }
+    }
+}
+
+impl From<io::Error> for GltfError {
+    #[inline]
+    fn from(e: io::Error) -> Self {
+        GltfError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for GltfError {
+    #[inline]
+    fn from(e: serde_json::Error) -> Self {
+        GltfError::Json(e)
+    }
+}
@@ -0,0 +1,804 @@
+// Copyright © 2019-2020 George Burton
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::{Container, DxgiFormat, Error, Format, TextureType};
+use alloc::{vec, vec::Vec};
+use core::{
+    convert::{TryFrom, TryInto},
+    fmt,
+};
+#[cfg(feature = "serde-serialize")]
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "std")]
+use std::{
+    fs::File,
+    io::{ErrorKind, Read},
+    path::Path,
+};
+
+/// Describes an existing compressed texture file. This is returned by [`inspect`].
+///
+/// [`inspect`]: fn.inspect.html
+#[cfg_attr(feature = "serde-serialize", derive(Deserialize, Serialize))]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct TextureInfo {
+    /// The container the texture is stored in.
+    pub container: Container,
+    /// The format of the texture data, or `None` if the format is not one which
+    /// nvtt can produce.
+    pub format: Option<Format>,
+    /// The `DXGI_FORMAT` of the texture data. This is read from the header of dds
+    /// files with the DX10 extension, and derived from the `format` otherwise.
+    pub dxgi_format: Option<DxgiFormat>,
+    /// The type of the texture.
+    pub texture_type: TextureType,
+    /// The width of the texture in pixels.
+    pub width: usize,
+    /// The height of the texture in pixels.
+    pub height: usize,
+    /// The depth of the texture in pixels.
+    pub depth: usize,
+    /// The number of textures in the texture array. This is `1` if the texture is
+    /// not an array. For cube map arrays, this is the number of cube maps.
+    pub array_length: usize,
+    /// Whether the texture is a cube map array. Cube map arrays have a `texture_type`
+    /// of `TextureType::Cube`.
+    #[cfg_attr(feature = "serde-serialize", serde(default))]
+    pub cube_array: bool,
+    /// The number of mipmap levels in the texture.
+    pub mip_count: usize,
+    /// Whether the texture is flagged as being in the sRGB colorspace.
+    pub srgb: bool,
+    /// Problems which were found in a dds file, and worked around while reading it.
+    /// This is always empty for ktx files.
+    #[cfg_attr(feature = "serde-serialize", serde(default))]
+    pub diagnostics: Vec<DdsDiagnostic>,
+}
+
+/// A problem found in a malformed dds file by [`inspect`] or [`inspect_bytes`]. The
+/// file can still be read, and the [`TextureInfo`] describes how it was interpreted.
+///
+/// [`inspect`]: fn.inspect.html
+/// [`inspect_bytes`]: fn.inspect_bytes.html
+/// [`TextureInfo`]: struct.TextureInfo.html
+#[cfg_attr(feature = "serde-serialize", derive(Deserialize, Serialize))]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum DdsDiagnostic {
+    /// The size in the header was not 124 bytes. The header is read as if it was.
+    InvalidHeaderSize(u32),
+    /// The FourCC code of the pixel format is not known, so the format of the texture
+    /// is `None`.
+    UnknownFourCc([u8; 4]),
+    /// The pixel format flags did not describe how the pixels are stored, so the FourCC
+    /// code was used anyway.
+    MissingPixelFormatFlags,
+    /// The header has a mipmap count and the mipmap capability, but does not set the
+    /// flag which marks the mipmap count as valid. The mipmap count is used anyway.
+    MissingMipCountFlag,
+    /// The mipmap count is larger than the number of levels in a full mip chain.
+    ExcessMipCount {
+        /// The mipmap count in the header.
+        found: usize,
+        /// The number of levels in a full mip chain, which is used instead.
+        max: usize,
+    },
+    /// The pitch or linear size in the header does not match the dimensions of the
+    /// texture. The value in the header is ignored.
+    WrongPitch {
+        /// The size which was expected from the format and dimensions.
+        expected: usize,
+        /// The size in the header.
+        found: usize,
+    },
+    /// The file ends before the last mipmap level of the texture. If the texture has a
+    /// single face, then its mipmap count is reduced to the complete levels.
+    TruncatedMipChain {
+        /// The mipmap count in the header.
+        mip_count: usize,
+        /// The number of mipmap levels which are present for every face.
+        complete: usize,
+    },
+}
+
+impl fmt::Display for DdsDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            DdsDiagnostic::InvalidHeaderSize(size) => {
+                write!(f, "the header size is {} bytes instead of 124", size)
+            }
+            DdsDiagnostic::UnknownFourCc(four_cc) => {
+                write!(f, "unknown FourCC code \"{}\"", four_cc.escape_ascii())
+            }
+            DdsDiagnostic::MissingPixelFormatFlags => {
+                f.write_str("the pixel format flags do not describe the pixel format")
+            }
+            DdsDiagnostic::MissingMipCountFlag => {
+                f.write_str("the mipmap count is not flagged as valid")
+            }
+            DdsDiagnostic::ExcessMipCount { found, max } => write!(
+                f,
+                "the mipmap count is {}, but a full mip chain has {} levels",
+                found, max
+            ),
+            DdsDiagnostic::WrongPitch { expected, found } => write!(
+                f,
+                "the pitch is {} bytes, but {} bytes were expected",
+                found, expected
+            ),
+            DdsDiagnostic::TruncatedMipChain {
+                mip_count,
+                complete,
+            } => write!(
+                f,
+                "the file ends after {} of {} mipmap levels",
+                complete, mip_count
+            ),
+        }
+    }
+}
+
+/// The size of the dds magic number, header and DX10 header extension.
+const DDS_HEADER_SIZE: usize = 4 + 124 + 20;
+/// The size of the ktx header.
+const KTX_HEADER_SIZE: usize = 64;
+
+const KTX_IDENTIFIER: [u8; 12] = [
+    0xAB, b'K', b'T', b'X', b' ', b'1', b'1', 0xBB, b'\r', b'\n', 0x1A, b'\n',
+];
+
+/// Read the header of the dds or ktx file at `path`, without reading or decompressing
+/// the texture data.
+///
+/// Malformed dds headers are read where possible, and each problem which was worked
+/// around is listed in the [`diagnostics`] of the returned `TextureInfo`. The length of
+/// a dds file is compared with the size of its mip chain, and a
+/// [`DdsDiagnostic::TruncatedMipChain`] is reported if the file ends early.
+///
+/// # Errors
+///
+/// If the file cannot be read, then this method will fail with [`Error::FileOpen`].
+/// If the file is not a dds or ktx file, or its header is too short to read, then this
+/// method will fail with [`Error::InvalidInput`].
+///
+/// [`diagnostics`]: struct.TextureInfo.html#structfield.diagnostics
+/// [`DdsDiagnostic::TruncatedMipChain`]: enum.DdsDiagnostic.html#variant.TruncatedMipChain
+/// [`Error::FileOpen`]: enum.Error.html#variant.FileOpen
+/// [`Error::InvalidInput`]: enum.Error.html#variant.InvalidInput
+#[cfg(feature = "std")]
+pub fn inspect<P: AsRef<Path>>(path: P) -> Result<TextureInfo, Error> {
+    let path = path.as_ref();
+    let mut header = [0; DDS_HEADER_SIZE];
+    let (len, file_len) = File::open(path)
+        .and_then(|file| {
+            let file_len = file.metadata()?.len();
+            let len = read_up_to(file, &mut header)?;
+            Ok((len, usize::try_from(file_len).unwrap_or(usize::MAX)))
+        })
+        .map_err(|e| {
+            log::error!(
+                "Could not read texture {p}\nCaused by: {e}",
+                p = path.display(),
+                e = e
+            );
+            Error::FileOpen
+        })?;
+
+    inspect_header(&header[..len], file_len)
+}
+
+/// Read the header of a dds or ktx file from the start of its contents. See
+/// [`inspect`] for more information.
+///
+/// If `bytes` contains more than the header of a dds file, then the texture data is
+/// also checked, and a [`DdsDiagnostic::TruncatedMipChain`] is reported if it ends
+/// early.
+///
+/// [`inspect`]: fn.inspect.html
+/// [`DdsDiagnostic::TruncatedMipChain`]: enum.DdsDiagnostic.html#variant.TruncatedMipChain
+pub fn inspect_bytes(bytes: &[u8]) -> Result<TextureInfo, Error> {
+    inspect_header(bytes, bytes.len())
+}
+
+/// Read the header at the start of `bytes`, which are the first bytes of a file with
+/// a total length of `file_len`.
+fn inspect_header(bytes: &[u8], file_len: usize) -> Result<TextureInfo, Error> {
+    if bytes.starts_with(b"DDS ") {
+        inspect_dds(bytes, file_len)
+    } else if bytes.starts_with(&KTX_IDENTIFIER) {
+        inspect_ktx(bytes)
+    } else {
+        Err(Error::InvalidInput)
+    }
+}
+
+/// Get the length of the header of the dds or ktx file `bytes`, described by `info`.
+/// The texture data starts immediately after the header.
+#[cfg(any(feature = "nvtt", feature = "ktx2", feature = "wgpu"))]
+pub(crate) fn header_len(bytes: &[u8], info: &TextureInfo) -> Option<usize> {
+    match info.container {
+        Container::Dds => Some(DDS_HEADER_SIZE - 20),
+        Container::Dds10 => Some(DDS_HEADER_SIZE),
+        Container::Ktx => {
+            let big_endian = bytes.get(12..16)? == [0x04, 0x03, 0x02, 0x01];
+            let key_value_len = read_u32(bytes, 60, big_endian)? as usize;
+            Some(KTX_HEADER_SIZE + key_value_len)
+        }
+        _ => None,
+    }
+}
+
+/// Read as many bytes as possible into `buf`, returning the number of bytes read.
+#[cfg(feature = "std")]
+fn read_up_to<R: Read>(mut reader: R, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut len = 0;
+    while len < buf.len() {
+        match reader.read(&mut buf[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(len)
+}
+
+#[inline]
+fn read_u32(bytes: &[u8], offset: usize, big_endian: bool) -> Option<u32> {
+    let word = bytes.get(offset..offset + 4)?.try_into().ok()?;
+    Some(if big_endian {
+        u32::from_be_bytes(word)
+    } else {
+        u32::from_le_bytes(word)
+    })
+}
+
+fn inspect_dds(bytes: &[u8], file_len: usize) -> Result<TextureInfo, Error> {
+    const DDSD_PITCH: u32 = 0x8;
+    const DDSD_MIPMAPCOUNT: u32 = 0x0002_0000;
+    const DDSD_LINEARSIZE: u32 = 0x0008_0000;
+    const DDSD_DEPTH: u32 = 0x0080_0000;
+    const DDPF_ALPHAPIXELS: u32 = 0x1;
+    const DDPF_FOURCC: u32 = 0x4;
+    const DDPF_RGB: u32 = 0x40;
+    const DDSCAPS_MIPMAP: u32 = 0x0040_0000;
+    const DDSCAPS2_CUBEMAP: u32 = 0x200;
+    const DDSCAPS2_VOLUME: u32 = 0x0020_0000;
+    const D3D10_RESOURCE_DIMENSION_TEXTURE3D: u32 = 4;
+    const D3D10_RESOURCE_MISC_TEXTURECUBE: u32 = 0x4;
+
+    let read = |offset| read_u32(bytes, offset, false).ok_or(Error::InvalidInput);
+    let mut diagnostics = vec![];
+
+    let header_size = read(4)?;
+    if header_size != 124 {
+        diagnostics.push(DdsDiagnostic::InvalidHeaderSize(header_size));
+    }
+
+    let flags = read(8)?;
+    let height = read(12)? as usize;
+    let width = read(16)? as usize;
+    let pitch = read(20)? as usize;
+    let depth = if flags & DDSD_DEPTH != 0 {
+        read(24)?.max(1) as usize
+    } else {
+        1
+    };
+    let header_mip_count = read(28)? as usize;
+    let pf_flags = read(80)?;
+    let four_cc: [u8; 4] = bytes
+        .get(84..88)
+        .and_then(|four_cc| four_cc.try_into().ok())
+        .ok_or(Error::InvalidInput)?;
+    let rgb_bit_count = read(88)? as usize;
+    let caps = read(108)?;
+    let caps2 = read(112)?;
+
+    let mut mip_count = if flags & DDSD_MIPMAPCOUNT != 0 {
+        header_mip_count.max(1)
+    } else if header_mip_count > 1 && caps & DDSCAPS_MIPMAP != 0 {
+        diagnostics.push(DdsDiagnostic::MissingMipCountFlag);
+        header_mip_count
+    } else {
+        1
+    };
+
+    let volume = depth > 1 || caps2 & DDSCAPS2_VOLUME != 0;
+    let cube = caps2 & DDSCAPS2_CUBEMAP != 0;
+
+    let (container, format, dxgi_format, texture_type, array_length, bits_per_pixel) =
+        if pf_flags & DDPF_FOURCC != 0 && &four_cc == b"DX10" {
+            let dxgi_format = read(128)?;
+            let dimension = read(132)?;
+            let misc_flags = read(136)?;
+            let array_length = read(140)?.max(1) as usize;
+            let dxgi_format = DxgiFormat::try_from(dxgi_format).ok();
+            let format = dxgi_format.and_then(DxgiFormat::format);
+
+            let texture_type = if dimension == D3D10_RESOURCE_DIMENSION_TEXTURE3D {
+                TextureType::D3
+            } else if misc_flags & D3D10_RESOURCE_MISC_TEXTURECUBE != 0 {
+                TextureType::Cube
+            } else if array_length > 1 {
+                TextureType::Array
+            } else {
+                TextureType::D2
+            };
+
+            (
+                Container::Dds10,
+                format,
+                dxgi_format,
+                texture_type,
+                array_length,
+                32,
+            )
+        } else {
+            let format = if pf_flags & DDPF_FOURCC != 0 {
+                let format = four_cc_format(&four_cc);
+                if format.is_none() {
+                    diagnostics.push(DdsDiagnostic::UnknownFourCc(four_cc));
+                }
+                format
+            } else if pf_flags & DDPF_RGB != 0 {
+                if pf_flags & DDPF_ALPHAPIXELS != 0 {
+                    Some(Format::Rgba)
+                } else {
+                    Some(Format::Rgb)
+                }
+            } else {
+                // Some old exporters write the FourCC code without setting its flag.
+                let format = four_cc_format(&four_cc);
+                if format.is_some() {
+                    diagnostics.push(DdsDiagnostic::MissingPixelFormatFlags);
+                }
+                format
+            };
+
+            let texture_type = if volume {
+                TextureType::D3
+            } else if cube {
+                TextureType::Cube
+            } else {
+                TextureType::D2
+            };
+            let dxgi_format = format.and_then(|format| format.to_dxgi(false));
+
+            (
+                Container::Dds,
+                format,
+                dxgi_format,
+                texture_type,
+                1,
+                rgb_bit_count,
+            )
+        };
+
+    let max_mip_count =
+        (usize::BITS - width.max(height).max(depth).max(1).leading_zeros()) as usize;
+    if mip_count > max_mip_count {
+        diagnostics.push(DdsDiagnostic::ExcessMipCount {
+            found: mip_count,
+            max: max_mip_count,
+        });
+        mip_count = max_mip_count;
+    }
+
+    let size_of_level = |mipmap: usize| {
+        let format = format?;
+        let extent = |dim: usize| (dim >> mipmap).max(1);
+        level_size(
+            format,
+            bits_per_pixel,
+            extent(width),
+            extent(height),
+            extent(depth),
+        )
+    };
+
+    if flags & (DDSD_PITCH | DDSD_LINEARSIZE) != 0 && pitch != 0 {
+        // The pitch is the size of a row of pixels or blocks, and the linear size is the
+        // size of a single slice of the top mipmap level.
+        let rows = if flags & DDSD_PITCH != 0 { 1 } else { height };
+        let expected = format.and_then(|format| level_size(format, bits_per_pixel, width, rows, 1));
+        if let Some(expected) = expected.filter(|&expected| expected != pitch) {
+            diagnostics.push(DdsDiagnostic::WrongPitch {
+                expected,
+                found: pitch,
+            });
+        }
+    }
+
+    let header_len = match container {
+        Container::Dds10 => DDS_HEADER_SIZE,
+        _ => DDS_HEADER_SIZE - 20,
+    };
+    if file_len > header_len {
+        let faces = if texture_type == TextureType::Cube {
+            6
+        } else {
+            1
+        };
+        let layers = array_length.saturating_mul(faces);
+        let chain_size = |levels: usize| {
+            (0..levels).try_fold(0usize, |size, mipmap| {
+                size.checked_add(size_of_level(mipmap)?.checked_mul(layers)?)
+            })
+        };
+
+        let available = file_len - header_len;
+        let fits = |levels: usize| chain_size(levels).is_some_and(|size| size <= available);
+        if format.is_some() && !fits(mip_count) {
+            let complete = (0..mip_count)
+                .take_while(|&levels| fits(levels + 1))
+                .count();
+            diagnostics.push(DdsDiagnostic::TruncatedMipChain {
+                mip_count,
+                complete,
+            });
+            // The faces of a dds file are stored one after the other, so the mip chain
+            // can only be shortened when there is a single face.
+            if layers == 1 && complete > 0 {
+                mip_count = complete;
+            }
+        }
+    }
+
+    Ok(TextureInfo {
+        container,
+        format,
+        dxgi_format,
+        texture_type,
+        width,
+        height,
+        depth,
+        array_length,
+        cube_array: texture_type == TextureType::Cube && array_length > 1,
+        mip_count,
+        srgb: dxgi_format.map(DxgiFormat::is_srgb) == Some(true),
+        diagnostics,
+    })
+}
+
+/// Get the `Format` of a legacy dds FourCC code.
+fn four_cc_format(four_cc: &[u8; 4]) -> Option<Format> {
+    match four_cc {
+        b"DXT1" => Some(Format::Bc1),
+        b"DXT3" => Some(Format::Bc2),
+        b"DXT5" => Some(Format::Bc3),
+        b"RXGB" => Some(Format::Bc3n),
+        b"ATI1" | b"BC4U" => Some(Format::Bc4),
+        b"ATI2" | b"BC5U" => Some(Format::Bc5),
+        b"CTX1" => Some(Format::Ctx1),
+        _ => None,
+    }
+}
+
+/// Get the size in bytes of a level of a dds texture, or `None` if it is not known
+/// for the `format`. `bits_per_pixel` is only used for uncompressed formats.
+fn level_size(
+    format: Format,
+    bits_per_pixel: usize,
+    width: usize,
+    height: usize,
+    depth: usize,
+) -> Option<usize> {
+    let blocks = |block_size: usize| {
+        width
+            .div_ceil(4)
+            .checked_mul(height.div_ceil(4))?
+            .checked_mul(depth)?
+            .checked_mul(block_size)
+    };
+    match format {
+        Format::Bc1 | Format::Bc1a | Format::Bc4 | Format::Ctx1 => blocks(8),
+        Format::Bc2 | Format::Bc3 | Format::Bc3n | Format::Bc5 | Format::Bc6 | Format::Bc7 => {
+            blocks(16)
+        }
+        Format::Rgb | Format::Rgba if bits_per_pixel > 0 => width
+            .checked_mul(bits_per_pixel)?
+            .div_ceil(8)
+            .checked_mul(height)?
+            .checked_mul(depth),
+        _ => None,
+    }
+}
+
+fn inspect_ktx(bytes: &[u8]) -> Result<TextureInfo, Error> {
+    if bytes.len() < KTX_HEADER_SIZE {
+        return Err(Error::InvalidInput);
+    }
+
+    // The endianness field is written as 0x04030201 in the endianness of the file.
+    let big_endian = match bytes[12..16] {
+        [0x01, 0x02, 0x03, 0x04] => false,
+        [0x04, 0x03, 0x02, 0x01] => true,
+        _ => return Err(Error::InvalidInput),
+    };
+    let read = |offset| read_u32(bytes, offset, big_endian).ok_or(Error::InvalidInput);
+
+    let gl_internal_format = read(28)?;
+    let width = read(36)? as usize;
+    let height = read(40)?.max(1) as usize;
+    let depth = read(44)?.max(1) as usize;
+    let array_elements = read(48)? as usize;
+    let faces = read(52)?;
+    let mip_count = read(56)?.max(1) as usize;
+    let (format, srgb) = gl_format_info(gl_internal_format);
+
+    let texture_type = if depth > 1 {
+        TextureType::D3
+    } else if faces == 6 {
+        TextureType::Cube
+    } else if array_elements > 0 {
+        TextureType::Array
+    } else {
+        TextureType::D2
+    };
+
+    Ok(TextureInfo {
+        container: Container::Ktx,
+        format,
+        dxgi_format: format.and_then(|format| format.to_dxgi(srgb)),
+        texture_type,
+        width,
+        height,
+        depth,
+        array_length: array_elements.max(1),
+        cube_array: texture_type == TextureType::Cube && array_elements > 0,
+        mip_count,
+        srgb,
+        diagnostics: vec![],
+    })
+}
+
+/// Get the `Format` of an OpenGL internal format, and whether it is an sRGB format.
+fn gl_format_info(gl_internal_format: u32) -> (Option<Format>, bool) {
+    match gl_internal_format {
+        0x8051 => (Some(Format::Rgb), false),
+        0x8C41 => (Some(Format::Rgb), true),
+        0x8058 => (Some(Format::Rgba), false),
+        0x8C43 => (Some(Format::Rgba), true),
+        0x83F0 => (Some(Format::Bc1), false),
+        0x8C4C => (Some(Format::Bc1), true),
+        0x83F1 => (Some(Format::Bc1a), false),
+        0x8C4D => (Some(Format::Bc1a), true),
+        0x83F2 => (Some(Format::Bc2), false),
+        0x8C4E => (Some(Format::Bc2), true),
+        0x83F3 => (Some(Format::Bc3), false),
+        0x8C4F => (Some(Format::Bc3), true),
+        0x8DBB => (Some(Format::Bc4), false),
+        0x8DBD => (Some(Format::Bc5), false),
+        0x8E8F => (Some(Format::Bc6), false),
+        0x8E8C => (Some(Format::Bc7), false),
+        0x8E8D => (Some(Format::Bc7), true),
+        0x8D64 => (Some(Format::Etc1), false),
+        0x9270 => (Some(Format::Etc2R), false),
+        0x9272 => (Some(Format::Etc2Rg), false),
+        0x9274 => (Some(Format::Etc2Rgb), false),
+        0x9275 => (Some(Format::Etc2Rgb), true),
+        0x9276 => (Some(Format::Etc2RgbA1), false),
+        0x9277 => (Some(Format::Etc2RgbA1), true),
+        0x9278 => (Some(Format::Etc2Rgba), false),
+        0x9279 => (Some(Format::Etc2Rgba), true),
+        0x8C00 => (Some(Format::Pvr4BppRgb), false),
+        0x8C01 => (Some(Format::Pvr2BppRgb), false),
+        0x8C02 => (Some(Format::Pvr4BppRgba), false),
+        0x8C03 => (Some(Format::Pvr2BppRgba), false),
+        _ => (None, false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "std")]
+    use std::{env, fs, process};
+
+    const DDSD_LINEARSIZE: u32 = 0x0008_0000;
+    const DDSD_MIPMAPCOUNT: u32 = 0x0002_0000;
+    const DDSD_DEPTH: u32 = 0x0080_0000;
+    const DDPF_FOURCC: u32 = 0x4;
+    const DDSCAPS2_VOLUME: u32 = 0x0020_0000;
+
+    /// The fields of a dds header which are used by the fixtures.
+    #[derive(Clone, Copy)]
+    struct DdsFixture {
+        flags: u32,
+        width: u32,
+        height: u32,
+        depth: u32,
+        pitch: u32,
+        mip_count: u32,
+        four_cc: [u8; 4],
+        caps2: u32,
+        /// The number of bytes of texture data after the header.
+        data_len: usize,
+    }
+
+    impl DdsFixture {
+        /// A 16x16 DXT1 texture with a full mip chain of 5 levels, which takes 184
+        /// bytes.
+        fn dxt1() -> Self {
+            Self {
+                flags: DDSD_MIPMAPCOUNT,
+                width: 16,
+                height: 16,
+                depth: 0,
+                pitch: 0,
+                mip_count: 5,
+                four_cc: *b"DXT1",
+                caps2: 0,
+                data_len: 184,
+            }
+        }
+
+        fn bytes(self) -> Vec<u8> {
+            let mut bytes = vec![0; DDS_HEADER_SIZE - 20 + self.data_len];
+            let mut write = |offset: usize, value: u32| {
+                bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+            };
+            write(4, 124);
+            write(8, self.flags);
+            write(12, self.height);
+            write(16, self.width);
+            write(20, self.pitch);
+            write(24, self.depth);
+            write(28, self.mip_count);
+            write(76, 32);
+            write(80, DDPF_FOURCC);
+            write(112, self.caps2);
+            bytes[..4].copy_from_slice(b"DDS ");
+            bytes[84..88].copy_from_slice(&self.four_cc);
+            bytes
+        }
+    }
+
+    #[test]
+    fn reads_a_well_formed_dds() {
+        let info = inspect_bytes(&DdsFixture::dxt1().bytes()).unwrap();
+        assert_eq!(info.container, Container::Dds);
+        assert_eq!(info.format, Some(Format::Bc1));
+        assert_eq!(info.texture_type, TextureType::D2);
+        assert_eq!((info.width, info.height, info.mip_count), (16, 16, 5));
+        assert_eq!(info.diagnostics, []);
+    }
+
+    #[test]
+    fn reports_a_truncated_mip_chain() {
+        // The first two levels take 128 and 32 bytes.
+        let fixture = DdsFixture {
+            data_len: 128 + 32,
+            ..DdsFixture::dxt1()
+        };
+        let expected = [DdsDiagnostic::TruncatedMipChain {
+            mip_count: 5,
+            complete: 2,
+        }];
+
+        let info = inspect_bytes(&fixture.bytes()).unwrap();
+        assert_eq!(info.diagnostics, expected);
+        assert_eq!(info.mip_count, 2);
+
+        // `inspect` only reads the header, so the length of the file is used instead.
+        #[cfg(feature = "std")]
+        {
+            let path = env::temp_dir().join(format!("nvtt_rs-truncated-{}.dds", process::id()));
+            fs::write(&path, fixture.bytes()).unwrap();
+            let info = inspect(&path);
+            let _ = fs::remove_file(&path);
+            assert_eq!(info.unwrap().diagnostics, expected);
+        }
+
+        // A header without any data is not checked.
+        let header = DdsFixture {
+            data_len: 0,
+            ..DdsFixture::dxt1()
+        };
+        assert_eq!(inspect_bytes(&header.bytes()).unwrap().diagnostics, []);
+    }
+
+    #[test]
+    fn reports_an_unknown_four_cc() {
+        let fixture = DdsFixture {
+            four_cc: *b"ABCD",
+            ..DdsFixture::dxt1()
+        };
+        let info = inspect_bytes(&fixture.bytes()).unwrap();
+        assert_eq!(info.format, None);
+        assert_eq!(info.diagnostics, [DdsDiagnostic::UnknownFourCc(*b"ABCD")]);
+    }
+
+    #[test]
+    fn reports_a_wrong_pitch() {
+        let fixture = DdsFixture {
+            flags: DDSD_MIPMAPCOUNT | DDSD_LINEARSIZE,
+            pitch: 100,
+            ..DdsFixture::dxt1()
+        };
+        let info = inspect_bytes(&fixture.bytes()).unwrap();
+        assert_eq!(
+            info.diagnostics,
+            [DdsDiagnostic::WrongPitch {
+                expected: 128,
+                found: 100,
+            }]
+        );
+
+        // The linear size of a volume texture is the size of a single slice.
+        let volume = DdsFixture {
+            flags: DDSD_LINEARSIZE | DDSD_DEPTH,
+            depth: 4,
+            pitch: 128,
+            mip_count: 1,
+            caps2: DDSCAPS2_VOLUME,
+            data_len: 4 * 128,
+            ..DdsFixture::dxt1()
+        };
+        let info = inspect_bytes(&volume.bytes()).unwrap();
+        assert_eq!(info.texture_type, TextureType::D3);
+        assert_eq!(info.diagnostics, []);
+    }
+
+    #[test]
+    fn reports_an_excess_mip_count() {
+        let fixture = DdsFixture {
+            width: 4,
+            height: 4,
+            mip_count: 5,
+            data_len: 8 * 3,
+            ..DdsFixture::dxt1()
+        };
+        let info = inspect_bytes(&fixture.bytes()).unwrap();
+        assert_eq!(
+            info.diagnostics,
+            [DdsDiagnostic::ExcessMipCount { found: 5, max: 3 }]
+        );
+        assert_eq!(info.mip_count, 3);
+    }
+
+    #[test]
+    fn reads_ktx_cube_arrays() {
+        let ktx = |array_elements: u32| {
+            let mut bytes = vec![0; KTX_HEADER_SIZE];
+            bytes[..12].copy_from_slice(&KTX_IDENTIFIER);
+            let mut write = |offset: usize, value: u32| {
+                bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+            };
+            write(12, 0x0403_0201);
+            write(28, 0x83F0);
+            write(36, 16);
+            write(40, 16);
+            write(48, array_elements);
+            write(52, 6);
+            write(56, 1);
+            bytes
+        };
+
+        let info = inspect_bytes(&ktx(0)).unwrap();
+        assert_eq!(info.texture_type, TextureType::Cube);
+        assert!(!info.cube_array);
+
+        let info = inspect_bytes(&ktx(2)).unwrap();
+        assert_eq!(info.texture_type, TextureType::Cube);
+        assert_eq!(info.array_length, 2);
+        assert!(info.cube_array);
+    }
+}
@@ -0,0 +1,787 @@
+// Copyright © 2019-2020 George Burton
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::{
+    inspect::{header_len, inspect_bytes},
+    Container, Error, Format, Surface, TextureType,
+};
+use ktx2::{Format as Ktx2Format, Level, Reader, SupercompressionScheme};
+use std::{borrow::Cow, convert::TryInto};
+
+/// Describes how the blocks of a format are stored in a ktx2 file.
+struct Ktx2Layout {
+    linear: Ktx2Format,
+    srgb: Option<Ktx2Format>,
+    /// The `khr_df_model` of the data format descriptor.
+    color_model: u8,
+    /// The width, height and size in bytes of a block.
+    block: (usize, usize, usize),
+    /// The channel id, bit offset and bit length of each sample in a block.
+    samples: &'static [(u8, u16, u8)],
+    is_float: bool,
+}
+
+impl Ktx2Layout {
+    const fn new(
+        linear: Ktx2Format,
+        srgb: Option<Ktx2Format>,
+        color_model: u8,
+        block: (usize, usize, usize),
+        samples: &'static [(u8, u16, u8)],
+    ) -> Self {
+        Self {
+            linear,
+            srgb,
+            color_model,
+            block,
+            samples,
+            is_float: false,
+        }
+    }
+
+    fn for_format(format: Format) -> Option<Self> {
+        const KHR_DF_MODEL_RGBSDA: u8 = 1;
+        const KHR_DF_MODEL_BC1A: u8 = 128;
+        const KHR_DF_MODEL_BC2: u8 = 129;
+        const KHR_DF_MODEL_BC3: u8 = 130;
+        const KHR_DF_MODEL_BC4: u8 = 131;
+        const KHR_DF_MODEL_BC5: u8 = 132;
+        const KHR_DF_MODEL_BC6H: u8 = 133;
+        const KHR_DF_MODEL_BC7: u8 = 134;
+        const KHR_DF_MODEL_ETC2: u8 = 161;
+        // The channel ids of each color model. The alpha channel is always `15`.
+        const R: u8 = 0;
+        const G: u8 = 1;
+        const B: u8 = 2;
+        const ETC2_COLOR: u8 = 2;
+        const A: u8 = 15;
+
+        let layout = match format {
+            // Uncompressed textures are written by nvtt in BGRA order.
+            Format::Rgb | Format::Rgba => Self::new(
+                Ktx2Format::B8G8R8A8_UNORM,
+                Some(Ktx2Format::B8G8R8A8_SRGB),
+                KHR_DF_MODEL_RGBSDA,
+                (1, 1, 4),
+                &[(B, 0, 8), (G, 8, 8), (R, 16, 8), (A, 24, 8)],
+            ),
+            Format::Bc1 | Format::Dxt1 | Format::Dxt1n => Self::new(
+                Ktx2Format::BC1_RGB_UNORM_BLOCK,
+                Some(Ktx2Format::BC1_RGB_SRGB_BLOCK),
+                KHR_DF_MODEL_BC1A,
+                (4, 4, 8),
+                &[(0, 0, 64)],
+            ),
+            Format::Bc1a | Format::Dxt1a => Self::new(
+                Ktx2Format::BC1_RGBA_UNORM_BLOCK,
+                Some(Ktx2Format::BC1_RGBA_SRGB_BLOCK),
+                KHR_DF_MODEL_BC1A,
+                (4, 4, 8),
+                &[(1, 0, 64)],
+            ),
+            Format::Bc2 | Format::Dxt3 => Self::new(
+                Ktx2Format::BC2_UNORM_BLOCK,
+                Some(Ktx2Format::BC2_SRGB_BLOCK),
+                KHR_DF_MODEL_BC2,
+                (4, 4, 16),
+                &[(A, 0, 64), (0, 64, 64)],
+            ),
+            Format::Bc3 | Format::Bc3n | Format::Dxt5 | Format::Dxt5n => Self::new(
+                Ktx2Format::BC3_UNORM_BLOCK,
+                Some(Ktx2Format::BC3_SRGB_BLOCK),
+                KHR_DF_MODEL_BC3,
+                (4, 4, 16),
+                &[(A, 0, 64), (0, 64, 64)],
+            ),
+            Format::Bc4 => Self::new(
+                Ktx2Format::BC4_UNORM_BLOCK,
+                None,
+                KHR_DF_MODEL_BC4,
+                (4, 4, 8),
+                &[(0, 0, 64)],
+            ),
+            Format::Bc5 => Self::new(
+                Ktx2Format::BC5_UNORM_BLOCK,
+                None,
+                KHR_DF_MODEL_BC5,
+                (4, 4, 16),
+                &[(R, 0, 64), (G, 64, 64)],
+            ),
+            Format::Bc6 => Self {
+                is_float: true,
+                ..Self::new(
+                    Ktx2Format::BC6H_UFLOAT_BLOCK,
+                    None,
+                    KHR_DF_MODEL_BC6H,
+                    (4, 4, 16),
+                    &[(0, 0, 128)],
+                )
+            },
+            Format::Bc7 => Self::new(
+                Ktx2Format::BC7_UNORM_BLOCK,
+                Some(Ktx2Format::BC7_SRGB_BLOCK),
+                KHR_DF_MODEL_BC7,
+                (4, 4, 16),
+                &[(0, 0, 128)],
+            ),
+            // Etc1 data can be decoded as Etc2.
+            Format::Etc1 | Format::Etc2Rgb => Self::new(
+                Ktx2Format::ETC2_R8G8B8_UNORM_BLOCK,
+                Some(Ktx2Format::ETC2_R8G8B8_SRGB_BLOCK),
+                KHR_DF_MODEL_ETC2,
+                (4, 4, 8),
+                &[(ETC2_COLOR, 0, 64)],
+            ),
+            Format::Etc2RgbA1 => Self::new(
+                Ktx2Format::ETC2_R8G8B8A1_UNORM_BLOCK,
+                Some(Ktx2Format::ETC2_R8G8B8A1_SRGB_BLOCK),
+                KHR_DF_MODEL_ETC2,
+                (4, 4, 8),
+                &[(ETC2_COLOR, 0, 64), (A, 0, 64)],
+            ),
+            Format::Etc2Rgba => Self::new(
+                Ktx2Format::ETC2_R8G8B8A8_UNORM_BLOCK,
+                Some(Ktx2Format::ETC2_R8G8B8A8_SRGB_BLOCK),
+                KHR_DF_MODEL_ETC2,
+                (4, 4, 16),
+                &[(A, 0, 64), (ETC2_COLOR, 64, 64)],
+            ),
+            Format::Etc2R => Self::new(
+                Ktx2Format::EAC_R11_UNORM_BLOCK,
+                None,
+                KHR_DF_MODEL_ETC2,
+                (4, 4, 8),
+                &[(R, 0, 64)],
+            ),
+            Format::Etc2Rg => Self::new(
+                Ktx2Format::EAC_R11G11_UNORM_BLOCK,
+                None,
+                KHR_DF_MODEL_ETC2,
+                (4, 4, 16),
+                &[(R, 0, 64), (G, 64, 64)],
+            ),
+            _ => return None,
+        };
+        Some(layout)
+    }
+
+    /// Get the size in bytes of a single 2d image of the given dimensions.
+    fn image_len(&self, width: usize, height: usize) -> usize {
+        let (block_width, block_height, block_len) = self.block;
+        width.div_ceil(block_width) * height.div_ceil(block_height) * block_len
+    }
+}
+
+impl Format {
+    /// Get the `ktx2::Format` which describes textures of this format in a ktx2
+    /// file, or `None` if the format cannot be stored in a ktx2 file.
+    ///
+    /// If `srgb` is `true`, then the sRGB variant of the format is returned, if one
+    /// exists. Uncompressed textures are written by nvtt in BGRA order, so
+    /// `Format::Rgb` and `Format::Rgba` map to `ktx2::Format::B8G8R8A8_UNORM`.
+    ///
+    /// # Notes
+    ///
+    /// This method requires the [`ktx2`] feature.
+    ///
+    /// [`ktx2`]: index.html#ktx2
+    pub fn to_ktx2(self, srgb: bool) -> Option<Ktx2Format> {
+        let layout = Ktx2Layout::for_format(self)?;
+        Some(match layout.srgb {
+            Some(srgb_format) if srgb => srgb_format,
+            _ => layout.linear,
+        })
+    }
+
+    /// Get the `Format` which nvtt uses to produce textures of the given
+    /// `ktx2::Format`, and whether that format is in the sRGB colorspace. Returns
+    /// `None` if nvtt cannot produce the format.
+    ///
+    /// # Notes
+    ///
+    /// This method requires the [`ktx2`] feature.
+    ///
+    /// [`ktx2`]: index.html#ktx2
+    pub fn from_ktx2(format: Ktx2Format) -> Option<(Self, bool)> {
+        [
+            Format::Rgba,
+            Format::Bc1,
+            Format::Bc1a,
+            Format::Bc2,
+            Format::Bc3,
+            Format::Bc4,
+            Format::Bc5,
+            Format::Bc6,
+            Format::Bc7,
+            Format::Etc2Rgb,
+            Format::Etc2RgbA1,
+            Format::Etc2Rgba,
+            Format::Etc2R,
+            Format::Etc2Rg,
+        ]
+        .iter()
+        .find_map(|&candidate| {
+            let layout = Ktx2Layout::for_format(candidate)?;
+            if layout.linear == format {
+                Some((candidate, false))
+            } else if layout.srgb == Some(format) {
+                Some((candidate, true))
+            } else {
+                None
+            }
+        })
+    }
+}
+
+/// Convert the dds or ktx texture `data`, such as the data of a
+/// `CompressionOutput::Memory` which was written with a header, into a ktx2 file.
+///
+/// The returned file can be opened with `ktx2::Reader`.
+///
+/// # Example
+///
+/// ```no_run
+/// # fn convert(output: nvtt_rs::CompressionOutput) -> Result<(), Box<dyn std::error::Error>> {
+/// use nvtt_rs::CompressionOutput;
+///
+/// if let CompressionOutput::Memory { data, .. } = output {
+///     let ktx2 = nvtt_rs::convert_to_ktx2(&data)?;
+///     let reader = ktx2::Reader::new(&ktx2)?;
+///     println!("{:?}", reader.header());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// If the header of the texture cannot be read, or `data` is too short to contain
+/// every image, then this function will fail with [`Error::InvalidInput`]. If the
+/// format of the texture cannot be stored in a ktx2 file, then this function will
+/// fail with [`Error::UnsupportedOutputFormat`].
+///
+/// # Notes
+///
+/// This function requires the [`ktx2`] feature.
+///
+/// [`Error::InvalidInput`]: enum.Error.html#variant.InvalidInput
+/// [`Error::UnsupportedOutputFormat`]: enum.Error.html#variant.UnsupportedOutputFormat
+/// [`ktx2`]: index.html#ktx2
+pub fn convert_to_ktx2(data: &[u8]) -> Result<Vec<u8>, Error> {
+    convert(data, Supercompression::None)
+}
+
+/// Convert the dds or ktx texture `data` into a ktx2 file, like [`convert_to_ktx2`],
+/// and supercompress each mipmap level with zstd at the given compression `level`.
+/// The level is clamped to the range supported by zstd, where `0` selects the
+/// default level, and higher levels compress more slowly to a smaller size.
+///
+/// # Example
+///
+/// ```no_run
+/// # fn convert(data: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+/// let ktx2 = nvtt_rs::convert_to_ktx2_zstd(data, 19)?;
+/// let reader = ktx2::Reader::new(&ktx2)?;
+/// assert!(reader.header().supercompression_scheme.is_some());
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// The errors of [`convert_to_ktx2`] are returned. If zstd fails to compress a level,
+/// then this function will fail with [`Error::Unknown`].
+///
+/// # Notes
+///
+/// This function requires the [`ktx2-zstd`] feature.
+///
+/// [`convert_to_ktx2`]: fn.convert_to_ktx2.html
+/// [`Error::Unknown`]: enum.Error.html#variant.Unknown
+/// [`ktx2-zstd`]: index.html#ktx2-zstd
+#[cfg(feature = "ktx2-zstd")]
+pub fn convert_to_ktx2_zstd(data: &[u8], level: i32) -> Result<Vec<u8>, Error> {
+    convert(data, Supercompression::Zstd(level))
+}
+
+/// How the mipmap levels of a ktx2 file are supercompressed.
+#[derive(Clone, Copy, Debug)]
+enum Supercompression {
+    None,
+    /// Compress each level with zstd, at the given compression level.
+    #[cfg(feature = "ktx2-zstd")]
+    Zstd(i32),
+}
+
+impl Supercompression {
+    /// The `supercompressionScheme` field of the ktx2 header.
+    fn scheme(self) -> u32 {
+        match self {
+            Self::None => 0,
+            #[cfg(feature = "ktx2-zstd")]
+            Self::Zstd(_) => 2,
+        }
+    }
+
+    /// Supercompress the data of a single mipmap `level`.
+    fn compress(self, level: Vec<u8>) -> Result<Vec<u8>, Error> {
+        match self {
+            Self::None => Ok(level),
+            #[cfg(feature = "ktx2-zstd")]
+            Self::Zstd(compression_level) => {
+                let range = zstd::compression_level_range();
+                let compression_level = compression_level.clamp(*range.start(), *range.end());
+                zstd::bulk::compress(&level, compression_level).map_err(|e| {
+                    log::error!("Could not supercompress ktx2 level\nCaused by: {}", e);
+                    Error::Unknown
+                })
+            }
+        }
+    }
+}
+
+fn convert(data: &[u8], supercompression: Supercompression) -> Result<Vec<u8>, Error> {
+    let info = inspect_bytes(data)?;
+    let format = info.format.ok_or(Error::UnsupportedOutputFormat)?;
+    let layout = Ktx2Layout::for_format(format).ok_or(Error::UnsupportedOutputFormat)?;
+    let vk_format = format
+        .to_ktx2(info.srgb)
+        .ok_or(Error::UnsupportedOutputFormat)?;
+
+    let faces = if info.texture_type == TextureType::Cube {
+        6
+    } else {
+        1
+    };
+    let layers = info.array_length.max(1);
+    let mip_count = info.mip_count.max(1);
+    let level_len = |level: usize| {
+        let depth = match info.texture_type {
+            TextureType::D3 => (info.depth >> level).max(1),
+            _ => 1,
+        };
+        let width = (info.width >> level).max(1);
+        let height = (info.height >> level).max(1);
+        layout.image_len(width, height) * depth
+    };
+
+    // Ktx2 files store every layer and face of a mipmap level together.
+    let mut levels = vec![Vec::new(); mip_count];
+    let mut offset = header_len(data, &info).ok_or(Error::InvalidInput)?;
+    let mut read = |offset: &mut usize, level: usize| -> Result<(), Error> {
+        let len = level_len(level);
+        let image = data
+            .get(*offset..*offset + len)
+            .ok_or(Error::InvalidInput)?;
+        levels[level].extend_from_slice(image);
+        *offset += len;
+        Ok(())
+    };
+    if info.container == Container::Ktx {
+        // Ktx files store each mipmap level after its size, and pad each image to
+        // 4 bytes.
+        for level in 0..mip_count {
+            offset += 4;
+            for _ in 0..layers * faces {
+                read(&mut offset, level)?;
+                offset = offset.div_ceil(4) * 4;
+            }
+        }
+    } else {
+        // Dds files store the full mipmap chain of each layer and face together.
+        for _ in 0..layers * faces {
+            for level in 0..mip_count {
+                read(&mut offset, level)?;
+            }
+        }
+    }
+
+    let header = Ktx2Header {
+        vk_format: vk_format.value(),
+        width: info.width as u32,
+        height: info.height as u32,
+        // The depth and layer count are 0 for textures which are not 3d or arrays.
+        depth: match info.texture_type {
+            TextureType::D3 => info.depth as u32,
+            _ => 0,
+        },
+        layers: match info.texture_type {
+            TextureType::Array => layers as u32,
+            _ => 0,
+        },
+        faces: faces as u32,
+    };
+    let levels = levels
+        .into_iter()
+        .map(|level| {
+            let len = level.len();
+            supercompression.compress(level).map(|data| (data, len))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(write_ktx2(
+        &header,
+        &layout,
+        info.srgb,
+        supercompression,
+        &levels,
+    ))
+}
+
+/// The fields of a ktx2 header which describe the texture.
+struct Ktx2Header {
+    vk_format: u32,
+    width: u32,
+    height: u32,
+    depth: u32,
+    layers: u32,
+    faces: u32,
+}
+
+/// Write a ktx2 file containing the mipmap `levels`. Each level is given as its data,
+/// which has been compressed with `supercompression`, and its uncompressed length.
+fn write_ktx2(
+    header: &Ktx2Header,
+    layout: &Ktx2Layout,
+    srgb: bool,
+    supercompression: Supercompression,
+    levels: &[(Vec<u8>, usize)],
+) -> Vec<u8> {
+    const KTX2_IDENTIFIER: [u8; 12] = [
+        0xAB, b'K', b'T', b'X', b' ', b'2', b'0', 0xBB, b'\r', b'\n', 0x1A, b'\n',
+    ];
+    const HEADER_SIZE: usize = 80;
+    const LEVEL_INDEX_SIZE: usize = 24;
+    const DFD_HEADER_SIZE: usize = 28;
+    const DFD_SAMPLE_SIZE: usize = 16;
+    const KHR_DF_PRIMARIES_BT709: u8 = 1;
+    const KHR_DF_TRANSFER_LINEAR: u8 = 1;
+    const KHR_DF_TRANSFER_SRGB: u8 = 2;
+    const KHR_DF_SAMPLE_DATATYPE_FLOAT: u8 = 0x80;
+
+    let dfd_offset = HEADER_SIZE + LEVEL_INDEX_SIZE * levels.len();
+    let dfd_size = DFD_HEADER_SIZE + DFD_SAMPLE_SIZE * layout.samples.len();
+    let (block_width, block_height, block_len) = layout.block;
+
+    let mut out = Vec::new();
+    let u32_le = |out: &mut Vec<u8>, value: u32| out.extend_from_slice(&value.to_le_bytes());
+    let u64_le = |out: &mut Vec<u8>, value: u64| out.extend_from_slice(&value.to_le_bytes());
+
+    out.extend_from_slice(&KTX2_IDENTIFIER);
+    u32_le(&mut out, header.vk_format);
+    // Every supported format has a type size of 1.
+    u32_le(&mut out, 1);
+    u32_le(&mut out, header.width);
+    u32_le(&mut out, header.height);
+    u32_le(&mut out, header.depth);
+    u32_le(&mut out, header.layers);
+    u32_le(&mut out, header.faces);
+    u32_le(&mut out, levels.len() as u32);
+    u32_le(&mut out, supercompression.scheme());
+
+    u32_le(&mut out, dfd_offset as u32);
+    u32_le(&mut out, dfd_size as u32);
+    // There is no key/value data, and zstd does not use supercompression global data.
+    u32_le(&mut out, 0);
+    u32_le(&mut out, 0);
+    u64_le(&mut out, 0);
+    u64_le(&mut out, 0);
+
+    // The levels are stored from the smallest to the largest, each aligned to the
+    // least common multiple of the block size and 4. Supercompressed levels are not
+    // aligned.
+    let is_supercompressed = supercompression.scheme() != 0;
+    let alignment = if is_supercompressed {
+        1
+    } else {
+        block_len.max(4)
+    };
+    let mut offsets = vec![0; levels.len()];
+    let mut offset = dfd_offset + dfd_size;
+    for (index, (level, _)) in levels.iter().enumerate().rev() {
+        offset = offset.div_ceil(alignment) * alignment;
+        offsets[index] = offset;
+        offset += level.len();
+    }
+    for ((level, uncompressed_len), &offset) in levels.iter().zip(&offsets) {
+        u64_le(&mut out, offset as u64);
+        u64_le(&mut out, level.len() as u64);
+        u64_le(&mut out, *uncompressed_len as u64);
+    }
+
+    // The data format descriptor contains a single basic block.
+    u32_le(&mut out, dfd_size as u32);
+    u32_le(&mut out, 0);
+    out.extend_from_slice(&2u16.to_le_bytes());
+    out.extend_from_slice(&(dfd_size as u16 - 4).to_le_bytes());
+    out.push(layout.color_model);
+    out.push(KHR_DF_PRIMARIES_BT709);
+    out.push(if srgb && layout.srgb.is_some() {
+        KHR_DF_TRANSFER_SRGB
+    } else {
+        KHR_DF_TRANSFER_LINEAR
+    });
+    out.push(0);
+    out.extend_from_slice(&[block_width as u8 - 1, block_height as u8 - 1, 0, 0]);
+    // The size of the planes is unknown when the levels are supercompressed.
+    let bytes_plane = if is_supercompressed { 0 } else { block_len };
+    out.extend_from_slice(&[bytes_plane as u8, 0, 0, 0, 0, 0, 0, 0]);
+    for &(channel, bit_offset, bit_length) in layout.samples {
+        out.extend_from_slice(&bit_offset.to_le_bytes());
+        out.push(bit_length - 1);
+        if layout.is_float {
+            out.push(channel | KHR_DF_SAMPLE_DATATYPE_FLOAT);
+            out.extend_from_slice(&[0, 0, 0, 0]);
+            u32_le(&mut out, 0.0f32.to_bits());
+            u32_le(&mut out, 1.0f32.to_bits());
+        } else {
+            out.push(channel);
+            out.extend_from_slice(&[0, 0, 0, 0]);
+            u32_le(&mut out, 0);
+            u32_le(&mut out, u32::MAX >> (32 - u32::from(bit_length).min(32)));
+        }
+    }
+
+    for ((level, _), &offset) in levels.iter().zip(&offsets).rev() {
+        out.resize(offset, 0);
+        out.extend_from_slice(level);
+    }
+    out
+}
+
+/// Read each mipmap level of an uncompressed 2d ktx2 texture into a `Surface`, so
+/// that the texture can be compressed with `Context::compress_mipmaps`.
+///
+/// The `R8G8B8A8`, `B8G8R8A8` and `R32G32B32A32_SFLOAT` formats are supported. The
+/// values of sRGB textures are not converted to linear space.
+///
+/// # Example
+///
+/// ```no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use nvtt_rs::{CompressionOptions, Context, OutputOptions};
+///
+/// let data = std::fs::read("albedo.ktx2")?;
+/// let mipmaps = nvtt_rs::surfaces_from_ktx2(&ktx2::Reader::new(&data)?)?;
+///
+/// let context = Context::new()?;
+/// let output = context.compress_mipmaps(
+///     &mipmaps,
+///     &CompressionOptions::new()?,
+///     &OutputOptions::new()?,
+/// )?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// If the texture is compressed, supercompressed, or is not a single 2d image, then
+/// this function will fail with [`Error::UnsupportedFeature`]. Levels which are
+/// supercompressed with zstd are supported with the [`ktx2-zstd`] feature. If a
+/// level is too short to contain its image, or cannot be decompressed, then this
+/// function will fail with [`Error::InvalidInput`].
+///
+/// # Notes
+///
+/// This function requires the [`ktx2`] feature.
+///
+/// [`Error::UnsupportedFeature`]: enum.Error.html#variant.UnsupportedFeature
+/// [`Error::InvalidInput`]: enum.Error.html#variant.InvalidInput
+/// [`ktx2`]: index.html#ktx2
+/// [`ktx2-zstd`]: index.html#ktx2-zstd
+pub fn surfaces_from_ktx2<D: AsRef<[u8]>>(reader: &Reader<D>) -> Result<Vec<Surface>, Error> {
+    let header = reader.header();
+    let is_zstd = cfg!(feature = "ktx2-zstd")
+        && header.supercompression_scheme == Some(SupercompressionScheme::Zstandard);
+    if (header.supercompression_scheme.is_some() && !is_zstd)
+        || header.pixel_depth > 1
+        || header.layer_count > 1
+        || header.face_count > 1
+    {
+        return Err(Error::UnsupportedFeature);
+    }
+
+    let format = header.format.ok_or(Error::UnsupportedFeature)?;
+    let (pixel_len, swap_red_blue, is_float) = match format {
+        Ktx2Format::R8G8B8A8_UNORM | Ktx2Format::R8G8B8A8_SRGB => (4, false, false),
+        Ktx2Format::B8G8R8A8_UNORM | Ktx2Format::B8G8R8A8_SRGB => (4, true, false),
+        Ktx2Format::R32G32B32A32_SFLOAT => (16, false, true),
+        _ => return Err(Error::UnsupportedFeature),
+    };
+
+    reader
+        .levels()
+        .enumerate()
+        .map(|(index, level)| {
+            let width = (header.pixel_width as usize >> index).max(1);
+            let height = (header.pixel_height as usize >> index).max(1);
+            let data = decompress_level(&level, is_zstd)?;
+            let image = data
+                .get(..width * height * pixel_len)
+                .ok_or(Error::InvalidInput)?;
+
+            let mut pixels = if is_float {
+                image
+                    .chunks_exact(4)
+                    .map(|x| f32::from_le_bytes(x.try_into().unwrap_or_default()))
+                    .collect::<Vec<_>>()
+            } else {
+                image
+                    .iter()
+                    .map(|&x| f32::from(x) / 255.0)
+                    .collect::<Vec<_>>()
+            };
+            if swap_red_blue {
+                pixels.chunks_exact_mut(4).for_each(|px| px.swap(0, 2));
+            }
+            Surface::from_rgba(width, height, &pixels)
+        })
+        .collect()
+}
+
+/// Get the data of a mipmap `level`, which is decompressed if the file is
+/// supercompressed with zstd.
+fn decompress_level<'a>(level: &Level<'a>, is_zstd: bool) -> Result<Cow<'a, [u8]>, Error> {
+    if !is_zstd {
+        return Ok(Cow::Borrowed(level.data));
+    }
+
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "ktx2-zstd")] {
+            zstd::bulk::decompress(level.data, level.uncompressed_byte_length as usize)
+                .map(Cow::Owned)
+                .map_err(|_| Error::InvalidInput)
+        } else {
+            Err(Error::UnsupportedFeature)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DDSD_MIPMAPCOUNT: u32 = 0x0002_0000;
+    const DDPF_ALPHAPIXELS: u32 = 0x1;
+    const DDPF_FOURCC: u32 = 0x4;
+    const DDPF_RGB: u32 = 0x40;
+    const DDSCAPS_TEXTURE: u32 = 0x1000;
+
+    /// Write a 2d dds file with the pixel format flags `pf_flags`, the `four_cc` code
+    /// and `rgb_bit_count`, followed by the data of each of the mipmap `levels`.
+    fn dds(
+        (width, height): (u32, u32),
+        (pf_flags, four_cc, rgb_bit_count): (u32, &[u8; 4], u32),
+        levels: &[Vec<u8>],
+    ) -> Vec<u8> {
+        let mut out = b"DDS ".to_vec();
+        let header = [
+            124,
+            DDSD_MIPMAPCOUNT,
+            height,
+            width,
+            0,
+            0,
+            levels.len() as u32,
+        ];
+        header
+            .iter()
+            .chain([0; 11].iter())
+            .chain([32, pf_flags].iter())
+            .for_each(|value| out.extend_from_slice(&value.to_le_bytes()));
+        out.extend_from_slice(four_cc);
+        [rgb_bit_count, 0, 0, 0, 0, DDSCAPS_TEXTURE, 0, 0, 0, 0]
+            .iter()
+            .for_each(|value: &u32| out.extend_from_slice(&value.to_le_bytes()));
+        levels.iter().for_each(|level| out.extend_from_slice(level));
+        out
+    }
+
+    /// The levels of an `8x8` `Bc1` texture, where each level is filled with its index.
+    fn bc1_levels() -> Vec<Vec<u8>> {
+        [32, 8, 8, 8]
+            .iter()
+            .enumerate()
+            .map(|(index, &len)| vec![index as u8 + 1; len])
+            .collect()
+    }
+
+    #[test]
+    fn bc1_dds_round_trips_through_ktx2() {
+        let levels = bc1_levels();
+        let ktx2 = convert_to_ktx2(&dds((8, 8), (DDPF_FOURCC, b"DXT1", 0), &levels)).unwrap();
+        let reader = Reader::new(&ktx2).unwrap();
+
+        let header = reader.header();
+        assert_eq!(header.format, Format::Bc1.to_ktx2(false));
+        assert_eq!(
+            Format::from_ktx2(header.format.unwrap()),
+            Some((Format::Bc1, false))
+        );
+        assert_eq!((header.pixel_width, header.pixel_height), (8, 8));
+        assert_eq!((header.pixel_depth, header.layer_count), (0, 0));
+        assert_eq!(header.face_count, 1);
+        assert_eq!(header.supercompression_scheme, None);
+
+        let data = reader.levels().map(|level| level.data).collect::<Vec<_>>();
+        assert_eq!(data, levels);
+    }
+
+    #[cfg(feature = "ktx2-zstd")]
+    #[test]
+    fn bc1_dds_round_trips_through_zstd_ktx2() {
+        let levels = bc1_levels();
+        let dds = dds((8, 8), (DDPF_FOURCC, b"DXT1", 0), &levels);
+        let ktx2 = convert_to_ktx2_zstd(&dds, 0).unwrap();
+        let reader = Reader::new(&ktx2).unwrap();
+
+        assert_eq!(
+            reader.header().supercompression_scheme,
+            Some(SupercompressionScheme::Zstandard)
+        );
+        let data = reader
+            .levels()
+            .map(|level| decompress_level(&level, true).unwrap().into_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(data, levels);
+    }
+
+    #[test]
+    fn rgba_dds_round_trips_into_surfaces() {
+        // The texels of a `2x2` image in bgra order, and a `1x1` mipmap.
+        let bgra = vec![
+            0, 64, 128, 255, 255, 0, 0, 128, 0, 255, 0, 0, 32, 32, 32, 32,
+        ];
+        let levels = [bgra.clone(), bgra[..4].to_vec()];
+        let pixel_format = (DDPF_RGB | DDPF_ALPHAPIXELS, &[0; 4], 32);
+        let ktx2 = convert_to_ktx2(&dds((2, 2), pixel_format, &levels)).unwrap();
+        let reader = Reader::new(&ktx2).unwrap();
+        assert_eq!(reader.header().format, Some(Ktx2Format::B8G8R8A8_UNORM));
+
+        let surfaces = surfaces_from_ktx2(&reader).unwrap();
+        assert_eq!(surfaces.len(), 2);
+        assert_eq!((surfaces[0].width(), surfaces[0].height()), (2, 2));
+        assert_eq!((surfaces[1].width(), surfaces[1].height()), (1, 1));
+        for (i, texel) in bgra.chunks_exact(4).enumerate() {
+            let expected = [texel[2], texel[1], texel[0], texel[3]].map(|x| f32::from(x) / 255.0);
+            assert_eq!(surfaces[0].pixel(i), expected);
+        }
+    }
+}
@@ -26,6 +26,9 @@
 //! # Example
 //!
 //! ```no_run
+//! # #[cfg(not(feature = "nvtt"))]
+//! # fn main() {}
+//! # #[cfg(feature = "nvtt")]
 //! # fn main() -> Result<(), Box<dyn std::error::Error>> {
 //! # let (w, h) = (0, 0);
 //! use nvtt_rs::{Compressor, CompressionOptions, Format, InputOptions, OutputOptions};
@@ -45,8 +48,81 @@
 //! # }
 //! ```
 //!
+//! The `new` constructors only fail if nvtt cannot allocate the object. Each of these
+//! types also implements `Default`, which panics in that case instead of returning an
+//! error.
+//!
+//! The [`TexturePipeline`] type bundles these settings into a single builder, which
+//! loads and compresses one texture:
+//!
+//! ```no_run
+//! # #[cfg(not(feature = "std"))]
+//! # fn main() {}
+//! # #[cfg(feature = "std")]
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use nvtt_rs::{Format, TexturePipeline};
+//!
+//! TexturePipeline::new()
+//!     .source("input.png")
+//!     .format(Format::Dxt1)
+//!     .to("output.dds")?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The [`inspect`] function can be used to read the format, dimensions and mipmap
+//! count of an existing dds or ktx file without decoding it:
+//!
+//! ```no_run
+//! # #[cfg(not(feature = "std"))]
+//! # fn main() {}
+//! # #[cfg(feature = "std")]
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let info = nvtt_rs::inspect("output.dds")?;
+//! println!("{:?} {}x{}, {} mips", info.format, info.width, info.height, info.mip_count);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Textures in the `Bc1` to `Bc5` formats can be recompressed to another format with
+//! [`transcode`], which keeps their layout, mipmaps and sRGB flag.
+//!
+//! When the format and container are known ahead of time, [`DdsOutput`],
+//! [`Dds10Output`] and [`KtxOutput`] check that the container can store the format
+//! at compile time, using the marker types in the [`formats`] module.
+//!
+//! # Threads
+//!
+//! [`InputOptions`], [`CompressionOptions`] and [`OutputOptions`] are `Send` and
+//! `Sync`, so a single set of options can be shared between the threads of a pool
+//! with an `Arc`, without cloning them. nvtt only reads the input and compression
+//! options while compressing. Compressing with a shared `OutputOptions` installs the
+//! output handlers on it, so compressions which share an `OutputOptions` run one at a
+//! time.
+//!
+//! A [`Compressor`] is `Send` but not `Sync`, because it collects the errors of each
+//! compression in a `RefCell`, so each thread needs its own `Compressor`.
+//!
 //! # Features
 //!
+//! ## `std`
+//!
+//! This feature is enabled by default, and by every other feature of this crate.
+//! Without it, the crate is `no_std`, and only needs the `alloc` crate. The
+//! [`Format`], [`Container`], [`TextureType`] and [`DxgiFormat`] enums are still
+//! available, along with [`inspect_bytes`], which reads the header of a dds or ktx file
+//! and checks that its mip chain is complete. This lets an engine runtime use the same
+//! header code on platforms which have no `std`.
+//!
+//! ## `nvtt`
+//!
+//! This feature is enabled by default, and builds and links nvtt through `nvtt_sys`.
+//! Without it, this crate does not need a C++ toolchain, but the [`Compressor`],
+//! [`Context`] and options types are not available. Enable the `fallback-encoders`
+//! feature to compress textures without nvtt, which a [`Batch`] or a
+//! [`TexturePipeline`] then uses for the formats it supports. Otherwise they fail with
+//! [`Error::UnsupportedFeature`].
+//!
 //! ## `nvtt_image_integration`
 //!
 //! This feature provides the convenience method [`InputOptions::set_image`], which
@@ -57,11 +133,167 @@
 //! provide automatic conversions from a [`DynamicImage`]. See the [`ValidImage`]
 //! type for more information.
 //!
+//! The `png`, `tga` and `bmp` codecs of the `image` crate are enabled, so that
+//! [`Surface::open`] and [`Surface::save`] can read and write those files. Other
+//! codecs can be enabled through the features of the `image` crate.
+//!
 //! # `serde-serialize`
 //!
 //! This feature provides [`serde`] impls for simple `enum` and `struct` types. It is not
 //! possible to serialize a [`Compressor`], [`CompressionOptions`], [`InputOptions`] or
 //! [`OutputOptions`].
+//!
+//! This feature also provides [`OutputOptions::set_write_metadata`], which writes a
+//! json file describing the texture next to the output file. A [`TextureRecord`]
+//! stores the metadata of a texture with its compressed data, which is embedded as
+//! base64 in json, or referenced by path, so that compression results can be
+//! persisted and reloaded.
+//!
+//! ## `memmap-output`
+//!
+//! This feature writes the compressed texture through a memory map when the
+//! [`OutputLocation::MappedFile`] output location is used. Without this feature, the
+//! file is written through a buffered writer instead.
+//!
+//! ## `pipeline`
+//!
+//! This feature provides the [`Batch`] type, which compresses many image files in
+//! parallel, selected by paths or glob patterns. Compressed textures can optionally
+//! be cached on disk, so that unchanged files are not compressed again.
+//!
+//! ## `watch`
+//!
+//! This feature provides the [`Watcher`] type, which watches the inputs of a
+//! [`Batch`] using the [`notify`] crate, and recompresses each file when it changes.
+//! This enables the `pipeline` feature.
+//!
+//! ## `basis`
+//!
+//! This feature provides the [`BasisOptions`] type, which compresses a [`Surface`]
+//! into a Basis Universal `.basis` file using the [`basis-universal`] crate. With the
+//! `pipeline` feature, [`Batch::basis`] compresses a batch into `.basis` files,
+//! using the same settings and cache as nvtt outputs. [`BasisOptions::compress_ktx2`]
+//! writes UASTC textures into a ktx2 file instead.
+//!
+//! ## `gltf`
+//!
+//! This feature provides the [`compress_gltf_textures`] function, which compresses the
+//! images used by a glTF file into ktx2 files, and references them through the
+//! `KHR_texture_basisu` extension. This enables the `basis` feature.
+//!
+//! ## `bevy`
+//!
+//! This feature provides the [`BevyImageSaver`] type, an `AssetSaver` for bevy's
+//! asset processor which compresses imported images into dds files. Each image is
+//! compressed with the [`TexturePreset`] for its [`TextureLabel`], which is set in the
+//! `.meta` file of the image. With the `basis` feature, presets can also write UASTC
+//! ktx2 files.
+//!
+//! ## `wgpu`
+//!
+//! This feature provides [`Format::to_wgpu`], which maps a format to the
+//! `wgpu::TextureFormat` used to sample it, and the [`wgpu_texture_descriptor`] and
+//! [`write_wgpu_texture`] functions, which create and upload a wgpu texture from the
+//! contents of a dds or ktx file.
+//!
+//! ## `ktx2`
+//!
+//! This feature provides [`Format::to_ktx2`] and [`Format::from_ktx2`], which map
+//! formats to and from the `ktx2::Format` used by the [`ktx2`] crate. The
+//! [`convert_to_ktx2`] function converts a dds or ktx texture into a ktx2 file which
+//! can be opened with `ktx2::Reader`, and [`surfaces_from_ktx2`] reads the levels of
+//! an uncompressed ktx2 texture back into [`Surface`]s so that they can be compressed.
+//!
+//! ## `ktx2-zstd`
+//!
+//! This feature enables the `ktx2` feature, and provides [`convert_to_ktx2_zstd`],
+//! which supercompresses each mipmap level of the ktx2 file with zstd. Supercompressed
+//! levels are also read by [`surfaces_from_ktx2`].
+//!
+//! ## `rayon`
+//!
+//! This feature resamples the channels and rows of a [`Surface`] in parallel using
+//! the [`rayon`] crate. This speeds up [`Surface::build_mipmaps`] and
+//! [`Context::compress_with_mipmaps`], which generate mipmaps in Rust rather than in
+//! nvtt.
+//!
+//! ## `fallback-encoders`
+//!
+//! This feature provides pure Rust encoders for the `Bc1` to `Bc7` formats. The
+//! [`fallback_encode`] function compresses a [`Surface`] into blocks, and
+//! [`fallback_compress`] compresses a chain of mipmaps into a dds file. When the
+//! `nvtt` feature is disabled, a [`Batch`] and a [`TexturePipeline`] use these
+//! encoders for any format supported by [`Format::has_fallback_encoder`]. The
+//! encoders are faster than nvtt, but produce lower quality textures.
+//!
+//! This is how the crate is built for `wasm32`, which nvtt does not support. Disable
+//! the default features, and compress [`Surface`]s into memory with
+//! [`TexturePipeline::to_memory`] or [`fallback_compress`]. Files cannot be read or
+//! written on `wasm32-unknown-unknown`, so the `pipeline` feature is not available,
+//! and loading or saving a `Surface` by path fails with an error.
+//!
+//! ## `preview`
+//!
+//! This feature provides the [`write_preview`] function, which writes a png comparing
+//! an image with its decoded compressed texture, alongside a heatmap of the error, so
+//! that artists can review compression artifacts. [`TexturePipeline::preview`] writes
+//! a preview next to each compressed texture. This enables the
+//! `nvtt_image_integration` feature.
+//!
+//! ## `capi`
+//!
+//! This feature provides the [`capi`] module, which exposes the [`Batch`] pipeline
+//! through a C ABI declared in `include/nvtt_rs.h`, so that tools which are not
+//! written in Rust can use it. This enables the `pipeline` feature. The shared library
+//! is built with `cargo rustc --release --features capi --crate-type cdylib`.
+//!
+//! ## `cli`
+//!
+//! This feature builds the `nvtt` command line tool, which can compress, decompress
+//! and print information about textures:
+//!
+//! ```sh
+//! nvtt compress input.png -o output.dds --format bc7 --quality production --mips
+//! nvtt decompress output.dds -o decompressed.png
+//! nvtt info output.dds
+//! ```
+//!
+//! The tool can be installed with `cargo install nvtt_rs --features cli`.
+//!
+//! ## `dylib`
+//!
+//! This feature links to nvtt as a shared library instead of a static library. The
+//! nvtt shared libraries must be available to the dynamic loader at runtime.
+//!
+//! ## `cuda`
+//!
+//! This feature builds nvtt with CUDA support, which is required for
+//! [`Compressor::enable_cuda_acceleration`] to have any effect. The CUDA toolkit must
+//! be installed, and can be located with the `CUDA_PATH` environment variable.
+//!
+//! ## `static-stdlib`
+//!
+//! This feature links the c++ stdlib statically on Linux and macOS, so that binaries
+//! do not depend on the version of `libstdc++` or `libc++` installed on the system.
+//! The static c++ stdlib must be installed alongside the c++ compiler.
+//!
+//! ## `vcpkg`
+//!
+//! This feature links to an nvtt installed with [vcpkg] on Windows, instead of building
+//! nvtt from source with Visual Studio.
+//!
+//! # Versions
+//!
+//! The version of nvtt which this crate was built against is available with
+//! [`version`], and the version which is linked at runtime with [`linked_version`].
+//! These only differ when nvtt is linked dynamically. Use [`version_at_least`] to
+//! check for a feature at runtime. Operations which require a newer nvtt than the
+//! one which is linked fail with [`Error::UnsupportedFeature`].
+//!
+//! Some APIs are only compiled when this crate is built against a new enough nvtt.
+//! [`Surface::load`] and [`Context::compress`] use `nvtt::Surface` and
+//! `nvtt::Context`, which were added in nvtt 2.1. When this crate is built against
+//! an older nvtt, they fail with [`Error::UnsupportedFeature`] instead.
 //! 
 //! # Dependencies
 //!
@@ -69,51 +301,266 @@
 //!
 //! This crate requires a valid cmake installation and a C++ compiler to build.
 //!
-//! ## Windows
+//! ## Cross-compilation
+//!
+//! nvtt is built for the cargo target, so this crate can be cross-compiled for
+//! targets such as `aarch64-unknown-linux-gnu`, Android and iOS. See the `nvtt_sys`
+//! readme for the environment variables which configure the target toolchain.
 //!
-//! This crate requires a valid installation of Visual Studio.
+//! ## Windows
 //!
-//! # Notes
+//! This crate requires a valid cmake installation, and an installation of Visual Studio
+//! between Visual Studio 2013 and Visual Studio 2022. nvtt is always built with the
+//! release C runtime, which is linked statically if the `crt-static` target feature
+//! is enabled.
 //!
-//! This crate does not currently work on Microsoft Windows due to incomplete work
-//! on the build system.
+//! Alternatively, a prebuilt nvtt can be installed with [vcpkg] and used by enabling
+//! the `vcpkg` feature.
 //!
 //! [wiki]: https://github.com/castano/nvidia-texture-tools/wiki/ApiDocumentation
+//! [vcpkg]: https://vcpkg.io
 //! [`InputOptions::set_image`]: struct.InputOptions.html#method.set_image
 //! [`InputOptions`]: struct.InputOptions.html
 //! [`image`]: https://docs.rs/image/latest/image
 //! [`DynamicImage`]: https://docs.rs/image/latest/image/enum.DynamicImage.html
 //! [`ValidImage`]: enum.ValidImage.html
 //! [`serde`]: https://serde.rs
+//! [`TextureRecord`]: struct.TextureRecord.html
 //! [`Compressor`]: struct.InputOptions.html
 //! [`CompressionOptions`]: struct.InputOptions.html
 //! [`OutputOptions`]: struct.InputOptions.html
+//! [`OutputOptions::set_write_metadata`]: struct.OutputOptions.html#method.set_write_metadata
+//! [`OutputLocation::MappedFile`]: enum.OutputLocation.html#variant.MappedFile
+//! [`Compressor::enable_cuda_acceleration`]: struct.Compressor.html#method.enable_cuda_acceleration
+//! [`version`]: fn.version.html
+//! [`Batch`]: struct.Batch.html
+//! [`Watcher`]: struct.Watcher.html
+//! [`notify`]: https://docs.rs/notify
+//! [`BasisOptions`]: struct.BasisOptions.html
+//! [`Surface`]: struct.Surface.html
+//! [`Surface::build_mipmaps`]: struct.Surface.html#method.build_mipmaps
+//! [`Surface::open`]: struct.Surface.html#method.open
+//! [`Surface::save`]: struct.Surface.html#method.save
+//! [`Surface::load`]: struct.Surface.html#method.load
+//! [`Context`]: struct.Context.html
+//! [`Context::compress`]: struct.Context.html#method.compress
+//! [`Context::compress_with_mipmaps`]: struct.Context.html#method.compress_with_mipmaps
+//! [`rayon`]: https://docs.rs/rayon
+//! [`fallback_encode`]: fn.fallback_encode.html
+//! [`fallback_compress`]: fn.fallback_compress.html
+//! [`Format::has_fallback_encoder`]: enum.Format.html#method.has_fallback_encoder
+//! [`basis-universal`]: https://docs.rs/basis-universal
+//! [`Batch::basis`]: struct.Batch.html#method.basis
+//! [`BasisOptions::compress_ktx2`]: struct.BasisOptions.html#method.compress_ktx2
+//! [`compress_gltf_textures`]: fn.compress_gltf_textures.html
+//! [`capi`]: capi/index.html
+//! [`Format::to_ktx2`]: enum.Format.html#method.to_ktx2
+//! [`Format::from_ktx2`]: enum.Format.html#method.from_ktx2
+//! [`ktx2`]: https://docs.rs/ktx2
+//! [`convert_to_ktx2`]: fn.convert_to_ktx2.html
+//! [`convert_to_ktx2_zstd`]: fn.convert_to_ktx2_zstd.html
+//! [`surfaces_from_ktx2`]: fn.surfaces_from_ktx2.html
+//! [`BevyImageSaver`]: struct.BevyImageSaver.html
+//! [`TexturePreset`]: struct.TexturePreset.html
+//! [`TextureLabel`]: enum.TextureLabel.html
+//! [`Format::to_wgpu`]: enum.Format.html#method.to_wgpu
+//! [`wgpu_texture_descriptor`]: fn.wgpu_texture_descriptor.html
+//! [`write_wgpu_texture`]: fn.write_wgpu_texture.html
+//! [`linked_version`]: fn.linked_version.html
+//! [`version_at_least`]: fn.version_at_least.html
+//! [`Error::UnsupportedFeature`]: enum.Error.html#variant.UnsupportedFeature
+//! [`inspect`]: fn.inspect.html
+//! [`inspect_bytes`]: fn.inspect_bytes.html
+//! [`Format`]: enum.Format.html
+//! [`Container`]: enum.Container.html
+//! [`TextureType`]: enum.TextureType.html
+//! [`DxgiFormat`]: enum.DxgiFormat.html
+//! [`transcode`]: fn.transcode.html
+//! [`DdsOutput`]: struct.DdsOutput.html
+//! [`Dds10Output`]: struct.Dds10Output.html
+//! [`KtxOutput`]: struct.KtxOutput.html
+//! [`formats`]: formats/index.html
+//! [`write_preview`]: fn.write_preview.html
+//! [`TexturePipeline::preview`]: struct.TexturePipeline.html#method.preview
+//! [`TexturePipeline`]: struct.TexturePipeline.html
+//! [`TexturePipeline::to_memory`]: struct.TexturePipeline.html#method.to_memory
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+#[cfg(all(feature = "nvtt", target_arch = "wasm32"))]
+compile_error!(
+    "nvtt cannot be built for wasm32. Disable the default features, and enable the \
+     `fallback-encoders` feature to compress textures with the pure Rust encoders."
+);
+
+#[cfg(feature = "std")]
+mod analysis;
+#[cfg(feature = "basis")]
+mod basis;
+#[cfg(feature = "nvtt")]
+mod benchmark;
+#[cfg(feature = "bevy")]
+mod bevy;
+#[cfg(feature = "capi")]
+pub mod capi;
+#[cfg(feature = "nvtt")]
+mod context;
+#[cfg(feature = "std")]
+mod cube_surface;
+#[cfg(feature = "fallback-encoders")]
+mod fallback;
+#[cfg(feature = "gltf")]
+mod gltf;
+mod inspect;
+#[cfg(feature = "ktx2")]
+mod ktx2_interop;
+#[cfg(feature = "pipeline")]
+mod pipeline;
+#[cfg(all(feature = "pipeline", target_arch = "wasm32"))]
+compile_error!(
+    "The `pipeline` feature reads and writes files from worker threads, which are not \
+     available on wasm32. Use a `TexturePipeline` with `TexturePipeline::to_memory` instead."
+);
+#[cfg(feature = "preview")]
+mod preview;
+#[cfg(not(feature = "nvtt"))]
+mod raw;
+#[cfg(feature = "std")]
+mod simd;
+#[cfg(feature = "std")]
+mod surface;
+#[cfg(feature = "std")]
+mod texture_pipeline;
+#[cfg(feature = "nvtt")]
+mod transcode;
+#[cfg(feature = "nvtt")]
+mod typed;
+#[cfg(feature = "watch")]
+mod watch;
+#[cfg(feature = "wgpu")]
+mod wgpu_interop;
+
+#[cfg(feature = "basis")]
+pub use crate::basis::{BasisFormat, BasisOptions};
+#[cfg(feature = "bevy")]
+pub use crate::bevy::{
+    BevyImageSaver, BevyImageSettings, BevySaverError, TextureLabel, TexturePreset,
+};
+#[cfg(feature = "fallback-encoders")]
+pub use crate::fallback::{fallback_compress, fallback_encode};
+#[cfg(feature = "gltf")]
+pub use crate::gltf::{compress_gltf_textures, GltfError};
+pub use crate::inspect::{inspect_bytes, DdsDiagnostic, TextureInfo};
+#[cfg(feature = "ktx2-zstd")]
+pub use crate::ktx2_interop::convert_to_ktx2_zstd;
+#[cfg(feature = "ktx2")]
+pub use crate::ktx2_interop::{convert_to_ktx2, surfaces_from_ktx2};
+#[cfg(feature = "pipeline")]
+pub use crate::pipeline::{Batch, BatchError, BatchResult, MemoryBudget};
+#[cfg(feature = "preview")]
+pub use crate::preview::{preview_surface, write_preview, PreviewOptions};
+#[cfg(feature = "watch")]
+pub use crate::watch::{WatchError, Watcher};
+#[cfg(feature = "wgpu")]
+pub use crate::wgpu_interop::{wgpu_texture_descriptor, write_wgpu_texture};
+#[cfg(feature = "std")]
+pub use crate::{
+    analysis::{AlphaAnalysis, AlphaUsage, ChannelMetrics, NormalMapAnalysis, QualityMetrics},
+    cube_surface::{CubeLayout, CubeSurface},
+    inspect::inspect,
+    surface::{
+        Channel, ChannelSource, Dither, NormalTransform, ResizeFilter, SpritePadding, SpriteRect,
+        Surface, ToneMapper,
+    },
+    texture_pipeline::{TexturePipeline, TextureSource},
+};
+#[cfg(feature = "nvtt")]
+pub use crate::{
+    analysis::{FormatCandidate, FormatComparison},
+    benchmark::{Benchmark, BenchmarkReport, BenchmarkResult},
+    context::Context,
+    transcode::{transcode, transcode_bytes},
+    typed::{
+        formats, Dds10Format, Dds10Output, DdsFormat, DdsOutput, KtxFormat, KtxOutput, TypedFormat,
+    },
+};
 
+#[cfg(not(feature = "nvtt"))]
+use crate::raw::*;
+#[cfg(feature = "nvtt")]
+use crate::{context::NativeContext, surface::NativeSurface};
+use alloc::string::{String, ToString};
 use cfg_if::cfg_if;
-use log::{error, trace};
+use core::{
+    any::type_name, cmp::PartialEq, convert::TryFrom, fmt, hash::Hash, num::NonZeroU32,
+    str::FromStr,
+};
+#[cfg(feature = "std")]
+use log::error;
+#[cfg(feature = "nvtt")]
+use log::{trace, warn};
+#[cfg(feature = "nvtt")]
 use nvtt_sys::*;
 #[cfg(feature = "serde-serialize")]
 use serde::{Serialize, Deserialize};
+#[cfg(feature = "nvtt")]
 use std::{
-    any::type_name,
     cell::{Cell, RefCell},
-    cmp::PartialEq,
-    convert::TryFrom,
-    error::Error as ErrorTrait,
-    ffi::{CStr, CString, NulError, OsStr},
-    fmt, mem,
+    collections::BTreeMap,
+    fs::{File, OpenOptions},
+    hash::Hasher,
+    io::{BufWriter, Write},
+    marker::PhantomData,
+    mem,
     os::raw::{c_int, c_uint, c_void},
-    path::Path,
-    ptr::NonNull,
-    slice, thread_local,
+    ptr::{self, NonNull},
+    slice,
+    sync::{Mutex, PoisonError},
+    thread_local,
+    time::{Duration, Instant},
+};
+#[cfg(feature = "std")]
+use std::{
+    error::Error as ErrorTrait,
+    ffi::{NulError, OsStr},
+    path::{Path, PathBuf},
 };
 
-/// Get the version of the linked `nvtt` library.
+/// Get the version of the `nvtt` headers which this crate was built against.
+///
+/// The version is encoded as `major * 10000 + minor * 100 + patch`.
+#[cfg(feature = "nvtt")]
 #[inline(always)]
 pub const fn version() -> u32 {
     NVTT_VERSION
 }
 
+/// Get the version of the `nvtt` library which is linked at runtime. This uses the
+/// same encoding as [`version`].
+///
+/// [`version`]: fn.version.html
+#[cfg(feature = "nvtt")]
+#[inline]
+pub fn linked_version() -> u32 {
+    unsafe { nvttVersion() }
+}
+
+/// Returns `true` if the `nvtt` library which is linked at runtime is at least
+/// version `major.minor`.
+#[cfg(feature = "nvtt")]
+#[inline]
+pub fn version_at_least(major: u32, minor: u32) -> bool {
+    linked_version() >= encode_version(major, minor)
+}
+
+#[cfg(feature = "nvtt")]
+#[inline(always)]
+const fn encode_version(major: u32, minor: u32) -> u32 {
+    major * 10000 + minor * 100
+}
+
 macro_rules! decl_enum {
     (
         $(#[$($attr:meta)*])*
@@ -168,6 +615,89 @@ macro_rules! decl_enum {
             }
         }
 
+        decl_enum!(@raw_eq $enum_name: $raw);
+    };
+    (
+        $(#[$($attr:meta)*])*
+        $v:vis enum $enum_name:ident: $raw:ident {
+            $(
+                $(#[$($brnch_attr:meta)*])*
+                $rust_nm:ident = $sys_nm:ident
+            ),*
+            $(,)?
+        } else {
+            $(#[$($other_attr:meta)*])*
+            $other:ident
+            $(
+                ,
+                $(#[$($extra_attr:meta)*])*
+                $extra_nm:ident => $extra_sys_nm:ident
+            )*
+            $(,)?
+        }
+    ) => {
+        $(#[$($attr)*])*
+        $v enum $enum_name {
+            $(
+                $(#[$($brnch_attr)*])*
+                $rust_nm,
+            )*
+            $(#[$($other_attr)*])*
+            $other($raw),
+            $(
+                $(#[$($extra_attr)*])*
+                $extra_nm,
+            )*
+        }
+
+        impl From<&'_ $enum_name> for $raw {
+            #[inline]
+            fn from(val: &'_ $enum_name) -> Self {
+                From::from(*val)
+            }
+        }
+
+        impl From<$enum_name> for $raw {
+            #[inline]
+            fn from(val: $enum_name) -> Self {
+                 match val {
+                    $(
+                        $enum_name :: $rust_nm => $sys_nm,
+                    )*
+                    $enum_name :: $other(raw) => raw,
+                    $(
+                        $enum_name :: $extra_nm => $extra_sys_nm,
+                    )*
+                }
+            }
+        }
+
+        impl From<$raw> for $enum_name {
+            // The raw values may overlap, so only the first value declared will be returned.
+            // Any value which is not declared is kept in the catch-all variant.
+            #[allow(nonstandard_style, unreachable_patterns)]
+            #[inline]
+            fn from(raw: $raw) -> Self {
+                match raw {
+                    $(
+                        $sys_nm => { $enum_name::$rust_nm }
+                    )*
+                    _ => $enum_name::$other(raw)
+                }
+            }
+        }
+
+        decl_enum!(@raw_eq $enum_name: $raw);
+    };
+    (@raw_eq $enum_name:ident: $raw:ident) => {
+        impl $enum_name {
+            /// Returns the raw nvtt value of this variant.
+            #[inline]
+            pub fn as_raw(self) -> $raw {
+                self.into()
+            }
+        }
+
         impl PartialEq<$raw> for $enum_name {
             #[inline]
             fn eq(&self, rhs: &$raw) -> bool {
@@ -186,10 +716,53 @@ macro_rules! decl_enum {
     };
 }
 
+/// Implements `Display` and `FromStr` for an enum declared with `decl_enum!`, using the
+/// given names. Names are parsed case-insensitively. The catch-all variant of an enum
+/// is written as its raw value, and any number which is not a named variant is parsed
+/// into it.
+macro_rules! decl_enum_names {
+    ($enum_name:ident { $($rust_nm:ident => $name:literal),* $(,)? } $(else $other:ident)?) => {
+        impl fmt::Display for $enum_name {
+            #[inline]
+            fn fmt(&self, fmtr: &mut fmt::Formatter<'_>) -> fmt::Result {
+                match *self {
+                    $(
+                        $enum_name::$rust_nm => fmtr.write_str($name),
+                    )*
+                    $(
+                        $enum_name::$other(raw) => write!(fmtr, "{}", raw),
+                    )?
+                }
+            }
+        }
+
+        impl FromStr for $enum_name {
+            type Err = ParseEnumError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                $(
+                    if s.eq_ignore_ascii_case($name) {
+                        return Ok($enum_name::$rust_nm);
+                    }
+                )*
+                $(
+                    if let Ok(raw) = s.parse() {
+                        // Convert through the raw value, so that named variants are
+                        // returned for the values which they represent.
+                        return Ok($enum_name::from($enum_name::$other(raw).as_raw()));
+                    }
+                )?
+                Err(ParseEnumError::new::<$enum_name>(s))
+            }
+        }
+    };
+}
+
 decl_enum! {
     /// The container format used to store the texture data.
     #[cfg_attr(feature = "serde-serialize", derive(Deserialize, Serialize))]
     #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+    #[non_exhaustive]
     pub enum Container: NvttContainer {
         /// Dds container. This is used to contain data compressed
         /// in the `dxt` format.
@@ -200,16 +773,29 @@ decl_enum! {
         /// Ktx container. This is used to contain data compressed
         /// in the `etc` format.
         Ktx = NvttContainer_NVTT_Container_KTX,
+    } else {
+        /// A container which is not known to this crate. This can be used to select
+        /// containers supported by a newer version of nvtt.
+        Other
     }
 }
 
+decl_enum_names!(Container {
+    Dds => "dds",
+    Dds10 => "dds10",
+    Ktx => "ktx",
+} else Other);
+
+#[cfg(feature = "std")]
 impl Container {
-    /// Gets the file extension of files used for the container.
+    /// Gets the file extension of files used for the container. This is empty
+    /// for `Container::Other`.
     #[inline]
     pub fn file_extension(&self) -> &OsStr {
         match *self {
             Self::Dds | Self::Dds10 => OsStr::new("dds"),
             Self::Ktx => OsStr::new("ktx"),
+            Self::Other(_) => OsStr::new(""),
         }
     }
 }
@@ -222,6 +808,7 @@ decl_enum! {
     /// [`wikipedia`]: https://en.wikipedia.org/wiki/Alpha_compositing
     #[cfg_attr(feature = "serde-serialize", derive(Deserialize, Serialize))]
     #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+    #[non_exhaustive]
     pub enum AlphaMode: NvttAlphaMode {
         /// The image does not contain any alpha information.
         None = NvttAlphaMode_NVTT_AlphaMode_None,
@@ -233,6 +820,12 @@ decl_enum! {
     }
 }
 
+decl_enum_names!(AlphaMode {
+    None => "none",
+    Premultiplied => "premultiplied",
+    Transparency => "transparency",
+});
+
 /// Parameters used to customise the kaiser filter used
 /// for mipmapping.
 #[cfg_attr(feature = "serde-serialize", derive(Deserialize, Serialize))]
@@ -246,6 +839,7 @@ pub struct KaiserParameters {
 /// Specify which type of filter used to calculate mipmaps.
 #[cfg_attr(feature = "serde-serialize", derive(Deserialize, Serialize))]
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[non_exhaustive]
 pub enum MipmapFilter {
     /// Use a box filter. This is the default.
     Box,
@@ -288,10 +882,60 @@ impl Default for MipmapFilter {
     }
 }
 
+/// Specify the colorspace in which mipmaps are downsampled. See
+/// [`InputOptions::set_mip_filter_colorspace`].
+///
+/// [`InputOptions::set_mip_filter_colorspace`]: struct.InputOptions.html#method.set_mip_filter_colorspace
+#[cfg_attr(feature = "serde-serialize", derive(Deserialize, Serialize))]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum MipFilterColorspace {
+    /// The input is gamma encoded, and is converted into linear light before it is
+    /// filtered, then converted back. This is correct for color textures, and is
+    /// nvtt's default.
+    Linear,
+    /// The stored values are filtered directly, without any gamma conversion. This is
+    /// correct for data such as normal maps, masks, and HDR textures which are already
+    /// linear.
+    Gamma,
+}
+
+impl MipFilterColorspace {
+    /// The gamma which the input is decoded with before it is filtered.
+    #[cfg(feature = "nvtt")]
+    fn input_gamma(self) -> f32 {
+        match self {
+            MipFilterColorspace::Linear => 2.2,
+            MipFilterColorspace::Gamma => 1.0,
+        }
+    }
+
+    /// Get the colorspace which mipmaps of textures compressed to `format` should be
+    /// filtered in. Formats which store normals, single channel data, or HDR values use
+    /// `MipFilterColorspace::Gamma`, and color formats use `MipFilterColorspace::Linear`.
+    ///
+    /// This is the colorspace which is used to compress `InputOptions` which have no
+    /// colorspace or gamma set.
+    pub fn default_for(format: Format) -> Self {
+        match format {
+            Format::Bc3n
+            | Format::Dxt5n
+            | Format::Dxt1n
+            | Format::Bc4
+            | Format::Bc5
+            | Format::Bc6
+            | Format::Ctx1
+            | Format::Etc2R
+            | Format::Etc2Rg => MipFilterColorspace::Gamma,
+            _ => MipFilterColorspace::Linear,
+        }
+    }
+}
+
 decl_enum! {
     /// Specify the quality level of the compression output.
     #[cfg_attr(feature = "serde-serialize", derive(Deserialize, Serialize))]
     #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+    #[non_exhaustive]
     pub enum Quality: NvttQuality {
         /// Produces the lowest quality level, but at the fastest speed.
         Fastest = NvttQuality_NVTT_Quality_Fastest,
@@ -307,6 +951,13 @@ decl_enum! {
     }
 }
 
+decl_enum_names!(Quality {
+    Fastest => "fastest",
+    Highest => "highest",
+    Normal => "normal",
+    Production => "production",
+});
+
 impl Default for Quality {
     #[inline]
     fn default() -> Self {
@@ -322,6 +973,7 @@ decl_enum! {
     /// [`Microsoft Documentation`]: https://docs.microsoft.com/en-us/windows/win32/direct3d10/d3d10-graphics-programming-guide-resources-block-compression
     #[cfg_attr(feature = "serde-serialize", derive(Deserialize, Serialize))]
     #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+    #[non_exhaustive]
     pub enum Format: NvttFormat {
         /// Use the `bc1` compression algorithm. This supports images with 3 rgb channels.
         Bc1 = NvttFormat_NVTT_Format_BC1,
@@ -357,18 +1009,384 @@ decl_enum! {
         Dxt5 = NvttFormat_NVTT_Format_DXT5,
         Dxt5n = NvttFormat_NVTT_Format_DXT5n,
         Etc1 = NvttFormat_NVTT_Format_ETC1,
+        /// Use unsigned EAC `R11` compression, which supports a single red channel.
+        ///
+        /// nvtt does not provide a signed EAC format, so signed data such as heights
+        /// must be biased into the `0..1` range before it is compressed.
         Etc2R = NvttFormat_NVTT_Format_ETC2_R,
+        /// Use unsigned EAC `RG11` compression, which supports the red and green
+        /// channels. This can be used to store two channel normal maps on GLES3
+        /// targets, with the [`NormalDecode`] of `Format::Bc5`.
+        ///
+        /// nvtt does not provide a signed EAC format, so the normals are stored biased
+        /// into the `0..1` range.
+        ///
+        /// [`NormalDecode`]: struct.NormalDecode.html
         Etc2Rg = NvttFormat_NVTT_Format_ETC2_RG,
         Etc2Rgb = NvttFormat_NVTT_Format_ETC2_RGB,
         Etc2Rgba = NvttFormat_NVTT_Format_ETC2_RGBA,
         Etc2Rgbm = NvttFormat_NVTT_Format_ETC2_RGBM,
         Etc2RgbA1 = NvttFormat_NVTT_Format_ETC2_RGB_A1,
+        /// Use `PVRTC1` compression at 2 bits per pixel, which supports rgb channels.
+        ///
+        /// nvtt does not provide the `PVRTC2` formats, so only `PVRTC1` textures can be
+        /// produced for PowerVR targets.
         Pvr2BppRgb = NvttFormat_NVTT_Format_PVR_2BPP_RGB,
+        /// Use `PVRTC1` compression at 2 bits per pixel, which supports rgba channels.
         Pvr2BppRgba = NvttFormat_NVTT_Format_PVR_2BPP_RGBA,
+        /// Use `PVRTC1` compression at 4 bits per pixel, which supports rgb channels.
         Pvr4BppRgb = NvttFormat_NVTT_Format_PVR_4BPP_RGB,
+        /// Use `PVRTC1` compression at 4 bits per pixel, which supports rgba channels.
         Pvr4BppRgba = NvttFormat_NVTT_Format_PVR_4BPP_RGBA,
         Rgb = NvttFormat_NVTT_Format_RGB,
         Rgba = NvttFormat_NVTT_Format_RGBA,
+    } else {
+        /// A format which is not known to this crate. This can be used to select
+        /// formats supported by a newer version of nvtt.
+        Other
+    }
+}
+
+decl_enum_names!(Format {
+    Bc1 => "bc1",
+    Bc1a => "bc1a",
+    Bc2 => "bc2",
+    Bc3 => "bc3",
+    Bc3n => "bc3n",
+    Bc3Rgbm => "bc3-rgbm",
+    Bc4 => "bc4",
+    Bc5 => "bc5",
+    Bc6 => "bc6",
+    Bc7 => "bc7",
+    Ctx1 => "ctx1",
+    Dxt1 => "dxt1",
+    Dxt1a => "dxt1a",
+    Dxt1n => "dxt1n",
+    Dxt3 => "dxt3",
+    Dxt5 => "dxt5",
+    Dxt5n => "dxt5n",
+    Etc1 => "etc1",
+    Etc2R => "etc2-r",
+    Etc2Rg => "etc2-rg",
+    Etc2Rgb => "etc2-rgb",
+    Etc2Rgba => "etc2-rgba",
+    Etc2Rgbm => "etc2-rgbm",
+    Etc2RgbA1 => "etc2-rgb-a1",
+    Pvr2BppRgb => "pvr-2bpp-rgb",
+    Pvr2BppRgba => "pvr-2bpp-rgba",
+    Pvr4BppRgb => "pvr-4bpp-rgb",
+    Pvr4BppRgba => "pvr-4bpp-rgba",
+    Rgb => "rgb",
+    Rgba => "rgba",
+} else Other);
+
+impl Format {
+    /// Gets the oldest version of nvtt, as `(major, minor)`, which can compress to
+    /// this format. Compressing with an older version of nvtt fails with
+    /// [`Error::UnsupportedFeature`].
+    ///
+    /// [`Error::UnsupportedFeature`]: enum.Error.html#variant.UnsupportedFeature
+    #[inline]
+    pub fn required_version(&self) -> (u32, u32) {
+        match *self {
+            Self::Bc3Rgbm
+            | Self::Bc6
+            | Self::Bc7
+            | Self::Etc1
+            | Self::Etc2R
+            | Self::Etc2Rg
+            | Self::Etc2Rgb
+            | Self::Etc2Rgba
+            | Self::Etc2Rgbm
+            | Self::Etc2RgbA1
+            | Self::Pvr2BppRgb
+            | Self::Pvr2BppRgba
+            | Self::Pvr4BppRgb
+            | Self::Pvr4BppRgba => (2, 1),
+            _ => (2, 0),
+        }
+    }
+
+    /// Gets the [`DxgiFormat`] which describes textures of this format, or `None` if
+    /// the format cannot be described by a `DXGI_FORMAT`. If `srgb` is `true`, then
+    /// the sRGB variant of the format is returned, if one exists.
+    ///
+    /// Uncompressed textures are written by nvtt in BGRA order, so `Format::Rgb` and
+    /// `Format::Rgba` map to `DxgiFormat::B8G8R8A8Unorm`.
+    ///
+    /// [`DxgiFormat`]: enum.DxgiFormat.html
+    pub fn to_dxgi(self, srgb: bool) -> Option<DxgiFormat> {
+        let dxgi_format = match self {
+            Self::Rgb | Self::Rgba => DxgiFormat::B8G8R8A8Unorm,
+            Self::Bc1 | Self::Bc1a | Self::Dxt1 | Self::Dxt1a | Self::Dxt1n => DxgiFormat::Bc1Unorm,
+            Self::Bc2 | Self::Dxt3 => DxgiFormat::Bc2Unorm,
+            Self::Bc3 | Self::Bc3n | Self::Bc3Rgbm | Self::Dxt5 | Self::Dxt5n => {
+                DxgiFormat::Bc3Unorm
+            }
+            Self::Bc4 => DxgiFormat::Bc4Unorm,
+            Self::Bc5 => DxgiFormat::Bc5Unorm,
+            Self::Bc6 => DxgiFormat::Bc6hUf16,
+            Self::Bc7 => DxgiFormat::Bc7Unorm,
+            _ => return None,
+        };
+
+        Some(if srgb {
+            dxgi_format.to_srgb()
+        } else {
+            dxgi_format
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+impl Format {
+    /// Gets the [`NormalDecode`] which unpacks normals stored in this format, or
+    /// `None` if the format is not used for two channel normal maps.
+    ///
+    /// `Format::Bc3n` and `Format::Dxt5n` store `x` in the alpha channel, while
+    /// `Format::Dxt1n` and `Format::Bc5` store it in the red channel.
+    ///
+    /// [`NormalDecode`]: struct.NormalDecode.html
+    #[inline]
+    pub fn normal_decode(self) -> Option<NormalDecode> {
+        match self {
+            Self::Bc3n | Self::Dxt5n => Some(NormalDecode {
+                x: Channel::Alpha,
+                y: Channel::Green,
+            }),
+            Self::Dxt1n | Self::Bc5 => Some(NormalDecode {
+                x: Channel::Red,
+                y: Channel::Green,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Describes how a shader reconstructs a normal from a two channel normal map:
+///
+/// ```glsl
+/// vec2 xy = vec2(texel[x], texel[y]) * SCALE + BIAS;
+/// vec3 n = vec3(xy, sqrt(max(1.0 - dot(xy, xy), 0.0)));
+/// ```
+///
+/// See [`Format::normal_decode`].
+///
+/// [`Format::normal_decode`]: enum.Format.html#method.normal_decode
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "serde-serialize", derive(Deserialize, Serialize))]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct NormalDecode {
+    /// The channel which stores the `x` component of the normal.
+    pub x: Channel,
+    /// The channel which stores the `y` component of the normal.
+    pub y: Channel,
+}
+
+#[cfg(feature = "std")]
+impl NormalDecode {
+    /// The scale applied to each sampled component.
+    pub const SCALE: f32 = 2.0;
+    /// The bias added to each scaled component.
+    pub const BIAS: f32 = -1.0;
+}
+
+// The values of the `DXGI_FORMAT` enum which can describe textures produced by nvtt.
+const DXGI_FORMAT_R8G8B8A8_TYPELESS: u32 = 27;
+const DXGI_FORMAT_R8G8B8A8_UNORM: u32 = 28;
+const DXGI_FORMAT_R8G8B8A8_UNORM_SRGB: u32 = 29;
+const DXGI_FORMAT_BC1_TYPELESS: u32 = 70;
+const DXGI_FORMAT_BC1_UNORM: u32 = 71;
+const DXGI_FORMAT_BC1_UNORM_SRGB: u32 = 72;
+const DXGI_FORMAT_BC2_TYPELESS: u32 = 73;
+const DXGI_FORMAT_BC2_UNORM: u32 = 74;
+const DXGI_FORMAT_BC2_UNORM_SRGB: u32 = 75;
+const DXGI_FORMAT_BC3_TYPELESS: u32 = 76;
+const DXGI_FORMAT_BC3_UNORM: u32 = 77;
+const DXGI_FORMAT_BC3_UNORM_SRGB: u32 = 78;
+const DXGI_FORMAT_BC4_TYPELESS: u32 = 79;
+const DXGI_FORMAT_BC4_UNORM: u32 = 80;
+const DXGI_FORMAT_BC4_SNORM: u32 = 81;
+const DXGI_FORMAT_BC5_TYPELESS: u32 = 82;
+const DXGI_FORMAT_BC5_UNORM: u32 = 83;
+const DXGI_FORMAT_BC5_SNORM: u32 = 84;
+const DXGI_FORMAT_B8G8R8A8_UNORM: u32 = 87;
+const DXGI_FORMAT_B8G8R8X8_UNORM: u32 = 88;
+const DXGI_FORMAT_B8G8R8A8_TYPELESS: u32 = 90;
+const DXGI_FORMAT_B8G8R8A8_UNORM_SRGB: u32 = 91;
+const DXGI_FORMAT_B8G8R8X8_TYPELESS: u32 = 92;
+const DXGI_FORMAT_B8G8R8X8_UNORM_SRGB: u32 = 93;
+const DXGI_FORMAT_BC6H_TYPELESS: u32 = 94;
+const DXGI_FORMAT_BC6H_UF16: u32 = 95;
+const DXGI_FORMAT_BC6H_SF16: u32 = 96;
+const DXGI_FORMAT_BC7_TYPELESS: u32 = 97;
+const DXGI_FORMAT_BC7_UNORM: u32 = 98;
+const DXGI_FORMAT_BC7_UNORM_SRGB: u32 = 99;
+
+decl_enum! {
+    /// A `DXGI_FORMAT` value, which describes the format of a texture to Direct3D. This
+    /// is stored in the DX10 extension of the dds header.
+    ///
+    /// Only the formats which can describe the data written by nvtt are included.
+    /// The [`TryFrom<u32>`] implementation fails for any other value.
+    ///
+    /// [`TryFrom<u32>`]: https://doc.rust-lang.org/stable/std/convert/trait.TryFrom.html
+    #[cfg_attr(feature = "serde-serialize", derive(Deserialize, Serialize))]
+    #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+    #[non_exhaustive]
+    pub enum DxgiFormat: u32 {
+        /// Typeless 8 bit rgba data.
+        R8G8B8A8Typeless = DXGI_FORMAT_R8G8B8A8_TYPELESS,
+        /// 8 bit rgba data.
+        R8G8B8A8Unorm = DXGI_FORMAT_R8G8B8A8_UNORM,
+        /// 8 bit rgba data in the sRGB colorspace.
+        R8G8B8A8UnormSrgb = DXGI_FORMAT_R8G8B8A8_UNORM_SRGB,
+        /// Typeless Bc1 data.
+        Bc1Typeless = DXGI_FORMAT_BC1_TYPELESS,
+        /// Bc1 data.
+        Bc1Unorm = DXGI_FORMAT_BC1_UNORM,
+        /// Bc1 data in the sRGB colorspace.
+        Bc1UnormSrgb = DXGI_FORMAT_BC1_UNORM_SRGB,
+        /// Typeless Bc2 data.
+        Bc2Typeless = DXGI_FORMAT_BC2_TYPELESS,
+        /// Bc2 data.
+        Bc2Unorm = DXGI_FORMAT_BC2_UNORM,
+        /// Bc2 data in the sRGB colorspace.
+        Bc2UnormSrgb = DXGI_FORMAT_BC2_UNORM_SRGB,
+        /// Typeless Bc3 data.
+        Bc3Typeless = DXGI_FORMAT_BC3_TYPELESS,
+        /// Bc3 data.
+        Bc3Unorm = DXGI_FORMAT_BC3_UNORM,
+        /// Bc3 data in the sRGB colorspace.
+        Bc3UnormSrgb = DXGI_FORMAT_BC3_UNORM_SRGB,
+        /// Typeless Bc4 data.
+        Bc4Typeless = DXGI_FORMAT_BC4_TYPELESS,
+        /// Bc4 data.
+        Bc4Unorm = DXGI_FORMAT_BC4_UNORM,
+        /// Bc4 data with signed normalized values.
+        Bc4Snorm = DXGI_FORMAT_BC4_SNORM,
+        /// Typeless Bc5 data.
+        Bc5Typeless = DXGI_FORMAT_BC5_TYPELESS,
+        /// Bc5 data.
+        Bc5Unorm = DXGI_FORMAT_BC5_UNORM,
+        /// Bc5 data with signed normalized values.
+        Bc5Snorm = DXGI_FORMAT_BC5_SNORM,
+        /// 8 bit bgra data.
+        B8G8R8A8Unorm = DXGI_FORMAT_B8G8R8A8_UNORM,
+        /// 8 bit bgr data, with an unused fourth channel.
+        B8G8R8X8Unorm = DXGI_FORMAT_B8G8R8X8_UNORM,
+        /// Typeless 8 bit bgra data.
+        B8G8R8A8Typeless = DXGI_FORMAT_B8G8R8A8_TYPELESS,
+        /// 8 bit bgra data in the sRGB colorspace.
+        B8G8R8A8UnormSrgb = DXGI_FORMAT_B8G8R8A8_UNORM_SRGB,
+        /// Typeless 8 bit bgr data, with an unused fourth channel.
+        B8G8R8X8Typeless = DXGI_FORMAT_B8G8R8X8_TYPELESS,
+        /// 8 bit bgr data in the sRGB colorspace, with an unused fourth channel.
+        B8G8R8X8UnormSrgb = DXGI_FORMAT_B8G8R8X8_UNORM_SRGB,
+        /// Typeless Bc6 data.
+        Bc6hTypeless = DXGI_FORMAT_BC6H_TYPELESS,
+        /// Bc6 data with unsigned half float values.
+        Bc6hUf16 = DXGI_FORMAT_BC6H_UF16,
+        /// Bc6 data with signed half float values.
+        Bc6hSf16 = DXGI_FORMAT_BC6H_SF16,
+        /// Typeless Bc7 data.
+        Bc7Typeless = DXGI_FORMAT_BC7_TYPELESS,
+        /// Bc7 data.
+        Bc7Unorm = DXGI_FORMAT_BC7_UNORM,
+        /// Bc7 data in the sRGB colorspace.
+        Bc7UnormSrgb = DXGI_FORMAT_BC7_UNORM_SRGB,
+    }
+}
+
+impl DxgiFormat {
+    /// Gets the `Format` of the data described by this `DxgiFormat`, or `None` if the
+    /// data cannot be produced by nvtt.
+    pub fn format(self) -> Option<Format> {
+        match self {
+            Self::R8G8B8A8Typeless
+            | Self::R8G8B8A8Unorm
+            | Self::R8G8B8A8UnormSrgb
+            | Self::B8G8R8A8Typeless
+            | Self::B8G8R8A8Unorm
+            | Self::B8G8R8A8UnormSrgb => Some(Format::Rgba),
+            Self::B8G8R8X8Typeless | Self::B8G8R8X8Unorm | Self::B8G8R8X8UnormSrgb => {
+                Some(Format::Rgb)
+            }
+            Self::Bc1Typeless | Self::Bc1Unorm | Self::Bc1UnormSrgb => Some(Format::Bc1),
+            Self::Bc2Typeless | Self::Bc2Unorm | Self::Bc2UnormSrgb => Some(Format::Bc2),
+            Self::Bc3Typeless | Self::Bc3Unorm | Self::Bc3UnormSrgb => Some(Format::Bc3),
+            Self::Bc4Typeless | Self::Bc4Unorm => Some(Format::Bc4),
+            Self::Bc5Typeless | Self::Bc5Unorm => Some(Format::Bc5),
+            Self::Bc6hTypeless | Self::Bc6hUf16 => Some(Format::Bc6),
+            Self::Bc7Typeless | Self::Bc7Unorm | Self::Bc7UnormSrgb => Some(Format::Bc7),
+            Self::Bc4Snorm | Self::Bc5Snorm | Self::Bc6hSf16 => None,
+        }
+    }
+
+    /// Returns `true` if this format stores data in the sRGB colorspace.
+    #[inline]
+    pub fn is_srgb(self) -> bool {
+        matches!(
+            self,
+            Self::R8G8B8A8UnormSrgb
+                | Self::B8G8R8A8UnormSrgb
+                | Self::B8G8R8X8UnormSrgb
+                | Self::Bc1UnormSrgb
+                | Self::Bc2UnormSrgb
+                | Self::Bc3UnormSrgb
+                | Self::Bc7UnormSrgb
+        )
+    }
+
+    /// Returns `true` if this is a typeless format.
+    #[inline]
+    pub fn is_typeless(self) -> bool {
+        matches!(
+            self,
+            Self::R8G8B8A8Typeless
+                | Self::B8G8R8A8Typeless
+                | Self::B8G8R8X8Typeless
+                | Self::Bc1Typeless
+                | Self::Bc2Typeless
+                | Self::Bc3Typeless
+                | Self::Bc4Typeless
+                | Self::Bc5Typeless
+                | Self::Bc6hTypeless
+                | Self::Bc7Typeless
+        )
+    }
+
+    /// Gets the sRGB variant of this format. If the format has no sRGB variant, then
+    /// it is returned unchanged.
+    pub fn to_srgb(self) -> Self {
+        match self {
+            Self::R8G8B8A8Typeless | Self::R8G8B8A8Unorm => Self::R8G8B8A8UnormSrgb,
+            Self::B8G8R8A8Typeless | Self::B8G8R8A8Unorm => Self::B8G8R8A8UnormSrgb,
+            Self::B8G8R8X8Typeless | Self::B8G8R8X8Unorm => Self::B8G8R8X8UnormSrgb,
+            Self::Bc1Typeless | Self::Bc1Unorm => Self::Bc1UnormSrgb,
+            Self::Bc2Typeless | Self::Bc2Unorm => Self::Bc2UnormSrgb,
+            Self::Bc3Typeless | Self::Bc3Unorm => Self::Bc3UnormSrgb,
+            Self::Bc7Typeless | Self::Bc7Unorm => Self::Bc7UnormSrgb,
+            other => other,
+        }
+    }
+
+    /// Gets the typeless variant of this format, which can be used to create a
+    /// resource that is viewed with both the linear and sRGB formats.
+    pub fn to_typeless(self) -> Self {
+        match self {
+            Self::R8G8B8A8Unorm | Self::R8G8B8A8UnormSrgb => Self::R8G8B8A8Typeless,
+            Self::B8G8R8A8Unorm | Self::B8G8R8A8UnormSrgb => Self::B8G8R8A8Typeless,
+            Self::B8G8R8X8Unorm | Self::B8G8R8X8UnormSrgb => Self::B8G8R8X8Typeless,
+            Self::Bc1Unorm | Self::Bc1UnormSrgb => Self::Bc1Typeless,
+            Self::Bc2Unorm | Self::Bc2UnormSrgb => Self::Bc2Typeless,
+            Self::Bc3Unorm | Self::Bc3UnormSrgb => Self::Bc3Typeless,
+            Self::Bc4Unorm | Self::Bc4Snorm => Self::Bc4Typeless,
+            Self::Bc5Unorm | Self::Bc5Snorm => Self::Bc5Typeless,
+            Self::Bc6hUf16 | Self::Bc6hSf16 => Self::Bc6hTypeless,
+            Self::Bc7Unorm | Self::Bc7UnormSrgb => Self::Bc7Typeless,
+            other => other,
+        }
     }
 }
 
@@ -376,6 +1394,7 @@ decl_enum! {
     /// Specify the color format of the input image.
     #[cfg_attr(feature = "serde-serialize", derive(Deserialize, Serialize))]
     #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+    #[non_exhaustive]
     pub enum InputFormat: NvttInputFormat {
         /// 4 unsigned byte channels comprised of `blue`, `green`, `red` and `alpha`.
         Bgra8Ub = NvttInputFormat_NVTT_InputFormat_BGRA_8UB,
@@ -392,6 +1411,7 @@ decl_enum! {
     /// Controls how the image edge length is rounded when the image is compressed.
     #[cfg_attr(feature = "serde-serialize", derive(Deserialize, Serialize))]
     #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+    #[non_exhaustive]
     pub enum RoundMode: NvttRoundMode {
         /// The image size is not changed.
         None = NvttRoundMode_NVTT_RoundMode_None,
@@ -421,6 +1441,7 @@ decl_enum! {
     /// The type of the texture.
     #[cfg_attr(feature = "serde-serialize", derive(Deserialize, Serialize))]
     #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+    #[non_exhaustive]
     pub enum TextureType: NvttTextureType {
         /// The texture is a standard 2D image with a width and
         /// a height.
@@ -446,6 +1467,7 @@ decl_enum! {
     /// Specify how the image should wrap if image boundaries are modified.
     #[cfg_attr(feature = "serde-serialize", derive(Deserialize, Serialize))]
     #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+    #[non_exhaustive]
     pub enum WrapMode: NvttWrapMode {
         /// Clamp the image edge to a single color.
         Clamp = NvttWrapMode_NVTT_WrapMode_Clamp,
@@ -456,6 +1478,12 @@ decl_enum! {
     }
 }
 
+decl_enum_names!(WrapMode {
+    Clamp => "clamp",
+    Mirror => "mirror",
+    Repeat => "repeat",
+});
+
 #[cfg_attr(feature = "serde-serialize", derive(Deserialize, Serialize))]
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct NormalMapFilter {
@@ -563,6 +1591,21 @@ impl TextureLayout {
         }
     }
 
+    /// Construct a new [`TextureLayout::Array`] which holds `cube_count` cube maps, with
+    /// faces of `face_width` by `face_height` pixels. Each cube map takes six
+    /// consecutive layers, which are selected with [`CubeFace::cube_array_layer`].
+    ///
+    /// nvtt has no cube map array texture type, so the array must be compressed with
+    /// [`OutputOptions::set_cube_array`] to be written as a cube map array.
+    ///
+    /// [`TextureLayout::Array`]: ./enum.TextureLayout.html#variant.Array
+    /// [`CubeFace::cube_array_layer`]: enum.CubeFace.html#method.cube_array_layer
+    /// [`OutputOptions::set_cube_array`]: struct.OutputOptions.html#method.set_cube_array
+    #[inline]
+    pub const fn cube_array(face_width: usize, face_height: usize, cube_count: usize) -> Self {
+        Self::array(face_width, face_height, cube_count * 6)
+    }
+
     /// Get the `TextureType` corresponding to this `TextureLayout`.
     #[inline]
     pub fn texture_type(&self) -> TextureType {
@@ -574,8 +1617,83 @@ impl TextureLayout {
         }
     }
 
-    /// Get the `TextureDimensions` of this `TextureLayout`. Used
-    /// internally.
+    /// Create a `TextureLayout` for a single texture with the given `extent`. This is a
+    /// [`TextureLayout::D2`] if the depth of the `extent` is `1`, and a
+    /// [`TextureLayout::D3`] otherwise.
+    ///
+    /// [`TextureLayout::D2`]: enum.TextureLayout.html#variant.D2
+    /// [`TextureLayout::D3`]: enum.TextureLayout.html#variant.D3
+    #[inline]
+    pub fn from_extent(extent: Extent3d) -> Self {
+        let (width, height) = (extent.width() as usize, extent.height() as usize);
+        match extent.depth() {
+            1 => Self::D2 { width, height },
+            depth => Self::D3 {
+                width,
+                height,
+                depth: depth as usize,
+            },
+        }
+    }
+
+    /// Get the extent of the top mipmap level of each face or layer of this
+    /// `TextureLayout`. Returns `None` if the layout does not contain any pixels.
+    #[inline]
+    pub fn extent(&self) -> Option<Extent3d> {
+        match *self {
+            Self::D2 { width, height }
+            | Self::Array { width, height, .. }
+            | Self::Cube {
+                face_width: width,
+                face_height: height,
+            } => Extent3d::from_usize(width, height, 1),
+            Self::D3 {
+                width,
+                height,
+                depth,
+            } => Extent3d::from_usize(width, height, depth),
+        }
+    }
+
+    /// Get the number of mipmap levels in a full mip chain of this `TextureLayout`,
+    /// down to a single pixel.
+    #[inline]
+    pub fn mip_count(&self) -> u32 {
+        let extent = match *self {
+            Self::D2 { width, height } | Self::Array { width, height, .. } => width.max(height),
+            Self::D3 {
+                width,
+                height,
+                depth,
+            } => width.max(height).max(depth),
+            Self::Cube {
+                face_width,
+                face_height,
+            } => face_width.max(face_height),
+        };
+        usize::BITS - extent.max(1).leading_zeros()
+    }
+
+    /// Returns `true` if every extent of this `TextureLayout`, including the array
+    /// length, is non-zero and can be passed to nvtt.
+    #[cfg(feature = "nvtt")]
+    fn is_valid(&self) -> bool {
+        let array_length = match *self {
+            Self::Array { array_length, .. } => array_length,
+            _ => 1,
+        };
+        let fits = |n: usize| n > 0 && c_int::try_from(n).is_ok();
+        let extent_fits = self.extent().is_some_and(|e| {
+            [e.width(), e.height(), e.depth()]
+                .iter()
+                .all(|&n| fits(n as usize))
+        });
+        extent_fits && fits(array_length)
+    }
+
+    /// Get the `TextureDimensions` of this `TextureLayout`. Used
+    /// internally.
+    #[cfg(feature = "nvtt")]
     #[inline]
     fn dimensions(&self) -> TextureDimensions {
         match *self {
@@ -616,8 +1734,211 @@ impl TextureLayout {
     }
 }
 
+/// Selects the face of a cube texture, or the layer of an array texture, which the
+/// data of a mipmap is set for.
+///
+/// 2D and 3D textures have a single face, which is selected with the default
+/// `CubeFace::ArrayLayer(0)`.
+#[cfg_attr(feature = "serde-serialize", derive(Deserialize, Serialize))]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum CubeFace {
+    /// The face of a cube texture which faces the positive x axis.
+    PositiveX,
+    /// The face of a cube texture which faces the negative x axis.
+    NegativeX,
+    /// The face of a cube texture which faces the positive y axis.
+    PositiveY,
+    /// The face of a cube texture which faces the negative y axis.
+    NegativeY,
+    /// The face of a cube texture which faces the positive z axis.
+    PositiveZ,
+    /// The face of a cube texture which faces the negative z axis.
+    NegativeZ,
+    /// The layer of an array texture with the given index.
+    ArrayLayer(u32),
+}
+
+impl CubeFace {
+    /// Every face of a cube texture, in the order in which they are stored.
+    pub const ALL: [CubeFace; 6] = [
+        CubeFace::PositiveX,
+        CubeFace::NegativeX,
+        CubeFace::PositiveY,
+        CubeFace::NegativeY,
+        CubeFace::PositiveZ,
+        CubeFace::NegativeZ,
+    ];
+
+    /// Get the index of the face which is passed to nvtt.
+    #[inline]
+    pub fn index(self) -> u32 {
+        match self {
+            CubeFace::PositiveX => 0,
+            CubeFace::NegativeX => 1,
+            CubeFace::PositiveY => 2,
+            CubeFace::NegativeY => 3,
+            CubeFace::PositiveZ => 4,
+            CubeFace::NegativeZ => 5,
+            CubeFace::ArrayLayer(layer) => layer,
+        }
+    }
+
+    /// Get the layer of a cube map array which holds `face` of the cube map at index
+    /// `cube`. See [`TextureLayout::cube_array`]. Returns `None` if `face` is an
+    /// `ArrayLayer`, or the layer index overflows.
+    ///
+    /// [`TextureLayout::cube_array`]: enum.TextureLayout.html#method.cube_array
+    #[inline]
+    pub fn cube_array_layer(cube: u32, face: CubeFace) -> Option<CubeFace> {
+        match face {
+            CubeFace::ArrayLayer(_) => None,
+            face => cube
+                .checked_mul(6)?
+                .checked_add(face.index())
+                .map(CubeFace::ArrayLayer),
+        }
+    }
+
+    /// Get the face index as the `c_int` which is passed to nvtt.
+    #[cfg(feature = "nvtt")]
+    #[inline]
+    fn raw_index(self) -> Result<c_int, Error> {
+        c_int::try_from(self.index()).map_err(|_| Error::InvalidInput)
+    }
+}
+
+impl Default for CubeFace {
+    #[inline]
+    fn default() -> Self {
+        CubeFace::ArrayLayer(0)
+    }
+}
+
+/// The width, height and depth of a texture or a mipmap level in pixels. Every
+/// dimension is at least `1`, so 2D textures have a depth of `1`.
+#[cfg_attr(feature = "serde-serialize", derive(Deserialize, Serialize))]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Extent3d {
+    width: NonZeroU32,
+    height: NonZeroU32,
+    depth: NonZeroU32,
+}
+
+impl Extent3d {
+    /// Create a new `Extent3d` with the given dimensions. Returns `None` if any
+    /// dimension is `0`.
+    #[inline]
+    pub fn new(width: u32, height: u32, depth: u32) -> Option<Self> {
+        Some(Self {
+            width: NonZeroU32::new(width)?,
+            height: NonZeroU32::new(height)?,
+            depth: NonZeroU32::new(depth)?,
+        })
+    }
+
+    /// Create a new `Extent3d` for a 2D texture with the given `width` and `height`.
+    /// Returns `None` if either dimension is `0`.
+    #[inline]
+    pub fn d2(width: u32, height: u32) -> Option<Self> {
+        Self::new(width, height, 1)
+    }
+
+    /// Create a new `Extent3d` from `usize` dimensions. Returns `None` if any
+    /// dimension is `0`, or does not fit into a `u32`.
+    #[inline]
+    pub(crate) fn from_usize(width: usize, height: usize, depth: usize) -> Option<Self> {
+        Self::new(
+            u32::try_from(width).ok()?,
+            u32::try_from(height).ok()?,
+            u32::try_from(depth).ok()?,
+        )
+    }
+
+    /// The width in pixels.
+    #[inline]
+    pub fn width(self) -> u32 {
+        self.width.get()
+    }
+
+    /// The height in pixels.
+    #[inline]
+    pub fn height(self) -> u32 {
+        self.height.get()
+    }
+
+    /// The depth in pixels.
+    #[inline]
+    pub fn depth(self) -> u32 {
+        self.depth.get()
+    }
+
+    /// The number of pixels in the texture.
+    #[inline]
+    pub fn pixel_count(self) -> usize {
+        self.width() as usize * self.height() as usize * self.depth() as usize
+    }
+
+    /// Get the extent of the given mipmap `level` of a texture with this extent. Each
+    /// dimension is halved for every level, down to a minimum of `1`.
+    #[inline]
+    pub fn mip_level(self, level: MipLevel) -> Self {
+        let shrink = |extent: NonZeroU32| {
+            NonZeroU32::new(extent.get().checked_shr(level.0).unwrap_or(0))
+                .unwrap_or(NonZeroU32::MIN)
+        };
+        Self {
+            width: shrink(self.width),
+            height: shrink(self.height),
+            depth: shrink(self.depth),
+        }
+    }
+
+    /// Get the dimensions as the `c_int`s which are passed to nvtt.
+    #[cfg(feature = "nvtt")]
+    #[inline]
+    fn to_raw(self) -> Result<(c_int, c_int, c_int), Error> {
+        let raw = |extent: u32| c_int::try_from(extent).map_err(|_| Error::InvalidInput);
+        Ok((raw(self.width())?, raw(self.height())?, raw(self.depth())?))
+    }
+}
+
+/// The index of a mipmap level of a texture, where `MipLevel(0)` is the largest level.
+///
+/// When the data of a mipmap level is set on an [`InputOptions`], the level is checked
+/// against the mip chain of its [`TextureLayout`].
+///
+/// [`InputOptions`]: struct.InputOptions.html
+/// [`TextureLayout`]: enum.TextureLayout.html
+#[cfg_attr(feature = "serde-serialize", derive(Deserialize, Serialize))]
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct MipLevel(pub u32);
+
+impl MipLevel {
+    /// Returns `true` if this level is part of the full mip chain of `layout`.
+    #[inline]
+    pub fn is_valid_for(self, layout: &TextureLayout) -> bool {
+        self.0 < layout.mip_count()
+    }
+}
+
+impl From<u32> for MipLevel {
+    #[inline]
+    fn from(level: u32) -> Self {
+        MipLevel(level)
+    }
+}
+
+impl From<MipLevel> for u32 {
+    #[inline]
+    fn from(level: MipLevel) -> Self {
+        level.0
+    }
+}
+
 /// Describes the dimensions of an input texture. Unused parameters
 /// are set to `1`.
+#[cfg(feature = "nvtt")]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 struct TextureDimensions {
     width: c_int,
     height: c_int,
@@ -625,6 +1946,7 @@ struct TextureDimensions {
     array_length: c_int,
 }
 
+#[cfg(feature = "nvtt")]
 impl Default for TextureDimensions {
     #[inline]
     fn default() -> Self {
@@ -637,20 +1959,51 @@ impl Default for TextureDimensions {
     }
 }
 
+#[cfg(any(feature = "nvtt", feature = "pipeline"))]
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+#[cfg(any(feature = "nvtt", feature = "pipeline"))]
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Feed `data` into the 64 bit FNV-1a hash `state`. This is used instead of
+/// the `std` hashers because the output must be stable across releases.
+#[cfg(any(feature = "nvtt", feature = "pipeline"))]
+#[inline]
+fn fnv1a_64(state: u64, data: &[u8]) -> u64 {
+    data.iter().fold(state, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+    })
+}
+
 /// The `Compressor` is used to perform the texture compression. This provides a
 /// safer interface for the [`NvttCompressor`] type.
 ///
 /// [`NvttCompressor`]: https://docs.rs/nvtt_sys/latest/nvtt_sys/struct.NvttCompressor.html
-#[derive(Debug)]
-pub struct Compressor(NonNull<NvttCompressor>);
+#[cfg(feature = "nvtt")]
+pub struct Compressor {
+    compressor: NonNull<NvttCompressor>,
+    /// Every error which was reported during the last call to `compress`.
+    errors: RefCell<Vec<Error>>,
+}
 
+#[cfg(feature = "nvtt")]
 impl Compressor {
     /// Create a new `Compressor`. If the `Compressor` cannot be created, returns
-    /// `Error::Unknown`.
+    /// `Error::Unknown`. If this crate was built without the [`nvtt`] feature, returns
+    /// `Error::UnsupportedFeature`.
+    ///
+    /// [`nvtt`]: index.html#nvtt
     #[inline]
     pub fn new() -> Result<Self, Error> {
+        if !cfg!(feature = "nvtt") {
+            return Err(Error::UnsupportedFeature);
+        }
         let compressor = unsafe { nvttCreateCompressor() };
-        NonNull::new(compressor).map(Self).ok_or(Error::Unknown)
+        NonNull::new(compressor)
+            .map(|compressor| Self {
+                compressor,
+                errors: RefCell::default(),
+            })
+            .ok_or(Error::Unknown)
     }
 
     /// Returns the underlying [`NvttCompressor`] pointer type. It is your responsibility
@@ -661,54 +2014,199 @@ impl Compressor {
     /// [`NvttCompressor`]: https://docs.rs/nvtt_sys/latest/nvtt_sys/struct.NvttCompressor.html
     #[inline]
     pub fn into_raw(self) -> *mut NvttCompressor {
-        let ptr = self.0.as_ptr();
+        let ptr = self.compressor.as_ptr();
+        drop(self.errors.take());
         mem::forget(self);
         ptr
     }
 
+    /// Returns the underlying [`NvttCompressor`] pointer without giving up ownership of it.
+    /// The pointer is valid for as long as this `Compressor` is alive, and is destroyed
+    /// when the `Compressor` is dropped.
+    ///
+    /// This can be used to call nvtt functions which are not wrapped by this crate.
+    /// Mutating the compressor through this pointer is unsafe: the `Compressor`
+    /// does not observe those changes, and must not be destroyed through it.
+    ///
+    /// [`NvttCompressor`]: https://docs.rs/nvtt_sys/latest/nvtt_sys/struct.NvttCompressor.html
+    #[inline]
+    pub fn as_raw(&self) -> *mut NvttCompressor {
+        self.compressor.as_ptr()
+    }
+
     /// If the platform supports the `cuda` api, this method can be used to enable
     /// gpu compression. This may give different results to a pure cpu implementation,
     /// so this is set to `false` by default.
     ///
-    /// On platforms without `cuda`, this function is a no-op.
+    /// On platforms without `cuda`, this function is a no-op. Use
+    /// [`try_enable_cuda_acceleration`] to detect this.
+    ///
+    /// [`try_enable_cuda_acceleration`]: struct.Compressor.html#method.try_enable_cuda_acceleration
     #[inline]
     pub fn enable_cuda_acceleration<B: Into<NvttBoolean>>(&mut self, enable: B) -> &mut Self {
         unsafe {
-            nvttEnableCudaAcceleration(self.0.as_ptr(), enable.into());
+            nvttEnableCudaAcceleration(self.compressor.as_ptr(), enable.into());
         }
         self
     }
 
+    /// Enable gpu compression using the `cuda` api, and report whether it could be
+    /// enabled. Unlike [`enable_cuda_acceleration`], this does not silently fall back
+    /// to cpu compression.
+    ///
+    /// # Errors
+    ///
+    /// If `cuda` is not available on this platform, then this method will fail with
+    /// [`Error::CudaUnavailable`], and cuda acceleration is left disabled.
+    ///
+    /// [`enable_cuda_acceleration`]: struct.Compressor.html#method.enable_cuda_acceleration
+    /// [`Error::CudaUnavailable`]: enum.Error.html#variant.CudaUnavailable
+    pub fn try_enable_cuda_acceleration(&mut self) -> Result<&mut Self, Error> {
+        if self
+            .enable_cuda_acceleration(true)
+            .is_cuda_acceleration_enabled()
+        {
+            Ok(self)
+        } else {
+            Err(Error::CudaUnavailable)
+        }
+    }
+
+    /// Returns `true` if gpu compression using the `cuda` api is available on this
+    /// platform.
+    ///
+    /// # Notes
+    ///
+    /// nvtt only reports whether `cuda` is available after it has been enabled on a
+    /// compressor, so this creates a temporary `Compressor` to check.
+    pub fn cuda_available() -> bool {
+        Compressor::new()
+            .map(|mut compressor| {
+                compressor
+                    .enable_cuda_acceleration(true)
+                    .is_cuda_acceleration_enabled()
+            })
+            .unwrap_or(false)
+    }
+
+    /// Get the encoder which this `Compressor` uses for the `compression_options`,
+    /// with the versions of nvtt and this crate.
+    pub fn encoder_identity(&self, compression_options: &CompressionOptions) -> EncoderIdentity {
+        EncoderIdentity {
+            encoder: Encoder::for_format(
+                compression_options.format(),
+                self.is_cuda_acceleration_enabled(),
+            ),
+            nvtt_version: linked_version(),
+            crate_version: env!("CARGO_PKG_VERSION").to_owned(),
+        }
+    }
+
     /// Returns `true` if cuda acceleration has been enabled. Otherwise, returns
     /// false.
     #[inline]
     pub fn is_cuda_acceleration_enabled(&self) -> bool {
-        unsafe { nvttIsCudaAccelerationEnabled(self.0.as_ptr()).into() }
+        unsafe { nvttIsCudaAccelerationEnabled(self.compressor.as_ptr()).into() }
     }
 
     /// Perform the compression.
-    pub fn compress(
+    ///
+    /// # Errors
+    ///
+    /// If the compression fails, then the returned [`CompressError`] describes which
+    /// stage of the compression failed, the output path, and the face and mipmap level
+    /// which was being processed.
+    ///
+    /// If the input is a cube map, and any face has no data for its top mipmap level,
+    /// then this method fails with `Error::InvalidInput` before compressing, and the
+    /// error has an [`InputDiagnosis::MissingCubeFaces`] diagnosis.
+    ///
+    /// If either channel of the `compress_options` uses `Dither::Ordered`, or a target
+    /// PSNR is set, then this method fails with `Error::UnsupportedFeature`, because
+    /// nvtt cannot apply ordered dithering or measure the compressed images. Use
+    /// [`Context::compress_mipmaps`] to compress these textures instead.
+    ///
+    /// The type of the output depends on the [`Destination`] of the `output_options`:
+    /// untyped options return a [`CompressionOutput`], options created with
+    /// [`OutputOptions::into_file`] return `()`, and options created with
+    /// [`OutputOptions::into_buffer`] return a [`CompressedTexture`].
+    ///
+    /// [`CompressError`]: struct.CompressError.html
+    /// [`InputDiagnosis::MissingCubeFaces`]: enum.InputDiagnosis.html#variant.MissingCubeFaces
+    /// [`Context::compress_mipmaps`]: struct.Context.html#method.compress_mipmaps
+    /// [`Destination`]: trait.Destination.html
+    /// [`CompressionOutput`]: enum.CompressionOutput.html
+    /// [`OutputOptions::into_file`]: struct.OutputOptions.html#method.into_file
+    /// [`OutputOptions::into_buffer`]: struct.OutputOptions.html#method.into_buffer
+    /// [`CompressedTexture`]: struct.CompressedTexture.html
+    #[inline]
+    pub fn compress<D: Destination>(
         &self,
         compress_options: &CompressionOptions,
         input_options: &InputOptions,
-        output_options: &OutputOptions,
-    ) -> Result<CompressionOutput, Error> {
+        output_options: &OutputOptions<D>,
+    ) -> Result<D::Output, CompressError> {
+        check_input_settings(compress_options, output_options)?;
+        self.compress_output(
+            compress_options,
+            input_options,
+            output_options,
+            Encode::Input,
+        )
+        .map(D::from_output)
+    }
+
+    /// Compress the texture selected by `encode`. The `input_options` describe the
+    /// texture, and are validated before it is compressed.
+    pub(crate) fn compress_output<D>(
+        &self,
+        compress_options: &CompressionOptions,
+        input_options: &InputOptions,
+        output_options: &OutputOptions<D>,
+        encode: Encode<'_>,
+    ) -> Result<CompressionOutput, CompressError> {
         thread_local! {
-            static ERR: Cell<NvttError> = Cell::new(0);
-            static OUT_DATA: RefCell<Vec<u8>> = RefCell::new(vec![]);
-            static HEIGHT: Cell<usize> = Cell::new(0);
-            static WIDTH: Cell<usize> = Cell::new(0);
-            static DEPTH: Cell<usize> = Cell::new(0);
-            static FACE: Cell<usize> = Cell::new(0);
-            static MIPLEVEL: Cell<usize> = Cell::new(0);
+            static ERR: Cell<NvttError> = const { Cell::new(0) };
+            static ERRORS: RefCell<Vec<Error>> = const { RefCell::new(Vec::new()) };
+            static HEIGHT: Cell<usize> = const { Cell::new(0) };
+            static WIDTH: Cell<usize> = const { Cell::new(0) };
+            static DEPTH: Cell<usize> = const { Cell::new(0) };
+            static FACE: Cell<usize> = const { Cell::new(0) };
+            static MIPLEVEL: Cell<usize> = const { Cell::new(0) };
+            static CURRENT_IMAGE: Cell<Option<(usize, usize)>> = const { Cell::new(None) };
+            #[cfg(feature = "memmap-output")]
+            static MAPPED_OUT: RefCell<Option<MappedOutput>> = const { RefCell::new(None) };
+            static FILE_OUT: RefCell<Option<BufWriter<File>>> = const { RefCell::new(None) };
+            static PROGRESS: RefCell<Option<ProgressState>> = const { RefCell::new(None) };
+            static REPORT: RefCell<Option<ReportState>> = const { RefCell::new(None) };
+        }
+
+        fn report_progress(update: impl FnOnce(&mut OutputProgress)) {
+            let report = PROGRESS.with(|p| {
+                p.borrow_mut().as_mut().map(|state| {
+                    update(&mut state.progress);
+                    state.progress.elapsed = state.start.elapsed();
+                    (state.handler, state.progress)
+                })
+            });
+
+            if let Some((handler, progress)) = report {
+                // @SAFETY: See `ProgressState::handler`.
+                unsafe { ((*handler).0)(&progress) }
+            }
+        }
+
+        fn record_error(err: NvttError) {
+            ERR.with(|e| e.set(err));
+            ERRORS.with(|errors| errors.borrow_mut().push(Error::from(err)));
         }
 
         extern "C" fn err_callback(err: NvttError) {
             error!(
                 "nvtt: Encountered an error while compressing\nCaused by: {err}",
-                err = Error::try_from(err).unwrap_or(Error::Unknown)
+                err = Error::from(err)
             );
-            ERR.with(|e| e.set(err));
+            record_error(err);
         }
 
         extern "C" fn output_begin_callback(
@@ -722,14 +2220,50 @@ impl Compressor {
             trace!("Beginning texture compression with image size {sz} ({w} x {h} x {d}), face = {fc}, mip = {mp}",
                 sz = size, w = width, h = height, d = depth, fc = face, mp = miplevel);
 
-            OUT_DATA.with(|d| d.borrow_mut().reserve(size as _));
-
             ERR.with(|e| e.set(0));
             WIDTH.with(|w| w.set(width as _));
             HEIGHT.with(|h| h.set(height as _));
             DEPTH.with(|d| d.set(depth as _));
             FACE.with(|f| f.set(face as _));
             MIPLEVEL.with(|ml| ml.set(miplevel as _));
+            CURRENT_IMAGE.with(|c| c.set(Some((face as _, miplevel as _))));
+
+            report_progress(|p| {
+                p.face = face as _;
+                p.miplevel = miplevel as _;
+                p.image_size = size as _;
+                p.image_bytes_written = 0;
+            });
+
+            REPORT.with(|r| {
+                if let Some(state) = r.borrow_mut().as_mut() {
+                    state.finish_image();
+                    state.image_start = Some(Instant::now());
+                    state.mips.push(MipReport {
+                        face: face as _,
+                        miplevel: miplevel as _,
+                        width: width as _,
+                        height: height as _,
+                        depth: depth as _,
+                        size: 0,
+                        elapsed: Duration::default(),
+                        metrics: None,
+                    });
+                }
+            });
+            REPORT_IMAGES.with(|i| {
+                if let Some(images) = i.borrow_mut().as_mut() {
+                    images.push(vec![]);
+                }
+            });
+        }
+
+        extern "C" fn output_end_callback() {
+            REPORT.with(|r| {
+                if let Some(state) = r.borrow_mut().as_mut() {
+                    state.finish_image();
+                }
+            });
         }
 
         extern "C" fn output_callback(data_ptr: *const c_void, len: c_int) -> bool {
@@ -746,42 +2280,321 @@ impl Compressor {
             };
 
             let data = unsafe { slice::from_raw_parts(data_ptr as *const u8, len) };
+
+            report_progress(|p| {
+                p.image_bytes_written += len;
+                p.total_bytes_written += len;
+            });
+
+            REPORT.with(|r| {
+                if let Some(mip) = r.borrow_mut().as_mut().and_then(|s| s.mips.last_mut()) {
+                    mip.size += len;
+                }
+            });
+            REPORT_IMAGES.with(|i| {
+                if let Some(image) = i.borrow_mut().as_mut().and_then(|i| i.last_mut()) {
+                    image.extend_from_slice(data);
+                }
+            });
+
+            #[cfg(feature = "memmap-output")]
+            {
+                let written = MAPPED_OUT.with(|m| m.borrow_mut().as_mut().map(|m| m.write(data)));
+                match written {
+                    Some(Ok(())) => return true,
+                    Some(Err(e)) => {
+                        error!(
+                            "Could not write texture data to mapped file\nCaused by: {e}",
+                            e = e
+                        );
+                        record_error(NvttError_NVTT_Error_FileWrite);
+                        return false;
+                    }
+                    None => {}
+                }
+            }
+
+            let written = FILE_OUT.with(|f| f.borrow_mut().as_mut().map(|f| f.write_all(data)));
+            match written {
+                Some(Ok(())) => return true,
+                Some(Err(e)) => {
+                    error!(
+                        "Could not write texture data to file\nCaused by: {e}",
+                        e = e
+                    );
+                    record_error(NvttError_NVTT_Error_FileWrite);
+                    return false;
+                }
+                None => {}
+            }
+
             OUT_DATA.with(|d| d.borrow_mut().extend_from_slice(data));
             true
         }
 
-        OUT_DATA.with(|d| d.borrow_mut().clear());
+        // nvtt compresses cube maps with missing faces, leaving the faces black, so
+        // they are rejected before compressing. A `Surface` is always complete, and
+        // its images are not set on the `input_options`.
+        let diagnose = || match encode {
+            Encode::Input => input_options.diagnose(),
+            Encode::Surface { .. } => None,
+        };
+        if let Some(diagnosis @ InputDiagnosis::MissingCubeFaces { .. }) = diagnose() {
+            return Err(CompressError {
+                diagnosis: Some(diagnosis),
+                ..CompressError::new(
+                    Error::InvalidInput,
+                    CompressionStage::InputValidation,
+                    output_options,
+                )
+            });
+        }
+
+        if output_options.cube_array {
+            let is_cube_array = match input_options.texture_layout {
+                Some(TextureLayout::Array { array_length, .. }) => array_length % 6 == 0,
+                _ => false,
+            };
+            if !is_cube_array || output_options.container != Container::Dds10 {
+                return Err(CompressError::new(
+                    Error::InvalidInput,
+                    CompressionStage::InputValidation,
+                    output_options,
+                ));
+            }
+        }
+
+        // Data before `OUT_START` belongs to the caller of `compress_into`.
+        OUT_DATA.with(|d| d.borrow_mut().truncate(OUT_START.with(Cell::get)));
+        CURRENT_IMAGE.with(|c| c.set(None));
+        ERRORS.with(|errors| errors.borrow_mut().clear());
+        self.errors.borrow_mut().clear();
+
+        let fail = |error, stage| CompressError {
+            image: CURRENT_IMAGE.with(Cell::get),
+            ..CompressError::new(error, stage, output_options)
+        };
+
+        let (major, minor) = compress_options.format().required_version();
+        if !version_at_least(major, minor) {
+            error!(
+                "nvtt: The format {f:?} requires nvtt {maj}.{min}, but nvtt {v} is linked",
+                f = compress_options.format(),
+                maj = major,
+                min = minor,
+                v = linked_version()
+            );
+            return Err(fail(
+                Error::UnsupportedFeature,
+                CompressionStage::InputValidation,
+            ));
+        }
+
+        // Held until the output has been written, so that compressions which share the
+        // `output_options` do not change its nvtt handlers or write its file at once.
+        let _output_guard = output_options
+            .compress_lock
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let _input_guard = input_options
+            .compress_lock
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let _default_gamma = input_options.default_gamma_for(compress_options.format());
+
+        let estimate_size = || match encode {
+            Encode::Input => self.estimate_input_size(input_options, compress_options),
+            Encode::Surface {
+                context, surface, ..
+            } => context.estimate_size(surface, compress_options),
+        };
+
+        // The output is always written by the output callbacks, so that the face and
+        // mipmap level which fails are known. A `Surface` without a header continues
+        // the texture which is already in the output file.
+        let append = matches!(encode, Encode::Surface { .. }) && !output_options.write_header;
+
+        #[cfg(feature = "memmap-output")]
+        let mapped_path = output_options.mapped_path().filter(|_| !append);
+        #[cfg(not(feature = "memmap-output"))]
+        let mapped_path: Option<&Path> = None;
+
+        #[cfg(feature = "memmap-output")]
+        {
+            if let Some(path) = mapped_path {
+                let capacity = estimate_size() + HEADER_CAPACITY;
+                let mapped = MappedOutput::create(path, capacity).map_err(|e| {
+                    error!(
+                        "Could not map output file {p}\nCaused by: {e}",
+                        p = path.display(),
+                        e = e
+                    );
+                    fail(Error::FileOpen, CompressionStage::Output)
+                })?;
+                MAPPED_OUT.with(|m| *m.borrow_mut() = Some(mapped));
+            }
+        }
+
+        let buffered_path = output_options
+            .out_path
+            .as_deref()
+            .filter(|_| mapped_path.is_none());
+        if let Some(path) = buffered_path {
+            let file = OpenOptions::new()
+                .write(true)
+                .create(true)
+                .append(append)
+                .truncate(!append)
+                .open(path);
+            let file = file.map_err(|e| {
+                error!(
+                    "Could not create output file {p}\nCaused by: {e}",
+                    p = path.display(),
+                    e = e
+                );
+                fail(Error::FileOpen, CompressionStage::Output)
+            })?;
+            FILE_OUT.with(|f| *f.borrow_mut() = Some(BufWriter::new(file)));
+        } else if output_options.out_path.is_none() {
+            // The whole texture is allocated up front, so that the buffer is not
+            // reallocated and copied as each image is appended to it.
+            let capacity = estimate_size() + HEADER_CAPACITY;
+            OUT_DATA.with(|d| d.borrow_mut().reserve_exact(capacity));
+        }
+
+        PROGRESS.with(|p| {
+            *p.borrow_mut() =
+                output_options
+                    .progress_handler
+                    .as_ref()
+                    .map(|handler| ProgressState {
+                        handler,
+                        start: Instant::now(),
+                        progress: OutputProgress {
+                            face: 0,
+                            miplevel: 0,
+                            image_bytes_written: 0,
+                            image_size: 0,
+                            total_bytes_written: 0,
+                            elapsed: Duration::default(),
+                        },
+                    })
+        });
+        REPORT.with(|r| {
+            *r.borrow_mut() = Some(ReportState {
+                start: Instant::now(),
+                image_start: None,
+                mips: vec![],
+            })
+            .filter(|_| output_options.record_report);
+        });
 
         let res = unsafe {
             let out_opts_ptr = output_options.out_opts.as_ptr();
 
             nvttSetOutputOptionsErrorHandler(out_opts_ptr, Some(err_callback));
+            nvttSetOutputOptionsOutputHandler(
+                out_opts_ptr,
+                Some(output_begin_callback), // begin image
+                Some(output_callback),
+                Some(output_end_callback),
+            );
 
-            if !output_options.write_to_file {
-                nvttSetOutputOptionsOutputHandler(
+            match encode {
+                Encode::Input => nvttCompress(
+                    self.compressor.as_ptr(),
+                    input_options.opts.as_ptr(),
+                    compress_options.opts.as_ptr(),
                     out_opts_ptr,
-                    Some(output_begin_callback), // begin image
-                    Some(output_callback),
-                    None, // end image
-                );
+                ),
+                Encode::Surface {
+                    context,
+                    surface,
+                    face,
+                    mipmap,
+                } => context.compress(
+                    surface,
+                    face,
+                    mipmap,
+                    compress_options,
+                    out_opts_ptr,
+                    output_options.write_header,
+                ),
             }
-
-            nvttCompress(
-                self.0.as_ptr(),
-                input_options.0.as_ptr(),
-                compress_options.0.as_ptr(),
-                output_options.out_opts.as_ptr(),
-            )
         };
 
+        PROGRESS.with(|p| p.borrow_mut().take());
+        if let Some(mut state) = REPORT.with(|r| r.borrow_mut().take()) {
+            state.finish_image();
+            *output_options
+                .report
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner) = Some(CompressionReport {
+                mips: state.mips,
+                elapsed: state.start.elapsed(),
+            });
+        }
+        *self.errors.borrow_mut() = ERRORS.with(|errors| errors.replace(vec![]));
+
+        if let Some(mut file) = FILE_OUT.with(|f| f.borrow_mut().take()) {
+            let flushed = file.flush();
+            if res == NvttBoolean::NVTT_True {
+                flushed.map_err(|e| {
+                    error!(
+                        "Could not finish writing output file\nCaused by: {e}",
+                        e = e
+                    );
+                    fail(Error::FileWrite, CompressionStage::Output)
+                })?;
+            }
+        }
+
+        #[cfg(feature = "memmap-output")]
+        {
+            if let Some(mapped) = MAPPED_OUT.with(|m| m.borrow_mut().take()) {
+                let finished = mapped.finish();
+                if res == NvttBoolean::NVTT_True {
+                    finished.map_err(|e| {
+                        error!(
+                            "Could not finish writing mapped file\nCaused by: {e}",
+                            e = e
+                        );
+                        fail(Error::FileWrite, CompressionStage::Output)
+                    })?;
+                }
+            }
+        }
+
         if res != NvttBoolean::NVTT_True {
             let mut err = 0;
             ERR.with(|e| err = e.get());
-            Err(Error::try_from(err).unwrap_or(Error::Unknown))
+            let error = Error::from(err);
+            let stage = match error {
+                Error::FileOpen | Error::FileWrite => CompressionStage::Output,
+                Error::InvalidInput => CompressionStage::InputValidation,
+                _ => CompressionStage::Encoding,
+            };
+            let diagnosis = match error {
+                Error::InvalidInput => diagnose(),
+                _ => None,
+            };
+            Err(CompressError {
+                diagnosis,
+                ..fail(error, stage)
+            })
         } else {
-            if !output_options.write_to_file {
+            if output_options.out_path.is_none() {
+                let mut data = OUT_DATA.with(|d| d.replace(vec![]));
+                if output_options.cube_array && output_options.write_header {
+                    let start = OUT_START.with(Cell::get);
+                    if let Err(e) = mark_cube_array(&mut data[start..]) {
+                        // The buffer is handed back to the caller of `compress_into`.
+                        OUT_DATA.with(|d| *d.borrow_mut() = data);
+                        return Err(fail(e, CompressionStage::Output));
+                    }
+                }
                 Ok(CompressionOutput::Memory {
-                    data: OUT_DATA.with(|d| d.replace(vec![])),
+                    data,
                     width: WIDTH.with(|w| w.get()),
                     height: HEIGHT.with(|h| h.get()),
                     depth: DEPTH.with(|d| d.get()),
@@ -789,42 +2602,353 @@ impl Compressor {
                     miplevel: MIPLEVEL.with(|ml| ml.get()),
                 })
             } else {
+                if let (true, true, Some(path)) = (
+                    output_options.cube_array,
+                    output_options.write_header,
+                    output_options.out_path.as_ref(),
+                ) {
+                    mark_cube_array_file(path).map_err(|e| fail(e, CompressionStage::Output))?;
+                }
+
+                #[cfg(feature = "serde-serialize")]
+                {
+                    if output_options.write_metadata {
+                        write_metadata_sidecar(
+                            self.encoder_identity(compress_options),
+                            compress_options,
+                            input_options,
+                            output_options,
+                        )
+                        .map_err(|e| fail(e, CompressionStage::Metadata))?;
+                    }
+                }
                 Ok(CompressionOutput::File)
             }
         }
     }
 
-    /// Estimate the final compressed size of the output texture.
-    #[inline]
-    pub fn estimate_size(
+    /// Perform the compression, writing any output which is saved into memory into
+    /// the buffers of `scratch` instead of a newly allocated `Vec`.
+    ///
+    /// Returns the bytes of the texture if it was saved into memory, or `None` if it
+    /// was saved into a file. The bytes are valid until `scratch` is next used. Once
+    /// the `scratch` buffers have grown to fit the largest texture, compressing into
+    /// memory does not allocate for the output.
+    ///
+    /// # Errors
+    ///
+    /// See [`compress`].
+    ///
+    /// [`compress`]: struct.Compressor.html#method.compress
+    pub fn compress_with_scratch<'s, D>(
         &self,
+        compress_options: &CompressionOptions,
         input_options: &InputOptions,
-        compression_options: &CompressionOptions,
-    ) -> usize {
-        unsafe {
-            nvttEstimateSize(
-                self.0.as_ptr(),
-                input_options.0.as_ptr(),
-                compression_options.0.as_ptr(),
-            ) as usize
+        output_options: &OutputOptions<D>,
+        scratch: &'s mut CompressScratch,
+    ) -> Result<Option<&'s [u8]>, CompressError> {
+        scratch.output.clear();
+        let written = self.compress_into(
+            compress_options,
+            input_options,
+            output_options,
+            &mut scratch.output,
+        )?;
+        match written {
+            Some(_) => Ok(Some(&scratch.output[..])),
+            None => Ok(None),
         }
     }
-}
 
-impl Drop for Compressor {
-    #[inline]
+    /// Perform the compression, appending any output which is saved into memory to the
+    /// end of `buffer` instead of a newly allocated `Vec`. This avoids copying the
+    /// texture into storage which is owned by the caller, such as a pooled buffer.
+    ///
+    /// Returns the number of bytes which were appended if the texture was saved into
+    /// memory, or `None` if it was saved into a file. If the compression fails, then
+    /// `buffer` is restored to its original length.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use nvtt_rs::{CompressError, CompressionOptions, Compressor, InputOptions, OutputOptions};
+    /// # fn compress(compressor: &Compressor, compression_options: &CompressionOptions, input_options: &InputOptions, output_options: &OutputOptions) -> Result<(), CompressError> {
+    /// let mut pool = Vec::with_capacity(64 * 1024 * 1024);
+    /// let start = pool.len();
+    /// if let Some(len) = compressor.compress_into(
+    ///     &compression_options,
+    ///     &input_options,
+    ///     &output_options,
+    ///     &mut pool,
+    /// )? {
+    ///     let texture = &pool[start..start + len];
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// See [`compress`].
+    ///
+    /// [`compress`]: struct.Compressor.html#method.compress
+    pub fn compress_into<D>(
+        &self,
+        compress_options: &CompressionOptions,
+        input_options: &InputOptions,
+        output_options: &OutputOptions<D>,
+        buffer: &mut Vec<u8>,
+    ) -> Result<Option<usize>, CompressError> {
+        check_input_settings(compress_options, output_options)?;
+
+        // `compress` writes after the existing contents of the buffer, and hands it
+        // back in the output.
+        let start = buffer.len();
+        OUT_START.with(|s| s.set(start));
+        OUT_DATA.with(|d| mem::swap(&mut *d.borrow_mut(), buffer));
+
+        let result = self.compress_output(
+            compress_options,
+            input_options,
+            output_options,
+            Encode::Input,
+        );
+        OUT_START.with(|s| s.set(0));
+        match result {
+            Ok(CompressionOutput::Memory { data, .. }) => {
+                *buffer = data;
+                Ok(Some(buffer.len() - start))
+            }
+            result => {
+                *buffer = OUT_DATA.with(|d| d.replace(vec![]));
+                buffer.truncate(start);
+                result.map(|_| None)
+            }
+        }
+    }
+
+    /// Take every error which was reported during the last call to [`compress`], in
+    /// the order in which they occurred. Unlike the error returned from [`compress`],
+    /// this includes errors from every face and mipmap level of the texture.
+    ///
+    /// [`compress`]: struct.Compressor.html#method.compress
+    #[inline]
+    pub fn take_errors(&self) -> Vec<Error> {
+        self.errors.take()
+    }
+
+    /// Estimate the final compressed size of the output texture.
+    pub fn estimate_size(
+        &self,
+        input_options: &InputOptions,
+        compression_options: &CompressionOptions,
+    ) -> usize {
+        // The gamma of the `input_options` is changed while the size is estimated, in
+        // the same way as when they are compressed.
+        let _input_guard = input_options
+            .compress_lock
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let _default_gamma = input_options.default_gamma_for(compression_options.format());
+        self.estimate_input_size(input_options, compression_options)
+    }
+
+    /// Estimate the final compressed size of the output texture, while the
+    /// `compress_lock` of the `input_options` is held.
+    fn estimate_input_size(
+        &self,
+        input_options: &InputOptions,
+        compression_options: &CompressionOptions,
+    ) -> usize {
+        unsafe {
+            nvttEstimateSize(
+                self.compressor.as_ptr(),
+                input_options.opts.as_ptr(),
+                compression_options.opts.as_ptr(),
+            ) as usize
+        }
+    }
+}
+
+#[cfg(feature = "nvtt")]
+impl fmt::Debug for Compressor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Compressor")
+            .field("cuda_acceleration", &self.is_cuda_acceleration_enabled())
+            .field("errors", &self.errors.borrow())
+            .finish()
+    }
+}
+
+#[cfg(feature = "nvtt")]
+impl Default for Compressor {
+    /// Create a new `Compressor`. See [`Compressor::new`].
+    ///
+    /// # Panics
+    ///
+    /// This will panic if nvtt fails to allocate the `Compressor`.
+    ///
+    /// [`Compressor::new`]: struct.Compressor.html#method.new
+    #[inline]
+    fn default() -> Self {
+        Self::new().expect("could not create the Compressor")
+    }
+}
+
+#[cfg(feature = "nvtt")]
+impl Drop for Compressor {
+    #[inline]
     fn drop(&mut self) {
         unsafe {
-            nvttDestroyCompressor(self.0.as_ptr());
+            nvttDestroyCompressor(self.compressor.as_ptr());
+        }
+    }
+}
+
+/// Check that the `compress_options` only use settings which nvtt can apply to the
+/// images of an `InputOptions`. Settings which only a `Context` can apply fail with
+/// `Error::UnsupportedFeature`.
+#[cfg(feature = "nvtt")]
+fn check_input_settings<D>(
+    compress_options: &CompressionOptions,
+    output_options: &OutputOptions<D>,
+) -> Result<(), CompressError> {
+    let (color_dither, alpha_dither) = compress_options.dithering();
+    if color_dither == Dither::Ordered || alpha_dither == Dither::Ordered {
+        error!("nvtt: Ordered dithering can only be applied when compressing with a `Context`");
+        return Err(CompressError::new(
+            Error::UnsupportedFeature,
+            CompressionStage::InputValidation,
+            output_options,
+        ));
+    }
+    if compress_options.target_psnr().is_some() {
+        error!("nvtt: A target PSNR can only be reached when compressing with a `Context`");
+        return Err(CompressError::new(
+            Error::UnsupportedFeature,
+            CompressionStage::InputValidation,
+            output_options,
+        ));
+    }
+    Ok(())
+}
+
+/// Selects what `Compressor::compress_output` compresses.
+#[cfg(feature = "nvtt")]
+#[derive(Clone, Copy)]
+pub(crate) enum Encode<'a> {
+    /// Compress the images set on the `InputOptions`.
+    Input,
+    /// Compress a single `surface` as the `face` and `mipmap` level of a texture, using
+    /// the nvtt context api. The `InputOptions` only describe the texture.
+    Surface {
+        context: &'a NativeContext,
+        surface: &'a NativeSurface,
+        face: CubeFace,
+        mipmap: c_int,
+    },
+}
+
+#[cfg(feature = "nvtt")]
+thread_local! {
+    /// The buffer which textures are written into by `Compressor::compress` when they
+    /// are saved into memory.
+    static OUT_DATA: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+    /// The length of the data which was in `OUT_DATA` before the compression started.
+    static OUT_START: Cell<usize> = const { Cell::new(0) };
+    /// If this is `Some`, then `Compressor::compress` appends a copy of each compressed
+    /// image to it, so that a `Context` can measure the error of each image.
+    pub(crate) static REPORT_IMAGES: RefCell<Option<Vec<Vec<u8>>>> = const { RefCell::new(None) };
+}
+
+/// Reusable buffers for compressing many textures without allocating for each one.
+///
+/// The buffers grow to fit the largest texture which they are used for, and are
+/// never shrunk.
+///
+/// # Example
+///
+/// ```no_run
+/// # fn main() -> Result<(), nvtt_rs::Error> {
+/// use nvtt_rs::{
+///     CompressScratch, CompressionOptions, Compressor, CubeFace, InputOptions, MipLevel,
+///     OutputOptions, Surface,
+/// };
+///
+/// # let surfaces: Vec<Surface> = vec![];
+/// let compressor = Compressor::new()?;
+/// let compression_options = CompressionOptions::new()?;
+/// let output_options = OutputOptions::new()?;
+/// let mut scratch = CompressScratch::new();
+///
+/// for surface in &surfaces {
+///     let mut input_options = InputOptions::new()?;
+///     input_options
+///         .set_texture_layout(surface.texture_layout())?
+///         .set_surface_with_scratch(surface, CubeFace::default(), MipLevel(0), &mut scratch)?;
+///
+///     let data = compressor
+///         .compress_with_scratch(&compression_options, &input_options, &output_options, &mut scratch)
+///         .map_err(|e| e.error())?;
+///     // Use `data` before the next texture is compressed.
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "nvtt")]
+#[derive(Clone, Debug, Default)]
+pub struct CompressScratch {
+    output: Vec<u8>,
+    pub(crate) input: Vec<u8>,
+}
+
+#[cfg(feature = "nvtt")]
+impl CompressScratch {
+    /// Create a new `CompressScratch` with empty buffers.
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a new `CompressScratch` which can hold a compressed texture of
+    /// `output_len` bytes without allocating. The size of a texture can be found
+    /// with [`Compressor::estimate_size`].
+    ///
+    /// [`Compressor::estimate_size`]: struct.Compressor.html#method.estimate_size
+    #[inline]
+    pub fn with_capacity(output_len: usize) -> Self {
+        Self {
+            output: Vec::with_capacity(output_len),
+            input: vec![],
         }
     }
+
+    /// Get the bytes of the last texture which was compressed into memory.
+    #[inline]
+    pub fn output(&self) -> &[u8] {
+        &self.output
+    }
+
+    /// Get the total capacity of the buffers in bytes.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.output.capacity() + self.input.capacity()
+    }
 }
 
 // @SAFETY: A `Compressor` cannot be copied or unsafely mutated in a shared way.
 // @NOTE: Not `Sync` because `Compressor::compress` could otherwise thrash thread local vars.
+#[cfg(feature = "nvtt")]
 unsafe impl Send for Compressor {}
 
 /// Communicates the output of a compressed texture.
+///
+/// With the [`serde-serialize`] feature, the `data` of a texture saved into memory is
+/// serialized as a base64 string in human readable formats, such as json.
+///
+/// [`serde-serialize`]: index.html#serde-serialize
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "serde-serialize", derive(Deserialize, Serialize))]
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub enum CompressionOutput {
     /// The texture was saved into the file specified on the `OutputOptions`.
@@ -832,6 +2956,7 @@ pub enum CompressionOutput {
     /// The texture was saved into memory.
     Memory {
         /// The bytes of the image.
+        #[cfg_attr(feature = "serde-serialize", serde(with = "base64_bytes"))]
         data: Vec<u8>,
         /// The width of the texture in pixels.
         width: usize,
@@ -846,19 +2971,566 @@ pub enum CompressionOutput {
     },
 }
 
+/// A texture which was compressed into memory by an [`OutputOptions<ToBuffer>`].
+///
+/// [`OutputOptions<ToBuffer>`]: struct.OutputOptions.html#method.into_buffer
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "serde-serialize", derive(Deserialize, Serialize))]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct CompressedTexture {
+    /// The bytes of the image.
+    #[cfg_attr(feature = "serde-serialize", serde(with = "base64_bytes"))]
+    pub data: Vec<u8>,
+    /// The width of the texture in pixels.
+    pub width: usize,
+    /// The height of the texture in pixels.
+    pub height: usize,
+    /// The depth of the texture.
+    pub depth: usize,
+    /// The face of the texture.
+    pub face: usize,
+    /// The mipmap level of the texture.
+    pub miplevel: usize,
+}
+
+#[cfg(feature = "std")]
+impl From<CompressedTexture> for CompressionOutput {
+    #[inline]
+    fn from(texture: CompressedTexture) -> Self {
+        CompressionOutput::Memory {
+            data: texture.data,
+            width: texture.width,
+            height: texture.height,
+            depth: texture.depth,
+            face: texture.face,
+            miplevel: texture.miplevel,
+        }
+    }
+}
+
+/// Describes the texture which will be produced from a set of options. This is
+/// written into the metadata sidecar file if [`OutputOptions::set_write_metadata`]
+/// is enabled.
+///
+/// [`OutputOptions::set_write_metadata`]: struct.OutputOptions.html#method.set_write_metadata
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "serde-serialize", derive(Deserialize, Serialize))]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct TextureMetadata {
+    /// The type of the texture.
+    pub texture_type: TextureType,
+    /// The dimensions of the top mipmap level of the texture.
+    #[cfg_attr(feature = "serde-serialize", serde(flatten))]
+    pub extent: Extent3d,
+    /// The number of textures in the texture array.
+    pub array_length: usize,
+    /// The number of mipmap levels in the texture.
+    pub mip_count: usize,
+    /// The output format of the texture.
+    pub format: Format,
+    /// The quality level used to compress the texture.
+    pub quality: Quality,
+    /// The container the texture is stored in.
+    pub container: Container,
+    /// Whether the texture is flagged as being in the sRGB colorspace.
+    pub srgb: bool,
+    /// A hex encoded 64 bit FNV-1a hash of the source data, if any was set
+    /// on the `InputOptions`.
+    pub source_hash: Option<String>,
+    /// The dimensions of each mipmap level in the texture, starting from the
+    /// top level.
+    #[cfg_attr(feature = "serde-serialize", serde(default))]
+    pub mip_levels: Vec<Extent3d>,
+    /// How to unpack the normals of the texture in a shader. This is set for the
+    /// `Bc3n`, `Dxt5n` and `Dxt1n` formats, and for `Bc5` textures which are marked
+    /// as normal maps on the `InputOptions`.
+    #[cfg_attr(feature = "serde-serialize", serde(default))]
+    pub normal_decode: Option<NormalDecode>,
+    /// The encoder which produced the texture. This is set in the metadata sidecar
+    /// written by [`Compressor::compress`], and is `None` otherwise.
+    ///
+    /// [`Compressor::compress`]: struct.Compressor.html#method.compress
+    #[cfg_attr(feature = "serde-serialize", serde(default))]
+    pub encoder: Option<EncoderIdentity>,
+}
+
+/// The encoder inside nvtt which compresses a texture to a [`Format`].
+///
+/// [`Format`]: enum.Format.html
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "serde-serialize", derive(Deserialize, Serialize))]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum Encoder {
+    /// The pixels are converted to the output pixel format without block compression.
+    Uncompressed,
+    /// The cpu `Bc1` to `Bc3` encoders, which are based on `squish`.
+    Squish,
+    /// The cpu `Bc4` and `Bc5` encoders.
+    Rgtc,
+    /// The `bc6h` library.
+    Bc6h,
+    /// The `bc7` library.
+    Bc7,
+    /// The `rg_etc1` library.
+    RgEtc1,
+    /// The `Etc2` and `Eac` encoders.
+    Etc2,
+    /// The `PVRTC1` encoder.
+    Pvrtc,
+    /// The cuda kernels, which are used for the `Dxt` formats when cuda acceleration
+    /// is enabled and available.
+    Cuda,
+}
+
+#[cfg(feature = "std")]
+impl Encoder {
+    /// Get the `Encoder` which nvtt uses to compress `format`, depending on whether
+    /// `cuda` acceleration is enabled.
+    pub fn for_format(format: Format, cuda: bool) -> Self {
+        match format {
+            Format::Bc1
+            | Format::Bc1a
+            | Format::Bc2
+            | Format::Bc3
+            | Format::Dxt1
+            | Format::Dxt1a
+            | Format::Dxt1n
+            | Format::Dxt3
+            | Format::Dxt5
+            | Format::Dxt5n
+            | Format::Ctx1
+                if cuda =>
+            {
+                Encoder::Cuda
+            }
+            Format::Bc4 | Format::Bc5 => Encoder::Rgtc,
+            Format::Bc6 => Encoder::Bc6h,
+            Format::Bc7 => Encoder::Bc7,
+            Format::Etc1 => Encoder::RgEtc1,
+            Format::Etc2R
+            | Format::Etc2Rg
+            | Format::Etc2Rgb
+            | Format::Etc2Rgba
+            | Format::Etc2Rgbm
+            | Format::Etc2RgbA1 => Encoder::Etc2,
+            Format::Pvr2BppRgb | Format::Pvr2BppRgba | Format::Pvr4BppRgb | Format::Pvr4BppRgba => {
+                Encoder::Pvrtc
+            }
+            Format::Rgb | Format::Rgba => Encoder::Uncompressed,
+            _ => Encoder::Squish,
+        }
+    }
+}
+
+/// Identifies the encoder and library versions which produced a texture, so that
+/// differences between the outputs of two machines can be traced.
+///
+/// None of the encoders in nvtt are randomized, so there are no seeds to record. The
+/// same input, options, encoder and nvtt version always produce the same output,
+/// although the cuda kernels do not produce the same output as the cpu encoders.
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "serde-serialize", derive(Deserialize, Serialize))]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct EncoderIdentity {
+    /// The encoder which compressed the texture.
+    pub encoder: Encoder,
+    /// The version of the nvtt library which was linked at runtime, encoded as by
+    /// [`linked_version`].
+    ///
+    /// [`linked_version`]: fn.linked_version.html
+    pub nvtt_version: u32,
+    /// The version of this crate.
+    pub crate_version: String,
+}
+
+#[cfg(feature = "nvtt")]
+impl TextureMetadata {
+    /// Create a new `TextureMetadata` from the given options. Returns `None` if
+    /// no `TextureLayout` has been set on the `input_options`, or the layout does not
+    /// contain any pixels.
+    pub fn new<D>(
+        compression_options: &CompressionOptions,
+        input_options: &InputOptions,
+        output_options: &OutputOptions<D>,
+    ) -> Option<Self> {
+        let layout = input_options.texture_layout()?;
+        let dims = layout.dimensions();
+        let extent = layout.extent()?;
+        let mip_count = layout.mip_count();
+        Some(Self {
+            texture_type: layout.texture_type(),
+            extent,
+            array_length: dims.array_length as usize,
+            mip_count: mip_count as usize,
+            format: compression_options.format(),
+            quality: compression_options.quality(),
+            container: output_options.container(),
+            srgb: output_options.srgb_flag(),
+            source_hash: input_options.source_hash.map(|h| format!("{:016x}", h)),
+            mip_levels: (0..mip_count)
+                .map(|level| extent.mip_level(MipLevel(level)))
+                .collect(),
+            normal_decode: compression_options.format().normal_decode().filter(|_| {
+                compression_options.format() != Format::Bc5
+                    || input_options.state.normal_map == Some(NvttBoolean::NVTT_True)
+            }),
+            encoder: None,
+        })
+    }
+}
+
+/// A compressed texture together with its [`TextureMetadata`], which can be stored in
+/// an asset database and reloaded later.
+///
+/// [`TextureMetadata`]: struct.TextureMetadata.html
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "serde-serialize", derive(Deserialize, Serialize))]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct TextureRecord {
+    /// The metadata of the texture.
+    pub metadata: TextureMetadata,
+    /// The compressed data of the texture.
+    pub data: TextureData,
+}
+
+#[cfg(feature = "nvtt")]
+impl TextureRecord {
+    /// Create a new `TextureRecord` from the `output` of a compression, which used the
+    /// given `output_options`. Textures which were saved into memory are embedded in
+    /// the record, and textures which were saved into a file are referenced by path.
+    ///
+    /// Returns `None` if the texture was saved into a file, and no path is set on the
+    /// `output_options`.
+    pub fn new(
+        metadata: TextureMetadata,
+        output: CompressionOutput,
+        output_options: &OutputOptions,
+    ) -> Option<Self> {
+        let data = match output {
+            CompressionOutput::Memory { data, .. } => TextureData::Embedded(data),
+            CompressionOutput::File => TextureData::External(output_options.out_path.clone()?),
+        };
+        Some(Self { metadata, data })
+    }
+}
+
+/// The compressed data of a [`TextureRecord`].
+///
+/// With the [`serde-serialize`] feature, embedded data is serialized as a base64
+/// string in human readable formats, such as json.
+///
+/// [`TextureRecord`]: struct.TextureRecord.html
+/// [`serde-serialize`]: index.html#serde-serialize
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "serde-serialize", derive(Deserialize, Serialize))]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum TextureData {
+    /// The contents of the compressed texture file.
+    Embedded(#[cfg_attr(feature = "serde-serialize", serde(with = "base64_bytes"))] Vec<u8>),
+    /// The path of the compressed texture file.
+    External(PathBuf),
+}
+
+#[cfg(feature = "std")]
+impl TextureData {
+    /// Get the contents of the compressed texture file, reading it from disk if the
+    /// data is external.
+    ///
+    /// # Errors
+    ///
+    /// If the external file cannot be read, then this method will fail with
+    /// [`Error::FileOpen`].
+    ///
+    /// [`Error::FileOpen`]: enum.Error.html#variant.FileOpen
+    pub fn load(&self) -> Result<std::borrow::Cow<'_, [u8]>, Error> {
+        match *self {
+            TextureData::Embedded(ref data) => Ok(data.into()),
+            TextureData::External(ref path) => std::fs::read(path).map(Into::into).map_err(|e| {
+                error!(
+                    "Could not read texture {p}\nCaused by: {e}",
+                    p = path.display(),
+                    e = e
+                );
+                Error::FileOpen
+            }),
+        }
+    }
+}
+
+/// Serializes byte buffers as base64 strings in human readable formats, and as raw
+/// bytes otherwise.
+#[cfg(feature = "serde-serialize")]
+mod base64_bytes {
+    use serde::{
+        de::{Error, SeqAccess, Visitor},
+        Deserializer, Serializer,
+    };
+    use std::fmt;
+
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    pub(crate) fn serialize<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&encode(data))
+        } else {
+            serializer.serialize_bytes(data)
+        }
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<u8>, D::Error> {
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(BytesVisitor)
+        } else {
+            deserializer.deserialize_byte_buf(BytesVisitor)
+        }
+    }
+
+    fn encode(data: &[u8]) -> String {
+        let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+        for chunk in data.chunks(3) {
+            let bytes = [
+                chunk[0],
+                *chunk.get(1).unwrap_or(&0),
+                *chunk.get(2).unwrap_or(&0),
+            ];
+            let n = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+            for i in 0..4 {
+                if i <= chunk.len() {
+                    out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3F) as usize] as char);
+                } else {
+                    out.push('=');
+                }
+            }
+        }
+        out
+    }
+
+    fn decode(text: &str) -> Option<Vec<u8>> {
+        let text = text.trim_end_matches('=').as_bytes();
+        let mut out = Vec::with_capacity(text.len() * 3 / 4);
+        for chunk in text.chunks(4) {
+            if chunk.len() == 1 {
+                return None;
+            }
+            let mut n = 0u32;
+            for (i, &c) in chunk.iter().enumerate() {
+                let value = ALPHABET.iter().position(|&a| a == c)? as u32;
+                n |= value << (18 - 6 * i);
+            }
+            out.extend_from_slice(&n.to_be_bytes()[1..chunk.len()]);
+        }
+        Some(out)
+    }
+
+    struct BytesVisitor;
+
+    impl<'de> Visitor<'de> for BytesVisitor {
+        type Value = Vec<u8>;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("base64 encoded data or a byte array")
+        }
+
+        fn visit_str<E: Error>(self, v: &str) -> Result<Vec<u8>, E> {
+            decode(v).ok_or_else(|| E::custom("invalid base64 data"))
+        }
+
+        fn visit_bytes<E: Error>(self, v: &[u8]) -> Result<Vec<u8>, E> {
+            Ok(v.to_vec())
+        }
+
+        fn visit_byte_buf<E: Error>(self, v: Vec<u8>) -> Result<Vec<u8>, E> {
+            Ok(v)
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<u8>, A::Error> {
+            let mut out = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+            while let Some(byte) = seq.next_element()? {
+                out.push(byte);
+            }
+            Ok(out)
+        }
+    }
+}
+
+/// The offsets and flags of the dds header fields which describe a cube map array.
+#[cfg(feature = "nvtt")]
+const DDS_CAPS_OFFSET: usize = 108;
+#[cfg(feature = "nvtt")]
+const DDS_CAPS2_OFFSET: usize = 112;
+#[cfg(feature = "nvtt")]
+const DDS10_MISC_FLAG_OFFSET: usize = 136;
+#[cfg(feature = "nvtt")]
+const DDS10_ARRAY_SIZE_OFFSET: usize = 140;
+#[cfg(feature = "nvtt")]
+const DDSCAPS_COMPLEX: u32 = 0x8;
+#[cfg(feature = "nvtt")]
+const DDSCAPS2_CUBEMAP_ALL_FACES: u32 = 0xFE00;
+#[cfg(feature = "nvtt")]
+const D3D10_RESOURCE_MISC_TEXTURECUBE: u32 = 0x4;
+
+/// Rewrite the header at the start of the dds10 file `data`, which nvtt wrote as an
+/// array of `6 * n` 2D textures, so that it describes an array of `n` cube maps.
+#[cfg(feature = "nvtt")]
+fn mark_cube_array(data: &mut [u8]) -> Result<(), Error> {
+    if !data.starts_with(b"DDS ") || data.len() < DDS10_ARRAY_SIZE_OFFSET + 4 {
+        return Err(Error::InvalidInput);
+    }
+
+    let mut update = |offset: usize, f: &dyn Fn(u32) -> u32| {
+        let field = &mut data[offset..offset + 4];
+        let value = u32::from_le_bytes([field[0], field[1], field[2], field[3]]);
+        field.copy_from_slice(&f(value).to_le_bytes());
+    };
+    update(DDS_CAPS_OFFSET, &|caps| caps | DDSCAPS_COMPLEX);
+    update(DDS_CAPS2_OFFSET, &|caps2| {
+        caps2 | DDSCAPS2_CUBEMAP_ALL_FACES
+    });
+    update(DDS10_MISC_FLAG_OFFSET, &|misc| {
+        misc | D3D10_RESOURCE_MISC_TEXTURECUBE
+    });
+    update(DDS10_ARRAY_SIZE_OFFSET, &|layers| layers / 6);
+    Ok(())
+}
+
+/// Rewrite the header of the dds10 file at `path` with [`mark_cube_array`].
+///
+/// [`mark_cube_array`]: fn.mark_cube_array.html
+#[cfg(feature = "nvtt")]
+fn mark_cube_array_file(path: &Path) -> Result<(), Error> {
+    use std::io::{Read, Seek, SeekFrom};
+
+    let mut header = [0; DDS10_ARRAY_SIZE_OFFSET + 4];
+    let result = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)
+        .and_then(|mut file| {
+            file.read_exact(&mut header)?;
+            mark_cube_array(&mut header)
+                .map_err(|_| std::io::Error::from(std::io::ErrorKind::InvalidData))?;
+            file.seek(SeekFrom::Start(0))?;
+            file.write_all(&header)
+        });
+
+    result.map_err(|e| {
+        error!(
+            "Could not write cube map array header to {p}\nCaused by: {e}",
+            p = path.display(),
+            e = e
+        );
+        Error::FileWrite
+    })
+}
+
+/// Write the `TextureMetadata` for the options into `{output}.meta.json`.
+#[cfg(feature = "nvtt")]
+#[cfg(feature = "serde-serialize")]
+fn write_metadata_sidecar<D>(
+    encoder: EncoderIdentity,
+    compression_options: &CompressionOptions,
+    input_options: &InputOptions,
+    output_options: &OutputOptions<D>,
+) -> Result<(), Error> {
+    let out_path = match output_options.out_path {
+        Some(ref p) => p,
+        None => return Ok(()),
+    };
+
+    let mut metadata = TextureMetadata::new(compression_options, input_options, output_options)
+        .ok_or(Error::InvalidInput)?;
+    metadata.encoder = Some(encoder);
+
+    let mut sidecar_path = out_path.clone().into_os_string();
+    sidecar_path.push(".meta.json");
+
+    let json = serde_json::to_vec_pretty(&metadata).map_err(|e| {
+        error!(
+            "Could not serialize texture metadata\nCaused by: {e}",
+            e = e
+        );
+        Error::FileWrite
+    })?;
+
+    std::fs::write(&sidecar_path, json).map_err(|e| {
+        error!(
+            "Could not write texture metadata to {p}\nCaused by: {e}",
+            p = Path::new(&sidecar_path).display(),
+            e = e
+        );
+        Error::FileWrite
+    })
+}
+
 /// Object which stores the compression options for the texture. This provides a
 /// safer interface for the [`NvttCompressionOptions`] type.
 ///
 /// [`NvttCompressionOptions`]: https://docs.rs/nvtt_sys/latest/nvtt_sys/struct.NvttCompressionOptions.html
-#[derive(Debug)]
-pub struct CompressionOptions(NonNull<NvttCompressionOptions>);
+#[cfg(feature = "nvtt")]
+pub struct CompressionOptions {
+    opts: NonNull<NvttCompressionOptions>,
+    format: Format,
+    quality: Quality,
+    color_weights: Option<[f32; 4]>,
+    pixel_format: Option<[c_uint; 5]>,
+    quantization: Option<(NvttBoolean, NvttBoolean, NvttBoolean, i32)>,
+    /// The color and alpha dithering, which may use modes that nvtt does not support.
+    dithering: (Dither, Dither),
+    target_psnr: Option<f32>,
+    target_formats: Vec<Format>,
+}
 
+#[cfg(feature = "nvtt")]
 impl CompressionOptions {
     /// Create a new `CompressionOptions`.
     #[inline]
     pub fn new() -> Result<Self, Error> {
         let opts = unsafe { nvttCreateCompressionOptions() };
-        NonNull::new(opts).map(Self).ok_or(Error::Unknown)
+        // @SAFETY: The pointer was just created by nvtt, and is owned by nothing else.
+        unsafe { Self::from_raw(opts) }.map_err(|_| Error::Unknown)
+    }
+
+    /// Take ownership of a [`NvttCompressionOptions`] pointer, such as one returned
+    /// from [`into_raw`]. The pointer is destroyed when the `CompressionOptions` is
+    /// dropped.
+    ///
+    /// The `format` and `quality` reported by the `CompressionOptions` start at their
+    /// defaults, as they cannot be read back from nvtt. Call [`set_format`] and
+    /// [`set_quality`] to keep them in sync with the native options.
+    ///
+    /// # Errors
+    ///
+    /// If `raw` is null, then this method will fail with [`Error::InvalidInput`].
+    ///
+    /// # Safety
+    ///
+    /// `raw` must have been created by [`nvttCreateCompressionOptions`], and must not
+    /// be owned or destroyed by anything else.
+    ///
+    /// [`into_raw`]: struct.CompressionOptions.html#method.into_raw
+    /// [`set_format`]: struct.CompressionOptions.html#method.set_format
+    /// [`set_quality`]: struct.CompressionOptions.html#method.set_quality
+    /// [`Error::InvalidInput`]: enum.Error.html#variant.InvalidInput
+    /// [`nvttCreateCompressionOptions`]: https://docs.rs/nvtt_sys/latest/nvtt_sys/fn.nvttCreateCompressionOptions.html
+    /// [`NvttCompressionOptions`]: https://docs.rs/nvtt_sys/latest/nvtt_sys/struct.NvttCompressionOptions.html
+    #[inline]
+    pub unsafe fn from_raw(raw: *mut NvttCompressionOptions) -> Result<Self, Error> {
+        NonNull::new(raw)
+            .ok_or(Error::InvalidInput)
+            .map(|opts| CompressionOptions {
+                opts,
+                format: Format::Dxt1,
+                quality: Quality::Normal,
+                color_weights: None,
+                pixel_format: None,
+                quantization: None,
+                dithering: (Dither::None, Dither::None),
+                target_psnr: None,
+                target_formats: vec![],
+            })
     }
 
     /// Returns the underlying [`NvttCompressionOptions`] pointer type. It is your
@@ -869,16 +3541,32 @@ impl CompressionOptions {
     /// [`NvttCompressionOptions`]: https://docs.rs/nvtt_sys/latest/nvtt_sys/struct.NvttCompressionOptions.html
     #[inline]
     pub fn into_raw(self) -> *mut NvttCompressionOptions {
-        let ptr = self.0.as_ptr();
+        let ptr = self.opts.as_ptr();
         mem::forget(self);
         ptr
     }
 
+    /// Returns the underlying [`NvttCompressionOptions`] pointer without giving up ownership of it.
+    /// The pointer is valid for as long as this `CompressionOptions` is alive, and is destroyed
+    /// when the `CompressionOptions` is dropped.
+    ///
+    /// This can be used to call nvtt functions which are not wrapped by this crate.
+    /// Mutating the options through this pointer is unsafe: the cached format and
+    /// quality of the `CompressionOptions` will not be updated, and the options must
+    /// not be destroyed through it.
+    ///
+    /// [`NvttCompressionOptions`]: https://docs.rs/nvtt_sys/latest/nvtt_sys/struct.NvttCompressionOptions.html
+    #[inline]
+    pub fn as_raw(&self) -> *mut NvttCompressionOptions {
+        self.opts.as_ptr()
+    }
+
     #[inline]
     pub fn set_color_weights(&mut self, r: f32, g: f32, b: f32, a: f32) -> &mut Self {
         unsafe {
-            nvttSetCompressionOptionsColorWeights(self.0.as_ptr(), r, g, b, a);
+            nvttSetCompressionOptionsColorWeights(self.opts.as_ptr(), r, g, b, a);
         }
+        self.color_weights = Some([r, g, b, a]);
         self
     }
 
@@ -886,11 +3574,27 @@ impl CompressionOptions {
     #[inline]
     pub fn set_format(&mut self, format: Format) -> &mut Self {
         unsafe {
-            nvttSetCompressionOptionsFormat(self.0.as_ptr(), format.into());
+            nvttSetCompressionOptionsFormat(self.opts.as_ptr(), format.into());
         }
+        self.format = format;
         self
     }
 
+    /// Get the output format of the compressed image. This is `Format::Dxt1` by default.
+    #[inline]
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// Set the layout of the pixels when the output format is `Format::Rgb` or
+    /// `Format::Rgba`.
+    ///
+    /// # Errors
+    ///
+    /// If `bitcount` is not one of `8`, `16`, `24` or `32`, or any of the masks do not
+    /// fit into `bitcount` bits, then this method will fail with [`Error::InvalidInput`].
+    ///
+    /// [`Error::InvalidInput`]: enum.Error.html#variant.InvalidInput
     #[inline]
     pub fn set_pixel_format(
         &mut self,
@@ -899,10 +3603,18 @@ impl CompressionOptions {
         gmask: c_uint,
         bmask: c_uint,
         amask: c_uint,
-    ) -> &mut Self {
+    ) -> Result<&mut Self, Error> {
+        if ![8, 16, 24, 32].contains(&bitcount) {
+            return Err(Error::InvalidInput);
+        }
+        let all_masks = rmask | gmask | bmask | amask;
+        if bitcount < 32 && all_masks >> bitcount != 0 {
+            return Err(Error::InvalidInput);
+        }
+
         unsafe {
             nvttSetCompressionOptionsPixelFormat(
-                self.0.as_ptr(),
+                self.opts.as_ptr(),
                 bitcount,
                 rmask,
                 gmask,
@@ -910,18 +3622,26 @@ impl CompressionOptions {
                 amask,
             )
         }
-        self
+        self.pixel_format = Some([bitcount, rmask, gmask, bmask, amask]);
+        Ok(self)
     }
 
     /// Set the `Quality` of the output image.
     #[inline]
     pub fn set_quality(&mut self, quality: Quality) -> &mut Self {
         unsafe {
-            nvttSetCompressionOptionsQuality(self.0.as_ptr(), quality.into());
+            nvttSetCompressionOptionsQuality(self.opts.as_ptr(), quality.into());
         }
+        self.quality = quality;
         self
     }
 
+    /// Get the `Quality` of the output image. This is `Quality::Normal` by default.
+    #[inline]
+    pub fn quality(&self) -> Quality {
+        self.quality
+    }
+
     /// Set quantization settings on the `CompressionOptions`.
     ///
     /// * If `color_dithering` is `true`, then dithering will be applied to the color channel.
@@ -929,6 +3649,13 @@ impl CompressionOptions {
     /// * If `binary_alpha` is `true`, Then only one bit will be used to encode alpha information.
     /// * `alpha_threshold` is used to determine if a pixel's alpha channel is high enough to be
     ///   transparent. This parameter is ignored if `binary_alpha` is set to false.
+    ///
+    /// # Errors
+    ///
+    /// If `alpha_threshold` is not in the range `0..=255`, then this method will fail with
+    /// [`Error::InvalidInput`].
+    ///
+    /// [`Error::InvalidInput`]: enum.Error.html#variant.InvalidInput
     #[inline]
     pub fn set_quanitzation(
         &mut self,
@@ -936,44 +3663,340 @@ impl CompressionOptions {
         alpha_dithering: impl Into<NvttBoolean>,
         binary_alpha: impl Into<NvttBoolean>,
         alpha_threshold: i32,
-    ) -> &mut Self {
+    ) -> Result<&mut Self, Error> {
+        if !(0..=255).contains(&alpha_threshold) {
+            return Err(Error::InvalidInput);
+        }
+
+        let quantization = (
+            color_dithering.into(),
+            alpha_dithering.into(),
+            binary_alpha.into(),
+            alpha_threshold,
+        );
+        self.dithering = (
+            Dither::from(bool::from(quantization.0)),
+            Dither::from(bool::from(quantization.1)),
+        );
         unsafe {
             nvttSetCompressionOptionsQuantization(
-                self.0.as_ptr(),
-                color_dithering.into(),
-                alpha_dithering.into(),
-                binary_alpha.into(),
-                alpha_threshold,
+                self.opts.as_ptr(),
+                quantization.0,
+                quantization.1,
+                quantization.2,
+                quantization.3,
             )
         }
-        self
+        self.quantization = Some(quantization);
+        Ok(self)
+    }
+
+    /// Set how the quantization error of the color and alpha channels is dithered.
+    ///
+    /// nvtt only supports `Dither::ErrorDiffusion`, so `Dither::Ordered` disables
+    /// dithering in nvtt. Instead, [`Context`] applies ordered dithering to each
+    /// `Surface` before it is compressed, using [`Context::quantize`]. The binary
+    /// alpha settings of [`set_quanitzation`] are kept.
+    ///
+    /// `Compressor::compress` cannot dither the images of an `InputOptions`, so it
+    /// fails with `Error::UnsupportedFeature` if either channel uses `Dither::Ordered`.
+    ///
+    /// [`Context`]: struct.Context.html
+    /// [`Context::quantize`]: struct.Context.html#method.quantize
+    /// [`set_quanitzation`]: struct.CompressionOptions.html#method.set_quanitzation
+    pub fn set_dithering(&mut self, color: Dither, alpha: Dither) -> &mut Self {
+        let (_, _, binary_alpha, alpha_threshold) = self.quantization.unwrap_or((
+            NvttBoolean::NVTT_False,
+            NvttBoolean::NVTT_False,
+            NvttBoolean::NVTT_False,
+            127,
+        ));
+        let is_diffused = |dither| dither == Dither::ErrorDiffusion;
+        let _ = self.set_quanitzation(
+            is_diffused(color),
+            is_diffused(alpha),
+            binary_alpha,
+            alpha_threshold,
+        );
+        self.dithering = (color, alpha);
+        self
+    }
+
+    /// Get the dithering of the color and alpha channels. This is `Dither::None` for
+    /// both by default.
+    #[inline]
+    pub fn dithering(&self) -> (Dither, Dither) {
+        self.dithering
+    }
+
+    /// Set the PSNR in decibels which the compressed texture should reach, rather than
+    /// a fixed `Quality`.
+    ///
+    /// When compressing with a [`Context`], the top mipmap level is compressed and
+    /// measured with the faster qualities first, and with each of the
+    /// [`target_formats`], using [`QualityMetrics::measure`]. The smallest and then
+    /// fastest configuration which reaches the target is used. If no configuration
+    /// reaches the target, then the one with the highest PSNR is used.
+    ///
+    /// The source images cannot be measured when compressing with a [`Compressor`]
+    /// directly, so [`Compressor::compress`] fails with `Error::UnsupportedFeature` if
+    /// a target PSNR is set.
+    ///
+    /// # Errors
+    ///
+    /// If `psnr` is not a positive, finite number, then this method will fail with
+    /// [`Error::InvalidInput`].
+    ///
+    /// [`Context`]: struct.Context.html
+    /// [`target_formats`]: struct.CompressionOptions.html#method.set_target_formats
+    /// [`QualityMetrics::measure`]: struct.QualityMetrics.html#method.measure
+    /// [`Compressor`]: struct.Compressor.html
+    /// [`Compressor::compress`]: struct.Compressor.html#method.compress
+    /// [`Error::InvalidInput`]: enum.Error.html#variant.InvalidInput
+    #[inline]
+    pub fn set_target_psnr(&mut self, psnr: f32) -> Result<&mut Self, Error> {
+        if !psnr.is_finite() || psnr <= 0.0 {
+            return Err(Error::InvalidInput);
+        }
+
+        self.target_psnr = Some(psnr);
+        Ok(self)
+    }
+
+    /// Stop targeting a PSNR, and compress with the `Quality` which is set.
+    #[inline]
+    pub fn clear_target_psnr(&mut self) -> &mut Self {
+        self.target_psnr = None;
+        self
+    }
+
+    /// Get the PSNR in decibels which the compressed texture should reach. This is
+    /// `None` by default.
+    #[inline]
+    pub fn target_psnr(&self) -> Option<f32> {
+        self.target_psnr
+    }
+
+    /// Set the formats which may be used instead of the `format` to reach the
+    /// [`target_psnr`]. This is empty by default, so that only the quality is changed.
+    ///
+    /// [`target_psnr`]: struct.CompressionOptions.html#method.set_target_psnr
+    #[inline]
+    pub fn set_target_formats(&mut self, formats: &[Format]) -> &mut Self {
+        self.target_formats = formats.to_vec();
+        self
+    }
+
+    /// Get the formats which may be used instead of the `format` to reach the
+    /// [`target_psnr`].
+    ///
+    /// [`target_psnr`]: struct.CompressionOptions.html#method.set_target_psnr
+    #[inline]
+    pub fn target_formats(&self) -> &[Format] {
+        &self.target_formats
+    }
+
+    /// The configuration set through the methods of the `CompressionOptions`, with
+    /// floating point values replaced by their bits so that it can be hashed.
+    #[inline]
+    fn key(&self) -> CompressionKey<'_> {
+        (
+            self.format,
+            self.quality,
+            self.color_weights.map(|weights| weights.map(f32::to_bits)),
+            self.pixel_format,
+            self.quantization,
+            self.dithering,
+            self.target_psnr.map(f32::to_bits),
+            &self.target_formats,
+        )
+    }
+}
+
+#[cfg(feature = "nvtt")]
+type CompressionKey<'a> = (
+    Format,
+    Quality,
+    Option<[u32; 4]>,
+    Option<[c_uint; 5]>,
+    Option<(NvttBoolean, NvttBoolean, NvttBoolean, i32)>,
+    (Dither, Dither),
+    Option<u32>,
+    &'a [Format],
+);
+
+#[cfg(feature = "nvtt")]
+impl Clone for CompressionOptions {
+    /// Create a new `CompressionOptions` with the same settings as this one, by
+    /// replaying them onto a new [`NvttCompressionOptions`].
+    ///
+    /// Only state which has been set through the methods of `CompressionOptions` is
+    /// cloned. Anything set directly on the pointer returned by [`as_raw`] is not
+    /// copied.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if nvtt fails to create the new `CompressionOptions`.
+    ///
+    /// [`as_raw`]: struct.CompressionOptions.html#method.as_raw
+    /// [`NvttCompressionOptions`]: https://docs.rs/nvtt_sys/latest/nvtt_sys/struct.NvttCompressionOptions.html
+    fn clone(&self) -> Self {
+        let mut cloned = CompressionOptions::default();
+        cloned.set_format(self.format).set_quality(self.quality);
+        if let Some([r, g, b, a]) = self.color_weights {
+            cloned.set_color_weights(r, g, b, a);
+        }
+        if let Some([bitcount, rmask, gmask, bmask, amask]) = self.pixel_format {
+            let _ = cloned.set_pixel_format(bitcount, rmask, gmask, bmask, amask);
+        }
+        if let Some((color, alpha, binary_alpha, threshold)) = self.quantization {
+            let _ = cloned.set_quanitzation(color, alpha, binary_alpha, threshold);
+        }
+        cloned.dithering = self.dithering;
+        cloned.target_psnr = self.target_psnr;
+        cloned.target_formats = self.target_formats.clone();
+        cloned
+    }
+}
+
+/// Two `CompressionOptions` are equal if the same configuration has been set on them
+/// through their methods. Changes made through [`as_raw`] are not compared.
+///
+/// [`as_raw`]: struct.CompressionOptions.html#method.as_raw
+#[cfg(feature = "nvtt")]
+impl PartialEq for CompressionOptions {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+#[cfg(feature = "nvtt")]
+impl Eq for CompressionOptions {}
+
+#[cfg(feature = "nvtt")]
+impl Hash for CompressionOptions {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state)
     }
 }
 
+#[cfg(feature = "nvtt")]
+impl fmt::Debug for CompressionOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let quantization = self.quantization.map(|(color, alpha, binary, threshold)| {
+            (
+                bool::from(color),
+                bool::from(alpha),
+                bool::from(binary),
+                threshold,
+            )
+        });
+        f.debug_struct("CompressionOptions")
+            .field("format", &self.format)
+            .field("quality", &self.quality)
+            .field("color_weights", &self.color_weights)
+            .field("pixel_format", &self.pixel_format)
+            .field("quantization", &quantization)
+            .field("dithering", &self.dithering)
+            .field("target_psnr", &self.target_psnr)
+            .field("target_formats", &self.target_formats)
+            .finish()
+    }
+}
+
+#[cfg(feature = "nvtt")]
+impl Default for CompressionOptions {
+    /// Create a new `CompressionOptions`. See [`CompressionOptions::new`].
+    ///
+    /// # Panics
+    ///
+    /// This will panic if nvtt fails to allocate the `CompressionOptions`.
+    ///
+    /// [`CompressionOptions::new`]: struct.CompressionOptions.html#method.new
+    #[inline]
+    fn default() -> Self {
+        Self::new().expect("could not create the CompressionOptions")
+    }
+}
+
+#[cfg(feature = "nvtt")]
 impl Drop for CompressionOptions {
     #[inline]
     fn drop(&mut self) {
-        unsafe { nvttDestroyCompressionOptions(self.0.as_ptr()) }
+        unsafe { nvttDestroyCompressionOptions(self.opts.as_ptr()) }
     }
 }
 
 // @SAFETY: A `CompressionOptions` cannot be copied or unsafely mutated in a shared way.
+#[cfg(feature = "nvtt")]
 unsafe impl Send for CompressionOptions {}
+#[cfg(feature = "nvtt")]
 unsafe impl Sync for CompressionOptions {}
 
 /// Object which stores the input options for the texture. This provides a
 /// safer interface for the [`NvttInputOptions`] type.
 ///
 /// [`NvttInputOptions`]: https://docs.rs/nvtt_sys/latest/nvtt_sys/struct.NvttInputOptions.html
-#[derive(Debug)]
-pub struct InputOptions(NonNull<NvttInputOptions>);
+#[cfg(feature = "nvtt")]
+pub struct InputOptions {
+    opts: NonNull<NvttInputOptions>,
+    texture_layout: Option<TextureLayout>,
+    /// A running hash of all the data passed to `set_mipmap_data`.
+    source_hash: Option<u64>,
+    /// The `(face, mipmap)` pairs which have been passed to `set_mipmap_data`, with the
+    /// number of partial alpha values and the total number of alpha values in each.
+    mipmap_data_set: BTreeMap<(i32, i32), (usize, usize)>,
+    /// The settings which have been applied, so that they can be replayed by `clone`.
+    state: InputState,
+    /// Held while compressing, because the default gamma for the mip filter colorspace
+    /// is applied to the nvtt options through a shared reference.
+    compress_lock: Mutex<()>,
+}
 
+#[cfg(feature = "nvtt")]
 impl InputOptions {
     /// Create a new `InputOptions`.
     #[inline]
     pub fn new() -> Result<Self, Error> {
         let opts = unsafe { nvttCreateInputOptions() };
-        NonNull::new(opts).map(Self).ok_or(Error::Unknown)
+        // @SAFETY: The pointer was just created by nvtt, and is owned by nothing else.
+        unsafe { Self::from_raw(opts) }.map_err(|_| Error::Unknown)
+    }
+
+    /// Take ownership of a [`NvttInputOptions`] pointer, such as one returned from
+    /// [`into_raw`]. The pointer is destroyed when the `InputOptions` is dropped.
+    ///
+    /// The texture layout and mipmap data cannot be read back from nvtt, so methods
+    /// which inspect them, such as [`diagnose`], behave as if they have not been set.
+    ///
+    /// # Errors
+    ///
+    /// If `raw` is null, then this method will fail with [`Error::InvalidInput`].
+    ///
+    /// # Safety
+    ///
+    /// `raw` must have been created by [`nvttCreateInputOptions`], and must not be
+    /// owned or destroyed by anything else.
+    ///
+    /// [`into_raw`]: struct.InputOptions.html#method.into_raw
+    /// [`diagnose`]: struct.InputOptions.html#method.diagnose
+    /// [`Error::InvalidInput`]: enum.Error.html#variant.InvalidInput
+    /// [`nvttCreateInputOptions`]: https://docs.rs/nvtt_sys/latest/nvtt_sys/fn.nvttCreateInputOptions.html
+    /// [`NvttInputOptions`]: https://docs.rs/nvtt_sys/latest/nvtt_sys/struct.NvttInputOptions.html
+    #[inline]
+    pub unsafe fn from_raw(raw: *mut NvttInputOptions) -> Result<Self, Error> {
+        NonNull::new(raw)
+            .ok_or(Error::InvalidInput)
+            .map(|opts| InputOptions {
+                opts,
+                texture_layout: None,
+                source_hash: None,
+                mipmap_data_set: BTreeMap::new(),
+                state: InputState::default(),
+                compress_lock: Mutex::new(()),
+            })
     }
 
     /// Returns the underlying [`NvttInputOptions`] pointer type. It is your responsibility
@@ -984,17 +4007,38 @@ impl InputOptions {
     /// [`NvttInputOptions`]: https://docs.rs/nvtt_sys/latest/nvtt_sys/struct.NvttInputOptions.html
     #[inline]
     pub fn into_raw(self) -> *mut NvttInputOptions {
-        let ptr = self.0.as_ptr();
+        let ptr = self.opts.as_ptr();
         mem::forget(self);
         ptr
     }
 
-    /// Set the `AlphaMode` on the `InputOptions`.
+    /// Returns the underlying [`NvttInputOptions`] pointer without giving up ownership of it.
+    /// The pointer is valid for as long as this `InputOptions` is alive, and is destroyed
+    /// when the `InputOptions` is dropped.
+    ///
+    /// This can be used to call nvtt functions which are not wrapped by this crate.
+    /// Mutating the options through this pointer is unsafe: the texture layout and
+    /// mipmap data tracked by the `InputOptions` will not be updated, and the options
+    /// must not be destroyed through it.
+    ///
+    /// [`NvttInputOptions`]: https://docs.rs/nvtt_sys/latest/nvtt_sys/struct.NvttInputOptions.html
+    #[inline]
+    pub fn as_raw(&self) -> *mut NvttInputOptions {
+        self.opts.as_ptr()
+    }
+
+    /// Set the `AlphaMode` on the `InputOptions`. When the mode is
+    /// `AlphaMode::Transparency`, nvtt weights the color of each texel by its alpha
+    /// when generating mipmaps, so that transparent texels do not bleed into the color
+    /// of the smaller levels. [`Surface::build_mipmaps`] does the same.
+    ///
+    /// [`Surface::build_mipmaps`]: struct.Surface.html#method.build_mipmaps
     #[inline]
     pub fn set_alpha_mode(&mut self, alpha_mode: AlphaMode) -> &mut Self {
         unsafe {
-            nvttSetInputOptionsAlphaMode(self.0.as_ptr(), alpha_mode.into());
+            nvttSetInputOptionsAlphaMode(self.opts.as_ptr(), alpha_mode.into());
         }
+        self.state.alpha_mode = Some(alpha_mode);
         self
     }
 
@@ -1004,9 +4048,11 @@ impl InputOptions {
         &mut self,
         convert_to_normal_map: impl Into<NvttBoolean>,
     ) -> &mut Self {
+        let convert_to_normal_map = convert_to_normal_map.into();
         unsafe {
-            nvttSetInputOptionsConvertToNormalMap(self.0.as_ptr(), convert_to_normal_map.into());
+            nvttSetInputOptionsConvertToNormalMap(self.opts.as_ptr(), convert_to_normal_map);
         }
+        self.state.convert_to_normal_map = Some(convert_to_normal_map);
         self
     }
 
@@ -1017,20 +4063,80 @@ impl InputOptions {
     #[inline]
     pub fn set_format(&mut self, format: InputFormat) -> &mut Self {
         unsafe {
-            nvttSetInputOptionsFormat(self.0.as_ptr(), format.into());
+            nvttSetInputOptionsFormat(self.opts.as_ptr(), format.into());
         }
+        self.state.format = Some(format);
         self
     }
 
-    /// Set the `input_gamma` and `output_gamma` on the `InputOptions`.
+    /// Set the `input_gamma` and `output_gamma` on the `InputOptions`. The input is
+    /// converted into linear light with `input_gamma` before mipmaps are generated, and
+    /// each mipmap is encoded with `output_gamma`. This replaces any colorspace set
+    /// with [`set_mip_filter_colorspace`].
+    ///
+    /// # Errors
+    ///
+    /// If either gamma value is not a positive, finite number, then this method will
+    /// fail with [`Error::InvalidInput`].
+    ///
+    /// [`Error::InvalidInput`]: enum.Error.html#variant.InvalidInput
+    /// [`set_mip_filter_colorspace`]: struct.InputOptions.html#method.set_mip_filter_colorspace
     #[inline]
-    pub fn set_gamma(&mut self, input_gamma: f32, output_gamma: f32) -> &mut Self {
-        unsafe {
-            nvttSetInputOptionsGamma(self.0.as_ptr(), input_gamma, output_gamma);
+    pub fn set_gamma(&mut self, input_gamma: f32, output_gamma: f32) -> Result<&mut Self, Error> {
+        let is_valid = |gamma: f32| gamma.is_finite() && gamma > 0.0;
+        if !is_valid(input_gamma) || !is_valid(output_gamma) {
+            return Err(Error::InvalidInput);
         }
+
+        set_nvtt_gamma(self.opts, input_gamma, output_gamma);
+        self.state.gamma = Some((input_gamma, output_gamma));
+        self.state.mip_filter_colorspace = None;
+        Ok(self)
+    }
+
+    /// Set whether mipmaps are downsampled in linear light, or from the stored values
+    /// directly. This only changes the input gamma: `MipFilterColorspace::Linear` uses
+    /// an input gamma of `2.2`, and `MipFilterColorspace::Gamma` uses `1.0`. An output
+    /// gamma set with [`set_gamma`] is kept, and otherwise the output is encoded with
+    /// the same gamma as the input.
+    ///
+    /// If no colorspace or gamma is set, then the colorspace is chosen when the texture
+    /// is compressed, with [`MipFilterColorspace::default_for`] the output format.
+    ///
+    /// [`set_gamma`]: struct.InputOptions.html#method.set_gamma
+    /// [`MipFilterColorspace::default_for`]: enum.MipFilterColorspace.html#method.default_for
+    #[inline]
+    pub fn set_mip_filter_colorspace(&mut self, colorspace: MipFilterColorspace) -> &mut Self {
+        let input_gamma = colorspace.input_gamma();
+        let output_gamma = self.state.gamma.map_or(input_gamma, |(_, output)| output);
+        set_nvtt_gamma(self.opts, input_gamma, output_gamma);
+        self.state.mip_filter_colorspace = Some(colorspace);
         self
     }
 
+    /// Get the colorspace in which mipmaps are downsampled. This is the colorspace set
+    /// with [`set_mip_filter_colorspace`], or else `MipFilterColorspace::Gamma` if the
+    /// input gamma set with [`set_gamma`] is `1.0`, and `MipFilterColorspace::Linear`
+    /// if it is anything else.
+    ///
+    /// If neither has been set, then this returns `None`, and the default colorspace for
+    /// the output format is used when the texture is compressed.
+    ///
+    /// [`set_mip_filter_colorspace`]: struct.InputOptions.html#method.set_mip_filter_colorspace
+    /// [`set_gamma`]: struct.InputOptions.html#method.set_gamma
+    #[inline]
+    pub fn mip_filter_colorspace(&self) -> Option<MipFilterColorspace> {
+        self.state.mip_filter_colorspace.or_else(|| {
+            self.state.gamma.map(|(input, _)| {
+                if input == 1.0 {
+                    MipFilterColorspace::Gamma
+                } else {
+                    MipFilterColorspace::Linear
+                }
+            })
+        })
+    }
+
     #[inline]
     pub fn set_height_evaluation(
         &mut self,
@@ -1041,13 +4147,14 @@ impl InputOptions {
     ) -> &mut Self {
         unsafe {
             nvttSetInputOptionsHeightEvaluation(
-                self.0.as_ptr(),
+                self.opts.as_ptr(),
                 red_scale,
                 green_scale,
                 blue_scale,
                 alpha_scale,
             );
         }
+        self.state.height_evaluation = Some([red_scale, green_scale, blue_scale, alpha_scale]);
         self
     }
 
@@ -1057,7 +4164,7 @@ impl InputOptions {
     /// [`MipmapFilter`]: enum.MipmapFilter.html
     #[inline]
     pub fn set_mipmap_filter(&mut self, mipmap_filter: MipmapFilter) -> &mut Self {
-        let opts_ptr = self.0.as_ptr();
+        let opts_ptr = self.opts.as_ptr();
         unsafe {
             nvttSetInputOptionsMipmapFilter(opts_ptr, mipmap_filter.into());
         }
@@ -1073,32 +4180,69 @@ impl InputOptions {
             }
         }
 
+        self.state.mipmap_filter = Some(mipmap_filter);
+        self
+    }
+
+    /// Specify whether mipmaps should be generated for the texture. If `max_level` is
+    /// `None`, then the full mipmap chain is generated. Mipmap levels which have been
+    /// set with [`set_mipmap_data`] are used instead of being generated.
+    ///
+    /// [`set_mipmap_data`]: struct.InputOptions.html#method.set_mipmap_data
+    #[inline]
+    pub fn set_mipmap_generation(
+        &mut self,
+        generate_mipmaps: impl Into<NvttBoolean>,
+        max_level: Option<u32>,
+    ) -> &mut Self {
+        let generate_mipmaps = generate_mipmaps.into();
+        self.state.mipmap_generation = Some((generate_mipmaps, max_level));
+        let max_level = max_level.map_or(-1, |level| level.min(c_int::MAX as u32) as c_int);
+        unsafe {
+            nvttSetInputOptionsMipmapGeneration(self.opts.as_ptr(), generate_mipmaps, max_level);
+        }
         self
     }
 
-    /// Sets the input data which should be compressed.
+    /// Sets the input data of the given `face` and `mipmap` level which should be
+    /// compressed. The `extent` is the size of the mipmap level in pixels.
     ///
-    /// The `data` is copied into the `InputOptions` object.
+    /// The `data` is copied by nvtt. It is only copied into the `InputOptions` as well
+    /// if [`set_retain_mipmap_data`] is enabled, so that it can be cloned and compared.
     ///
     /// # Errors
     ///
-    /// If the dimensions of the image do not match the length of the `data`,
-    /// then this method will fail with [`Error::Unknown`].
+    /// If the `extent` does not match the length of the `data`, then this method
+    /// will fail with [`Error::Unknown`]. If any dimension of the `extent`, or the
+    /// index of the `face` is too large to be passed to nvtt, then this method will fail with
+    /// [`Error::InvalidInput`]. If the `mipmap` level is not part of the mip chain
+    /// of the [`TextureLayout`] set on the `InputOptions`, then this method will fail
+    /// with [`Error::InvalidMipLevel`].
     ///
     /// [`Error::Unknown`]: enum.Error.html#variant.Unknown
+    /// [`Error::InvalidInput`]: enum.Error.html#variant.InvalidInput
+    /// [`Error::InvalidMipLevel`]: enum.Error.html#variant.InvalidMipLevel
+    /// [`TextureLayout`]: enum.TextureLayout.html
+    /// [`set_retain_mipmap_data`]: struct.InputOptions.html#method.set_retain_mipmap_data
     #[inline]
     pub fn set_mipmap_data(
         &mut self,
         data: &[u8],
-        w: i32,
-        h: i32,
-        d: i32,
-        face: i32,
-        mipmap: i32,
+        extent: Extent3d,
+        face: CubeFace,
+        mipmap: MipLevel,
     ) -> Result<&mut Self, Error> {
+        let (w, h, d) = extent.to_raw()?;
+        let face = face.raw_index()?;
+        if let Some(ref layout) = self.texture_layout {
+            if !mipmap.is_valid_for(layout) {
+                return Err(Error::InvalidMipLevel);
+            }
+        }
+        let mipmap = c_int::try_from(mipmap.0).map_err(|_| Error::InvalidMipLevel)?;
         let result = unsafe {
             nvttSetInputOptionsMipmapData(
-                self.0.as_ptr(),
+                self.opts.as_ptr(),
                 data.as_ptr() as *const _,
                 w,
                 h,
@@ -1109,15 +4253,56 @@ impl InputOptions {
         };
 
         match result {
-            NvttBoolean::NVTT_True => Ok(self),
+            NvttBoolean::NVTT_True => {
+                self.source_hash =
+                    Some(fnv1a_64(self.source_hash.unwrap_or(FNV_OFFSET_BASIS), data));
+                let format = self.state.format.unwrap_or(InputFormat::Bgra8Ub);
+                self.mipmap_data_set
+                    .insert((face, mipmap), count_partial_alpha(format, data));
+                if self.state.retain_mipmap_data {
+                    self.state.set_mipmap_data(data, extent, face, mipmap);
+                }
+                Ok(self)
+            }
             NvttBoolean::NVTT_False => Err(Error::Unknown),
         }
     }
 
+    /// Set whether a copy of the data passed to [`set_mipmap_data`] is kept by the
+    /// `InputOptions`. This is disabled by default, because it doubles the memory
+    /// used by each image.
+    ///
+    /// Only retained mipmap data is copied by `clone`. Without it, a clone has the same
+    /// settings but no images, and mipmap data is only compared by its hash. Data which
+    /// was set before this is enabled is not retained.
+    ///
+    /// [`set_mipmap_data`]: struct.InputOptions.html#method.set_mipmap_data
+    #[inline]
+    pub fn set_retain_mipmap_data(&mut self, retain: bool) -> &mut Self {
+        self.state.retain_mipmap_data = retain;
+        if !retain {
+            self.state.mipmap_data.clear();
+        }
+        self
+    }
+
+    /// Returns `true` if the `InputOptions` keeps a copy of its mipmap data. See
+    /// [`set_retain_mipmap_data`].
+    ///
+    /// [`set_retain_mipmap_data`]: struct.InputOptions.html#method.set_retain_mipmap_data
+    #[inline]
+    pub fn retains_mipmap_data(&self) -> bool {
+        self.state.retain_mipmap_data
+    }
+
     /// Resets the `InputOptions` back to the default state.
     #[inline]
     pub fn reset(&mut self) -> &mut Self {
-        unsafe { nvttResetInputOptionsTextureLayout(self.0.as_ptr()) }
+        unsafe { nvttResetInputOptionsTextureLayout(self.opts.as_ptr()) }
+        self.texture_layout = None;
+        self.source_hash = None;
+        self.mipmap_data_set.clear();
+        self.state.mipmap_data.clear();
         self
     }
 
@@ -1134,45 +4319,62 @@ impl InputOptions {
     pub fn set_image<'a, I: Into<ValidImage<'a>>>(
         &mut self,
         image: I,
-        face: i32,
-        mipmap: i32,
+        face: CubeFace,
+        mipmap: MipLevel,
     ) -> Result<&mut Self, Error> {
         let image = image.into();
         let (w, h) = image.image_dimensions();
-        let image_layout = TextureLayout::d2(w as _, h as _);
+        let extent = Extent3d::d2(w, h).ok_or(Error::InvalidInput)?;
 
         self.reset()
             .set_format(image.format())
-            .set_texture_layout(image_layout)
-            .set_mipmap_data(image.data_bytes(), w as _, h as _, 1, face, mipmap)?;
+            .set_texture_layout(TextureLayout::from_extent(extent))?
+            .set_mipmap_data(image.data_bytes(), extent, face, mipmap)?;
 
         Ok(self)
     }
 
-    /// Constrain the texture size to the value in `max_extents`.
+    /// Constrain the texture size to the value in `max_extents`. A value of `0`
+    /// means that the texture size is not constrained.
+    ///
+    /// # Errors
+    ///
+    /// If `max_extents` is negative, then this method will fail with
+    /// [`Error::InvalidInput`].
+    ///
+    /// [`Error::InvalidInput`]: enum.Error.html#variant.InvalidInput
     #[inline]
-    pub fn set_max_extents(&mut self, max_extents: c_int) -> &mut Self {
+    pub fn set_max_extents(&mut self, max_extents: c_int) -> Result<&mut Self, Error> {
+        if max_extents < 0 {
+            return Err(Error::InvalidInput);
+        }
+
         unsafe {
-            nvttSetInputOptionsMaxExtents(self.0.as_ptr(), max_extents);
+            nvttSetInputOptionsMaxExtents(self.opts.as_ptr(), max_extents);
         }
-        self
+        self.state.max_extents = Some(max_extents);
+        Ok(self)
     }
 
     /// Specify whether the image is a normal map. Normal maps may be compressed
     /// differently to better preserve the normal information.
     #[inline]
     pub fn set_normal_map(&mut self, is_normal_map: impl Into<NvttBoolean>) -> &mut Self {
+        let is_normal_map = is_normal_map.into();
         unsafe {
-            nvttSetInputOptionsNormalMap(self.0.as_ptr(), is_normal_map.into());
+            nvttSetInputOptionsNormalMap(self.opts.as_ptr(), is_normal_map);
         }
+        self.state.normal_map = Some(is_normal_map);
         self
     }
 
     #[inline]
     pub fn set_normalize_mipmaps(&mut self, normalize_mips: impl Into<NvttBoolean>) -> &mut Self {
+        let normalize_mips = normalize_mips.into();
         unsafe {
-            nvttSetInputOptionsNormalizeMipmaps(self.0.as_ptr(), normalize_mips.into());
+            nvttSetInputOptionsNormalizeMipmaps(self.opts.as_ptr(), normalize_mips);
         }
+        self.state.normalize_mipmaps = Some(normalize_mips);
         self
     }
 
@@ -1180,7 +4382,7 @@ impl InputOptions {
     pub fn set_normal_filter(&mut self, filter: NormalMapFilter) -> &mut Self {
         unsafe {
             nvttSetInputOptionsNormalFilter(
-                self.0.as_ptr(),
+                self.opts.as_ptr(),
                 filter.small,
                 filter.medium,
                 filter.big,
@@ -1188,6 +4390,7 @@ impl InputOptions {
             );
         }
 
+        self.state.normal_filter = Some(filter);
         self
     }
 
@@ -1195,20 +4398,36 @@ impl InputOptions {
     #[inline]
     pub fn set_round_mode(&mut self, round_mode: RoundMode) -> &mut Self {
         unsafe {
-            nvttSetInputOptionsRoundMode(self.0.as_ptr(), round_mode.into());
+            nvttSetInputOptionsRoundMode(self.opts.as_ptr(), round_mode.into());
         }
+        self.state.round_mode = Some(round_mode);
         self
     }
 
     /// Sets the layout of the texture on the `InputOptions`.
+    ///
+    /// # Errors
+    ///
+    /// If the width, height, depth or array length of the `texture_layout` is `0`, or
+    /// is too large to pass to nvtt, then this method will fail with
+    /// [`Error::InvalidInput`].
+    ///
+    /// [`Error::InvalidInput`]: enum.Error.html#variant.InvalidInput
     #[inline]
-    pub fn set_texture_layout(&mut self, texture_layout: TextureLayout) -> &mut Self {
+    pub fn set_texture_layout(
+        &mut self,
+        texture_layout: TextureLayout,
+    ) -> Result<&mut Self, Error> {
+        if !texture_layout.is_valid() {
+            return Err(Error::InvalidInput);
+        }
+
         let tex_type = texture_layout.texture_type();
         let tex_dims = texture_layout.dimensions();
 
         unsafe {
             nvttSetInputOptionsTextureLayout(
-                self.0.as_ptr(),
+                self.opts.as_ptr(),
                 tex_type.into(),
                 tex_dims.width,
                 tex_dims.height,
@@ -1217,105 +4436,605 @@ impl InputOptions {
             )
         }
 
-        self
+        self.texture_layout = Some(texture_layout);
+        self.source_hash = None;
+        self.mipmap_data_set.clear();
+        self.state.mipmap_data.clear();
+        Ok(self)
+    }
+
+    /// Check the state which has been set on the `InputOptions` for problems which
+    /// would cause nvtt to reject the input with `Error::InvalidInput`, or, for cube
+    /// maps, produce a texture with missing faces. Returns `None` if no problem was
+    /// found.
+    ///
+    /// This is used to explain `Error::InvalidInput` errors from [`Compressor::compress`],
+    /// and can also be called before compressing.
+    ///
+    /// [`Compressor::compress`]: struct.Compressor.html#method.compress
+    pub fn diagnose(&self) -> Option<InputDiagnosis> {
+        let layout = match self.texture_layout {
+            Some(layout) => layout,
+            None => return Some(InputDiagnosis::TextureLayoutNotSet),
+        };
+
+        let face_count = match layout {
+            TextureLayout::D2 { .. } | TextureLayout::D3 { .. } => 1,
+            TextureLayout::Array { array_length, .. } => array_length,
+            TextureLayout::Cube { .. } => {
+                let mut missing = [false; 6];
+                for (face, missing) in missing.iter_mut().enumerate() {
+                    *missing = !self.mipmap_data_set.contains_key(&(face as i32, 0));
+                }
+                return if missing.contains(&true) {
+                    Some(InputDiagnosis::MissingCubeFaces { missing })
+                } else {
+                    None
+                };
+            }
+        };
+
+        (0..face_count)
+            .find(|&face| !self.mipmap_data_set.contains_key(&(face as i32, 0)))
+            .map(|face| InputDiagnosis::MissingFaceData { face })
+    }
+
+    /// Get the `TextureLayout` which was last set on the `InputOptions`, if any.
+    #[inline]
+    pub fn texture_layout(&self) -> Option<TextureLayout> {
+        self.texture_layout
     }
 
     /// Set the `WrapMode` on the `InputOptions`.
     #[inline]
     pub fn set_wrap_mode(&mut self, wrap_mode: WrapMode) -> &mut Self {
         unsafe {
-            nvttSetInputOptionsWrapMode(self.0.as_ptr(), wrap_mode.into());
+            nvttSetInputOptionsWrapMode(self.opts.as_ptr(), wrap_mode.into());
         }
+        self.state.wrap_mode = Some(wrap_mode);
         self
     }
 }
 
-impl Drop for InputOptions {
-    #[inline]
-    fn drop(&mut self) {
-        unsafe { nvttDestroyInputOptions(self.0.as_ptr()) }
+/// Configure the options to compress a texture with 1-bit punch-through alpha, such as
+/// for cutout foliage or fences. Every texel with an alpha greater than
+/// `alpha_threshold` is opaque, and all other texels are fully transparent.
+///
+/// This sets the `format` on the `compression_options`, enables binary alpha
+/// quantization with the `alpha_threshold`, and sets `AlphaMode::Transparency` on the
+/// `input_options`. If the mipmap data which has been set on the `input_options` has
+/// many alpha values which are not close to `0` or `1`, then a warning is logged, as
+/// those texels will visibly change.
+///
+/// # Errors
+///
+/// If `format` is not `Format::Bc1a`, `Format::Dxt1a` or `Format::Etc2RgbA1`, then this
+/// function will fail with [`Error::UnsupportedOutputFormat`].
+///
+/// [`Error::UnsupportedOutputFormat`]: enum.Error.html#variant.UnsupportedOutputFormat
+#[cfg(feature = "nvtt")]
+pub fn configure_punch_through_alpha(
+    compression_options: &mut CompressionOptions,
+    input_options: &mut InputOptions,
+    format: Format,
+    alpha_threshold: u8,
+) -> Result<(), Error> {
+    match format {
+        Format::Bc1a | Format::Dxt1a | Format::Etc2RgbA1 => {}
+        _ => return Err(Error::UnsupportedOutputFormat),
     }
-}
-
-// @SAFETY: An `InputOptions` cannot be copied or unsafely mutated in a shared way.
-unsafe impl Send for InputOptions {}
-unsafe impl Sync for InputOptions {}
 
-cfg_if! {
-    if #[cfg(feature = "nvtt_image_integration")] {
-        use image::{Bgra, DynamicImage, ImageBuffer, Luma, Rgba};
-        use maybe_owned::MaybeOwned;
-        use safe_transmute::transmute_to_bytes;
-        use std::ops::Deref;
-
-        /// An enumeration of the valid image buffer types which can be
-        /// used with nvtt.
-        ///
-        /// # Notes
-        ///
-        /// This type requires the [`nvtt_image_integration`] feature.
-        ///
-        /// [`nvtt_image_integration`]: index.html#nvtt_image_integration
-        #[derive(Clone, Debug)]
-        pub enum ValidImage<'a> {
-            /// A bgra image with byte values for each subpixel.
-            Bgra(MaybeOwned<'a, ImageBuffer<Bgra<u8>, Vec<u8>>>),
-            /// An rgba image with floats for each subpixel.
-            Rgba(MaybeOwned<'a, ImageBuffer<Rgba<f32>, Vec<f32>>>),
-            /// A luma image with floats for each subpixel.
-            Luma(MaybeOwned<'a, ImageBuffer<Luma<f32>, Vec<f32>>>),
+    let (color_dither, alpha_dither) = compression_options.dithering();
+    compression_options.set_format(format).set_quanitzation(
+        color_dither == Dither::ErrorDiffusion,
+        alpha_dither == Dither::ErrorDiffusion,
+        true,
+        i32::from(alpha_threshold),
+    )?;
+    input_options.set_alpha_mode(AlphaMode::Transparency);
+
+    if let Some(fraction) = input_options.partial_alpha_fraction() {
+        if fraction > PARTIAL_ALPHA_WARN_FRACTION {
+            warn!(
+                "{:.1}% of the texels have partial alpha, which will be lost when \
+                 compressing to {}",
+                fraction * 100.0,
+                format
+            );
         }
+    }
 
-        impl ValidImage<'_> {
-            /// Create a new `ValidImage` from `image`.
-            #[inline]
-            pub fn new<I: Into<Self>>(image: I) -> Self {
-                image.into()
-            }
-
-            #[inline]
-            fn format(&self) -> InputFormat {
-                match *self {
-                    ValidImage::Bgra(_) => InputFormat::Bgra8Ub,
-                    ValidImage::Rgba(_) => InputFormat::Rgba32F,
-                    ValidImage::Luma(_) => InputFormat::R32F,
-                }
-            }
+    Ok(())
+}
 
-            #[inline]
-            fn image_dimensions(&self) -> (u32, u32) {
-                match *self {
-                    ValidImage::Bgra(ref i) => i.dimensions(),
-                    ValidImage::Rgba(ref i) => i.dimensions(),
-                    ValidImage::Luma(ref i) => i.dimensions(),
-                }
-            }
+/// The fraction of texels with partial alpha above which
+/// `configure_punch_through_alpha` logs a warning.
+#[cfg(feature = "nvtt")]
+const PARTIAL_ALPHA_WARN_FRACTION: f32 = 0.01;
 
-            #[inline]
-            fn data_bytes(&self) -> &[u8] {
-                match *self {
-                    ValidImage::Bgra(ref i) => i.deref(),
-                    ValidImage::Rgba(ref i) => transmute_to_bytes(i.deref()),
-                    ValidImage::Luma(ref i) => transmute_to_bytes(i.deref()),
-                }
-            }
+#[cfg(feature = "nvtt")]
+impl InputOptions {
+    /// Get the fraction of the alpha values in the mipmap data set on the
+    /// `InputOptions` which are not close to `0.0` or `1.0`. Returns `None` if no
+    /// mipmap data has been set.
+    fn partial_alpha_fraction(&self) -> Option<f32> {
+        let (partial, total) = self
+            .mipmap_data_set
+            .values()
+            .fold((0, 0), |(partial, total), counts| {
+                (partial + counts.0, total + counts.1)
+            });
+
+        if total == 0 {
+            None
+        } else {
+            Some(partial as f32 / total as f32)
         }
+    }
 
-        impl From<DynamicImage> for ValidImage<'_> {
-            #[inline]
-            fn from(img: DynamicImage) -> Self {
-                ValidImage::Bgra(MaybeOwned::Owned(img.to_bgra()))
-            }
+    /// If no mip filter colorspace or gamma has been set, then apply the gamma of
+    /// [`MipFilterColorspace::default_for`] the output `format` to the nvtt options.
+    /// The gamma is reset to nvtt's default when the returned guard is dropped, so
+    /// this must only be called while `compress_lock` is held.
+    ///
+    /// [`MipFilterColorspace::default_for`]: enum.MipFilterColorspace.html#method.default_for
+    fn default_gamma_for(&self, format: Format) -> Option<DefaultGamma> {
+        if self.state.mip_filter_colorspace.is_some() || self.state.gamma.is_some() {
+            return None;
         }
 
-        impl From<&'_ DynamicImage> for ValidImage<'_> {
-            #[inline]
-            fn from(img: &'_ DynamicImage) -> Self {
-                ValidImage::Bgra(MaybeOwned::Owned(img.to_bgra()))
+        // nvtt filters in linear light by default, so only the gamma colorspace needs
+        // to be applied.
+        match MipFilterColorspace::default_for(format) {
+            MipFilterColorspace::Linear => None,
+            MipFilterColorspace::Gamma => {
+                set_nvtt_gamma(self.opts, 1.0, 1.0);
+                Some(DefaultGamma(self.opts))
             }
         }
+    }
+}
 
-        macro_rules! impl_maybeowned_from {
+/// Resets the gamma of the nvtt options to nvtt's default of `2.2` when it is dropped.
+/// See `InputOptions::default_gamma_for`.
+#[cfg(feature = "nvtt")]
+struct DefaultGamma(NonNull<NvttInputOptions>);
+
+#[cfg(feature = "nvtt")]
+impl Drop for DefaultGamma {
+    fn drop(&mut self) {
+        let gamma = MipFilterColorspace::Linear.input_gamma();
+        set_nvtt_gamma(self.0, gamma, gamma);
+    }
+}
+
+/// Set the input and output gamma of the nvtt input options `opts`.
+#[cfg(feature = "nvtt")]
+fn set_nvtt_gamma(opts: NonNull<NvttInputOptions>, input_gamma: f32, output_gamma: f32) {
+    #[cfg(test)]
+    tests::NVTT_GAMMA.with(|gamma| gamma.borrow_mut().push((input_gamma, output_gamma)));
+    unsafe { nvttSetInputOptionsGamma(opts.as_ptr(), input_gamma, output_gamma) }
+}
+
+/// Count the alpha values in `data` of the given `format` which are not close to
+/// `0.0` or `1.0`, and the total number of alpha values.
+#[cfg(feature = "nvtt")]
+fn count_partial_alpha(format: InputFormat, data: &[u8]) -> (usize, usize) {
+    let is_partial = |alpha: f32| alpha > 0.05 && alpha < 0.95;
+    let count = |pixels: std::slice::ChunksExact<'_, u8>, alpha: &dyn Fn(&[u8]) -> f32| {
+        let total = pixels.len();
+        let partial = pixels.filter(|pixel| is_partial(alpha(pixel))).count();
+        (partial, total)
+    };
+
+    match format {
+        InputFormat::Bgra8Ub => count(data.chunks_exact(4), &|px| f32::from(px[3]) / 255.0),
+        InputFormat::Rgba16F => count(data.chunks_exact(8), &|px| {
+            surface::f16_to_f32(u16::from_ne_bytes([px[6], px[7]]))
+        }),
+        InputFormat::Rgba32F => count(data.chunks_exact(16), &|px| {
+            f32::from_ne_bytes([px[12], px[13], px[14], px[15]])
+        }),
+        // There is no alpha channel, so every texel is opaque.
+        InputFormat::R32F => (0, data.len() / 4),
+    }
+}
+
+#[cfg(feature = "nvtt")]
+impl Clone for InputOptions {
+    /// Create a new `InputOptions` with the same settings and mipmap data as this
+    /// one, by replaying them onto a new [`NvttInputOptions`].
+    ///
+    /// Only state which has been set through the methods of `InputOptions` is cloned.
+    /// Anything set directly on the pointer returned by [`as_raw`] is not copied. The
+    /// mipmap data is only cloned if it is retained, see [`set_retain_mipmap_data`].
+    ///
+    /// # Panics
+    ///
+    /// This will panic if nvtt fails to create the new `InputOptions`.
+    ///
+    /// [`as_raw`]: struct.InputOptions.html#method.as_raw
+    /// [`set_retain_mipmap_data`]: struct.InputOptions.html#method.set_retain_mipmap_data
+    /// [`NvttInputOptions`]: https://docs.rs/nvtt_sys/latest/nvtt_sys/struct.NvttInputOptions.html
+    fn clone(&self) -> Self {
+        let mut cloned = InputOptions::default();
+        self.state.replay(&mut cloned, self.texture_layout);
+        if self.state.retain_mipmap_data {
+            cloned.source_hash = self.source_hash;
+        }
+        cloned
+    }
+}
+
+/// Two `InputOptions` are equal if the same configuration and mipmap data has been set
+/// on them through their methods. Changes made through [`as_raw`] are not compared.
+/// Mipmap data which is not retained is compared by its hash.
+///
+/// [`as_raw`]: struct.InputOptions.html#method.as_raw
+#[cfg(feature = "nvtt")]
+impl PartialEq for InputOptions {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.texture_layout == other.texture_layout
+            && self.source_hash == other.source_hash
+            && self.state.key() == other.state.key()
+    }
+}
+
+#[cfg(feature = "nvtt")]
+impl Eq for InputOptions {}
+
+#[cfg(feature = "nvtt")]
+impl Hash for InputOptions {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.texture_layout.hash(state);
+        self.source_hash.hash(state);
+        self.state.key().hash(state);
+    }
+}
+
+/// The settings applied to an `InputOptions`. nvtt has no way to read these back or to
+/// copy its input options, so they are stored in order to implement `Clone`, `PartialEq`
+/// and `Hash`.
+#[cfg(feature = "nvtt")]
+#[derive(Debug, Default)]
+struct InputState {
+    alpha_mode: Option<AlphaMode>,
+    convert_to_normal_map: Option<NvttBoolean>,
+    format: Option<InputFormat>,
+    gamma: Option<(f32, f32)>,
+    mip_filter_colorspace: Option<MipFilterColorspace>,
+    height_evaluation: Option<[f32; 4]>,
+    mipmap_filter: Option<MipmapFilter>,
+    mipmap_generation: Option<(NvttBoolean, Option<u32>)>,
+    max_extents: Option<c_int>,
+    normal_map: Option<NvttBoolean>,
+    normalize_mipmaps: Option<NvttBoolean>,
+    normal_filter: Option<NormalMapFilter>,
+    round_mode: Option<RoundMode>,
+    wrap_mode: Option<WrapMode>,
+    /// Whether `set_mipmap_data` keeps a copy of its data. This is not compared.
+    retain_mipmap_data: bool,
+    /// A copy of the data passed to `set_mipmap_data` for each face and mipmap level,
+    /// if `retain_mipmap_data` is set.
+    mipmap_data: Vec<MipmapData>,
+}
+
+#[cfg(feature = "nvtt")]
+impl InputState {
+    /// Record a copy of the `data` for the given `face` and `mipmap`, replacing any
+    /// data which was previously set for them.
+    fn set_mipmap_data(&mut self, data: &[u8], extent: Extent3d, face: c_int, mipmap: c_int) {
+        let format = self.format.unwrap_or(InputFormat::Bgra8Ub);
+        let entry = MipmapData {
+            format,
+            extent,
+            face: CubeFace::ArrayLayer(face as u32),
+            mipmap: MipLevel(mipmap as u32),
+            data: data.to_vec(),
+        };
+        match self
+            .mipmap_data
+            .iter_mut()
+            .find(|existing| existing.face == entry.face && existing.mipmap == entry.mipmap)
+        {
+            Some(existing) => *existing = entry,
+            None => self.mipmap_data.push(entry),
+        }
+    }
+
+    /// The recorded settings, with floating point values replaced by their bits so that
+    /// they can be hashed.
+    fn key(&self) -> InputKey<'_> {
+        let mipmap_filter = self.mipmap_filter.map(|filter| match filter {
+            MipmapFilter::Box => (0, None),
+            MipmapFilter::Triangle => (1, None),
+            MipmapFilter::Kaiser(params) => (
+                2,
+                params.map(|p| [p.width.to_bits(), p.alpha.to_bits(), p.stretch.to_bits()]),
+            ),
+        });
+        let normal_filter = self.normal_filter.map(|f| {
+            [
+                f.small.to_bits(),
+                f.medium.to_bits(),
+                f.big.to_bits(),
+                f.large.to_bits(),
+            ]
+        });
+        (
+            (
+                self.alpha_mode,
+                self.convert_to_normal_map,
+                self.format,
+                self.gamma.map(|(i, o)| [i.to_bits(), o.to_bits()]),
+                self.mip_filter_colorspace,
+                self.height_evaluation
+                    .map(|scales| scales.map(f32::to_bits)),
+                mipmap_filter,
+            ),
+            (
+                self.mipmap_generation,
+                self.max_extents,
+                self.normal_map,
+                self.normalize_mipmaps,
+                normal_filter,
+                self.round_mode,
+            ),
+            self.wrap_mode,
+            &self.mipmap_data,
+        )
+    }
+
+    /// Apply the recorded settings to `opts`.
+    fn replay(&self, opts: &mut InputOptions, texture_layout: Option<TextureLayout>) {
+        if let Some(alpha_mode) = self.alpha_mode {
+            opts.set_alpha_mode(alpha_mode);
+        }
+        if let Some(convert_to_normal_map) = self.convert_to_normal_map {
+            opts.convert_to_normal_map(convert_to_normal_map);
+        }
+        if let Some((input_gamma, output_gamma)) = self.gamma {
+            let _ = opts.set_gamma(input_gamma, output_gamma);
+        }
+        if let Some(colorspace) = self.mip_filter_colorspace {
+            opts.set_mip_filter_colorspace(colorspace);
+        }
+        if let Some([r, g, b, a]) = self.height_evaluation {
+            opts.set_height_evaluation(r, g, b, a);
+        }
+        if let Some(mipmap_filter) = self.mipmap_filter {
+            opts.set_mipmap_filter(mipmap_filter);
+        }
+        if let Some((generate_mipmaps, max_level)) = self.mipmap_generation {
+            opts.set_mipmap_generation(generate_mipmaps, max_level);
+        }
+        if let Some(max_extents) = self.max_extents {
+            let _ = opts.set_max_extents(max_extents);
+        }
+        if let Some(normal_map) = self.normal_map {
+            opts.set_normal_map(normal_map);
+        }
+        if let Some(normalize_mipmaps) = self.normalize_mipmaps {
+            opts.set_normalize_mipmaps(normalize_mipmaps);
+        }
+        if let Some(normal_filter) = self.normal_filter {
+            opts.set_normal_filter(normal_filter);
+        }
+        if let Some(round_mode) = self.round_mode {
+            opts.set_round_mode(round_mode);
+        }
+        if let Some(wrap_mode) = self.wrap_mode {
+            opts.set_wrap_mode(wrap_mode);
+        }
+        if let Some(texture_layout) = texture_layout {
+            let _ = opts.set_texture_layout(texture_layout);
+        }
+        opts.set_retain_mipmap_data(self.retain_mipmap_data);
+        // The format is applied before each mipmap, as nvtt reads the data in the
+        // format which is set at the time.
+        for mipmap in &self.mipmap_data {
+            let _ = opts.set_format(mipmap.format).set_mipmap_data(
+                &mipmap.data,
+                mipmap.extent,
+                mipmap.face,
+                mipmap.mipmap,
+            );
+        }
+        match self.format {
+            Some(format) => {
+                opts.set_format(format);
+            }
+            None => opts.state.format = None,
+        }
+    }
+}
+
+#[cfg(feature = "nvtt")]
+type InputKey<'a> = (
+    (
+        Option<AlphaMode>,
+        Option<NvttBoolean>,
+        Option<InputFormat>,
+        Option<[u32; 2]>,
+        Option<MipFilterColorspace>,
+        Option<[u32; 4]>,
+        Option<(u8, Option<[u32; 3]>)>,
+    ),
+    (
+        Option<(NvttBoolean, Option<u32>)>,
+        Option<c_int>,
+        Option<NvttBoolean>,
+        Option<NvttBoolean>,
+        Option<[u32; 4]>,
+        Option<RoundMode>,
+    ),
+    Option<WrapMode>,
+    &'a [MipmapData],
+);
+
+/// A copy of the data passed to `InputOptions::set_mipmap_data`.
+#[cfg(feature = "nvtt")]
+#[derive(Eq, Hash, PartialEq)]
+struct MipmapData {
+    format: InputFormat,
+    extent: Extent3d,
+    face: CubeFace,
+    mipmap: MipLevel,
+    data: Vec<u8>,
+}
+
+#[cfg(feature = "nvtt")]
+impl fmt::Debug for MipmapData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MipmapData")
+            .field("format", &self.format)
+            .field("extent", &self.extent)
+            .field("face", &self.face)
+            .field("mipmap", &self.mipmap)
+            .field("len", &self.data.len())
+            .finish()
+    }
+}
+
+#[cfg(feature = "nvtt")]
+impl fmt::Debug for InputOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = &self.state;
+        let flag = |flag: Option<NvttBoolean>| flag.map(bool::from);
+        f.debug_struct("InputOptions")
+            .field("texture_layout", &self.texture_layout)
+            .field("format", &state.format)
+            .field("alpha_mode", &state.alpha_mode)
+            .field("gamma", &state.gamma)
+            .field("mip_filter_colorspace", &state.mip_filter_colorspace)
+            .field("wrap_mode", &state.wrap_mode)
+            .field("round_mode", &state.round_mode)
+            .field("max_extents", &state.max_extents)
+            .field("mipmap_filter", &state.mipmap_filter)
+            .field(
+                "mipmap_generation",
+                &state
+                    .mipmap_generation
+                    .map(|(generate, max_level)| (bool::from(generate), max_level)),
+            )
+            .field("normal_map", &flag(state.normal_map))
+            .field("convert_to_normal_map", &flag(state.convert_to_normal_map))
+            .field("normalize_mipmaps", &flag(state.normalize_mipmaps))
+            .field("normal_filter", &state.normal_filter)
+            .field("height_evaluation", &state.height_evaluation)
+            .field("retain_mipmap_data", &state.retain_mipmap_data)
+            .field("mipmaps", &state.mipmap_data)
+            .finish()
+    }
+}
+
+#[cfg(feature = "nvtt")]
+impl Default for InputOptions {
+    /// Create a new `InputOptions`. See [`InputOptions::new`].
+    ///
+    /// # Panics
+    ///
+    /// This will panic if nvtt fails to allocate the `InputOptions`.
+    ///
+    /// [`InputOptions::new`]: struct.InputOptions.html#method.new
+    #[inline]
+    fn default() -> Self {
+        Self::new().expect("could not create the InputOptions")
+    }
+}
+
+#[cfg(feature = "nvtt")]
+impl Drop for InputOptions {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe { nvttDestroyInputOptions(self.opts.as_ptr()) }
+    }
+}
+
+// @SAFETY: An `InputOptions` cannot be copied. Its gamma is only mutated through a
+// shared reference by `Compressor::compress`, which holds `compress_lock` while it does so.
+#[cfg(feature = "nvtt")]
+unsafe impl Send for InputOptions {}
+#[cfg(feature = "nvtt")]
+unsafe impl Sync for InputOptions {}
+
+cfg_if! {
+    if #[cfg(all(feature = "nvtt", feature = "nvtt_image_integration"))] {
+        use image::{Bgra, DynamicImage, ImageBuffer, Luma, Rgba};
+        use maybe_owned::MaybeOwned;
+        use safe_transmute::transmute_to_bytes;
+        use std::ops::Deref;
+
+        /// An enumeration of the valid image buffer types which can be
+        /// used with nvtt.
+        ///
+        /// # Notes
+        ///
+        /// This type requires the [`nvtt_image_integration`] feature.
+        ///
+        /// [`nvtt_image_integration`]: index.html#nvtt_image_integration
+        #[derive(Clone, Debug)]
+        pub enum ValidImage<'a> {
+            /// A bgra image with byte values for each subpixel.
+            Bgra(MaybeOwned<'a, ImageBuffer<Bgra<u8>, Vec<u8>>>),
+            /// An rgba image with floats for each subpixel.
+            Rgba(MaybeOwned<'a, ImageBuffer<Rgba<f32>, Vec<f32>>>),
+            /// A luma image with floats for each subpixel.
+            Luma(MaybeOwned<'a, ImageBuffer<Luma<f32>, Vec<f32>>>),
+        }
+
+        impl ValidImage<'_> {
+            /// Create a new `ValidImage` from `image`.
+            #[inline]
+            pub fn new<I: Into<Self>>(image: I) -> Self {
+                image.into()
+            }
+
+            #[inline]
+            fn format(&self) -> InputFormat {
+                match *self {
+                    ValidImage::Bgra(_) => InputFormat::Bgra8Ub,
+                    ValidImage::Rgba(_) => InputFormat::Rgba32F,
+                    ValidImage::Luma(_) => InputFormat::R32F,
+                }
+            }
+
+            #[inline]
+            fn image_dimensions(&self) -> (u32, u32) {
+                match *self {
+                    ValidImage::Bgra(ref i) => i.dimensions(),
+                    ValidImage::Rgba(ref i) => i.dimensions(),
+                    ValidImage::Luma(ref i) => i.dimensions(),
+                }
+            }
+
+            #[inline]
+            fn data_bytes(&self) -> &[u8] {
+                match *self {
+                    ValidImage::Bgra(ref i) => i.deref(),
+                    ValidImage::Rgba(ref i) => transmute_to_bytes(i.deref()),
+                    ValidImage::Luma(ref i) => transmute_to_bytes(i.deref()),
+                }
+            }
+        }
+
+        impl From<DynamicImage> for ValidImage<'_> {
+            #[inline]
+            fn from(img: DynamicImage) -> Self {
+                ValidImage::Bgra(MaybeOwned::Owned(img.to_bgra()))
+            }
+        }
+
+        impl From<&'_ DynamicImage> for ValidImage<'_> {
+            #[inline]
+            fn from(img: &'_ DynamicImage) -> Self {
+                ValidImage::Bgra(MaybeOwned::Owned(img.to_bgra()))
+            }
+        }
+
+        macro_rules! impl_maybeowned_from {
             ($( ($pix:ident, $subpix:ident) ),+ $(,)?) => {
                 $(
                     impl From<ImageBuffer<$pix<$subpix>, Vec<$subpix>>> for ValidImage<'_> {
@@ -1351,28 +5070,236 @@ cfg_if! {
 /// Object which stores the output options for the texture. This provides a
 /// safer interface for the [`NvttOutputOptions`] type.
 ///
+/// The [`Destination`] type parameter records where the texture is written. An
+/// `OutputOptions` created with [`new`] can write to any [`OutputLocation`], and
+/// compressing with it returns a [`CompressionOutput`]. Converting it with
+/// [`into_file`] or [`into_buffer`] fixes the destination, so that
+/// [`Compressor::compress`] returns `()` or a [`CompressedTexture`] respectively.
+///
+/// ```no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use nvtt_rs::{CompressionOptions, Compressor, InputOptions, OutputOptions};
+///
+/// let compressor = Compressor::new()?;
+/// let input_options = InputOptions::new()?;
+/// let compression_options = CompressionOptions::new()?;
+/// let output_options = OutputOptions::new()?.into_buffer();
+///
+/// let texture = compressor.compress(&compression_options, &input_options, &output_options)?;
+/// std::fs::write("OutFile.dds", &texture.data[..])?;
+/// # Ok(())
+/// # }
+/// ```
+///
 /// [`NvttOutputOptions`]: https://docs.rs/nvtt_sys/latest/nvtt_sys/struct.NvttOutputOptions.html
-#[derive(Debug)]
-pub struct OutputOptions {
+/// [`Destination`]: trait.Destination.html
+/// [`new`]: struct.OutputOptions.html#method.new
+/// [`OutputLocation`]: enum.OutputLocation.html
+/// [`CompressionOutput`]: enum.CompressionOutput.html
+/// [`into_file`]: struct.OutputOptions.html#method.into_file
+/// [`into_buffer`]: struct.OutputOptions.html#method.into_buffer
+/// [`Compressor::compress`]: struct.Compressor.html#method.compress
+/// [`CompressedTexture`]: struct.CompressedTexture.html
+#[cfg(feature = "nvtt")]
+pub struct OutputOptions<D = AnyDestination> {
     out_opts: NonNull<NvttOutputOptions>,
-    /// If this is `true`, then the `OutputOptions` will use nvtt's native file output
-    /// system rather than using the callbacks.
-    write_to_file: bool,
+    /// If this is `Some`, then the output callbacks write the texture into the file
+    /// at the path rather than into memory.
+    out_path: Option<PathBuf>,
+    container: Container,
+    srgb_flag: bool,
+    write_header: bool,
+    /// If this is `true`, then the dds10 header is rewritten after compression to
+    /// describe the array texture as an array of cube maps.
+    cube_array: bool,
+    #[cfg(feature = "serde-serialize")]
+    write_metadata: bool,
+    /// If this is `true`, then the file at `out_path` is written through a memory
+    /// map rather than a buffered writer.
+    memory_mapped: bool,
+    progress_handler: Option<ProgressHandler>,
+    record_report: bool,
+    report_metrics: bool,
+    /// The report of the last compression, if `record_report` is enabled.
+    report: Mutex<Option<CompressionReport>>,
+    /// Held while compressing, because the handlers of the nvtt options are set
+    /// through a shared reference.
+    compress_lock: Mutex<()>,
+    destination: PhantomData<fn() -> D>,
 }
 
+#[cfg(feature = "nvtt")]
 impl OutputOptions {
     /// Create a new `OutputOptions`.
     #[inline]
     pub fn new() -> Result<Self, Error> {
         let opts = unsafe { nvttCreateOutputOptions() };
-        NonNull::new(opts)
-            .ok_or(Error::Unknown)
+        // @SAFETY: The pointer was just created by nvtt, and is owned by nothing else.
+        unsafe { Self::from_raw(opts) }.map_err(|_| Error::Unknown)
+    }
+
+    /// Take ownership of a [`NvttOutputOptions`] pointer, such as one returned from
+    /// [`into_raw`]. The pointer is destroyed when the `OutputOptions` is dropped.
+    ///
+    /// The output location and container cannot be read back from nvtt, so the
+    /// `OutputOptions` starts with its default settings. Any handlers installed on
+    /// the native options are replaced when compressing to an [`OutputLocation`]
+    /// which uses them.
+    ///
+    /// # Errors
+    ///
+    /// If `raw` is null, then this method will fail with [`Error::InvalidInput`].
+    ///
+    /// # Safety
+    ///
+    /// `raw` must have been created by [`nvttCreateOutputOptions`], and must not be
+    /// owned or destroyed by anything else.
+    ///
+    /// [`into_raw`]: struct.OutputOptions.html#method.into_raw
+    /// [`OutputLocation`]: enum.OutputLocation.html
+    /// [`Error::InvalidInput`]: enum.Error.html#variant.InvalidInput
+    /// [`nvttCreateOutputOptions`]: https://docs.rs/nvtt_sys/latest/nvtt_sys/fn.nvttCreateOutputOptions.html
+    /// [`NvttOutputOptions`]: https://docs.rs/nvtt_sys/latest/nvtt_sys/struct.NvttOutputOptions.html
+    #[inline]
+    pub unsafe fn from_raw(raw: *mut NvttOutputOptions) -> Result<Self, Error> {
+        NonNull::new(raw)
+            .ok_or(Error::InvalidInput)
             .map(|out_opts| OutputOptions {
                 out_opts,
-                write_to_file: false,
+                out_path: None,
+                container: Container::Dds,
+                srgb_flag: false,
+                write_header: true,
+                cube_array: false,
+                #[cfg(feature = "serde-serialize")]
+                write_metadata: false,
+                memory_mapped: false,
+                progress_handler: None,
+                record_report: false,
+                report_metrics: false,
+                report: Mutex::new(None),
+                compress_lock: Mutex::new(()),
+                destination: PhantomData,
             })
     }
 
+    /// Set the output location. This can be either a path or an in-memory
+    /// buffer. For more information, see the [`OutputLocation`] type.
+    ///
+    /// # Notes
+    ///
+    /// Files are written by the `OutputOptions` rather than by nvtt, so any path which
+    /// can be opened is supported, and this method does not currently fail. The data
+    /// can also be written into a file using another method by passing
+    /// [`OutputLocation::Buffer`], as shown below.
+    ///
+    /// The `OutputOptions` will write to a buffer unless specified otherwise.
+    ///
+    /// ## Example
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use nvtt_rs::{
+    ///     CompressionOptions, CompressionOutput, Compressor,
+    ///     InputOptions, OutputLocation, OutputOptions,
+    /// };
+    ///
+    /// let compressor = Compressor::new()?;
+    /// let input_opts = InputOptions::new()?;
+    /// let compression_opts = CompressionOptions::new()?;
+    /// let mut output_opts = OutputOptions::new()?;
+    ///
+    /// output_opts.set_output_location(OutputLocation::Buffer);
+    ///
+    /// // Set other texture options here...
+    ///
+    /// match compressor.compress(&compression_opts, &input_opts, &output_opts)? {
+    ///     CompressionOutput::Memory { data, .. } => {
+    ///         std::fs::write("OutFile.dds", &data[..])?;
+    ///     }
+    ///     _ => {}
+    /// };
+    ///
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`OutputLocation`]: enum.OutputLocation.html
+    /// [`OutputLocation::Buffer`]: enum.OutputLocation.html#variant.Buffer
+    #[inline]
+    pub fn set_output_location<'a, T: 'a + ?Sized + Into<OutputLocation<'a>>>(
+        &mut self,
+        out_location: T,
+    ) -> Result<&mut Self, PathConvertError> {
+        #[inline(never)]
+        fn inner(
+            opts: &mut OutputOptions,
+            loc: OutputLocation<'_>,
+        ) -> Result<(), PathConvertError> {
+            match loc {
+                OutputLocation::File(p) => {
+                    opts.out_path = Some(p.to_path_buf());
+                    opts.memory_mapped = false;
+                    Ok(())
+                }
+                OutputLocation::MappedFile(p) => {
+                    opts.out_path = Some(p.to_path_buf());
+                    opts.memory_mapped = true;
+                    Ok(())
+                }
+                OutputLocation::Buffer => {
+                    opts.out_path = None;
+                    opts.memory_mapped = false;
+                    Ok(())
+                }
+            }
+        }
+
+        inner(self, out_location.into()).map(|_| self)
+    }
+
+    /// Fix the destination of the `OutputOptions` to the file at `path`. Compressing
+    /// with the returned options writes the texture into the file, and returns `()`.
+    ///
+    /// # Errors
+    ///
+    /// See [`set_output_location`].
+    ///
+    /// [`set_output_location`]: struct.OutputOptions.html#method.set_output_location
+    pub fn into_file<P: AsRef<Path>>(
+        mut self,
+        path: P,
+    ) -> Result<OutputOptions<ToFile>, PathConvertError> {
+        self.set_output_location(path.as_ref())?;
+        Ok(self.retype())
+    }
+
+    /// Fix the destination of the `OutputOptions` to the file at `path`, which is
+    /// written through a memory map. See [`OutputLocation::MappedFile`].
+    ///
+    /// The file is only memory mapped with the [`memmap-output`] feature.
+    ///
+    /// [`OutputLocation::MappedFile`]: enum.OutputLocation.html#variant.MappedFile
+    /// [`memmap-output`]: index.html#memmap-output
+    pub fn into_mapped_file<P: AsRef<Path>>(mut self, path: P) -> OutputOptions<ToFile> {
+        self.out_path = Some(path.as_ref().to_path_buf());
+        self.memory_mapped = true;
+        self.retype()
+    }
+
+    /// Fix the destination of the `OutputOptions` to an in-memory buffer. Compressing
+    /// with the returned options returns the texture as a [`CompressedTexture`].
+    ///
+    /// [`CompressedTexture`]: struct.CompressedTexture.html
+    pub fn into_buffer(mut self) -> OutputOptions<ToBuffer> {
+        self.out_path = None;
+        self.memory_mapped = false;
+        self.retype()
+    }
+}
+
+#[cfg(feature = "nvtt")]
+impl<D> OutputOptions<D> {
     /// Returns the underlying [`NvttOutputOptions`] pointer type. It is your responsibility
     /// to call [`nvttDestroyOutputOptions`] on this value to clean up the [`NvttOutputOptions`]
     /// resources.
@@ -1386,141 +5313,675 @@ impl OutputOptions {
         ptr
     }
 
-    /// Set the output location. This can be either a path or an in-memory
-    /// buffer. For more information, see the [`OutputLocation`] type.
-    ///
-    /// # Notes
-    ///
-    /// `nvtt` only supports ASCII filenames on Windows. If you need to support
-    /// non-ASCII filenames, you will need to pass [`OutputLocation::Buffer`],
-    /// and then write the data into the file using another method. An example of
-    /// to do this is shown below.
-    ///
-    /// The `OutputOptions` will write to a buffer unless specified otherwise.
-    ///
-    /// ## Example
-    ///
-    /// ```no_run
-    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
-    /// use nvtt_rs::{
-    ///     CompressionOptions, CompressionOutput, Compressor,
-    ///     InputOptions, OutputLocation, OutputOptions,
-    /// };
-    ///
-    /// let compressor = Compressor::new()?;
-    /// let input_opts = InputOptions::new()?;
-    /// let compression_opts = CompressionOptions::new()?;
-    /// let mut output_opts = OutputOptions::new()?;
-    ///
-    /// output_opts.set_output_location(OutputLocation::Buffer);
-    ///
-    /// // Set other texture options here...
-    ///
-    /// match compressor.compress(&compression_opts, &input_opts, &output_opts)? {
-    ///     CompressionOutput::Memory { data, .. } => {
-    ///         std::fs::write("OutFile.dds", &data[..])?;
-    ///     }
-    ///     _ => {}
-    /// };
-    ///
-    /// # Ok(())
-    /// # }
-    /// ```
-    ///
-    /// [`OutputLocation`]: enum.OutputLocation.html
-    /// [`OutputLocation::Buffer`]: enum.OutputLocation.html#variant.Buffer
+    /// Returns the underlying [`NvttOutputOptions`] pointer without giving up ownership of it.
+    /// The pointer is valid for as long as this `OutputOptions` is alive, and is destroyed
+    /// when the `OutputOptions` is dropped.
+    ///
+    /// This can be used to call nvtt functions which are not wrapped by this crate.
+    /// Mutating the options through this pointer is unsafe: replacing the output
+    /// handlers installed by the `OutputOptions` will break writing to its
+    /// [`OutputLocation`], and the options must not be destroyed through it.
+    ///
+    /// [`OutputLocation`]: enum.OutputLocation.html
+    ///
+    /// [`NvttOutputOptions`]: https://docs.rs/nvtt_sys/latest/nvtt_sys/struct.NvttOutputOptions.html
+    #[inline]
+    pub fn as_raw(&self) -> *mut NvttOutputOptions {
+        self.out_opts.as_ptr()
+    }
+
+    /// Get the output location which was last set on the `OutputOptions`. This
+    /// is [`OutputLocation::Buffer`] by default.
+    ///
+    /// [`OutputLocation::Buffer`]: enum.OutputLocation.html#variant.Buffer
+    #[inline]
+    pub fn output_location(&self) -> OutputLocation<'_> {
+        match self.out_path {
+            Some(ref p) if self.memory_mapped => OutputLocation::MappedFile(p),
+            Some(ref p) => OutputLocation::File(p),
+            None => OutputLocation::Buffer,
+        }
+    }
+
+    /// Set a handler which is called with the current [`OutputProgress`] every time
+    /// compressed data is written while [`Compressor::compress`] is running.
+    ///
+    /// # Notes
+    ///
+    /// * The handler is called on the thread which called [`Compressor::compress`].
+    /// * If the output location is a file, then the data will be written by this crate
+    ///   rather than by nvtt while a progress handler is set.
+    /// * Panicking inside the handler will abort the process, as it is called from
+    ///   inside nvtt.
+    ///
+    /// [`OutputProgress`]: struct.OutputProgress.html
+    /// [`Compressor::compress`]: struct.Compressor.html#method.compress
+    #[inline]
+    pub fn set_progress_handler<F>(&mut self, handler: F) -> &mut Self
+    where
+        F: 'static + Fn(&OutputProgress) + Send + Sync,
+    {
+        self.progress_handler = Some(ProgressHandler(Box::new(handler)));
+        self
+    }
+
+    /// Remove the progress handler from the `OutputOptions`, if one has been set.
+    #[inline]
+    pub fn clear_progress_handler(&mut self) -> &mut Self {
+        self.progress_handler = None;
+        self
+    }
+
+    /// Set whether a [`CompressionReport`] is recorded while compressing, which lists
+    /// the time taken to encode each face and mipmap level, and its compressed size.
+    /// The report of the last compression can be read with [`report`].
+    ///
+    /// If the output location is a file, then the data will be written by this crate
+    /// rather than by nvtt while reports are recorded.
+    ///
+    /// [`CompressionReport`]: struct.CompressionReport.html
+    /// [`report`]: struct.OutputOptions.html#method.report
+    #[inline]
+    pub fn set_record_report(&mut self, record_report: bool) -> &mut Self {
+        self.record_report = record_report;
+        self
+    }
+
+    /// Returns `true` if a [`CompressionReport`] is recorded while compressing. This is
+    /// `false` by default.
+    ///
+    /// [`CompressionReport`]: struct.CompressionReport.html
+    #[inline]
+    pub fn record_report(&self) -> bool {
+        self.record_report
+    }
+
+    /// Set whether the recorded [`CompressionReport`] includes the [`QualityMetrics`]
+    /// of each image. This has no effect unless [`set_record_report`] is enabled.
+    ///
+    /// Measuring the error needs the source images, so the metrics are only measured
+    /// when compressing with a [`Context`], and only for formats which can be decoded
+    /// by [`transcode`].
+    ///
+    /// [`CompressionReport`]: struct.CompressionReport.html
+    /// [`QualityMetrics`]: struct.QualityMetrics.html
+    /// [`set_record_report`]: struct.OutputOptions.html#method.set_record_report
+    /// [`Context`]: struct.Context.html
+    /// [`transcode`]: fn.transcode.html
+    #[inline]
+    pub fn set_report_metrics(&mut self, report_metrics: bool) -> &mut Self {
+        self.report_metrics = report_metrics;
+        self
+    }
+
+    /// Returns `true` if the recorded [`CompressionReport`] includes the
+    /// [`QualityMetrics`] of each image. This is `false` by default.
+    ///
+    /// [`CompressionReport`]: struct.CompressionReport.html
+    /// [`QualityMetrics`]: struct.QualityMetrics.html
+    #[inline]
+    pub fn report_metrics(&self) -> bool {
+        self.record_report && self.report_metrics
+    }
+
+    /// Get the [`CompressionReport`] of the last compression which used these options,
+    /// or `None` if no report has been recorded. See [`set_record_report`].
+    ///
+    /// [`CompressionReport`]: struct.CompressionReport.html
+    /// [`set_record_report`]: struct.OutputOptions.html#method.set_record_report
+    pub fn report(&self) -> Option<CompressionReport> {
+        self.report
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Run `f` on the report of the last compression, if one was recorded.
+    pub(crate) fn update_report(&self, f: impl FnOnce(&mut CompressionReport)) {
+        if let Some(report) = self
+            .report
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .as_mut()
+        {
+            f(report);
+        }
+    }
+
+    /// Returns the path of the output file if it should be written through a memory map.
+    #[cfg(feature = "memmap-output")]
+    #[inline]
+    fn mapped_path(&self) -> Option<&Path> {
+        match self.out_path {
+            Some(ref p) if self.memory_mapped => Some(p),
+            _ => None,
+        }
+    }
+
+    /// If set to `true`, then the `OutputOptions` will write texture metadata into a
+    /// header section of the file.
+    #[inline]
+    pub fn set_write_header<B: Into<NvttBoolean>>(&mut self, write_header: B) -> &mut Self {
+        let write_header = write_header.into();
+        unsafe {
+            nvttSetOutputOptionsOutputHeader(self.out_opts.as_ptr(), write_header);
+        }
+        self.write_header = write_header.into();
+        self
+    }
+
+    /// Returns `true` if the `OutputOptions` will write a header section into the file.
+    /// This is `true` by default.
+    #[inline]
+    pub fn write_header(&self) -> bool {
+        self.write_header
+    }
+
+    /// If `write_srgb` is set to true, then the output image will be in the [sRGB] colorspace.
+    ///
+    /// [sRGB]: https://en.wikipedia.org/wiki/SRGB
+    #[inline]
+    pub fn set_srgb_flag<B: Into<NvttBoolean>>(&mut self, write_srgb: B) -> &mut Self {
+        let write_srgb = write_srgb.into();
+        unsafe {
+            nvttSetOutputOptionsSrgbFlag(self.out_opts.as_ptr(), write_srgb);
+        }
+        self.srgb_flag = write_srgb.into();
+        self
+    }
+
+    /// Returns `true` if the output image will be flagged as being in the [sRGB] colorspace.
+    /// This is `false` by default.
+    ///
+    /// [sRGB]: https://en.wikipedia.org/wiki/SRGB
+    #[inline]
+    pub fn srgb_flag(&self) -> bool {
+        self.srgb_flag
+    }
+
+    /// Set the `Container` type of the output image.
+    #[inline]
+    pub fn set_container(&mut self, container: Container) -> &mut Self {
+        unsafe {
+            nvttSetOutputOptionsContainer(self.out_opts.as_ptr(), container.into());
+        }
+        self.container = container;
+        self
+    }
+
+    /// Get the `Container` type of the output image. This is `Container::Dds` by default.
+    #[inline]
+    pub fn container(&self) -> Container {
+        self.container
+    }
+
+    /// If set to `true`, then the output is written as an array of cube maps. nvtt
+    /// cannot compress cube map arrays directly, so the input must be an array texture
+    /// with six layers for each cube map, laid out with [`TextureLayout::cube_array`],
+    /// and the dds10 header is rewritten once the texture has been compressed.
+    ///
+    /// When this is enabled, compressing fails with `Error::InvalidInput` unless the
+    /// container is `Container::Dds10`, and the input is an array texture with a
+    /// multiple of six layers.
+    ///
+    /// [`TextureLayout::cube_array`]: enum.TextureLayout.html#method.cube_array
+    #[inline]
+    pub fn set_cube_array(&mut self, cube_array: bool) -> &mut Self {
+        self.cube_array = cube_array;
+        self
+    }
+
+    /// Returns `true` if the output is written as an array of cube maps. This is
+    /// `false` by default.
+    #[inline]
+    pub fn cube_array(&self) -> bool {
+        self.cube_array
+    }
+
+    /// If set to `true`, then a `{output}.meta.json` file describing the texture will be
+    /// written next to the output file. See the [`TextureMetadata`] type for the contents
+    /// of the file.
+    ///
+    /// This only has an effect when the output location is a file.
+    ///
+    /// # Notes
+    ///
+    /// This method requires the [`serde-serialize`] feature.
+    ///
+    /// [`TextureMetadata`]: struct.TextureMetadata.html
+    /// [`serde-serialize`]: index.html#serde-serialize
+    #[cfg(feature = "serde-serialize")]
+    #[inline]
+    pub fn set_write_metadata(&mut self, write_metadata: bool) -> &mut Self {
+        self.write_metadata = write_metadata;
+        self
+    }
+
+    /// Returns `true` if a metadata sidecar file will be written next to the output file.
+    ///
+    /// # Notes
+    ///
+    /// This method requires the [`serde-serialize`] feature.
+    ///
+    /// [`serde-serialize`]: index.html#serde-serialize
+    #[cfg(feature = "serde-serialize")]
+    #[inline]
+    pub fn write_metadata(&self) -> bool {
+        self.write_metadata
+    }
+
+    /// Erase the destination of the `OutputOptions`, so that it can be passed to
+    /// functions which take untyped options. The output location is kept.
+    #[inline]
+    pub fn into_any(self) -> OutputOptions {
+        self.retype()
+    }
+
+    fn retype<E>(self) -> OutputOptions<E> {
+        let this = mem::ManuallyDrop::new(self);
+        // @SAFETY: Every field is read out of `this` exactly once, and `this` is never
+        // dropped, so ownership of the fields and the nvtt options moves to the result.
+        unsafe {
+            OutputOptions {
+                out_opts: this.out_opts,
+                out_path: ptr::read(&this.out_path),
+                container: this.container,
+                srgb_flag: this.srgb_flag,
+                write_header: this.write_header,
+                cube_array: this.cube_array,
+                #[cfg(feature = "serde-serialize")]
+                write_metadata: this.write_metadata,
+                memory_mapped: this.memory_mapped,
+                progress_handler: ptr::read(&this.progress_handler),
+                record_report: this.record_report,
+                report_metrics: this.report_metrics,
+                report: ptr::read(&this.report),
+                compress_lock: ptr::read(&this.compress_lock),
+                destination: PhantomData,
+            }
+        }
+    }
+}
+
+#[cfg(feature = "nvtt")]
+impl<D> fmt::Debug for OutputOptions<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("OutputOptions");
+        debug
+            .field("out_path", &self.out_path)
+            .field("container", &self.container)
+            .field("srgb_flag", &self.srgb_flag)
+            .field("write_header", &self.write_header)
+            .field("cube_array", &self.cube_array);
+        #[cfg(feature = "serde-serialize")]
+        debug.field("write_metadata", &self.write_metadata);
+        debug.field("memory_mapped", &self.memory_mapped);
+        debug
+            .field("progress_handler", &self.progress_handler)
+            .field("record_report", &self.record_report)
+            .field("report_metrics", &self.report_metrics)
+            .finish()
+    }
+}
+
+#[cfg(feature = "nvtt")]
+impl Default for OutputOptions {
+    /// Create a new `OutputOptions`. See [`OutputOptions::new`].
+    ///
+    /// # Panics
+    ///
+    /// This will panic if nvtt fails to allocate the `OutputOptions`.
+    ///
+    /// [`OutputOptions::new`]: struct.OutputOptions.html#method.new
+    #[inline]
+    fn default() -> Self {
+        Self::new().expect("could not create the OutputOptions")
+    }
+}
+
+#[cfg(feature = "nvtt")]
+impl<D> Drop for OutputOptions<D> {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe { nvttDestroyOutputOptions(self.out_opts.as_ptr()) }
+    }
+}
+
+// @SAFETY: An `OutputOptions` cannot be copied. The nvtt options are only mutated through
+// a shared reference by `Compressor::compress`, which holds `compress_lock` while it does so.
+#[cfg(feature = "nvtt")]
+unsafe impl<D> Send for OutputOptions<D> {}
+#[cfg(feature = "nvtt")]
+unsafe impl<D> Sync for OutputOptions<D> {}
+
+/// The destination of an [`OutputOptions`], which determines the type returned by
+/// [`Compressor::compress`]. This trait is sealed, and is implemented by
+/// [`AnyDestination`], [`ToFile`] and [`ToBuffer`].
+///
+/// [`OutputOptions`]: struct.OutputOptions.html
+/// [`Compressor::compress`]: struct.Compressor.html#method.compress
+/// [`AnyDestination`]: enum.AnyDestination.html
+/// [`ToFile`]: enum.ToFile.html
+/// [`ToBuffer`]: enum.ToBuffer.html
+#[cfg(feature = "std")]
+pub trait Destination: destination::Sealed {
+    /// The result of a successful compression.
+    type Output;
+
+    #[doc(hidden)]
+    fn from_output(output: CompressionOutput) -> Self::Output;
+}
+
+#[cfg(feature = "std")]
+mod destination {
+    pub trait Sealed {}
+
+    impl Sealed for super::AnyDestination {}
+    impl Sealed for super::ToFile {}
+    impl Sealed for super::ToBuffer {}
+}
+
+/// The destination of an [`OutputOptions`] which can write to any [`OutputLocation`].
+///
+/// [`OutputOptions`]: struct.OutputOptions.html
+/// [`OutputLocation`]: enum.OutputLocation.html
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum AnyDestination {}
+
+#[cfg(feature = "std")]
+impl Destination for AnyDestination {
+    type Output = CompressionOutput;
+
+    #[inline]
+    fn from_output(output: CompressionOutput) -> Self::Output {
+        output
+    }
+}
+
+/// The destination of an [`OutputOptions`] which writes to a file.
+///
+/// [`OutputOptions`]: struct.OutputOptions.html
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ToFile {}
+
+#[cfg(feature = "std")]
+impl Destination for ToFile {
+    type Output = ();
+
+    #[inline]
+    fn from_output(_: CompressionOutput) -> Self::Output {}
+}
+
+/// The destination of an [`OutputOptions`] which writes to an in-memory buffer.
+///
+/// [`OutputOptions`]: struct.OutputOptions.html
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ToBuffer {}
+
+#[cfg(feature = "std")]
+impl Destination for ToBuffer {
+    type Output = CompressedTexture;
+
+    #[inline]
+    fn from_output(output: CompressionOutput) -> Self::Output {
+        match output {
+            CompressionOutput::Memory {
+                data,
+                width,
+                height,
+                depth,
+                face,
+                miplevel,
+            } => CompressedTexture {
+                data,
+                width,
+                height,
+                depth,
+                face,
+                miplevel,
+            },
+            // The output location of an `OutputOptions<ToBuffer>` cannot be changed.
+            CompressionOutput::File => unreachable!("buffered output was written to a file"),
+        }
+    }
+}
+
+// The option types are documented as being shareable between threads, so check that
+// they stay `Send` and `Sync` as their fields change.
+#[cfg(feature = "nvtt")]
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    fn assert_send<T: Send>() {}
+    assert_send_sync::<CompressionOptions>();
+    assert_send_sync::<InputOptions>();
+    assert_send_sync::<OutputOptions>();
+    assert_send_sync::<OutputOptions<ToFile>>();
+    assert_send_sync::<OutputOptions<ToBuffer>>();
+    assert_send_sync::<Surface>();
+    assert_send::<Compressor>();
+    assert_send::<Context>();
+};
+
+/// Space reserved in a memory mapped output file or an output buffer for the container
+/// header, which is not included in [`Compressor::estimate_size`].
+#[cfg(feature = "nvtt")]
+const HEADER_CAPACITY: usize = 256;
+
+/// Writes the compressed texture data into a file through a memory map.
+#[cfg(all(feature = "nvtt", feature = "memmap-output"))]
+struct MappedOutput {
+    file: std::fs::File,
+    map: memmap2::MmapMut,
+    len: usize,
+}
+
+#[cfg(all(feature = "nvtt", feature = "memmap-output"))]
+impl MappedOutput {
+    /// Create the file at `path`, and map the first `capacity` bytes of it.
+    fn create(path: &Path, capacity: usize) -> std::io::Result<Self> {
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        let map = Self::map(&file, capacity.max(1))?;
+        Ok(Self { file, map, len: 0 })
+    }
+
     #[inline]
-    pub fn set_output_location<'a, T: 'a + ?Sized + Into<OutputLocation<'a>>>(
-        &mut self,
-        out_location: T,
-    ) -> Result<&mut Self, PathConvertError> {
-        #[inline(never)]
-        fn inner(
-            opts: &mut OutputOptions,
-            loc: OutputLocation<'_>,
-        ) -> Result<(), PathConvertError> {
-            match loc {
-                OutputLocation::File(p) => {
-                    #[inline(always)]
-                    fn to_c_filepath(path: &Path) -> Result<CString, PathConvertError> {
-                        cfg_if! {
-                            if #[cfg(target_family = "windows")] {
-                                match path.to_str() {
-                                    Some(s) => {
-                                        if !s.is_ascii() {
-                                            return Err(PathConvertError::AsciiConvert)
-                                        }
-                                        CString::new(s.as_bytes()).map_err(From::from)
-                                    }
-                                    None => Err(PathConvertError::Utf8Convert),
-                                }
-                            } else if #[cfg(target_family = "unix")] {
-                                use std::os::unix::ffi::OsStrExt;
-                                CString::new(path.as_os_str().as_bytes()).map_err(From::from)
-                            } else {
-                                compile_error!("This platform is unsupported");
-                            }
-                        }
-                    }
+    fn map(file: &std::fs::File, capacity: usize) -> std::io::Result<memmap2::MmapMut> {
+        file.set_len(capacity as u64)?;
+        unsafe { memmap2::MmapMut::map_mut(file) }
+    }
 
-                    let out_file = to_c_filepath(p)?;
-                    unsafe {
-                        nvttSetOutputOptionsFileName(opts.out_opts.as_ptr(), out_file.as_ptr());
-                    }
-                    opts.write_to_file = true;
-                    Ok(())
-                }
-                OutputLocation::Buffer => {
-                    opts.write_to_file = false;
-                    Ok(())
-                }
-            }
+    /// Append `data` to the file, growing the mapping if the estimated
+    /// size was too small.
+    fn write(&mut self, data: &[u8]) -> std::io::Result<()> {
+        let end = self.len + data.len();
+        if end > self.map.len() {
+            self.map.flush()?;
+            let capacity = end.max(self.map.len() * 2);
+            self.map = Self::map(&self.file, capacity)?;
         }
 
-        inner(self, out_location.into()).map(|_| self)
+        self.map[self.len..end].copy_from_slice(data);
+        self.len = end;
+        Ok(())
     }
 
-    /// If set to `true`, then the `OutputOptions` will write texture metadata into a
-    /// header section of the file.
+    /// Flush the mapping, and truncate the file to the length of the written data.
+    fn finish(self) -> std::io::Result<()> {
+        let Self { file, map, len } = self;
+        map.flush()?;
+        mem::drop(map);
+        file.set_len(len as u64)
+    }
+}
+
+/// Describes the progress of writing a compressed texture. This is passed to the
+/// handler set with [`OutputOptions::set_progress_handler`].
+///
+/// [`OutputOptions::set_progress_handler`]: struct.OutputOptions.html#method.set_progress_handler
+#[cfg(feature = "nvtt")]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OutputProgress {
+    /// The face of the image currently being written.
+    pub face: usize,
+    /// The mipmap level of the image currently being written.
+    pub miplevel: usize,
+    /// The number of bytes of the current image which have been written.
+    pub image_bytes_written: usize,
+    /// The size of the current image in bytes.
+    pub image_size: usize,
+    /// The total number of bytes written so far, including any headers.
+    pub total_bytes_written: usize,
+    /// The time elapsed since compression started.
+    pub elapsed: Duration,
+}
+
+#[cfg(feature = "nvtt")]
+impl OutputProgress {
+    /// Returns the average number of bytes written per second since compression started.
     #[inline]
-    pub fn set_write_header<B: Into<NvttBoolean>>(&mut self, write_header: B) -> &mut Self {
-        unsafe {
-            nvttSetOutputOptionsOutputHeader(self.out_opts.as_ptr(), write_header.into());
+    pub fn bytes_per_second(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs > 0.0 {
+            self.total_bytes_written as f64 / secs
+        } else {
+            0.0
         }
-        self
     }
+}
 
-    /// If `write_srgb` is set to true, then the output image will be in the [sRGB] colorspace.
+/// Wrapper around a progress handler closure, so that the `OutputOptions`
+/// can implement `Debug`.
+#[cfg(feature = "nvtt")]
+struct ProgressHandler(Box<dyn Fn(&OutputProgress) + Send + Sync>);
+
+#[cfg(feature = "nvtt")]
+impl fmt::Debug for ProgressHandler {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ProgressHandler")
+    }
+}
+
+/// The time taken to encode, and the size of, a single face and mipmap level of a
+/// compressed texture. This is part of a [`CompressionReport`].
+///
+/// [`CompressionReport`]: struct.CompressionReport.html
+#[cfg(feature = "nvtt")]
+#[cfg_attr(feature = "serde-serialize", derive(Deserialize, Serialize))]
+#[derive(Clone, Debug, PartialEq)]
+pub struct MipReport {
+    /// The face of the image.
+    pub face: usize,
+    /// The mipmap level of the image.
+    pub miplevel: usize,
+    /// The width of the image in pixels.
+    pub width: usize,
+    /// The height of the image in pixels.
+    pub height: usize,
+    /// The depth of the image in pixels.
+    pub depth: usize,
+    /// The size of the compressed image in bytes.
+    pub size: usize,
+    /// The time taken to encode the image.
+    pub elapsed: Duration,
+    /// The error of the compressed image, if [`OutputOptions::set_report_metrics`] is
+    /// enabled and it could be measured.
     ///
-    /// [sRGB]: https://en.wikipedia.org/wiki/SRGB
+    /// [`OutputOptions::set_report_metrics`]: struct.OutputOptions.html#method.set_report_metrics
+    pub metrics: Option<QualityMetrics>,
+}
+
+/// A report of each image written by a compression. This is recorded when
+/// [`OutputOptions::set_record_report`] is enabled.
+///
+/// # Example
+///
+/// ```no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use nvtt_rs::{CompressionOptions, Compressor, InputOptions, OutputOptions};
+///
+/// let compressor = Compressor::new()?;
+/// let input_options = InputOptions::new()?;
+/// let compression_options = CompressionOptions::new()?;
+/// let mut output_options = OutputOptions::new()?;
+/// output_options.set_record_report(true);
+///
+/// compressor.compress(&compression_options, &input_options, &output_options)?;
+/// if let Some(mip) = output_options.report().as_ref().and_then(|r| r.largest()) {
+///     println!("face {} mip {} is {} bytes", mip.face, mip.miplevel, mip.size);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`OutputOptions::set_record_report`]: struct.OutputOptions.html#method.set_record_report
+#[cfg(feature = "nvtt")]
+#[cfg_attr(feature = "serde-serialize", derive(Deserialize, Serialize))]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CompressionReport {
+    /// Each image in the order in which it was written.
+    pub mips: Vec<MipReport>,
+    /// The time taken by the whole compression.
+    pub elapsed: Duration,
+}
+
+#[cfg(feature = "nvtt")]
+impl CompressionReport {
+    /// Returns the total size of the compressed images in bytes, excluding headers.
     #[inline]
-    pub fn set_srgb_flag<B: Into<NvttBoolean>>(&mut self, write_srgb: B) -> &mut Self {
-        unsafe {
-            nvttSetOutputOptionsSrgbFlag(self.out_opts.as_ptr(), write_srgb.into());
-        }
-        self
+    pub fn total_size(&self) -> usize {
+        self.mips.iter().map(|mip| mip.size).sum()
     }
 
-    /// Set the `Container` type of the output image.
+    /// Returns the largest compressed image.
     #[inline]
-    pub fn set_container(&mut self, container: Container) -> &mut Self {
-        unsafe {
-            nvttSetOutputOptionsContainer(self.out_opts.as_ptr(), container.into());
-        }
-        self
+    pub fn largest(&self) -> Option<&MipReport> {
+        self.mips.iter().max_by_key(|mip| mip.size)
     }
-}
 
-impl Drop for OutputOptions {
+    /// Returns the image which took the longest to encode.
     #[inline]
-    fn drop(&mut self) {
-        unsafe { nvttDestroyOutputOptions(self.out_opts.as_ptr()) }
+    pub fn slowest(&self) -> Option<&MipReport> {
+        self.mips.iter().max_by_key(|mip| mip.elapsed)
+    }
+}
+
+/// The `CompressionReport` which is being recorded by `Compressor::compress`.
+#[cfg(feature = "nvtt")]
+struct ReportState {
+    start: Instant,
+    /// When the current image began, if it has not finished yet.
+    image_start: Option<Instant>,
+    mips: Vec<MipReport>,
+}
+
+#[cfg(feature = "nvtt")]
+impl ReportState {
+    /// Record the time taken by the current image, if it has not been recorded yet.
+    fn finish_image(&mut self) {
+        if let (Some(start), Some(mip)) = (self.image_start.take(), self.mips.last_mut()) {
+            mip.elapsed = start.elapsed();
+        }
     }
 }
 
-// @SAFETY: An `OutputOptions` cannot be copied or unsafely mutated in a shared way.
-unsafe impl Send for OutputOptions {}
-unsafe impl Sync for OutputOptions {}
+/// The state used to report progress from the output callbacks.
+#[cfg(feature = "nvtt")]
+struct ProgressState {
+    // @SAFETY: This points into the `OutputOptions` passed to `Compressor::compress`, and
+    // is only dereferenced while that borrow is alive.
+    handler: *const ProgressHandler,
+    start: Instant,
+    progress: OutputProgress,
+}
 
 /// This enum is used to define the output location of the compressed
 /// texture data.
@@ -1528,10 +5989,27 @@ unsafe impl Sync for OutputOptions {}
 /// See the method [`OutputOptions::set_output_location`] for more information.
 ///
 /// [`OutputOptions::set_output_location`]: struct.OutputOptions.html#method.set_output_location
+#[cfg(feature = "nvtt")]
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub enum OutputLocation<'a> {
     /// Output the texture to the file specified by the `Path`.
     File(&'a Path),
+    /// Output the texture to the file specified by the `Path`, writing the data
+    /// through a memory map.
+    ///
+    /// The file is pre-sized using [`Compressor::estimate_size`], and is truncated
+    /// to the length of the compressed data once compression finishes. This avoids
+    /// buffering the whole texture on the heap, and can reduce the number of system
+    /// calls needed to write very large textures.
+    ///
+    /// # Notes
+    ///
+    /// The file is only memory mapped with the [`memmap-output`] feature. Without it,
+    /// the file is written through a buffered writer instead.
+    ///
+    /// [`Compressor::estimate_size`]: struct.Compressor.html#method.estimate_size
+    /// [`memmap-output`]: index.html#memmap-output
+    MappedFile(&'a Path),
     /// Output the texture into an in-memory buffer. This will be returned
     /// by [`Compressor::compress`].
     ///
@@ -1539,6 +6017,7 @@ pub enum OutputLocation<'a> {
     Buffer,
 }
 
+#[cfg(feature = "nvtt")]
 impl<'a, P: 'a + ?Sized + AsRef<Path>> From<&'a P> for OutputLocation<'a> {
     #[inline]
     fn from(p: &'a P) -> Self {
@@ -1549,6 +6028,7 @@ impl<'a, P: 'a + ?Sized + AsRef<Path>> From<&'a P> for OutputLocation<'a> {
 decl_enum! {
     /// An error which may occur during compression.
     #[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+    #[non_exhaustive]
     pub enum Error: NvttError {
         /// An error occurred while running a CUDA kernel.
         CudaError = NvttError_NVTT_Error_CudaError,
@@ -1564,6 +6044,18 @@ decl_enum! {
         UnsupportedFeature = NvttError_NVTT_Error_UnsupportedFeature,
         /// The requested output format is not supported.
         UnsupportedOutputFormat = NvttError_NVTT_Error_UnsupportedOutputFormat,
+    } else {
+        /// An error code which is not known to this crate. This may be returned
+        /// when linking against a newer version of nvtt.
+        Other,
+        /// CUDA acceleration was requested, but is not available on this platform. This
+        /// is reported by this crate rather than nvtt, and converts into the raw
+        /// `CudaError` code.
+        CudaUnavailable => NvttError_NVTT_Error_CudaError,
+        /// The mipmap level is not part of the mip chain of the texture layout. This is
+        /// reported by this crate rather than nvtt, and converts into the raw
+        /// `InvalidInput` code.
+        InvalidMipLevel => NvttError_NVTT_Error_InvalidInput,
     }
 }
 
@@ -1574,23 +6066,263 @@ impl Default for Error {
     }
 }
 
+impl Error {
+    /// Get the message which describes this error. This does not include the code of
+    /// an `Error::Other`. The messages of the nvtt errors are the same as those
+    /// returned by `nvttErrorString`, so that they do not depend on nvtt being linked.
+    fn message(&self) -> &'static str {
+        match *self {
+            Error::CudaError => "CUDA error",
+            Error::FileOpen => "Error opening file",
+            Error::FileWrite => "Error writing through output handler",
+            Error::InvalidInput => "Invalid input",
+            Error::Unknown => "Unknown error",
+            Error::UnsupportedFeature => "Unsupported feature",
+            Error::UnsupportedOutputFormat => {
+                "The container file does not support the selected output format"
+            }
+            Error::Other(_) => "Unrecognised nvtt error",
+            Error::CudaUnavailable => "CUDA acceleration is not available",
+            Error::InvalidMipLevel => "The mipmap level is outside of the texture's mip chain",
+        }
+    }
+}
+
 impl fmt::Display for Error {
     #[inline]
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let s = unsafe { CStr::from_ptr(nvttErrorString(self.into())) };
-        f.write_str(&s.to_string_lossy())
+        match *self {
+            Error::Other(code) => write!(f, "{} (code {})", self.message(), code),
+            _ => f.write_str(self.message()),
+        }
     }
 }
 
+#[cfg(feature = "std")]
 impl ErrorTrait for Error {
     #[inline]
     fn description(&self) -> &'static str {
-        let s = unsafe { CStr::from_ptr(nvttErrorString(self.into())) };
-        s.to_str().unwrap_or("An unknown error occurred")
+        self.message()
+    }
+}
+
+/// The stage of the compression in which a [`CompressError`] occurred.
+///
+/// [`CompressError`]: struct.CompressError.html
+#[cfg(feature = "std")]
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum CompressionStage {
+    /// The options or the input images were rejected.
+    InputValidation,
+    /// The texture could not be encoded.
+    Encoding,
+    /// The output could not be opened or written.
+    Output,
+    /// The metadata sidecar file could not be written.
+    Metadata,
+    /// The compressed texture could not be decoded. This is only reported by
+    /// [`QualityMetrics::measure`].
+    ///
+    /// [`QualityMetrics::measure`]: struct.QualityMetrics.html#method.measure
+    Decoding,
+}
+
+#[cfg(feature = "std")]
+impl fmt::Display for CompressionStage {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match *self {
+            CompressionStage::InputValidation => "input validation",
+            CompressionStage::Encoding => "encoding",
+            CompressionStage::Output => "writing the output",
+            CompressionStage::Metadata => "writing the metadata",
+            CompressionStage::Decoding => "decoding the output",
+        })
+    }
+}
+
+/// A problem with the state of an [`InputOptions`], found by [`InputOptions::diagnose`].
+///
+/// [`InputOptions`]: struct.InputOptions.html
+/// [`InputOptions::diagnose`]: struct.InputOptions.html#method.diagnose
+#[cfg(feature = "std")]
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum InputDiagnosis {
+    /// No `TextureLayout` was set.
+    TextureLayoutNotSet,
+    /// No mipmap data was provided for the top mipmap level of `face`.
+    MissingFaceData {
+        /// The face which is missing data.
+        face: usize,
+    },
+    /// No mipmap data was provided for the top mipmap level of some of the faces of a
+    /// cube map. Use [`InputDiagnosis::missing_cube_faces`] to list them.
+    ///
+    /// [`InputDiagnosis::missing_cube_faces`]: enum.InputDiagnosis.html#method.missing_cube_faces
+    MissingCubeFaces {
+        /// Whether each face is missing, in the order of [`CubeFace::ALL`].
+        ///
+        /// [`CubeFace::ALL`]: enum.CubeFace.html#associatedconstant.ALL
+        missing: [bool; 6],
+    },
+}
+
+#[cfg(feature = "std")]
+impl InputDiagnosis {
+    /// Get the faces of a cube map which are missing data. This is empty unless the
+    /// diagnosis is `InputDiagnosis::MissingCubeFaces`.
+    pub fn missing_cube_faces(&self) -> Vec<CubeFace> {
+        match *self {
+            InputDiagnosis::MissingCubeFaces { missing } => CubeFace::ALL
+                .iter()
+                .zip(missing.iter())
+                .filter(|&(_, &missing)| missing)
+                .map(|(&face, _)| face)
+                .collect(),
+            _ => vec![],
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl fmt::Display for InputDiagnosis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            InputDiagnosis::TextureLayoutNotSet => f.write_str("texture layout not set"),
+            InputDiagnosis::MissingFaceData { face } => {
+                write!(f, "no mipmap data was provided for face {}", face)
+            }
+            InputDiagnosis::MissingCubeFaces { .. } => {
+                f.write_str("no mipmap data was provided for the cube faces")?;
+                for (i, face) in self.missing_cube_faces().iter().enumerate() {
+                    let separator = if i == 0 { " " } else { ", " };
+                    write!(f, "{}{:?}", separator, face)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// An error returned from [`Compressor::compress`]. This wraps the [`Error`] reported
+/// by nvtt with the context in which it occurred.
+///
+/// [`Compressor::compress`]: struct.Compressor.html#method.compress
+/// [`Error`]: enum.Error.html
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct CompressError {
+    error: Error,
+    stage: CompressionStage,
+    path: Option<PathBuf>,
+    image: Option<(usize, usize)>,
+    diagnosis: Option<InputDiagnosis>,
+}
+
+#[cfg(feature = "std")]
+impl CompressError {
+    #[cfg(feature = "nvtt")]
+    #[inline]
+    pub(crate) fn new<D>(
+        error: Error,
+        stage: CompressionStage,
+        output_options: &OutputOptions<D>,
+    ) -> Self {
+        Self::with_path(error, stage, output_options.out_path.clone())
+    }
+
+    #[inline]
+    pub(crate) fn with_path(error: Error, stage: CompressionStage, path: Option<PathBuf>) -> Self {
+        Self {
+            error,
+            stage,
+            path,
+            image: None,
+            diagnosis: None,
+        }
+    }
+
+    /// Returns the underlying `Error`.
+    #[inline]
+    pub fn error(&self) -> Error {
+        self.error
+    }
+
+    /// Returns the stage of the compression which failed.
+    #[inline]
+    pub fn stage(&self) -> CompressionStage {
+        self.stage
+    }
+
+    /// Returns the path of the output file, or `None` if the texture was being
+    /// written into memory.
+    #[inline]
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Returns the face which was being processed, or `None` if the error occurred
+    /// before any image was processed.
+    #[inline]
+    pub fn face(&self) -> Option<usize> {
+        self.image.map(|(face, _)| face)
+    }
+
+    /// Returns the mipmap level which was being processed, or `None` if the error
+    /// occurred before any image was processed.
+    #[inline]
+    pub fn miplevel(&self) -> Option<usize> {
+        self.image.map(|(_, miplevel)| miplevel)
+    }
+
+    /// Returns the likely cause of an `Error::InvalidInput`, found by cross-checking
+    /// the state of the `InputOptions`. See [`InputOptions::diagnose`].
+    ///
+    /// [`InputOptions::diagnose`]: struct.InputOptions.html#method.diagnose
+    #[inline]
+    pub fn diagnosis(&self) -> Option<InputDiagnosis> {
+        self.diagnosis
+    }
+}
+
+#[cfg(feature = "std")]
+impl fmt::Display for CompressError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Compression failed during {}", self.stage)?;
+        if let Some(ref path) = self.path {
+            write!(f, " of {}", path.display())?;
+        }
+        if let Some((face, miplevel)) = self.image {
+            write!(f, " (face {}, mipmap {})", face, miplevel)?;
+        }
+        write!(f, ": {}", self.error)?;
+        if let Some(diagnosis) = self.diagnosis {
+            write!(f, " ({})", diagnosis)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl ErrorTrait for CompressError {
+    #[inline]
+    fn source(&self) -> Option<&(dyn ErrorTrait + 'static)> {
+        Some(&self.error)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<CompressError> for Error {
+    #[inline]
+    fn from(err: CompressError) -> Self {
+        err.error
     }
 }
 
 /// An error type for when a path could not be converted.
+#[cfg(feature = "std")]
 #[derive(Clone, Debug)]
 pub enum PathConvertError {
     /// An error occurred while converting the path into utf8.
@@ -1602,6 +6334,7 @@ pub enum PathConvertError {
     Nul(NulError),
 }
 
+#[cfg(feature = "std")]
 impl fmt::Display for PathConvertError {
     #[inline]
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -1615,6 +6348,7 @@ impl fmt::Display for PathConvertError {
     }
 }
 
+#[cfg(feature = "std")]
 impl ErrorTrait for PathConvertError {
     #[inline]
     fn source(&self) -> Option<&(dyn ErrorTrait + 'static)> {
@@ -1625,6 +6359,7 @@ impl ErrorTrait for PathConvertError {
     }
 }
 
+#[cfg(feature = "std")]
 impl From<NulError> for PathConvertError {
     #[inline]
     fn from(e: NulError) -> Self {
@@ -1671,4 +6406,221 @@ impl<RawValue: fmt::Display> fmt::Display for EnumConvertError<RawValue> {
     }
 }
 
+#[cfg(feature = "std")]
 impl<RawValue> ErrorTrait for EnumConvertError<RawValue> where RawValue: fmt::Debug + fmt::Display {}
+
+/// An error type which may be generated when parsing an enum from a string using
+/// the [`FromStr`] trait.
+///
+/// [`FromStr`]: https://doc.rust-lang.org/stable/std/str/trait.FromStr.html
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ParseEnumError {
+    value: String,
+    enum_name: &'static str,
+}
+
+impl ParseEnumError {
+    /// Create a new `ParseEnumError`.
+    #[inline]
+    fn new<T>(value: &str) -> Self {
+        Self {
+            value: value.to_string(),
+            enum_name: type_name::<T>(),
+        }
+    }
+
+    /// Returns the string which could not be parsed.
+    #[inline]
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+}
+
+impl fmt::Display for ParseEnumError {
+    #[inline]
+    fn fmt(&self, fmtr: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            fmtr,
+            "Could not parse `{val}` as a {enum_nm}",
+            val = self.value,
+            enum_nm = self.enum_name
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl ErrorTrait for ParseEnumError {}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "nvtt")]
+    thread_local! {
+        /// The gamma values which have been set on nvtt input options by this thread.
+        pub(super) static NVTT_GAMMA: RefCell<Vec<(f32, f32)>> = const { RefCell::new(Vec::new()) };
+    }
+
+    #[test]
+    fn error_description_matches_display() {
+        for err in [Error::CudaUnavailable, Error::InvalidMipLevel] {
+            #[allow(deprecated)]
+            let description = err.description();
+            assert_eq!(description, err.to_string());
+        }
+        assert_eq!(
+            Error::Other(42).to_string(),
+            "Unrecognised nvtt error (code 42)"
+        );
+    }
+
+    #[cfg(feature = "nvtt")]
+    #[test]
+    fn estimate_size_applies_the_default_gamma() {
+        let compressor = Compressor::new().unwrap();
+        let mut compression_options = CompressionOptions::new().unwrap();
+        let mut input_options = InputOptions::new().unwrap();
+        let take_gamma = || NVTT_GAMMA.with(|gamma| gamma.take());
+
+        // Formats which are filtered in gamma space get a gamma of 1.0 while the size
+        // is estimated, and nvtt's default afterwards.
+        compression_options.set_format(Format::Bc5);
+        take_gamma();
+        compressor.estimate_size(&input_options, &compression_options);
+        assert_eq!(take_gamma(), [(1.0, 1.0), (2.2, 2.2)]);
+
+        compression_options.set_format(Format::Bc1);
+        compressor.estimate_size(&input_options, &compression_options);
+        assert_eq!(take_gamma(), []);
+
+        // The gamma set by the user is left in place.
+        input_options.set_gamma(2.0, 2.4).unwrap();
+        assert_eq!(take_gamma(), [(2.0, 2.4)]);
+        compression_options.set_format(Format::Bc5);
+        compressor.estimate_size(&input_options, &compression_options);
+        assert_eq!(take_gamma(), []);
+    }
+
+    #[cfg(feature = "nvtt")]
+    #[test]
+    fn diagnose_counts_faces_of_the_layout() {
+        let mut input_options = InputOptions::new().unwrap();
+        assert_eq!(
+            input_options.diagnose(),
+            Some(InputDiagnosis::TextureLayoutNotSet)
+        );
+
+        // The faces are counted without multiplying the dimensions, which would
+        // overflow for large 3d textures.
+        let side = 1 << 30;
+        let layout = TextureLayout::D3 {
+            width: side,
+            height: side,
+            depth: side,
+        };
+        input_options.set_texture_layout(layout).unwrap();
+        assert_eq!(
+            input_options.diagnose(),
+            Some(InputDiagnosis::MissingFaceData { face: 0 })
+        );
+
+        let layout = TextureLayout::Cube {
+            face_width: 4,
+            face_height: 4,
+        };
+        input_options.set_texture_layout(layout).unwrap();
+        assert_eq!(
+            input_options.diagnose(),
+            Some(InputDiagnosis::MissingCubeFaces { missing: [true; 6] })
+        );
+    }
+
+    #[cfg(feature = "nvtt")]
+    #[test]
+    fn clone_replays_settings_and_retained_mipmap_data() {
+        let extent = Extent3d::new(2, 2, 1).unwrap();
+        let texels = [0x80u8; 16];
+        let mut input_options = InputOptions::new().unwrap();
+        input_options
+            .set_format(InputFormat::Bgra8Ub)
+            .set_wrap_mode(WrapMode::Repeat)
+            .set_round_mode(RoundMode::ToNearestPowerOfTwo)
+            .set_mipmap_generation(true, Some(1))
+            .set_retain_mipmap_data(true)
+            .set_texture_layout(TextureLayout::d2(2, 2))
+            .unwrap()
+            .set_mipmap_data(&texels, extent, CubeFace::ArrayLayer(0), MipLevel(0))
+            .unwrap();
+
+        let cloned = input_options.clone();
+        assert_eq!(cloned.texture_layout(), Some(TextureLayout::d2(2, 2)));
+        assert!(cloned.retains_mipmap_data());
+        assert_eq!(cloned.diagnose(), None);
+        assert_eq!(cloned.state.mipmap_data.len(), 1);
+        assert!(cloned.state.key() == input_options.state.key());
+
+        // Without a copy of the mipmap data, only the settings are cloned.
+        input_options.set_retain_mipmap_data(false);
+        let cloned = input_options.clone();
+        assert_eq!(cloned.texture_layout(), Some(TextureLayout::d2(2, 2)));
+        assert_eq!(cloned.state.wrap_mode, Some(WrapMode::Repeat));
+        assert_eq!(
+            cloned.diagnose(),
+            Some(InputDiagnosis::MissingFaceData { face: 0 })
+        );
+    }
+
+    #[cfg(feature = "nvtt")]
+    fn hash_of(value: &impl Hash) -> u64 {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        value.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[cfg(feature = "nvtt")]
+    #[test]
+    fn options_compare_their_recorded_configuration() {
+        let mut a = CompressionOptions::new().unwrap();
+        let mut b = CompressionOptions::new().unwrap();
+        assert!(a == b);
+        a.set_format(Format::Bc7)
+            .set_color_weights(1.0, 2.0, 1.0, 0.5);
+        assert!(a != b);
+        b.set_format(Format::Bc7)
+            .set_color_weights(1.0, 2.0, 1.0, 0.5);
+        assert!(a == b);
+        assert_eq!(hash_of(&a), hash_of(&b));
+        b.set_quality(Quality::Highest);
+        assert!(a != b);
+
+        let extent = Extent3d::new(2, 2, 1).unwrap();
+        let mut a = InputOptions::new().unwrap();
+        let mut b = InputOptions::new().unwrap();
+        assert!(a == b);
+        for options in [&mut a, &mut b] {
+            options
+                .set_gamma(2.2, 2.2)
+                .unwrap()
+                .set_texture_layout(TextureLayout::d2(2, 2))
+                .unwrap()
+                .set_mipmap_data(&[0x80; 16], extent, CubeFace::ArrayLayer(0), MipLevel(0))
+                .unwrap();
+        }
+        assert!(a == b);
+        assert_eq!(hash_of(&a), hash_of(&b));
+
+        // Mipmap data which is not retained is still compared by its hash.
+        b.set_mipmap_data(&[0x40; 16], extent, CubeFace::ArrayLayer(0), MipLevel(0))
+            .unwrap();
+        assert!(a != b);
+
+        // Only retained mipmap data is cloned, so only then is the clone equal.
+        assert!(a.clone() != a);
+        a.set_retain_mipmap_data(true)
+            .set_mipmap_data(&[0x80; 16], extent, CubeFace::ArrayLayer(0), MipLevel(0))
+            .unwrap();
+        let cloned = a.clone();
+        assert!(cloned == a);
+        assert_eq!(hash_of(&cloned), hash_of(&a));
+    }
+}
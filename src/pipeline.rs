@@ -0,0 +1,1189 @@
+// Copyright © 2019-2020 George Burton
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::{
+    analysis, fnv1a_64, CompressError, CompressionStage, Container, Error, Format,
+    NormalMapAnalysis, Quality, Surface, FNV_OFFSET_BASIS,
+};
+#[cfg(feature = "fallback-encoders")]
+use crate::{fallback::fallback_container, fallback_compress, MipmapFilter};
+#[cfg(feature = "nvtt")]
+use crate::{
+    linked_version, CompressionOptions, Compressor, CubeFace, InputOptions, MipLevel, OutputOptions,
+};
+#[cfg(feature = "basis")]
+use crate::{BasisFormat, BasisOptions};
+use cfg_if::cfg_if;
+use glob::PatternError;
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet},
+    error::Error as ErrorTrait,
+    ffi::OsString,
+    fmt, fs,
+    path::{Component, Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Condvar, Mutex, PoisonError,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Compresses many image files with the same settings, using a thread for each
+/// available cpu.
+///
+/// # Example
+///
+/// ```no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use nvtt_rs::{Batch, Format};
+///
+/// let results = Batch::new()
+///     .add_glob("textures/**/*.png")
+///     .output_dir("cooked/")
+///     .format(Format::Bc7)
+///     .run()?;
+///
+/// for result in results.iter().filter(|r| r.result().is_err()) {
+///     eprintln!("Could not compress {}", result.input().display());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Notes
+///
+/// This type requires the [`pipeline`] feature.
+///
+/// [`pipeline`]: index.html#pipeline
+#[derive(Clone, Debug)]
+pub struct Batch {
+    files: Vec<PathBuf>,
+    globs: Vec<String>,
+    output_dir: Option<PathBuf>,
+    format: Format,
+    quality: Quality,
+    container: Option<Container>,
+    mipmaps: bool,
+    srgb: bool,
+    threads: Option<usize>,
+    cuda: bool,
+    pipelined: bool,
+    memory_budget: MemoryBudget,
+    cache_dir: Option<PathBuf>,
+    #[cfg(feature = "basis")]
+    basis: Option<BasisFormat>,
+}
+
+impl Default for Batch {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Batch {
+    /// Create a new `Batch` with no inputs. By default, each file is compressed to
+    /// `Format::Bc1` with `Quality::Normal`, and mipmaps are generated.
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            files: vec![],
+            globs: vec![],
+            output_dir: None,
+            format: Format::Bc1,
+            quality: Quality::Normal,
+            container: None,
+            mipmaps: true,
+            srgb: false,
+            threads: None,
+            cuda: false,
+            pipelined: false,
+            memory_budget: MemoryBudget::Unlimited,
+            cache_dir: None,
+            #[cfg(feature = "basis")]
+            basis: None,
+        }
+    }
+
+    /// Add a single image file to the batch.
+    #[inline]
+    pub fn add_file<P: Into<PathBuf>>(&mut self, path: P) -> &mut Self {
+        self.files.push(path.into());
+        self
+    }
+
+    /// Add every file matching the glob `pattern` to the batch, such as
+    /// `"textures/**/*.png"`. The pattern is expanded when the batch is run.
+    #[inline]
+    pub fn add_glob<S: Into<String>>(&mut self, pattern: S) -> &mut Self {
+        self.globs.push(pattern.into());
+        self
+    }
+
+    /// Set the directory which the compressed textures are written to. Files found
+    /// with a glob keep their path relative to the directory part of the pattern.
+    ///
+    /// If no output directory is set, then each texture is written next to its input.
+    #[inline]
+    pub fn output_dir<P: Into<PathBuf>>(&mut self, dir: P) -> &mut Self {
+        self.output_dir = Some(dir.into());
+        self
+    }
+
+    /// Set the output format of the compressed textures.
+    #[inline]
+    pub fn format(&mut self, format: Format) -> &mut Self {
+        self.format = format;
+        self
+    }
+
+    /// Set the compression quality.
+    #[inline]
+    pub fn quality(&mut self, quality: Quality) -> &mut Self {
+        self.quality = quality;
+        self
+    }
+
+    /// Set the output container. By default, `Container::Dds10` is used for
+    /// `Format::Bc6` and `Format::Bc7`, and `Container::Dds` otherwise.
+    #[inline]
+    pub fn container(&mut self, container: Container) -> &mut Self {
+        self.container = Some(container);
+        self
+    }
+
+    /// Set whether mipmaps are generated for each texture.
+    #[inline]
+    pub fn mipmaps(&mut self, generate_mipmaps: bool) -> &mut Self {
+        self.mipmaps = generate_mipmaps;
+        self
+    }
+
+    /// Set whether the compressed textures are marked as containing srgb data.
+    #[inline]
+    pub fn srgb(&mut self, srgb: bool) -> &mut Self {
+        self.srgb = srgb;
+        self
+    }
+
+    /// Set the number of threads used to compress the batch. By default, this is the
+    /// number of available cpus.
+    #[inline]
+    pub fn threads(&mut self, threads: usize) -> &mut Self {
+        self.threads = Some(threads.max(1));
+        self
+    }
+
+    /// Set whether each thread enables cuda acceleration on its [`Compressor`]. If cuda
+    /// is not available, then the files are compressed on the cpu.
+    ///
+    /// [`Compressor`]: struct.Compressor.html
+    #[inline]
+    pub fn cuda(&mut self, enable: bool) -> &mut Self {
+        self.cuda = enable;
+        self
+    }
+
+    /// Set whether the batch is compressed as a pipeline. By default, each thread
+    /// reads, decodes and compresses a file before moving on to the next one.
+    ///
+    /// When the batch is pipelined, files are read, decoded and converted into the
+    /// input of the encoder on separate loader threads, while the compression threads
+    /// encode the files which are already staged. One file is staged ahead of each
+    /// compression thread, so the next texture is ready to be handed to nvtt as soon
+    /// as the current one finishes. This keeps the gpu busy when [`cuda`] is enabled,
+    /// where it would otherwise be idle while each file is loaded.
+    ///
+    /// # Notes
+    ///
+    /// The nvtt C api does not expose its cuda streams, so the upload of a texture to
+    /// the gpu still happens inside each compression, and cannot be overlapped with
+    /// the encoding of another texture on the same compressor.
+    ///
+    /// [`cuda`]: struct.Batch.html#method.cuda
+    #[inline]
+    pub fn pipelined(&mut self, pipelined: bool) -> &mut Self {
+        self.pipelined = pipelined;
+        self
+    }
+
+    /// Limit the memory used by the files which are compressed at the same time. By
+    /// default, every thread compresses a file without any limit, so a batch of very
+    /// large textures can use more memory than is available.
+    ///
+    /// When a file would exceed the `budget`, its thread waits until other files have
+    /// finished compressing.
+    #[inline]
+    pub fn memory_budget(&mut self, budget: MemoryBudget) -> &mut Self {
+        self.memory_budget = budget;
+        self
+    }
+
+    /// Enable caching of compressed textures in `dir`.
+    ///
+    /// Each texture is cached under a hash of the contents of its input file and the
+    /// settings of the batch. If a cached texture exists when a file is compressed,
+    /// then it is copied to the output path instead of compressing the file again.
+    /// The cache is never pruned, so stale entries must be removed by deleting the
+    /// directory.
+    #[inline]
+    pub fn cache_dir<P: Into<PathBuf>>(&mut self, dir: P) -> &mut Self {
+        self.cache_dir = Some(dir.into());
+        self
+    }
+
+    /// Compress each file into a Basis Universal `.basis` file of the given `format`
+    /// instead of using nvtt. The quality, mipmap and srgb settings of the batch are
+    /// used, and the format and container are ignored.
+    ///
+    /// # Notes
+    ///
+    /// This method requires the [`basis`] feature.
+    ///
+    /// [`basis`]: index.html#basis
+    #[cfg(feature = "basis")]
+    #[inline]
+    pub fn basis(&mut self, format: BasisFormat) -> &mut Self {
+        self.basis = Some(format);
+        self
+    }
+
+    /// Get the file extension of the compressed textures.
+    fn output_extension(&self) -> OsString {
+        #[cfg(feature = "basis")]
+        {
+            if self.basis.is_some() {
+                return OsString::from("basis");
+            }
+        }
+        self.output_container().file_extension().to_os_string()
+    }
+
+    /// Get the container which the textures are written into.
+    fn output_container(&self) -> Container {
+        self.container.unwrap_or(match self.format {
+            Format::Bc6 | Format::Bc7 => Container::Dds10,
+            _ => Container::Dds,
+        })
+    }
+
+    /// Expand the files and globs in the batch into pairs of input and output paths.
+    /// Files which are matched more than once are only included once.
+    ///
+    /// # Errors
+    ///
+    /// If any glob pattern is invalid, then this method will fail with
+    /// [`BatchError::Pattern`]. If two inputs would be compressed into the same output
+    /// file, such as `a.png` and `a.jpg`, then this method will fail with
+    /// [`BatchError::OutputCollision`].
+    ///
+    /// [`BatchError::Pattern`]: enum.BatchError.html#variant.Pattern
+    /// [`BatchError::OutputCollision`]: enum.BatchError.html#variant.OutputCollision
+    pub fn jobs(&self) -> Result<Vec<(PathBuf, PathBuf)>, BatchError> {
+        let extension = self.output_extension();
+        let output_path = |input: &Path, relative: &Path| {
+            let output = match self.output_dir {
+                Some(ref dir) => dir.join(relative),
+                None => input.to_path_buf(),
+            };
+            output.with_extension(&extension)
+        };
+
+        let mut jobs = vec![];
+        for file in &self.files {
+            let relative = file.file_name().map(Path::new).unwrap_or(file);
+            jobs.push((file.clone(), output_path(file, relative)));
+        }
+
+        for pattern in &self.globs {
+            let base = glob_base(pattern);
+            // Unreadable paths are skipped, as they cannot be compressed.
+            for input in glob::glob(pattern)?.filter_map(Result::ok) {
+                if !input.is_file() {
+                    continue;
+                }
+                let relative = match input.strip_prefix(&base) {
+                    Ok(relative) if relative.file_name().is_some() => relative,
+                    _ => input.file_name().map(Path::new).unwrap_or(&input),
+                };
+                let output = output_path(&input, relative);
+                jobs.push((input, output));
+            }
+        }
+
+        let mut seen = HashSet::new();
+        jobs.retain(|(input, _)| seen.insert(input.clone()));
+
+        let mut outputs = HashMap::with_capacity(jobs.len());
+        for (input, output) in &jobs {
+            match outputs.entry(output) {
+                Entry::Vacant(entry) => {
+                    entry.insert(input);
+                }
+                Entry::Occupied(entry) => {
+                    return Err(BatchError::OutputCollision {
+                        inputs: [entry.get().to_path_buf(), input.clone()],
+                        output: output.clone(),
+                    });
+                }
+            }
+        }
+        Ok(jobs)
+    }
+
+    /// Compress every file in the batch. A [`BatchResult`] is returned for each file,
+    /// in the same order as [`jobs`].
+    ///
+    /// # Errors
+    ///
+    /// If the [`jobs`] of the batch cannot be listed, then this method will fail before
+    /// any file is compressed. Errors from compressing individual files are reported in
+    /// each `BatchResult`.
+    ///
+    /// [`BatchResult`]: struct.BatchResult.html
+    /// [`jobs`]: struct.Batch.html#method.jobs
+    pub fn run(&self) -> Result<Vec<BatchResult>, BatchError> {
+        let jobs = self.jobs()?;
+        let threads = self
+            .threads
+            .or_else(|| thread::available_parallelism().ok().map(usize::from))
+            .unwrap_or(1)
+            .min(jobs.len().max(1));
+
+        let results = Mutex::new((0..jobs.len()).map(|_| None).collect::<Vec<_>>());
+        let store = |index: usize, result: BatchResult| {
+            let mut results = results.lock().unwrap_or_else(PoisonError::into_inner);
+            results[index] = Some(result);
+        };
+
+        let budget = BudgetTracker::new(self.memory_budget);
+        if self.pipelined {
+            self.run_pipelined(&jobs, threads, &budget, &store);
+        } else {
+            let next_job = AtomicUsize::new(0);
+            thread::scope(|scope| {
+                for _ in 0..threads {
+                    scope.spawn(|| {
+                        let worker = self.worker();
+                        loop {
+                            let index = next_job.fetch_add(1, Ordering::Relaxed);
+                            let (input, output) = match jobs.get(index) {
+                                Some(job) => job,
+                                None => break,
+                            };
+
+                            let _reservation = budget.reserve(self.estimate_memory(input));
+                            store(index, self.run_job(&worker, input, output));
+                        }
+                    });
+                }
+            });
+        }
+
+        Ok(results
+            .into_inner()
+            .unwrap_or_else(|e| e.into_inner())
+            .into_iter()
+            .flatten()
+            .collect())
+    }
+
+    /// Compress the `jobs`, staging each file on a loader thread before it is passed
+    /// to one of the `threads` compression threads.
+    fn run_pipelined<F>(
+        &self,
+        jobs: &[(PathBuf, PathBuf)],
+        threads: usize,
+        budget: &BudgetTracker,
+        store: &F,
+    ) where
+        F: Fn(usize, BatchResult) + Sync,
+    {
+        let next_job = AtomicUsize::new(0);
+        // The channel is bounded so that only one file is staged ahead of each
+        // compression thread, which limits the memory used by decoded images.
+        let (sender, receiver) = mpsc::sync_channel(threads);
+        let receiver = Mutex::new(receiver);
+
+        thread::scope(|scope| {
+            for _ in 0..threads {
+                let sender = sender.clone();
+                let next_job = &next_job;
+                scope.spawn(move || loop {
+                    let index = next_job.fetch_add(1, Ordering::Relaxed);
+                    let (input, output) = match jobs.get(index) {
+                        Some(job) => job,
+                        None => break,
+                    };
+
+                    // The memory is reserved until the file has been encoded.
+                    let reservation = budget.reserve(self.estimate_memory(input));
+                    let start = Instant::now();
+                    let staged = self.stage_file(input, output);
+                    if sender
+                        .send((index, staged, start.elapsed(), reservation))
+                        .is_err()
+                    {
+                        break;
+                    }
+                });
+            }
+            // The loaders hold the remaining senders, so the channel closes once every
+            // file has been staged.
+            drop(sender);
+
+            for _ in 0..threads {
+                scope.spawn(|| {
+                    let worker = self.worker();
+                    loop {
+                        let next = receiver.lock().ok().and_then(|r| r.recv().ok());
+                        let (index, staged, staging_time, _reservation) = match next {
+                            Some(next) => next,
+                            None => break,
+                        };
+
+                        let (input, output) = &jobs[index];
+                        let start = Instant::now();
+                        let result =
+                            staged.and_then(|staged| self.encode_staged(&worker, staged, output));
+                        store(
+                            index,
+                            batch_result(input, output, staging_time + start.elapsed(), result),
+                        );
+                    }
+                });
+            }
+        });
+    }
+
+    /// Estimate the memory used to compress the file at `input`, from the size of the
+    /// file and the dimensions of the image, without decoding it.
+    fn estimate_memory(&self, input: &Path) -> usize {
+        // The decoded image, and the copy of it which is passed to nvtt.
+        const DECODED_BYTES_PER_PIXEL: usize = 8;
+        // nvtt converts the image to 32 bit floats, and the output is at most
+        // 4 bytes per pixel.
+        const ENCODED_BYTES_PER_PIXEL: usize = 20;
+
+        if matches!(self.memory_budget, MemoryBudget::Unlimited) {
+            return 0;
+        }
+
+        let file_len = fs::metadata(input).map_or(0, |metadata| metadata.len() as usize);
+        let pixels = image::image_dimensions(input).map_or(0, |(w, h)| w as usize * h as usize);
+        // A full mip chain adds a third to the size of the image.
+        let encoded = if self.mipmaps {
+            ENCODED_BYTES_PER_PIXEL * pixels * 4 / 3
+        } else {
+            ENCODED_BYTES_PER_PIXEL * pixels
+        };
+        file_len + DECODED_BYTES_PER_PIXEL * pixels + encoded
+    }
+
+    /// Create the `Worker` used by a thread of the batch.
+    pub(crate) fn worker(&self) -> Worker {
+        Worker {
+            #[cfg(feature = "nvtt")]
+            compressor: Compressor::new().ok().map(|mut compressor| {
+                if self.cuda {
+                    compressor.enable_cuda_acceleration(true);
+                }
+                compressor
+            }),
+        }
+    }
+
+    /// Get the directories which contain the inputs of the batch, and whether each
+    /// directory must be searched recursively.
+    #[cfg(feature = "watch")]
+    pub(crate) fn input_dirs(&self) -> Vec<(PathBuf, bool)> {
+        let dir = |path: PathBuf| {
+            if path.as_os_str().is_empty() {
+                PathBuf::from(".")
+            } else {
+                path
+            }
+        };
+
+        let files = self.files.iter().map(|file| {
+            let parent = file.parent().map(Path::to_path_buf).unwrap_or_default();
+            (dir(parent), false)
+        });
+        let globs = self
+            .globs
+            .iter()
+            .map(|pattern| (dir(glob_base(pattern)), true));
+
+        let mut dirs = files.chain(globs).collect::<Vec<_>>();
+        dirs.sort();
+        dirs.dedup();
+        dirs
+    }
+
+    /// Compress a single job, timing how long it takes.
+    pub(crate) fn run_job(&self, worker: &Worker, input: &Path, output: &Path) -> BatchResult {
+        let start = Instant::now();
+        let result = self
+            .stage_file(input, output)
+            .and_then(|staged| self.encode_staged(worker, staged, output));
+        batch_result(input, output, start.elapsed(), result)
+    }
+
+    /// Read the file at `input`, and convert it into the input of the encoder. If the
+    /// texture is in the cache, then it is restored to `output` instead.
+    fn stage_file(&self, input: &Path, output: &Path) -> Result<Staged, CompressError> {
+        let open_error = |e: &dyn std::fmt::Display| {
+            log::error!(
+                "Could not open image {p}\nCaused by: {e}",
+                p = input.display(),
+                e = e
+            );
+            batch_error(Error::FileOpen, output)
+        };
+        let bytes = fs::read(input).map_err(|e| open_error(&e))?;
+
+        if let Some(parent) = output.parent() {
+            fs::create_dir_all(parent).map_err(|_| batch_error(Error::FileOpen, output))?;
+        }
+
+        let cached = self.cache_path(&bytes);
+        if let Some(ref cached) = cached {
+            if restore_cached(cached, output) {
+                return Ok(Staged::Cached);
+            }
+        }
+
+        let image = match image::ImageFormat::from_path(input) {
+            Ok(format) => image::load_from_memory_with_format(&bytes, format),
+            Err(_) => image::load_from_memory(&bytes),
+        }
+        .map_err(|e| open_error(&e))?;
+
+        #[cfg(feature = "basis")]
+        {
+            if self.basis.is_some() {
+                return Ok(Staged::Basis(Surface::from(&image), cached));
+            }
+        }
+
+        // The image is only converted for the warning if the format can damage normals.
+        let mut surface = None;
+        if !NormalMapAnalysis::preserves_normals(self.format, self.srgb) {
+            let converted = surface.insert(Surface::from(&image));
+            analysis::warn_color_normal_map(converted, self.format, self.srgb, &input.display());
+        }
+
+        #[cfg(feature = "fallback-encoders")]
+        {
+            if self.uses_fallback() {
+                let surface = surface.unwrap_or_else(|| Surface::from(&image));
+                let mipmaps = if self.mipmaps {
+                    surface.build_mipmaps(MipmapFilter::Box)
+                } else {
+                    vec![surface]
+                };
+                return Ok(Staged::Fallback(mipmaps, cached));
+            }
+        }
+
+        cfg_if! {
+            if #[cfg(feature = "nvtt")] {
+                let mut input_options = InputOptions::new().map_err(|e| batch_error(e, output))?;
+                input_options
+                    .set_image(&image, CubeFace::default(), MipLevel(0))
+                    .map_err(|e| batch_error(e, output))?
+                    .set_mipmap_generation(self.mipmaps, None);
+
+                Ok(Staged::Nvtt(input_options, cached))
+            } else {
+                // Without nvtt, only the fallback and basis encoders can compress the image.
+                log::error!(
+                    "Cannot compress {p} to {f:?} without nvtt",
+                    p = input.display(),
+                    f = self.format
+                );
+                Err(batch_error(Error::UnsupportedFeature, output))
+            }
+        }
+    }
+
+    /// Returns `true` if the files are compressed with the fallback encoders, because
+    /// this crate was built without nvtt, and the encoders support the format and
+    /// container of the batch.
+    #[cfg(feature = "fallback-encoders")]
+    fn uses_fallback(&self) -> bool {
+        #[cfg(feature = "basis")]
+        {
+            if self.basis.is_some() {
+                return false;
+            }
+        }
+        !cfg!(feature = "nvtt") && fallback_container(self.format) == Some(self.output_container())
+    }
+
+    /// Compress a `staged` file to `output`. Returns `true` if the texture was taken
+    /// from the cache. Files staged for nvtt fail if the `worker` has no compressor.
+    #[cfg_attr(not(feature = "nvtt"), allow(unused_variables))]
+    fn encode_staged(
+        &self,
+        worker: &Worker,
+        staged: Staged,
+        output: &Path,
+    ) -> Result<bool, CompressError> {
+        match staged {
+            Staged::Cached => Ok(true),
+            #[cfg(feature = "nvtt")]
+            Staged::Nvtt(input_options, cached) => {
+                self.encode_nvtt(worker.compressor.as_ref(), &input_options, output)?;
+
+                if let Some(ref cached) = cached {
+                    store_cached(output, cached);
+                }
+                Ok(false)
+            }
+            #[cfg(feature = "fallback-encoders")]
+            Staged::Fallback(mipmaps, cached) => {
+                let data = fallback_compress(&mipmaps, self.format).map_err(|e| CompressError {
+                    stage: CompressionStage::Encoding,
+                    ..batch_error(e, output)
+                })?;
+                fs::write(output, data).map_err(|_| CompressError {
+                    stage: CompressionStage::Output,
+                    ..batch_error(Error::FileWrite, output)
+                })?;
+
+                if let Some(ref cached) = cached {
+                    store_cached(output, cached);
+                }
+                Ok(false)
+            }
+            #[cfg(feature = "basis")]
+            Staged::Basis(surface, cached) => {
+                let format = self
+                    .basis
+                    .ok_or_else(|| batch_error(Error::Unknown, output))?;
+                let data = BasisOptions::new(format)
+                    .quality(self.quality)
+                    .mipmaps(self.mipmaps)
+                    .srgb(self.srgb)
+                    // The batch already compresses a file on each thread.
+                    .threads(1)
+                    .compress(&surface)
+                    .map_err(|e| CompressError {
+                        stage: CompressionStage::Encoding,
+                        ..batch_error(e, output)
+                    })?;
+                fs::write(output, data).map_err(|_| CompressError {
+                    stage: CompressionStage::Output,
+                    ..batch_error(Error::FileWrite, output)
+                })?;
+
+                if let Some(ref cached) = cached {
+                    store_cached(output, cached);
+                }
+                Ok(false)
+            }
+        }
+    }
+
+    /// Compress the `input_options` to `output` with nvtt. This fails if the
+    /// `compressor` of the thread could not be created.
+    #[cfg(feature = "nvtt")]
+    fn encode_nvtt(
+        &self,
+        compressor: Option<&Compressor>,
+        input_options: &InputOptions,
+        output: &Path,
+    ) -> Result<(), CompressError> {
+        let mut compression_options =
+            CompressionOptions::new().map_err(|e| batch_error(e, output))?;
+        compression_options
+            .set_format(self.format)
+            .set_quality(self.quality);
+
+        let mut output_options = OutputOptions::new().map_err(|e| batch_error(e, output))?;
+        output_options
+            .set_output_location(output)
+            .map_err(|_| batch_error(Error::FileOpen, output))?
+            .set_container(self.output_container())
+            .set_srgb_flag(self.srgb);
+
+        compressor
+            .ok_or_else(|| batch_error(Error::Unknown, output))?
+            .compress(&compression_options, input_options, &output_options)?;
+        Ok(())
+    }
+
+    /// Get the path in the cache of the texture compressed from the input file
+    /// contents `bytes`, or `None` if caching is disabled.
+    fn cache_path(&self, bytes: &[u8]) -> Option<PathBuf> {
+        let dir = self.cache_dir.as_ref()?;
+        // The versions are included so that upgrading either library invalidates
+        // the cache.
+        #[cfg(feature = "nvtt")]
+        let nvtt_version = linked_version();
+        // Without nvtt, the textures are compressed by the encoders of this crate.
+        #[cfg(not(feature = "nvtt"))]
+        let nvtt_version = 0;
+        #[allow(unused_mut)]
+        let mut settings = format!(
+            "{}:{}:{:?}:{:?}:{:?}:{}:{}",
+            env!("CARGO_PKG_VERSION"),
+            nvtt_version,
+            self.format,
+            self.quality,
+            self.output_container(),
+            self.mipmaps,
+            self.srgb,
+        );
+        #[cfg(feature = "basis")]
+        {
+            if let Some(format) = self.basis {
+                settings = format!("{}:{:?}", settings, format);
+            }
+        }
+        // The gpu encoders do not produce the same output as the cpu encoders.
+        if self.cuda {
+            settings = format!("{}:cuda", settings);
+        }
+        let hash = fnv1a_64(fnv1a_64(FNV_OFFSET_BASIS, bytes), settings.as_bytes());
+        Some(
+            dir.join(format!("{:016x}", hash))
+                .with_extension(self.output_extension()),
+        )
+    }
+}
+
+/// Limits the memory used by the files which a [`Batch`] compresses at the same time.
+///
+/// The memory needed by each file is estimated from the size of the file and the
+/// dimensions of the image before it is decoded, so the budget is approximate.
+///
+/// [`Batch`]: struct.Batch.html
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum MemoryBudget {
+    /// Files are compressed on every thread, regardless of their size.
+    Unlimited,
+    /// The estimated memory of the files being compressed at the same time is kept
+    /// within this number of bytes. A file which needs more than the whole budget is
+    /// compressed on its own.
+    Bytes(usize),
+}
+
+impl Default for MemoryBudget {
+    #[inline]
+    fn default() -> Self {
+        MemoryBudget::Unlimited
+    }
+}
+
+/// Tracks the memory reserved by the files which are being compressed, and blocks
+/// threads until their file fits into the budget.
+struct BudgetTracker {
+    limit: Option<usize>,
+    used: Mutex<usize>,
+    released: Condvar,
+}
+
+impl BudgetTracker {
+    fn new(budget: MemoryBudget) -> Self {
+        Self {
+            limit: match budget {
+                MemoryBudget::Unlimited => None,
+                MemoryBudget::Bytes(bytes) => Some(bytes),
+            },
+            used: Mutex::new(0),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Wait until `bytes` fit into the budget, and reserve them until the returned
+    /// `Reservation` is dropped.
+    fn reserve(&self, bytes: usize) -> Reservation<'_> {
+        let limit = match self.limit {
+            Some(limit) => limit,
+            None => {
+                return Reservation {
+                    tracker: self,
+                    bytes: 0,
+                }
+            }
+        };
+
+        let bytes = bytes.min(limit);
+        let mut used = self.used.lock().unwrap_or_else(|e| e.into_inner());
+        while *used > 0 && *used + bytes > limit {
+            used = self.released.wait(used).unwrap_or_else(|e| e.into_inner());
+        }
+        *used += bytes;
+        Reservation {
+            tracker: self,
+            bytes,
+        }
+    }
+}
+
+/// Memory reserved in a `BudgetTracker`, which is released when it is dropped.
+struct Reservation<'a> {
+    tracker: &'a BudgetTracker,
+    bytes: usize,
+}
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        if self.bytes > 0 {
+            let mut used = self.tracker.used.lock().unwrap_or_else(|e| e.into_inner());
+            *used -= self.bytes;
+            self.tracker.released.notify_all();
+        }
+    }
+}
+
+/// The state which each thread of a [`Batch`] keeps between the files which it
+/// compresses.
+///
+/// [`Batch`]: struct.Batch.html
+pub(crate) struct Worker {
+    /// The compressor for the files compressed with nvtt, or `None` if it could not
+    /// be created. Each thread uses its own compressor, as they cannot be shared.
+    #[cfg(feature = "nvtt")]
+    compressor: Option<Compressor>,
+}
+
+/// A file which has been read and converted into the input of the encoder. The
+/// `Option<PathBuf>` is the path of the texture in the cache, if caching is enabled.
+enum Staged {
+    /// The texture was restored from the cache, so it does not need to be encoded.
+    Cached,
+    /// The image is ready to be compressed by nvtt.
+    #[cfg(feature = "nvtt")]
+    Nvtt(InputOptions, Option<PathBuf>),
+    /// The image is ready to be compressed into a Basis Universal file.
+    #[cfg(feature = "basis")]
+    Basis(Surface, Option<PathBuf>),
+    /// The mipmaps are ready to be compressed with the fallback encoders, because
+    /// nvtt is not available.
+    #[cfg(feature = "fallback-encoders")]
+    Fallback(Vec<Surface>, Option<PathBuf>),
+}
+
+/// Create the `BatchResult` of a job which took `elapsed` to compress.
+fn batch_result(
+    input: &Path,
+    output: &Path,
+    elapsed: Duration,
+    result: Result<bool, CompressError>,
+) -> BatchResult {
+    BatchResult {
+        input: input.to_path_buf(),
+        output: output.to_path_buf(),
+        elapsed,
+        cached: result == Ok(true),
+        result: result.map(|_| ()),
+    }
+}
+
+/// Replace `output` with a copy of the `cached` texture, if it exists.
+///
+/// The entry is copied rather than hard linked, because the output is later written
+/// in place when its input changes, which would also overwrite a linked entry.
+fn restore_cached(cached: &Path, output: &Path) -> bool {
+    if !cached.is_file() {
+        return false;
+    }
+    let _ = fs::remove_file(output);
+    fs::copy(cached, output).is_ok()
+}
+
+/// Copy the compressed texture at `output` into the cache. Failing to write the
+/// cache does not fail the compression.
+#[cfg(any(feature = "nvtt", feature = "fallback-encoders", feature = "basis"))]
+fn store_cached(output: &Path, cached: &Path) {
+    // The texture is copied to a temporary file first, so that other threads or
+    // processes never see a partially written entry.
+    let temp = cached.with_extension(format!("{}.tmp", std::process::id()));
+    let result = cached
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::copy(output, &temp))
+        .and_then(|_| fs::rename(&temp, cached));
+
+    if let Err(e) = result {
+        let _ = fs::remove_file(&temp);
+        log::warn!(
+            "Could not cache texture {p}\nCaused by: {e}",
+            p = output.display(),
+            e = e
+        );
+    }
+}
+
+/// Create an error for a file which failed before it was passed to nvtt.
+fn batch_error(error: Error, output: &Path) -> CompressError {
+    CompressError {
+        error,
+        stage: CompressionStage::InputValidation,
+        path: Some(output.to_path_buf()),
+        image: None,
+        diagnosis: None,
+    }
+}
+
+/// Get the directory part of a glob `pattern`, which contains no wildcards.
+fn glob_base(pattern: &str) -> PathBuf {
+    Path::new(pattern)
+        .components()
+        .take_while(|component| match component {
+            Component::Normal(part) => {
+                let part = part.to_string_lossy();
+                !part.contains(&['*', '?', '['][..])
+            }
+            _ => true,
+        })
+        .collect()
+}
+
+/// An error which stops a [`Batch`] before any file is compressed.
+///
+/// [`Batch`]: struct.Batch.html
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum BatchError {
+    /// A glob pattern of the batch is invalid.
+    Pattern(PatternError),
+    /// Two inputs would be compressed into the same output file.
+    OutputCollision {
+        /// The inputs which share the output, in the order of the batch.
+        inputs: [PathBuf; 2],
+        /// The output path.
+        output: PathBuf,
+    },
+}
+
+impl fmt::Display for BatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            BatchError::Pattern(ref e) => write!(f, "Invalid glob pattern: {}", e),
+            BatchError::OutputCollision {
+                ref inputs,
+                ref output,
+            } => write!(
+                f,
+                "{} and {} would both be compressed into {}",
+                inputs[0].display(),
+                inputs[1].display(),
+                output.display()
+            ),
+        }
+    }
+}
+
+impl ErrorTrait for BatchError {
+    #[inline]
+    fn source(&self) -> Option<&(dyn ErrorTrait + 'static)> {
+        match *self {
+            BatchError::Pattern(ref e) => Some(e),
+            BatchError::OutputCollision { .. } => None,
+        }
+    }
+}
+
+impl From<PatternError> for BatchError {
+    #[inline]
+    fn from(e: PatternError) -> Self {
+        BatchError::Pattern(e)
+    }
+}
+
+/// The result of compressing a single file in a [`Batch`].
+///
+/// [`Batch`]: struct.Batch.html
+#[derive(Clone, Debug)]
+pub struct BatchResult {
+    input: PathBuf,
+    output: PathBuf,
+    elapsed: Duration,
+    cached: bool,
+    result: Result<(), CompressError>,
+}
+
+impl BatchResult {
+    /// The path of the input image.
+    #[inline]
+    pub fn input(&self) -> &Path {
+        &self.input
+    }
+
+    /// The path of the compressed texture.
+    #[inline]
+    pub fn output(&self) -> &Path {
+        &self.output
+    }
+
+    /// The time taken to load and compress the image.
+    #[inline]
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Whether the texture was taken from the cache instead of being compressed. See
+    /// [`Batch::cache_dir`].
+    ///
+    /// [`Batch::cache_dir`]: struct.Batch.html#method.cache_dir
+    #[inline]
+    pub fn is_cached(&self) -> bool {
+        self.cached
+    }
+
+    /// Whether the image was compressed successfully.
+    #[inline]
+    pub fn result(&self) -> Result<(), &CompressError> {
+        self.result.as_ref().map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+
+    /// Create an empty directory for the files of a test.
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("nvtt_rs-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn cache_path_depends_on_the_contents_and_settings() {
+        let mut batch = Batch::new();
+        assert_eq!(batch.cache_path(b"texture"), None);
+
+        batch.cache_dir("cache").format(Format::Bc1);
+        let path = batch.cache_path(b"texture").unwrap();
+        assert_eq!(path.parent(), Some(Path::new("cache")));
+        assert_eq!(path.extension(), Some(batch.output_extension().as_os_str()));
+        assert_eq!(batch.cache_path(b"texture"), Some(path.clone()));
+        assert_ne!(batch.cache_path(b"other texture"), Some(path.clone()));
+
+        let changes: [fn(&mut Batch); 5] = [
+            |batch| {
+                batch.format(Format::Bc3);
+            },
+            |batch| {
+                batch.quality(Quality::Highest);
+            },
+            |batch| {
+                batch.mipmaps(false);
+            },
+            |batch| {
+                batch.srgb(true);
+            },
+            |batch| {
+                batch.cuda(true);
+            },
+        ];
+        for change in changes.iter() {
+            let mut changed = batch.clone();
+            change(&mut changed);
+            assert_ne!(changed.cache_path(b"texture"), Some(path.clone()));
+        }
+    }
+
+    #[test]
+    fn jobs_restore_cached_textures() {
+        let dir = test_dir("restore-cached");
+        let (input, output) = (dir.join("albedo.png"), dir.join("albedo.dds"));
+        // The input is not decoded when the texture is in the cache.
+        fs::write(&input, b"not an image").unwrap();
+
+        let mut batch = Batch::new();
+        batch.cache_dir(dir.join("cache"));
+        let cached = batch.cache_path(b"not an image").unwrap();
+        fs::create_dir_all(cached.parent().unwrap()).unwrap();
+        fs::write(&cached, b"cached texture").unwrap();
+
+        let result = batch.run_job(&batch.worker(), &input, &output);
+        let data = fs::read(&output);
+        let _ = fs::remove_dir_all(&dir);
+        assert!(result.result().is_ok());
+        assert!(result.is_cached());
+        assert_eq!(data.unwrap(), b"cached texture");
+    }
+
+    #[cfg(all(feature = "fallback-encoders", not(feature = "nvtt")))]
+    #[test]
+    fn jobs_store_compressed_textures_in_the_cache() {
+        let dir = test_dir("store-cached");
+        let (input, output) = (dir.join("albedo.png"), dir.join("albedo.dds"));
+        image::RgbaImage::from_pixel(8, 8, image::Rgba([255, 128, 0, 255]))
+            .save(&input)
+            .unwrap();
+
+        let mut batch = Batch::new();
+        batch
+            .format(Format::Bc1)
+            .container(Container::Dds)
+            .cache_dir(dir.join("cache"));
+        let worker = batch.worker();
+        let first = batch.run_job(&worker, &input, &output);
+        let compressed = fs::read(&output).unwrap();
+        fs::remove_file(&output).unwrap();
+        let second = batch.run_job(&worker, &input, &output);
+        let restored = fs::read(&output);
+        let _ = fs::remove_dir_all(&dir);
+
+        assert!(first.result().is_ok());
+        assert!(!first.is_cached());
+        assert!(second.result().is_ok());
+        assert!(second.is_cached());
+        assert_eq!(restored.unwrap(), compressed);
+    }
+
+    #[test]
+    fn jobs_reject_inputs_with_the_same_output() {
+        let mut batch = Batch::new();
+        batch.add_file("textures/a.png").add_file("textures/b.png");
+        assert_eq!(batch.jobs().unwrap().len(), 2);
+
+        batch.add_file("textures/a.jpg");
+        match batch.jobs() {
+            Err(BatchError::OutputCollision { inputs, output }) => {
+                let expected = [PathBuf::from("textures/a.png"), "textures/a.jpg".into()];
+                assert_eq!(inputs, expected);
+                assert_eq!(output, Path::new("textures/a.dds"));
+            }
+            result => panic!("expected an output collision, got {:?}", result),
+        }
+
+        // Inputs in different directories are written next to their inputs, so they
+        // only collide when they are written into the same output directory.
+        let mut batch = Batch::new();
+        batch.add_file("a/albedo.png").add_file("b/albedo.png");
+        assert_eq!(batch.jobs().unwrap().len(), 2);
+        batch.output_dir("cooked");
+        assert!(matches!(
+            batch.jobs(),
+            Err(BatchError::OutputCollision { .. })
+        ));
+    }
+}
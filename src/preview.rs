@@ -0,0 +1,148 @@
+// Copyright © 2019-2020 George Burton
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::{Error, ResizeFilter, RoundMode, Surface};
+#[cfg(feature = "serde-serialize")]
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Settings for the preview images written by [`write_preview`].
+///
+/// [`write_preview`]: fn.write_preview.html
+#[cfg_attr(feature = "serde-serialize", derive(Deserialize, Serialize))]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PreviewOptions {
+    /// The largest width or height of each image in the preview. Larger images are
+    /// scaled down to fit. This is `256` by default.
+    pub max_size: usize,
+    /// Whether a heatmap of the difference between the images is added after the
+    /// decoded image. This is `true` by default.
+    pub heatmap: bool,
+    /// The difference which is shown at full brightness in the heatmap. Smaller
+    /// values exaggerate the error. This is `0.1` by default.
+    pub heatmap_range: f32,
+}
+
+impl Default for PreviewOptions {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            max_size: 256,
+            heatmap: true,
+            heatmap_range: 0.1,
+        }
+    }
+}
+
+/// Build a preview image, with the `reference` image on the left and the `decoded`
+/// image on the right, followed by a heatmap of the difference between them if it is
+/// enabled in the `options`.
+///
+/// Only the first depth slice of 3D surfaces is shown, and the alpha of the preview
+/// is always `1.0`, so that the color of transparent pixels is visible. The heatmap
+/// shows the largest difference of any channel at each pixel, going from black
+/// through red and yellow to white.
+///
+/// # Errors
+///
+/// If the dimensions of the two surfaces differ, or either is null, then this
+/// function will fail with [`Error::InvalidInput`].
+///
+/// [`Error::InvalidInput`]: enum.Error.html#variant.InvalidInput
+pub fn preview_surface(
+    reference: &Surface,
+    decoded: &Surface,
+    options: &PreviewOptions,
+) -> Result<Surface, Error> {
+    let dimensions = |s: &Surface| (s.width(), s.height(), s.depth());
+    if dimensions(reference) != dimensions(decoded) || reference.is_null() {
+        return Err(Error::InvalidInput);
+    }
+
+    let (width, height) = (reference.width(), reference.height());
+    let mut heatmap = Surface::new(width, height);
+    for i in 0..width * height {
+        let (a, b) = (reference.pixel(i), decoded.pixel(i));
+        let error = a
+            .iter()
+            .zip(b.iter())
+            .map(|(a, b)| (a - b).abs())
+            .fold(0.0, f32::max);
+        let t = 3.0 * error / options.heatmap_range.max(f32::EPSILON);
+        let [r, g, b] = [t, t - 1.0, t - 2.0].map(|c| c.clamp(0.0, 1.0));
+        heatmap.set_pixel(i, [r, g, b, 1.0]);
+    }
+
+    let mut panels = vec![slice(reference), slice(decoded)];
+    if options.heatmap {
+        panels.push(heatmap);
+    }
+    for panel in &mut panels {
+        panel.resize_to_fit(options.max_size, RoundMode::None, ResizeFilter::default());
+    }
+
+    let (panel_width, panel_height) = (panels[0].width(), panels[0].height());
+    let mut preview = Surface::new(panel_width * panels.len(), panel_height);
+    for (n, panel) in panels.iter().enumerate() {
+        for y in 0..panel_height {
+            for x in 0..panel_width {
+                let [r, g, b, _] = panel.pixel(x + y * panel_width);
+                let dst = n * panel_width + x + y * preview.width();
+                preview.set_pixel(dst, [r, g, b, 1.0]);
+            }
+        }
+    }
+    Ok(preview)
+}
+
+/// Write a png preview comparing the `reference` image with the `decoded` image to
+/// `path`. See [`preview_surface`] for the layout of the preview.
+///
+/// # Errors
+///
+/// If the preview cannot be built, then this function will fail as described by
+/// [`preview_surface`]. If the preview cannot be written, then this function will fail
+/// with [`Error::FileWrite`].
+///
+/// # Notes
+///
+/// This function requires the [`preview`] feature.
+///
+/// [`preview_surface`]: fn.preview_surface.html
+/// [`Error::FileWrite`]: enum.Error.html#variant.FileWrite
+/// [`preview`]: index.html#preview
+pub fn write_preview<P: AsRef<Path>>(
+    reference: &Surface,
+    decoded: &Surface,
+    path: P,
+    options: &PreviewOptions,
+) -> Result<(), Error> {
+    preview_surface(reference, decoded, options)?.save(path)
+}
+
+/// Copy the first depth slice of `surface` into a new 2D `Surface`.
+fn slice(surface: &Surface) -> Surface {
+    let (width, height) = (surface.width(), surface.height());
+    let mut slice = Surface::new(width, height);
+    for i in 0..width * height {
+        slice.set_pixel(i, surface.pixel(i));
+    }
+    slice
+}
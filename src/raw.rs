@@ -0,0 +1,104 @@
+// Copyright © 2019-2020 George Burton
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! The raw values of the nvtt enums, which are used when this crate is built without
+//! the `nvtt` feature. These are the same as the values in `nvtt.h`, so that a raw
+//! value means the same thing whether or not nvtt is linked.
+
+#![allow(nonstandard_style)]
+
+pub const NvttFormat_NVTT_Format_RGB: NvttFormat = 0;
+pub const NvttFormat_NVTT_Format_RGBA: NvttFormat = 0;
+pub const NvttFormat_NVTT_Format_DXT1: NvttFormat = 1;
+pub const NvttFormat_NVTT_Format_DXT1a: NvttFormat = 2;
+pub const NvttFormat_NVTT_Format_DXT3: NvttFormat = 3;
+pub const NvttFormat_NVTT_Format_DXT5: NvttFormat = 4;
+pub const NvttFormat_NVTT_Format_DXT5n: NvttFormat = 5;
+pub const NvttFormat_NVTT_Format_BC1: NvttFormat = 1;
+pub const NvttFormat_NVTT_Format_BC1a: NvttFormat = 2;
+pub const NvttFormat_NVTT_Format_BC2: NvttFormat = 3;
+pub const NvttFormat_NVTT_Format_BC3: NvttFormat = 4;
+pub const NvttFormat_NVTT_Format_BC3n: NvttFormat = 5;
+pub const NvttFormat_NVTT_Format_BC4: NvttFormat = 6;
+pub const NvttFormat_NVTT_Format_BC5: NvttFormat = 7;
+pub const NvttFormat_NVTT_Format_DXT1n: NvttFormat = 8;
+pub const NvttFormat_NVTT_Format_CTX1: NvttFormat = 9;
+pub const NvttFormat_NVTT_Format_BC6: NvttFormat = 10;
+pub const NvttFormat_NVTT_Format_BC7: NvttFormat = 11;
+pub const NvttFormat_NVTT_Format_BC3_RGBM: NvttFormat = 12;
+pub const NvttFormat_NVTT_Format_ETC1: NvttFormat = 13;
+pub const NvttFormat_NVTT_Format_ETC2_R: NvttFormat = 14;
+pub const NvttFormat_NVTT_Format_ETC2_RG: NvttFormat = 15;
+pub const NvttFormat_NVTT_Format_ETC2_RGB: NvttFormat = 16;
+pub const NvttFormat_NVTT_Format_ETC2_RGBA: NvttFormat = 17;
+pub const NvttFormat_NVTT_Format_ETC2_RGB_A1: NvttFormat = 18;
+pub const NvttFormat_NVTT_Format_ETC2_RGBM: NvttFormat = 19;
+pub const NvttFormat_NVTT_Format_PVR_2BPP_RGB: NvttFormat = 20;
+pub const NvttFormat_NVTT_Format_PVR_4BPP_RGB: NvttFormat = 21;
+pub const NvttFormat_NVTT_Format_PVR_2BPP_RGBA: NvttFormat = 22;
+pub const NvttFormat_NVTT_Format_PVR_4BPP_RGBA: NvttFormat = 23;
+pub type NvttFormat = u32;
+pub const NvttQuality_NVTT_Quality_Fastest: NvttQuality = 0;
+pub const NvttQuality_NVTT_Quality_Normal: NvttQuality = 1;
+pub const NvttQuality_NVTT_Quality_Production: NvttQuality = 2;
+pub const NvttQuality_NVTT_Quality_Highest: NvttQuality = 3;
+pub type NvttQuality = u32;
+pub const NvttWrapMode_NVTT_WrapMode_Clamp: NvttWrapMode = 0;
+pub const NvttWrapMode_NVTT_WrapMode_Repeat: NvttWrapMode = 1;
+pub const NvttWrapMode_NVTT_WrapMode_Mirror: NvttWrapMode = 2;
+pub type NvttWrapMode = u32;
+pub const NvttTextureType_NVTT_TextureType_2D: NvttTextureType = 0;
+pub const NvttTextureType_NVTT_TextureType_Cube: NvttTextureType = 1;
+pub const NvttTextureType_TextureType_3D: NvttTextureType = 2;
+pub const NvttTextureType_TextureType_Array: NvttTextureType = 3;
+pub type NvttTextureType = u32;
+pub const NvttInputFormat_NVTT_InputFormat_BGRA_8UB: NvttInputFormat = 0;
+pub const NvttInputFormat_NVTT_InputFormat_RGBA_16F: NvttInputFormat = 1;
+pub const NvttInputFormat_NVTT_InputFormat_RGBA_32F: NvttInputFormat = 2;
+pub const NvttInputFormat_NVTT_InputFormat_R_32F: NvttInputFormat = 3;
+pub type NvttInputFormat = u32;
+pub const NvttMipmapFilter_NVTT_MipmapFilter_Box: NvttMipmapFilter = 0;
+pub const NvttMipmapFilter_NVTT_MipmapFilter_Triangle: NvttMipmapFilter = 1;
+pub const NvttMipmapFilter_NVTT_MipmapFilter_Kaiser: NvttMipmapFilter = 2;
+pub type NvttMipmapFilter = u32;
+pub const NvttRoundMode_NVTT_RoundMode_None: NvttRoundMode = 0;
+pub const NvttRoundMode_NVTT_RoundMode_ToNextPowerOfTwo: NvttRoundMode = 1;
+pub const NvttRoundMode_NVTT_RoundMode_ToNearestPowerOfTwo: NvttRoundMode = 2;
+pub const NvttRoundMode_NVTT_RoundMode_ToPreviousPowerOfTwo: NvttRoundMode = 3;
+pub const NvttRoundMode_NVTT_RoundMode_ToNextMultipleOfFour: NvttRoundMode = 4;
+pub const NvttRoundMode_NVTT_RoundMode_ToNearestMultipleOfFour: NvttRoundMode = 5;
+pub const NvttRoundMode_NVTT_RoundMode_ToPreviousMultipleOfFour: NvttRoundMode = 6;
+pub type NvttRoundMode = u32;
+pub const NvttAlphaMode_NVTT_AlphaMode_None: NvttAlphaMode = 0;
+pub const NvttAlphaMode_NVTT_AlphaMode_Transparency: NvttAlphaMode = 1;
+pub const NvttAlphaMode_NVTT_AlphaMode_Premultiplied: NvttAlphaMode = 2;
+pub type NvttAlphaMode = u32;
+pub const NvttError_NVTT_Error_Unknown: NvttError = 0;
+pub const NvttError_NVTT_Error_InvalidInput: NvttError = 1;
+pub const NvttError_NVTT_Error_UnsupportedFeature: NvttError = 2;
+pub const NvttError_NVTT_Error_CudaError: NvttError = 3;
+pub const NvttError_NVTT_Error_FileOpen: NvttError = 4;
+pub const NvttError_NVTT_Error_FileWrite: NvttError = 5;
+pub const NvttError_NVTT_Error_UnsupportedOutputFormat: NvttError = 6;
+pub type NvttError = u32;
+pub const NvttContainer_NVTT_Container_DDS: NvttContainer = 0;
+pub const NvttContainer_NVTT_Container_DDS10: NvttContainer = 1;
+pub const NvttContainer_NVTT_Container_KTX: NvttContainer = 2;
+pub type NvttContainer = u32;
@@ -0,0 +1,171 @@
+// Copyright © 2019-2020 George Burton
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::surface::f16_to_f32;
+
+/// Define a conversion function, which dispatches to a version compiled with avx2
+/// enabled when the cpu supports it.
+///
+/// Each conversion is written as a simple loop which the compiler can vectorize.
+/// Sse2 and neon are always available on x86_64 and aarch64, so the default version
+/// already uses them, and on x86 the wider avx2 version is selected at runtime.
+macro_rules! multiversion {
+    ($(#[$attr:meta])* fn $name:ident($($arg:ident: $ty:ty),* $(,)?) $body:block) => {
+        $(#[$attr])*
+        pub(crate) fn $name($($arg: $ty),*) {
+            #[inline(always)]
+            fn generic($($arg: $ty),*) $body
+
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            {
+                #[target_feature(enable = "avx2")]
+                unsafe fn avx2($($arg: $ty),*) {
+                    generic($($arg),*)
+                }
+
+                if is_x86_feature_detected!("avx2") {
+                    // @SAFETY: The cpu supports avx2, which was checked above.
+                    unsafe { avx2($($arg),*) };
+                    return;
+                }
+            }
+
+            generic($($arg),*)
+        }
+    };
+}
+
+multiversion! {
+    /// Convert interleaved 8 bit unorm pixels in `src` into floats in the `0..1` range,
+    /// writing each channel into its own plane.
+    fn unorm8_to_planes(src: &[u8], r: &mut [f32], g: &mut [f32], b: &mut [f32], a: &mut [f32]) {
+        let planes = r.iter_mut().zip(g).zip(b).zip(a);
+        for (px, (((r, g), b), a)) in src.chunks_exact(4).zip(planes) {
+            *r = f32::from(px[0]) / 255.0;
+            *g = f32::from(px[1]) / 255.0;
+            *b = f32::from(px[2]) / 255.0;
+            *a = f32::from(px[3]) / 255.0;
+        }
+    }
+}
+
+multiversion! {
+    /// Convert the planes of a `Surface` into interleaved 8 bit unorm pixels in `dst`.
+    /// Values are clamped to the `0..1` range.
+    #[cfg(feature = "nvtt_image_integration")]
+    fn planes_to_unorm8(r: &[f32], g: &[f32], b: &[f32], a: &[f32], dst: &mut [u8]) {
+        let planes = r.iter().zip(g).zip(b).zip(a);
+        for (px, (((r, g), b), a)) in dst.chunks_exact_mut(4).zip(planes) {
+            px[0] = (r.clamp(0.0, 1.0) * 255.0).round() as u8;
+            px[1] = (g.clamp(0.0, 1.0) * 255.0).round() as u8;
+            px[2] = (b.clamp(0.0, 1.0) * 255.0).round() as u8;
+            px[3] = (a.clamp(0.0, 1.0) * 255.0).round() as u8;
+        }
+    }
+}
+
+multiversion! {
+    /// Read interleaved native endian 32 bit float pixels in `src`, writing each
+    /// channel into its own plane.
+    fn f32_to_planes(src: &[u8], r: &mut [f32], g: &mut [f32], b: &mut [f32], a: &mut [f32]) {
+        let read = |bytes: &[u8]| f32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        let planes = r.iter_mut().zip(g).zip(b).zip(a);
+        for (px, (((r, g), b), a)) in src.chunks_exact(16).zip(planes) {
+            *r = read(&px[0..4]);
+            *g = read(&px[4..8]);
+            *b = read(&px[8..12]);
+            *a = read(&px[12..16]);
+        }
+    }
+}
+
+multiversion! {
+    /// Write the planes of a `Surface` into `dst` as interleaved native endian 32 bit
+    /// float pixels, which is the `Rgba32F` input format of nvtt.
+    #[cfg(feature = "nvtt")]
+    fn planes_to_f32(r: &[f32], g: &[f32], b: &[f32], a: &[f32], dst: &mut [u8]) {
+        let planes = r.iter().zip(g).zip(b).zip(a);
+        for (px, (((r, g), b), a)) in dst.chunks_exact_mut(16).zip(planes) {
+            px[0..4].copy_from_slice(&r.to_ne_bytes());
+            px[4..8].copy_from_slice(&g.to_ne_bytes());
+            px[8..12].copy_from_slice(&b.to_ne_bytes());
+            px[12..16].copy_from_slice(&a.to_ne_bytes());
+        }
+    }
+}
+
+/// Convert interleaved native endian half float pixels in `src` into floats, writing
+/// each channel into its own plane. The hardware conversion is used when the cpu
+/// supports f16c.
+pub(crate) fn f16_to_planes(
+    src: &[u8],
+    r: &mut [f32],
+    g: &mut [f32],
+    b: &mut [f32],
+    a: &mut [f32],
+) {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        if is_x86_feature_detected!("f16c") {
+            // @SAFETY: The cpu supports f16c, which was checked above.
+            unsafe { f16_to_planes_f16c(src, r, g, b, a) };
+            return;
+        }
+    }
+
+    let read = |bytes: &[u8]| f16_to_f32(u16::from_ne_bytes([bytes[0], bytes[1]]));
+    let planes = r.iter_mut().zip(g).zip(b).zip(a);
+    for (px, (((r, g), b), a)) in src.chunks_exact(8).zip(planes) {
+        *r = read(&px[0..2]);
+        *g = read(&px[2..4]);
+        *b = read(&px[4..6]);
+        *a = read(&px[6..8]);
+    }
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "f16c")]
+unsafe fn f16_to_planes_f16c(
+    src: &[u8],
+    r: &mut [f32],
+    g: &mut [f32],
+    b: &mut [f32],
+    a: &mut [f32],
+) {
+    #[cfg(target_arch = "x86")]
+    use std::arch::x86::*;
+    #[cfg(target_arch = "x86_64")]
+    use std::arch::x86_64::*;
+
+    let planes = r.iter_mut().zip(g).zip(b).zip(a);
+    for (px, (((r, g), b), a)) in src.chunks_exact(8).zip(planes) {
+        let mut rgba = [0.0f32; 4];
+        // @SAFETY: `px` contains the 8 bytes of the four halfs which are loaded, and
+        // `rgba` has room for the four converted floats. Neither needs to be aligned.
+        unsafe {
+            let halfs = _mm_loadl_epi64(px.as_ptr() as *const __m128i);
+            _mm_storeu_ps(rgba.as_mut_ptr(), _mm_cvtph_ps(halfs));
+        }
+        *r = rgba[0];
+        *g = rgba[1];
+        *b = rgba[2];
+        *a = rgba[3];
+    }
+}
@@ -0,0 +1,2355 @@
+// Copyright © 2019-2020 George Burton
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::{
+    simd, AlphaMode, Error, Extent3d, InputFormat, KaiserParameters, MipLevel, MipmapFilter,
+    NormalMapFilter, RoundMode, TextureLayout, WrapMode,
+};
+#[cfg(feature = "nvtt")]
+use crate::{CompressScratch, CubeFace, InputOptions};
+use cfg_if::cfg_if;
+#[cfg(feature = "serde-serialize")]
+use serde::{Deserialize, Serialize};
+use std::{f32::consts::PI, fmt, path::Path};
+
+/// Selects a single channel of a [`Surface`].
+///
+/// [`Surface`]: struct.Surface.html
+#[cfg_attr(feature = "serde-serialize", derive(Deserialize, Serialize))]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Channel {
+    /// The red channel.
+    Red,
+    /// The green channel.
+    Green,
+    /// The blue channel.
+    Blue,
+    /// The alpha channel.
+    Alpha,
+}
+
+impl Channel {
+    /// All of the channels, in the order they are stored in a `Surface`.
+    pub const ALL: [Channel; 4] = [Channel::Red, Channel::Green, Channel::Blue, Channel::Alpha];
+
+    #[inline]
+    const fn index(self) -> usize {
+        match self {
+            Channel::Red => 0,
+            Channel::Green => 1,
+            Channel::Blue => 2,
+            Channel::Alpha => 3,
+        }
+    }
+}
+
+/// Specify which type of filter is used to resize a [`Surface`].
+///
+/// [`Surface`]: struct.Surface.html
+#[cfg_attr(feature = "serde-serialize", derive(Deserialize, Serialize))]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ResizeFilter {
+    /// Use a box filter.
+    Box,
+    /// Use a triangle filter.
+    Triangle,
+    /// Use a kaiser filter. If the parameters are set, then
+    /// they will override the defaults.
+    Kaiser(Option<KaiserParameters>),
+    /// Use a Mitchell-Netravali filter. This is the default.
+    Mitchell,
+}
+
+impl Default for ResizeFilter {
+    #[inline]
+    fn default() -> Self {
+        ResizeFilter::Mitchell
+    }
+}
+
+/// Specify how the `x` and `y` components of a unit normal are projected into
+/// two channels by [`Surface::transform_normals`], and reconstructed by
+/// [`Surface::reconstruct_z`].
+///
+/// [`Surface::transform_normals`]: struct.Surface.html#method.transform_normals
+/// [`Surface::reconstruct_z`]: struct.Surface.html#method.reconstruct_z
+#[cfg_attr(feature = "serde-serialize", derive(Deserialize, Serialize))]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum NormalTransform {
+    /// Keep the `x` and `y` components as they are. The `z` component is reconstructed
+    /// as `sqrt(1 - x² - y²)`. This is the layout expected by BC5 and DXT5n.
+    Orthographic,
+    /// Project the normal from the south pole of the unit sphere onto the `z = 0` plane.
+    Stereographic,
+    /// Project the normal onto the paraboloid `z = 1 - x² - y²`.
+    Paraboloid,
+}
+
+/// Specify the operator used by [`Surface::tone_map`] to map high dynamic range
+/// colors into the `0..1` range.
+///
+/// [`Surface::tone_map`]: struct.Surface.html#method.tone_map
+#[cfg_attr(feature = "serde-serialize", derive(Deserialize, Serialize))]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ToneMapper {
+    /// Clamp each color channel into the `0..1` range.
+    Linear,
+    /// Apply the Reinhard operator `x / (1 + x)` to each color channel.
+    Reinhard,
+    /// Apply Krzysztof Narkowicz's fit of the ACES filmic curve to each color channel.
+    Aces,
+}
+
+/// Specify how the quantization error is distributed when the values of a [`Surface`]
+/// are reduced to a lower precision, such as when compressing to a 565 color format.
+///
+/// `bool` values convert into `Dither::None` and `Dither::ErrorDiffusion`.
+///
+/// [`Surface`]: struct.Surface.html
+#[cfg_attr(feature = "serde-serialize", derive(Deserialize, Serialize))]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Dither {
+    /// Round each value to the nearest representable value. This can cause banding in
+    /// smooth gradients.
+    None,
+    /// Offset each value by a 4x4 Bayer matrix before rounding. The pattern is regular
+    /// and stable between mipmap levels and frames, which suits UI textures.
+    Ordered,
+    /// Diffuse the error of each value into its neighbours using the Floyd-Steinberg
+    /// kernel. This is the dithering performed by nvtt.
+    ErrorDiffusion,
+}
+
+impl Default for Dither {
+    #[inline]
+    fn default() -> Self {
+        Dither::None
+    }
+}
+
+impl From<bool> for Dither {
+    #[inline]
+    fn from(dither: bool) -> Self {
+        if dither {
+            Dither::ErrorDiffusion
+        } else {
+            Dither::None
+        }
+    }
+}
+
+/// The value of a single channel of an image built by [`Surface::pack_channels`].
+///
+/// [`Surface::pack_channels`]: struct.Surface.html#method.pack_channels
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ChannelSource<'a> {
+    /// Fill the channel with a constant value.
+    Constant(f32),
+    /// Copy the channel from a channel of another `Surface`.
+    Channel(&'a Surface, Channel),
+}
+
+impl Default for ChannelSource<'_> {
+    #[inline]
+    fn default() -> Self {
+        ChannelSource::Constant(0.0)
+    }
+}
+
+impl From<f32> for ChannelSource<'_> {
+    #[inline]
+    fn from(value: f32) -> Self {
+        ChannelSource::Constant(value)
+    }
+}
+
+impl<'a> From<(&'a Surface, Channel)> for ChannelSource<'a> {
+    #[inline]
+    fn from((surface, channel): (&'a Surface, Channel)) -> Self {
+        ChannelSource::Channel(surface, channel)
+    }
+}
+
+/// The position and size of a sprite which is packed into a sprite sheet or atlas,
+/// in pixels.
+#[cfg_attr(feature = "serde-serialize", derive(Deserialize, Serialize))]
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct SpriteRect {
+    /// The x coordinate of the left edge of the sprite.
+    pub x: usize,
+    /// The y coordinate of the top edge of the sprite.
+    pub y: usize,
+    /// The width of the sprite.
+    pub width: usize,
+    /// The height of the sprite.
+    pub height: usize,
+}
+
+impl SpriteRect {
+    /// Create a new `SpriteRect`.
+    #[inline]
+    pub const fn new(x: usize, y: usize, width: usize, height: usize) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+}
+
+/// The amount that each sprite is extruded by [`Surface::extrude_sprites`].
+///
+/// Each mipmap level halves the padding, so a sprite which is extruded by `pixels`
+/// at `mip_level` is extruded by `pixels << mip_level` in the top level image. Pixels
+/// are extruded by at least a whole `4x4` block at that level, so that the block
+/// compressor never sees pixels of a neighbouring sprite.
+///
+/// [`Surface::extrude_sprites`]: struct.Surface.html#method.extrude_sprites
+#[cfg_attr(feature = "serde-serialize", derive(Deserialize, Serialize))]
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct SpritePadding {
+    /// The number of pixels of padding which is kept at `mip_level`.
+    pub pixels: usize,
+    /// The smallest mipmap level which should not bleed between sprites.
+    pub mip_level: MipLevel,
+}
+
+impl SpritePadding {
+    /// Create a new `SpritePadding` which keeps `pixels` of padding around each sprite
+    /// down to `mip_level`.
+    #[inline]
+    pub fn new(pixels: usize, mip_level: impl Into<MipLevel>) -> Self {
+        Self {
+            pixels,
+            mip_level: mip_level.into(),
+        }
+    }
+
+    /// The number of pixels each sprite is extruded by in the top level image.
+    #[inline]
+    pub fn top_level_pixels(self) -> usize {
+        self.pixels
+            .max(4)
+            .checked_shl(self.mip_level.0)
+            .unwrap_or(usize::MAX)
+    }
+}
+
+/// The 4x4 Bayer matrix used for `Dither::Ordered`.
+const BAYER_4X4: [f32; 16] = [
+    0.0, 8.0, 2.0, 10.0, 12.0, 4.0, 14.0, 6.0, 3.0, 11.0, 1.0, 9.0, 15.0, 7.0, 13.0, 5.0,
+];
+
+/// A floating point rgba image which can be processed before it is compressed. This
+/// mirrors the [`nvtt::Surface`] type.
+///
+/// Each channel is stored as a separate plane of `f32` values, so that per-channel
+/// operations are cheap. A `Surface` can be passed to the compressor with
+/// [`InputOptions::set_surface`].
+///
+/// # Notes
+///
+/// `Surface` is a reimplementation of `nvtt::Surface` in Rust, and none of its
+/// processing is done by nvtt. This gives the same results with every version of nvtt,
+/// and when this crate is built without nvtt. With nvtt 2.1, the `nvttSurface*` shims
+/// of `nvtt_sys` are only used to load image files, and to pass a `Surface` to a
+/// [`Context`].
+///
+/// [`nvtt::Surface`]: https://github.com/castano/nvidia-texture-tools/wiki/ApiDocumentation
+/// [`InputOptions::set_surface`]: struct.InputOptions.html#method.set_surface
+/// [`Context`]: struct.Context.html
+#[derive(Clone, PartialEq)]
+pub struct Surface {
+    width: usize,
+    height: usize,
+    depth: usize,
+    /// The planar channel data. Each channel contains `width * height * depth` values.
+    data: Vec<f32>,
+    wrap_mode: WrapMode,
+    alpha_mode: AlphaMode,
+    normal_map: bool,
+}
+
+impl Surface {
+    /// Create a new 2D `Surface` with the given dimensions. All channels are
+    /// initialised to `0.0`.
+    #[inline]
+    pub fn new(width: usize, height: usize) -> Self {
+        Self::with_depth(width, height, 1)
+    }
+
+    /// Create a new 3D `Surface` with the given dimensions. All channels are
+    /// initialised to `0.0`.
+    #[inline]
+    pub fn with_depth(width: usize, height: usize, depth: usize) -> Self {
+        Self {
+            width,
+            height,
+            depth,
+            data: vec![0.0; 4 * width * height * depth],
+            wrap_mode: WrapMode::Mirror,
+            alpha_mode: AlphaMode::None,
+            normal_map: false,
+        }
+    }
+
+    /// Create a new `Surface` from raw pixel `data` in the given `InputFormat`.
+    ///
+    /// The data is in native endian byte order, as it would be passed to
+    /// [`InputOptions::set_mipmap_data`].
+    ///
+    /// # Errors
+    ///
+    /// If any dimension is `0`, or the dimensions of the image do not match the length
+    /// of the `data`, then this method will fail with [`Error::InvalidInput`].
+    ///
+    /// [`InputOptions::set_mipmap_data`]: struct.InputOptions.html#method.set_mipmap_data
+    /// [`Error::InvalidInput`]: enum.Error.html#variant.InvalidInput
+    pub fn from_raw(
+        format: InputFormat,
+        width: usize,
+        height: usize,
+        depth: usize,
+        data: &[u8],
+    ) -> Result<Self, Error> {
+        let count = width * height * depth;
+        let bytes_per_pixel = match format {
+            InputFormat::Bgra8Ub => 4,
+            InputFormat::Rgba16F => 8,
+            InputFormat::Rgba32F => 16,
+            InputFormat::R32F => 4,
+        };
+
+        if count == 0 || data.len() != count * bytes_per_pixel {
+            return Err(Error::InvalidInput);
+        }
+
+        let mut surface = Self::with_depth(width, height, depth);
+        let (r, g, b, a) = surface.planes_mut();
+        match format {
+            InputFormat::Bgra8Ub => simd::unorm8_to_planes(data, b, g, r, a),
+            InputFormat::Rgba16F => simd::f16_to_planes(data, r, g, b, a),
+            InputFormat::Rgba32F => simd::f32_to_planes(data, r, g, b, a),
+            InputFormat::R32F => {
+                for (i, px) in data.chunks_exact(bytes_per_pixel).enumerate() {
+                    let r = f32::from_ne_bytes([px[0], px[1], px[2], px[3]]);
+                    surface.set_pixel(i, [r, 0.0, 0.0, 1.0]);
+                }
+            }
+        }
+
+        Ok(surface)
+    }
+
+    /// Create a new 2D `Surface` from interleaved rgba `f32` values.
+    ///
+    /// # Errors
+    ///
+    /// If any dimension is `0`, or the dimensions of the image do not match the length
+    /// of the `data`, then this method will fail with [`Error::InvalidInput`].
+    ///
+    /// [`Error::InvalidInput`]: enum.Error.html#variant.InvalidInput
+    pub fn from_rgba(width: usize, height: usize, data: &[f32]) -> Result<Self, Error> {
+        let count = width * height;
+        if count == 0 || data.len() != count * 4 {
+            return Err(Error::InvalidInput);
+        }
+
+        let mut surface = Self::new(width, height);
+        for (i, px) in data.chunks_exact(4).enumerate() {
+            surface.set_pixel(i, [px[0], px[1], px[2], px[3]]);
+        }
+        Ok(surface)
+    }
+
+    /// Create a new 2D `Surface` from separate planes of `f32` values for each channel.
+    /// If `alpha` is `None`, then the alpha channel is set to `1.0`.
+    ///
+    /// # Errors
+    ///
+    /// If any dimension is `0`, or the length of any plane is not `width * height`,
+    /// then this method will fail with [`Error::InvalidInput`].
+    ///
+    /// [`Error::InvalidInput`]: enum.Error.html#variant.InvalidInput
+    pub fn from_planes(
+        width: usize,
+        height: usize,
+        red: &[f32],
+        green: &[f32],
+        blue: &[f32],
+        alpha: Option<&[f32]>,
+    ) -> Result<Self, Error> {
+        let count = width * height;
+        let planes_valid = [Some(red), Some(green), Some(blue), alpha]
+            .iter()
+            .flatten()
+            .all(|plane| plane.len() == count);
+        if count == 0 || !planes_valid {
+            return Err(Error::InvalidInput);
+        }
+
+        let mut data = Vec::with_capacity(4 * count);
+        data.extend_from_slice(red);
+        data.extend_from_slice(green);
+        data.extend_from_slice(blue);
+        match alpha {
+            Some(alpha) => data.extend_from_slice(alpha),
+            None => data.resize(4 * count, 1.0),
+        }
+
+        let mut surface = Self::new(width, height);
+        surface.data = data;
+        Ok(surface)
+    }
+
+    /// Load a `Surface` from the image file at `path`.
+    ///
+    /// # Notes
+    ///
+    /// This method requires the [`nvtt_image_integration`] feature.
+    ///
+    /// # Errors
+    ///
+    /// If the file cannot be opened or decoded, then this method will fail with
+    /// [`Error::FileOpen`].
+    ///
+    /// [`nvtt_image_integration`]: index.html#nvtt_image_integration
+    /// [`Error::FileOpen`]: enum.Error.html#variant.FileOpen
+    #[cfg(feature = "nvtt_image_integration")]
+    pub fn open<P: AsRef<std::path::Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        image::open(path).map(Surface::from).map_err(|e| {
+            log::error!(
+                "Could not open image {p}\nCaused by: {e}",
+                p = path.display(),
+                e = e
+            );
+            Error::FileOpen
+        })
+    }
+
+    /// Load a `Surface` from the file at `path` using nvtt. Unlike [`open`], this can
+    /// load compressed textures such as `dds` files, which are decompressed. Only the
+    /// first face and mipmap of the texture is loaded.
+    ///
+    /// # Errors
+    ///
+    /// If the file cannot be opened or decoded by nvtt, then this method will fail with
+    /// [`Error::FileOpen`]. `nvtt::Surface` was added in nvtt 2.1, so if this crate was
+    /// built against an older nvtt, then this method will fail with
+    /// [`Error::UnsupportedFeature`].
+    ///
+    /// [`open`]: struct.Surface.html#method.open
+    /// [`Error::FileOpen`]: enum.Error.html#variant.FileOpen
+    /// [`Error::UnsupportedFeature`]: enum.Error.html#variant.UnsupportedFeature
+    #[inline]
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        NativeSurface::load(path.as_ref())
+    }
+
+    /// Convert the `Surface` into an 8 bit rgba image. Values are clamped to the `0..1`
+    /// range. Only the first slice of a 3D `Surface` is converted.
+    ///
+    /// # Notes
+    ///
+    /// This method requires the [`nvtt_image_integration`] feature.
+    ///
+    /// [`nvtt_image_integration`]: index.html#nvtt_image_integration
+    #[cfg(feature = "nvtt_image_integration")]
+    pub fn to_image(&self) -> image::DynamicImage {
+        let mut img = image::RgbaImage::new(self.width as u32, self.height as u32);
+        let (r, g, b, a) = self.planes();
+        simd::planes_to_unorm8(r, g, b, a, &mut img);
+        image::DynamicImage::ImageRgba8(img)
+    }
+
+    /// Save the `Surface` into the image file at `path`, which can be useful to inspect
+    /// the intermediate stages of processing. The format of the file is chosen from the
+    /// extension of `path`. See [`to_image`] for how the `Surface` is converted.
+    ///
+    /// # Notes
+    ///
+    /// This method requires the [`nvtt_image_integration`] feature.
+    ///
+    /// # Errors
+    ///
+    /// If the image cannot be encoded or written, then this method will fail with
+    /// [`Error::FileWrite`].
+    ///
+    /// [`to_image`]: struct.Surface.html#method.to_image
+    /// [`nvtt_image_integration`]: index.html#nvtt_image_integration
+    /// [`Error::FileWrite`]: enum.Error.html#variant.FileWrite
+    #[cfg(feature = "nvtt_image_integration")]
+    pub fn save<P: AsRef<std::path::Path>>(&self, path: P) -> Result<(), Error> {
+        let path = path.as_ref();
+        self.to_image().save(path).map_err(|e| {
+            log::error!(
+                "Could not save image {p}\nCaused by: {e}",
+                p = path.display(),
+                e = e
+            );
+            Error::FileWrite
+        })
+    }
+
+    /// The width of the `Surface` in pixels.
+    #[inline]
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// The height of the `Surface` in pixels.
+    #[inline]
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The depth of the `Surface` in pixels. This is `1` for 2D surfaces.
+    #[inline]
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// The dimensions of the `Surface` as an `Extent3d`. Returns `None` if the
+    /// `Surface` does not contain any pixels.
+    #[inline]
+    pub fn extent(&self) -> Option<Extent3d> {
+        Extent3d::from_usize(self.width, self.height, self.depth)
+    }
+
+    /// Returns `true` if the `Surface` does not contain any pixels.
+    #[inline]
+    pub fn is_null(&self) -> bool {
+        self.pixel_count() == 0
+    }
+
+    /// Get the `TextureLayout` describing this `Surface`.
+    #[inline]
+    pub fn texture_layout(&self) -> TextureLayout {
+        if self.depth > 1 {
+            TextureLayout::d3(self.width, self.height, self.depth)
+        } else {
+            TextureLayout::d2(self.width, self.height)
+        }
+    }
+
+    /// Get the values of a single `Channel`.
+    #[inline]
+    pub fn channel(&self, channel: Channel) -> &[f32] {
+        let count = self.pixel_count();
+        let start = channel.index() * count;
+        &self.data[start..start + count]
+    }
+
+    /// Get the values of a single `Channel` mutably.
+    #[inline]
+    pub fn channel_mut(&mut self, channel: Channel) -> &mut [f32] {
+        let count = self.pixel_count();
+        let start = channel.index() * count;
+        &mut self.data[start..start + count]
+    }
+
+    /// Get the `WrapMode` used when sampling past the edges of the `Surface`.
+    /// This is `WrapMode::Mirror` by default.
+    #[inline]
+    pub fn wrap_mode(&self) -> WrapMode {
+        self.wrap_mode
+    }
+
+    /// Set the `WrapMode` used when sampling past the edges of the `Surface`.
+    #[inline]
+    pub fn set_wrap_mode(&mut self, wrap_mode: WrapMode) -> &mut Self {
+        self.wrap_mode = wrap_mode;
+        self
+    }
+
+    /// Get the `AlphaMode` of the `Surface`. This is `AlphaMode::None` by default.
+    #[inline]
+    pub fn alpha_mode(&self) -> AlphaMode {
+        self.alpha_mode
+    }
+
+    /// Set the `AlphaMode` of the `Surface`.
+    #[inline]
+    pub fn set_alpha_mode(&mut self, alpha_mode: AlphaMode) -> &mut Self {
+        self.alpha_mode = alpha_mode;
+        self
+    }
+
+    /// Returns `true` if the `Surface` contains a normal map.
+    #[inline]
+    pub fn is_normal_map(&self) -> bool {
+        self.normal_map
+    }
+
+    /// Specify whether the `Surface` contains a normal map.
+    #[inline]
+    pub fn set_normal_map(&mut self, is_normal_map: bool) -> &mut Self {
+        self.normal_map = is_normal_map;
+        self
+    }
+
+    /// Returns the number of mipmap levels in a full mip chain starting
+    /// at this `Surface`, including the `Surface` itself.
+    #[inline]
+    pub fn count_mipmaps(&self) -> usize {
+        let mut extent = self.width.max(self.height).max(self.depth);
+        let mut count = 1;
+        while extent > 1 {
+            extent /= 2;
+            count += 1;
+        }
+        count
+    }
+
+    /// Returns `true` if the `Surface` is larger than `1` pixel in any dimension.
+    #[inline]
+    pub fn can_make_next_mipmap(&self) -> bool {
+        self.width > 1 || self.height > 1 || self.depth > 1
+    }
+
+    /// Replace the `Surface` with the next mipmap level, using `filter` to downsample
+    /// the image. Returns `false` if the `Surface` cannot be made any smaller.
+    ///
+    /// If the `AlphaMode` of the `Surface` is `AlphaMode::Transparency`, then the color
+    /// of each texel is weighted by its alpha, as nvtt does when generating mipmaps.
+    /// This stops the color of transparent texels from darkening the edges of decals
+    /// and foliage. Texels where the filtered alpha is close to `0.0` keep the unweighted color.
+    pub fn build_next_mipmap(&mut self, filter: MipmapFilter) -> bool {
+        if self.is_null() || !self.can_make_next_mipmap() {
+            return false;
+        }
+
+        let kernel = Kernel::from(filter);
+        let (w, h, d) = (
+            (self.width / 2).max(1),
+            (self.height / 2).max(1),
+            (self.depth / 2).max(1),
+        );
+        if self.alpha_mode != AlphaMode::Transparency {
+            self.resample(w, h, d, &kernel);
+            return true;
+        }
+
+        let mut weighted = self.clone();
+        weighted.premultiply_alpha().resample(w, h, d, &kernel);
+        self.resample(w, h, d, &kernel);
+        for i in 0..self.pixel_count() {
+            let [r, g, b, a] = weighted.pixel(i);
+            if a > f32::EPSILON {
+                self.set_pixel(i, [r / a, g / a, b / a, a]);
+            }
+        }
+        true
+    }
+
+    /// Build the full mip chain of the `Surface`, using `filter` to downsample each
+    /// level from the one above it. The first `Surface` in the returned chain is a
+    /// copy of this `Surface`, and the chain ends at the `1x1x1` level, so it can be
+    /// passed directly to [`Context::compress_mipmaps`].
+    ///
+    /// This can be used instead of the mipmap generation in nvtt, for example when
+    /// it is disabled or is the bottleneck of a compression. When the `rayon` feature
+    /// is enabled, the channels and rows of each level are resampled in parallel.
+    ///
+    /// If the `Surface` is null, then the returned chain is empty.
+    ///
+    /// [`Context::compress_mipmaps`]: struct.Context.html#method.compress_mipmaps
+    pub fn build_mipmaps(&self, filter: MipmapFilter) -> Vec<Surface> {
+        if self.is_null() {
+            return Vec::new();
+        }
+
+        let mut mipmaps = Vec::with_capacity(self.count_mipmaps());
+        let mut level = self.clone();
+        loop {
+            let mut next = level.clone();
+            let has_next = next.build_next_mipmap(filter);
+            mipmaps.push(level);
+            if !has_next {
+                break;
+            }
+            level = next;
+        }
+        mipmaps
+    }
+
+    /// Build the mip chain of a roughness map, increasing the roughness of each level
+    /// to account for the detail of the paired `normal_map` which is lost when it is
+    /// downsampled. This prevents specular aliasing and overly shiny distant surfaces.
+    ///
+    /// The roughness is read from `channel` of this `Surface`, and is the perceptual
+    /// roughness, which is squared to give the `alpha` of the GGX distribution. Each
+    /// level of `normal_map` is built with `filter`, and the length of its averaged
+    /// normals is used to fit a von Mises-Fisher distribution with sharpness `kappa`,
+    /// whose variance is added to the roughness as `alpha^2 + 2 / kappa`. This is the
+    /// approach of Toksvig and LEAN mapping. The other channels are filtered
+    /// normally.
+    ///
+    /// The `normal_map` should be built and renormalized separately, for example with
+    /// [`build_mipmaps`] and [`normalize`], and compressed alongside the returned
+    /// chain.
+    ///
+    /// # Errors
+    ///
+    /// If the `normal_map` is not the same size as this `Surface`, then this method
+    /// will fail with [`Error::InvalidInput`].
+    ///
+    /// [`build_mipmaps`]: struct.Surface.html#method.build_mipmaps
+    /// [`normalize`]: struct.Surface.html#method.normalize
+    /// [`Error::InvalidInput`]: enum.Error.html#variant.InvalidInput
+    pub fn build_roughness_mipmaps(
+        &self,
+        channel: Channel,
+        normal_map: &Surface,
+        filter: MipmapFilter,
+    ) -> Result<Vec<Surface>, Error> {
+        if (self.width, self.height, self.depth)
+            != (normal_map.width, normal_map.height, normal_map.depth)
+        {
+            return Err(Error::InvalidInput);
+        }
+
+        let mut mipmaps = self.build_mipmaps(filter);
+        let normals = normal_map.build_mipmaps(filter);
+        for (level, normals) in mipmaps.iter_mut().zip(normals.iter()).skip(1) {
+            let roughness = level.channel_mut(channel);
+            for (i, value) in roughness.iter_mut().enumerate() {
+                let [r, g, b, _] = normals.pixel(i);
+                let (x, y, z) = (unpack(r), unpack(g), unpack(b));
+                let len = (x * x + y * y + z * z).sqrt().min(1.0);
+                if len >= 0.9999 {
+                    continue;
+                }
+
+                let kappa = len * (3.0 - len * len) / (1.0 - len * len);
+                let alpha_sq = value.max(0.0).powi(4);
+                let adjusted = if kappa > 0.0 {
+                    (alpha_sq + 2.0 / kappa).min(1.0)
+                } else {
+                    1.0
+                };
+                *value = adjusted.sqrt().sqrt();
+            }
+        }
+        Ok(mipmaps)
+    }
+
+    /// Resize the `Surface` to the given dimensions, using `filter` to resample the image.
+    pub fn resize(
+        &mut self,
+        width: usize,
+        height: usize,
+        depth: usize,
+        filter: ResizeFilter,
+    ) -> &mut Self {
+        if self.is_null() || width * height * depth == 0 {
+            self.width = width;
+            self.height = height;
+            self.depth = depth;
+            self.data = vec![0.0; 4 * width * height * depth];
+            return self;
+        }
+
+        self.resample(width, height, depth, &Kernel::from(filter));
+        self
+    }
+
+    /// Resize the `Surface` so that no dimension is larger than `max_extent`, and then
+    /// round each dimension using `round_mode`. The aspect ratio of the image is
+    /// preserved when it is scaled down. If `max_extent` is `0`, then the image is only
+    /// rounded.
+    pub fn resize_to_fit(
+        &mut self,
+        max_extent: usize,
+        round_mode: RoundMode,
+        filter: ResizeFilter,
+    ) -> &mut Self {
+        let (w, h, d) = target_extents(
+            (self.width, self.height, self.depth),
+            max_extent,
+            round_mode,
+        );
+        if (w, h, d) != (self.width, self.height, self.depth) {
+            self.resize(w, h, d, filter);
+        }
+        self
+    }
+
+    /// Change the size of the `Surface` without resampling the image. The image stays
+    /// anchored at the origin; pixels outside the new size are discarded, and new
+    /// pixels are set to `0.0`.
+    pub fn canvas_resize(&mut self, width: usize, height: usize, depth: usize) -> &mut Self {
+        let mut resized = Self::with_depth(width, height, depth);
+        let (copy_w, copy_h, copy_d) = (
+            width.min(self.width),
+            height.min(self.height),
+            depth.min(self.depth),
+        );
+
+        for z in 0..copy_d {
+            for y in 0..copy_h {
+                for x in 0..copy_w {
+                    let src = x + y * self.width + z * self.width * self.height;
+                    let dst = x + y * width + z * width * height;
+                    resized.set_pixel(dst, self.pixel(src));
+                }
+            }
+        }
+
+        self.width = width;
+        self.height = height;
+        self.depth = depth;
+        self.data = resized.data;
+        self
+    }
+
+    /// Add a border of `pixels` pixels around each edge of the `Surface`, which is
+    /// filled by sampling past the edges of the image with its [`WrapMode`]. For 3D
+    /// surfaces, every slice is padded.
+    ///
+    /// Block compression and mipmap filtering then see the same neighbouring pixels
+    /// at the edges of the image as the sampler does when the texture is tiled, which
+    /// reduces visible seams. The image is offset by `pixels`, so texture coordinates
+    /// must be inset by the same amount.
+    ///
+    /// [`WrapMode`]: enum.WrapMode.html
+    pub fn pad_edges(&mut self, pixels: usize) -> &mut Self {
+        if pixels == 0 || self.is_null() {
+            return self;
+        }
+
+        let (width, height) = (self.width + 2 * pixels, self.height + 2 * pixels);
+        let mut padded = Self::with_depth(width, height, self.depth);
+        for z in 0..self.depth {
+            for y in 0..height {
+                let sy = wrap_coord(y as isize - pixels as isize, self.height, self.wrap_mode);
+                for x in 0..width {
+                    let sx = wrap_coord(x as isize - pixels as isize, self.width, self.wrap_mode);
+                    let src = sx + sy * self.width + z * self.width * self.height;
+                    let dst = x + y * width + z * width * height;
+                    padded.set_pixel(dst, self.pixel(src));
+                }
+            }
+        }
+
+        self.width = width;
+        self.height = height;
+        self.data = padded.data;
+        self
+    }
+
+    /// Crop the `Surface` to the rectangle starting at `x`, `y` which is `width` pixels
+    /// wide and `height` pixels high. For 3D surfaces, every slice is cropped.
+    ///
+    /// # Errors
+    ///
+    /// If the rectangle is empty, or is not contained inside the `Surface`, then this
+    /// method will fail with [`Error::InvalidInput`].
+    ///
+    /// [`Error::InvalidInput`]: enum.Error.html#variant.InvalidInput
+    pub fn crop(
+        &mut self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    ) -> Result<&mut Self, Error> {
+        if width == 0 || height == 0 || x + width > self.width || y + height > self.height {
+            return Err(Error::InvalidInput);
+        }
+
+        let mut cropped = Self::with_depth(width, height, self.depth);
+        for z in 0..self.depth {
+            for cy in 0..height {
+                for cx in 0..width {
+                    let src = (x + cx) + (y + cy) * self.width + z * self.width * self.height;
+                    let dst = cx + cy * width + z * width * height;
+                    cropped.set_pixel(dst, self.pixel(src));
+                }
+            }
+        }
+
+        self.width = width;
+        self.height = height;
+        self.data = cropped.data;
+        Ok(self)
+    }
+
+    /// Extrude the edge pixels of each sprite in `rects` outwards into the space
+    /// around it, so that mipmap filtering and block compression do not blend
+    /// neighbouring sprites together. See [`SpritePadding`] for how far each sprite is
+    /// extruded.
+    ///
+    /// Pixels inside a sprite are never changed. A pixel between two sprites is
+    /// taken from the nearest one. For 3D surfaces, every slice is extruded.
+    ///
+    /// # Errors
+    ///
+    /// If a rectangle is empty, or is not contained inside the `Surface`, then this
+    /// method will fail with [`Error::InvalidInput`].
+    ///
+    /// [`SpritePadding`]: struct.SpritePadding.html
+    /// [`Error::InvalidInput`]: enum.Error.html#variant.InvalidInput
+    pub fn extrude_sprites(
+        &mut self,
+        rects: &[SpriteRect],
+        padding: SpritePadding,
+    ) -> Result<&mut Self, Error> {
+        let (width, height) = (self.width, self.height);
+        if rects.iter().any(|r| {
+            r.width == 0 || r.height == 0 || r.x + r.width > width || r.y + r.height > height
+        }) {
+            return Err(Error::InvalidInput);
+        }
+
+        // For each pixel outside of the sprites, find the nearest sprite pixel.
+        let pad = padding.top_level_pixels();
+        let mut nearest = vec![(usize::MAX, 0); width * height];
+        for rect in rects {
+            let (x0, y0) = (rect.x.saturating_sub(pad), rect.y.saturating_sub(pad));
+            let x1 = rect
+                .x
+                .saturating_add(rect.width)
+                .saturating_add(pad)
+                .min(width);
+            let y1 = rect
+                .y
+                .saturating_add(rect.height)
+                .saturating_add(pad)
+                .min(height);
+            for y in y0..y1 {
+                let sy = y.clamp(rect.y, rect.y + rect.height - 1);
+                for x in x0..x1 {
+                    let sx = x.clamp(rect.x, rect.x + rect.width - 1);
+                    let distance = x.abs_diff(sx).max(y.abs_diff(sy));
+                    let pixel = &mut nearest[x + y * width];
+                    if distance < pixel.0 {
+                        *pixel = (distance, sx + sy * width);
+                    }
+                }
+            }
+        }
+
+        for (index, &(distance, source)) in nearest.iter().enumerate() {
+            if distance == 0 || distance == usize::MAX {
+                continue;
+            }
+            for z in 0..self.depth {
+                let slice = z * width * height;
+                self.set_pixel(slice + index, self.pixel(slice + source));
+            }
+        }
+        Ok(self)
+    }
+
+    /// Copy the values of the `src` channel into the `dst` channel.
+    pub fn copy_channel(&mut self, src: Channel, dst: Channel) -> &mut Self {
+        if src != dst {
+            let count = self.pixel_count();
+            let start = src.index() * count;
+            self.data
+                .copy_within(start..start + count, dst.index() * count);
+        }
+        self
+    }
+
+    /// Copy the values of the `src` channel of `other` into the `dst` channel of
+    /// this `Surface`. This can be used to pack channels from several images into
+    /// a single image.
+    ///
+    /// # Errors
+    ///
+    /// If `other` does not have the same dimensions as this `Surface`, then this method
+    /// will fail with [`Error::InvalidInput`].
+    ///
+    /// [`Error::InvalidInput`]: enum.Error.html#variant.InvalidInput
+    pub fn copy_channel_from(
+        &mut self,
+        other: &Surface,
+        src: Channel,
+        dst: Channel,
+    ) -> Result<&mut Self, Error> {
+        if (self.width, self.height, self.depth) != (other.width, other.height, other.depth) {
+            return Err(Error::InvalidInput);
+        }
+        self.channel_mut(dst).copy_from_slice(other.channel(src));
+        Ok(self)
+    }
+
+    /// Build a new `Surface` whose red, green, blue and alpha channels are taken from
+    /// `sources`, such as the packed metallic, roughness and occlusion textures used by
+    /// PBR materials.
+    ///
+    /// The new `Surface` is the size of the largest source, and smaller sources are
+    /// resized to fit with `filter`. Channels taken from a null `Surface` are filled with
+    /// `0.0`. The other properties of the new `Surface`, such as its `WrapMode`, are set
+    /// to their defaults.
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use nvtt_rs::{Channel, Format, ResizeFilter, Surface, TexturePipeline};
+    ///
+    /// let metallic = Surface::load("metallic.png")?;
+    /// let roughness = Surface::load("roughness.png")?;
+    /// let packed = Surface::pack_channels(
+    ///     [
+    ///         (&metallic, Channel::Red).into(),
+    ///         (&roughness, Channel::Red).into(),
+    ///         1.0.into(),
+    ///         1.0.into(),
+    ///     ],
+    ///     ResizeFilter::default(),
+    /// )?;
+    ///
+    /// TexturePipeline::new()
+    ///     .source(packed)
+    ///     .format(Format::Bc1)
+    ///     .to("metallic_roughness.dds")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If none of the `sources` are taken from a `Surface`, or every source `Surface`
+    /// is null, then this method will fail with [`Error::InvalidInput`].
+    ///
+    /// [`Error::InvalidInput`]: enum.Error.html#variant.InvalidInput
+    pub fn pack_channels(
+        sources: [ChannelSource<'_>; 4],
+        filter: ResizeFilter,
+    ) -> Result<Surface, Error> {
+        let surfaces = sources.iter().filter_map(|source| match *source {
+            ChannelSource::Channel(surface, _) if !surface.is_null() => Some(surface),
+            _ => None,
+        });
+        let (width, height, depth) = surfaces
+            .map(|s| (s.width, s.height, s.depth))
+            .reduce(|(w, h, d), (sw, sh, sd)| (w.max(sw), h.max(sh), d.max(sd)))
+            .ok_or(Error::InvalidInput)?;
+
+        let mut packed = Surface::with_depth(width, height, depth);
+        for (dst, source) in Channel::ALL.iter().zip(sources.iter()) {
+            match *source {
+                ChannelSource::Constant(value) => {
+                    packed.fill_channel(*dst, value);
+                }
+                ChannelSource::Channel(surface, _) if surface.is_null() => {
+                    packed.fill_channel(*dst, 0.0);
+                }
+                ChannelSource::Channel(surface, src)
+                    if (surface.width, surface.height, surface.depth) == (width, height, depth) =>
+                {
+                    packed
+                        .channel_mut(*dst)
+                        .copy_from_slice(surface.channel(src));
+                }
+                ChannelSource::Channel(surface, src) => {
+                    let mut resized = surface.clone();
+                    resized.resize(width, height, depth, filter);
+                    packed
+                        .channel_mut(*dst)
+                        .copy_from_slice(resized.channel(src));
+                }
+            }
+        }
+        Ok(packed)
+    }
+
+    /// Set every value of `channel` to `value`.
+    pub fn fill_channel(&mut self, channel: Channel, value: f32) -> &mut Self {
+        self.channel_mut(channel)
+            .iter_mut()
+            .for_each(|x| *x = value);
+        self
+    }
+
+    /// Rearrange the channels of the `Surface`. Each argument selects the channel
+    /// which is copied into the red, green, blue and alpha channels respectively.
+    pub fn swizzle(&mut self, r: Channel, g: Channel, b: Channel, a: Channel) -> &mut Self {
+        let mut data = Vec::with_capacity(self.data.len());
+        for &channel in [r, g, b, a].iter() {
+            data.extend_from_slice(self.channel(channel));
+        }
+        self.data = data;
+        self
+    }
+
+    /// Clamp the values of `channel` to the range `min..=max`.
+    pub fn clamp(&mut self, channel: Channel, min: f32, max: f32) -> &mut Self {
+        self.channel_mut(channel)
+            .iter_mut()
+            .for_each(|x| *x = x.max(min).min(max));
+        self
+    }
+
+    /// Round the values of `channel` to the nearest value representable with `bits`
+    /// bits, which is clamped to the range `1..=24`. Values are clamped to the `0..1`
+    /// range. The quantization error is distributed as selected by `dither`.
+    pub fn quantize(
+        &mut self,
+        channel: Channel,
+        bits: u32,
+        dither: impl Into<Dither>,
+    ) -> &mut Self {
+        let scale = ((1u32 << bits.clamp(1, 24)) - 1) as f32;
+        self.quantize_with(channel, dither.into(), 1.0 / scale, |x| {
+            (x.clamp(0.0, 1.0) * scale).round() / scale
+        });
+        self
+    }
+
+    /// Quantize the color channels of the `Surface` to the precision of a 565 color
+    /// format, such as the endpoints of `Format::Bc1`. This can be used to dither
+    /// 16-bit color textures before they are compressed, to reduce banding.
+    pub fn quantize_rgb565(&mut self, dither: Dither) -> &mut Self {
+        self.quantize(Channel::Red, 5, dither)
+            .quantize(Channel::Green, 6, dither)
+            .quantize(Channel::Blue, 5, dither)
+    }
+
+    /// Set each value of `channel` to `1.0` if it is greater than `threshold`, and to
+    /// `0.0` otherwise. This can be used to prepare the alpha channel for 1-bit alpha
+    /// formats. The error is distributed as selected by `dither`.
+    pub fn binarize(
+        &mut self,
+        channel: Channel,
+        threshold: f32,
+        dither: impl Into<Dither>,
+    ) -> &mut Self {
+        self.quantize_with(channel, dither.into(), 1.0, |x| {
+            if x > threshold {
+                1.0
+            } else {
+                0.0
+            }
+        });
+        self
+    }
+
+    /// Mirror the `Surface` horizontally.
+    pub fn flip_x(&mut self) -> &mut Self {
+        let w = self.width;
+        self.remap(self.width, self.height, |x, y| (w - 1 - x, y));
+        self
+    }
+
+    /// Mirror the `Surface` vertically. This converts between the OpenGL and
+    /// Direct3D texture coordinate conventions.
+    pub fn flip_y(&mut self) -> &mut Self {
+        let h = self.height;
+        self.remap(self.width, self.height, |x, y| (x, h - 1 - y));
+        self
+    }
+
+    /// Reverse the order of the slices of a 3D `Surface`.
+    pub fn flip_z(&mut self) -> &mut Self {
+        let (w, h, d) = (self.width, self.height, self.depth);
+        let mut flipped = self.clone();
+        for z in 0..d {
+            for i in 0..w * h {
+                flipped.set_pixel(i + z * w * h, self.pixel(i + (d - 1 - z) * w * h));
+            }
+        }
+        self.data = flipped.data;
+        self
+    }
+
+    /// Rotate the `Surface` by 90 degrees clockwise. The width and height of the
+    /// `Surface` are swapped.
+    pub fn rotate_90(&mut self) -> &mut Self {
+        let h = self.height;
+        self.remap(self.height, self.width, |x, y| (y, h - 1 - x));
+        self
+    }
+
+    /// Rotate the `Surface` by 180 degrees.
+    pub fn rotate_180(&mut self) -> &mut Self {
+        let (w, h) = (self.width, self.height);
+        self.remap(self.width, self.height, |x, y| (w - 1 - x, h - 1 - y));
+        self
+    }
+
+    /// Rotate the `Surface` by 270 degrees clockwise (90 degrees anticlockwise).
+    /// The width and height of the `Surface` are swapped.
+    pub fn rotate_270(&mut self) -> &mut Self {
+        let w = self.width;
+        self.remap(self.height, self.width, |x, y| (w - 1 - y, x));
+        self
+    }
+
+    /// Blur every channel of the `Surface` with a gaussian kernel with the standard
+    /// deviation `sigma`, measured in pixels. If `sigma` is not positive, then the
+    /// `Surface` is left unchanged.
+    pub fn blur(&mut self, sigma: f32) -> &mut Self {
+        if sigma > 0.0 && !self.is_null() {
+            self.convolve(&Kernel::Gaussian { sigma });
+        }
+        self
+    }
+
+    /// Sharpen the `Surface` with an unsharp mask. The image is blurred with a gaussian
+    /// kernel with the standard deviation `sigma`, and the difference between the original
+    /// and the blurred image is scaled by `amount` and added back to the original.
+    pub fn sharpen(&mut self, sigma: f32, amount: f32) -> &mut Self {
+        if sigma > 0.0 && !self.is_null() {
+            let mut blurred = self.clone();
+            blurred.convolve(&Kernel::Gaussian { sigma });
+            for (value, blurred) in self.data.iter_mut().zip(blurred.data.iter()) {
+                *value += amount * (*value - *blurred);
+            }
+        }
+        self
+    }
+
+    /// Convert the `Surface` into a normal map, using the alpha channel as the height.
+    /// The gradient of the height map is sampled at four scales, which are weighted
+    /// by the values in `filter`. The resulting normals are packed into the `0..1`
+    /// range in the rgb channels, and the height is kept in the alpha channel.
+    pub fn to_normal_map(&mut self, filter: NormalMapFilter) -> &mut Self {
+        let weights = [filter.small, filter.medium, filter.big, filter.large];
+        let total_weight = weights.iter().sum::<f32>();
+        let (w, h, wrap) = (self.width, self.height, self.wrap_mode);
+        let heights = self.channel(Channel::Alpha).to_vec();
+
+        for z in 0..self.depth {
+            let slice = z * w * h;
+            let height_at = |x: isize, y: isize| {
+                heights[slice + wrap_coord(x, w, wrap) + wrap_coord(y, h, wrap) * w]
+            };
+
+            for y in 0..h {
+                for x in 0..w {
+                    let (xi, yi) = (x as isize, y as isize);
+                    let (mut dx, mut dy) = (0.0, 0.0);
+                    for (scale, &weight) in (1..).zip(weights.iter()) {
+                        if weight == 0.0 {
+                            continue;
+                        }
+                        let step = 2.0 * scale as f32;
+                        dx +=
+                            weight * (height_at(xi + scale, yi) - height_at(xi - scale, yi)) / step;
+                        dy +=
+                            weight * (height_at(xi, yi + scale) - height_at(xi, yi - scale)) / step;
+                    }
+                    if total_weight != 0.0 {
+                        dx /= total_weight;
+                        dy /= total_weight;
+                    }
+
+                    let index = slice + x + y * w;
+                    let n = normalize3([-dx, -dy, 1.0]);
+                    let alpha = self.pixel(index)[3];
+                    self.set_pixel(index, [pack(n[0]), pack(n[1]), pack(n[2]), alpha]);
+                }
+            }
+        }
+
+        self.normal_map = true;
+        self
+    }
+
+    /// Renormalize the packed normals in the rgb channels of the `Surface`. This should
+    /// be done after the normal map is filtered, for example after building a mipmap.
+    pub fn normalize(&mut self) -> &mut Self {
+        for i in 0..self.pixel_count() {
+            let [r, g, b, a] = self.pixel(i);
+            let n = normalize3([unpack(r), unpack(g), unpack(b)]);
+            self.set_pixel(i, [pack(n[0]), pack(n[1]), pack(n[2]), a]);
+        }
+        self
+    }
+
+    /// Project the packed normals in the rgb channels of the `Surface` into the red and
+    /// green channels using `transform`. The blue channel is set to `0.5`, which is a
+    /// packed `0.0`.
+    pub fn transform_normals(&mut self, transform: NormalTransform) -> &mut Self {
+        for i in 0..self.pixel_count() {
+            let [r, g, b, a] = self.pixel(i);
+            let [x, y, z] = normalize3([unpack(r), unpack(g), unpack(b)]);
+            let (x, y) = match transform {
+                NormalTransform::Orthographic => (x, y),
+                NormalTransform::Stereographic => (x / (1.0 + z), y / (1.0 + z)),
+                NormalTransform::Paraboloid => {
+                    let a = x * x + y * y;
+                    let t = if a > 0.0 {
+                        (-z + (z * z + 4.0 * a).sqrt()) / (2.0 * a)
+                    } else {
+                        1.0
+                    };
+                    (x * t, y * t)
+                }
+            };
+            self.set_pixel(i, [pack(x), pack(y), 0.5, a]);
+        }
+        self
+    }
+
+    /// Reconstruct the `z` component of the normals stored in the red and green channels
+    /// of the `Surface`, which were projected with `transform`. This is the inverse of
+    /// [`transform_normals`].
+    ///
+    /// [`transform_normals`]: struct.Surface.html#method.transform_normals
+    pub fn reconstruct_z(&mut self, transform: NormalTransform) -> &mut Self {
+        for i in 0..self.pixel_count() {
+            let [r, g, _, a] = self.pixel(i);
+            let (x, y) = (unpack(r), unpack(g));
+            let n = match transform {
+                NormalTransform::Orthographic => [x, y, (1.0 - x * x - y * y).max(0.0).sqrt()],
+                NormalTransform::Stereographic => {
+                    let denom = 2.0 / (1.0 + x * x + y * y);
+                    [x * denom, y * denom, denom - 1.0]
+                }
+                NormalTransform::Paraboloid => normalize3([x, y, 1.0 - x * x - y * y]),
+            };
+            let n = normalize3(n);
+            self.set_pixel(i, [pack(n[0]), pack(n[1]), pack(n[2]), a]);
+        }
+        self
+    }
+
+    /// Pack the normals stored in the red and green channels of the `Surface` into the
+    /// layout used by `Format::Bc3n` and `Format::Dxt5n`. The `x` component is moved
+    /// into the alpha channel, the `y` component stays in the green channel, red is set
+    /// to `1.0`, and blue is set to `0.0`.
+    ///
+    /// nvtt applies this swizzle itself when compressing to `Format::Bc3n`, so this is
+    /// only needed when the texture is compressed to `Format::Bc3` instead. The
+    /// [`NormalDecode`] of `Format::Bc3n` describes how to unpack the normals.
+    ///
+    /// [`NormalDecode`]: struct.NormalDecode.html
+    pub fn swizzle_dxt5n(&mut self) -> &mut Self {
+        self.copy_channel(Channel::Red, Channel::Alpha)
+            .fill_channel(Channel::Red, 1.0)
+            .fill_channel(Channel::Blue, 0.0)
+    }
+
+    /// Convert the color channels of the `Surface` from gamma space into linear space,
+    /// by raising them to the power of `gamma`. The alpha channel is not modified.
+    pub fn to_linear(&mut self, gamma: f32) -> &mut Self {
+        if gamma != 1.0 {
+            self.map_color_channels(|x| x.max(0.0).powf(gamma));
+        }
+        self
+    }
+
+    /// Convert the color channels of the `Surface` from linear space into gamma space,
+    /// by raising them to the power of `1.0 / gamma`. The alpha channel is not modified.
+    pub fn to_gamma(&mut self, gamma: f32) -> &mut Self {
+        if gamma != 1.0 {
+            self.map_color_channels(|x| x.max(0.0).powf(1.0 / gamma));
+        }
+        self
+    }
+
+    /// Convert the color channels of the `Surface` from linear space into the sRGB
+    /// color space, using the piecewise sRGB transfer function. The alpha channel is
+    /// not modified.
+    pub fn to_srgb(&mut self) -> &mut Self {
+        self.map_color_channels(|x| {
+            if x <= 0.0031308 {
+                12.92 * x.max(0.0)
+            } else {
+                1.055 * x.powf(1.0 / 2.4) - 0.055
+            }
+        });
+        self
+    }
+
+    /// Convert the color channels of the `Surface` from the sRGB color space into
+    /// linear space. This is the inverse of [`to_srgb`].
+    ///
+    /// [`to_srgb`]: struct.Surface.html#method.to_srgb
+    pub fn to_linear_from_srgb(&mut self) -> &mut Self {
+        self.map_color_channels(|x| {
+            if x <= 0.04045 {
+                x.max(0.0) / 12.92
+            } else {
+                ((x + 0.055) / 1.055).powf(2.4)
+            }
+        });
+        self
+    }
+
+    /// Map the high dynamic range color channels of the `Surface` into the `0..1` range.
+    /// The colors are multiplied by `exposure` before `tone_mapper` is applied. The alpha
+    /// channel is not modified.
+    pub fn tone_map(&mut self, tone_mapper: ToneMapper, exposure: f32) -> &mut Self {
+        self.map_color_channels(|x| {
+            let x = (x * exposure).max(0.0);
+            let mapped = match tone_mapper {
+                ToneMapper::Linear => x,
+                ToneMapper::Reinhard => x / (1.0 + x),
+                ToneMapper::Aces => (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14),
+            };
+            mapped.min(1.0)
+        });
+        self
+    }
+
+    /// Multiply the color channels of the `Surface` by the alpha channel, and set the
+    /// `AlphaMode` to `AlphaMode::Premultiplied`.
+    pub fn premultiply_alpha(&mut self) -> &mut Self {
+        for i in 0..self.pixel_count() {
+            let [r, g, b, a] = self.pixel(i);
+            self.set_pixel(i, [r * a, g * a, b * a, a]);
+        }
+        self.alpha_mode = AlphaMode::Premultiplied;
+        self
+    }
+
+    /// Divide the color channels of the `Surface` by the alpha channel, and set the
+    /// `AlphaMode` to `AlphaMode::Transparency`. Pixels with an alpha of `0.0` are
+    /// left unchanged.
+    pub fn demultiply_alpha(&mut self) -> &mut Self {
+        for i in 0..self.pixel_count() {
+            let [r, g, b, a] = self.pixel(i);
+            if a != 0.0 {
+                self.set_pixel(i, [r / a, g / a, b / a, a]);
+            }
+        }
+        self.alpha_mode = AlphaMode::Transparency;
+        self
+    }
+
+    /// Fill the color of the fully transparent pixels of the `Surface` from the
+    /// nearest pixels with an alpha greater than `alpha_threshold`, leaving the alpha
+    /// channel unchanged. This is also known as solidifying or dilating the image.
+    ///
+    /// The color of transparent pixels is still blended in by bilinear filtering and
+    /// block compression, so filling it prevents dark halos around the edges of
+    /// cutouts. Each pass grows the filled region by one pixel, with each new pixel
+    /// taking the average color of its filled neighbours. For 3D surfaces, each slice
+    /// is filled separately. If no pixel is opaque, then the `Surface` is unchanged.
+    pub fn solidify(&mut self, alpha_threshold: f32) -> &mut Self {
+        let (width, height) = (self.width, self.height);
+        let slice_len = width * height;
+        if slice_len == 0 {
+            return self;
+        }
+
+        for z in 0..self.depth {
+            let start = z * slice_len;
+            let mut filled = self.channel(Channel::Alpha)[start..start + slice_len]
+                .iter()
+                .map(|&a| a > alpha_threshold)
+                .collect::<Vec<_>>();
+            if !filled.contains(&true) {
+                continue;
+            }
+
+            let mut front = (0..slice_len).filter(|&i| !filled[i]).collect::<Vec<_>>();
+            while !front.is_empty() {
+                let mut grown = vec![];
+                for &i in &front {
+                    let (x, y) = (i % width, i / width);
+                    let (mut sum, mut count) = ([0.0; 3], 0);
+                    for (dx, dy) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
+                        let (nx, ny) = (x as isize + dx, y as isize + dy);
+                        if nx < 0 || ny < 0 || nx >= width as isize || ny >= height as isize {
+                            continue;
+                        }
+                        let n = nx as usize + ny as usize * width;
+                        if filled[n] {
+                            let [r, g, b, _] = self.pixel(start + n);
+                            sum = [sum[0] + r, sum[1] + g, sum[2] + b];
+                            count += 1;
+                        }
+                    }
+                    if count > 0 {
+                        let count = count as f32;
+                        grown.push((i, [sum[0] / count, sum[1] / count, sum[2] / count]));
+                    }
+                }
+
+                for &(i, [r, g, b]) in &grown {
+                    let a = self.pixel(start + i)[3];
+                    self.set_pixel(start + i, [r, g, b, a]);
+                    filled[i] = true;
+                }
+                front.retain(|&i| !filled[i]);
+            }
+        }
+        self
+    }
+
+    /// Returns the fraction of pixels in the `Surface` whose alpha is greater than
+    /// `alpha_ref`. This is the coverage of an alpha-tested texture which uses
+    /// `alpha_ref` as the cutoff value.
+    pub fn alpha_test_coverage(&self, alpha_ref: f32) -> f32 {
+        let alpha = self.channel(Channel::Alpha);
+        if alpha.is_empty() {
+            return 0.0;
+        }
+        let covered = alpha.iter().filter(|&&a| a > alpha_ref).count();
+        covered as f32 / alpha.len() as f32
+    }
+
+    /// Returns the average value of `channel`, or `0.0` if the `Surface` is empty.
+    pub fn average(&self, channel: Channel) -> f32 {
+        let values = self.channel(channel);
+        if values.is_empty() {
+            return 0.0;
+        }
+        let sum = values.iter().map(|&x| f64::from(x)).sum::<f64>();
+        (sum / values.len() as f64) as f32
+    }
+
+    /// Returns the minimum and maximum values of `channel`, or `None` if the
+    /// `Surface` is empty.
+    pub fn range(&self, channel: Channel) -> Option<(f32, f32)> {
+        self.channel(channel)
+            .iter()
+            .fold(None, |range, &x| match range {
+                None => Some((x, x)),
+                Some((min, max)) => Some((min.min(x), max.max(x))),
+            })
+    }
+
+    /// Returns a histogram of `channel` with `bin_count` buckets which evenly divide
+    /// the range `min..max`. Values outside of the range are counted in the first or
+    /// last bucket.
+    pub fn histogram(&self, channel: Channel, min: f32, max: f32, bin_count: usize) -> Vec<usize> {
+        let mut bins = vec![0; bin_count];
+        if bin_count == 0 || max <= min {
+            return bins;
+        }
+
+        let scale = bin_count as f32 / (max - min);
+        for &x in self.channel(channel) {
+            let bin = ((x - min) * scale).floor().max(0.0) as usize;
+            bins[bin.min(bin_count - 1)] += 1;
+        }
+        bins
+    }
+
+    /// Returns the pixel data as interleaved rgba `f32` values.
+    pub fn to_rgba(&self) -> Vec<f32> {
+        let mut out = Vec::with_capacity(self.data.len());
+        for i in 0..self.pixel_count() {
+            out.extend_from_slice(&self.pixel(i));
+        }
+        out
+    }
+
+    /// Apply `f` to every value in the red, green and blue channels.
+    #[inline]
+    pub(crate) fn map_color_channels<F: FnMut(f32) -> f32>(&mut self, mut f: F) {
+        let count = self.pixel_count();
+        self.data[..3 * count].iter_mut().for_each(|x| *x = f(*x));
+    }
+
+    /// Replace each value of `channel` with `step(value)`, distributing the error as
+    /// selected by `dither`. `spacing` is the distance between the values produced by
+    /// `step`, which scales the offsets used for ordered dithering.
+    fn quantize_with<F: Fn(f32) -> f32>(
+        &mut self,
+        channel: Channel,
+        dither: Dither,
+        spacing: f32,
+        step: F,
+    ) {
+        let (w, h) = (self.width, self.height);
+        let values = self.channel_mut(channel);
+        match dither {
+            Dither::None => {
+                values.iter_mut().for_each(|x| *x = step(*x));
+                return;
+            }
+            Dither::Ordered => {
+                for (i, x) in values.iter_mut().enumerate() {
+                    let (px, py) = (i % w.max(1), i / w.max(1) % h.max(1));
+                    let offset = (BAYER_4X4[(py % 4) * 4 + px % 4] + 0.5) / 16.0 - 0.5;
+                    *x = step(*x + offset * spacing);
+                }
+                return;
+            }
+            Dither::ErrorDiffusion => {}
+        }
+
+        for slice in values.chunks_mut((w * h).max(1)) {
+            for y in 0..h {
+                for x in 0..w {
+                    let index = x + y * w;
+                    let old = slice[index];
+                    let new = step(old);
+                    let error = old - new;
+                    slice[index] = new;
+
+                    if x + 1 < w {
+                        slice[index + 1] += error * 7.0 / 16.0;
+                    }
+                    if y + 1 < h {
+                        if x > 0 {
+                            slice[index + w - 1] += error * 3.0 / 16.0;
+                        }
+                        slice[index + w] += error * 5.0 / 16.0;
+                        if x + 1 < w {
+                            slice[index + w + 1] += error / 16.0;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[inline]
+    pub(crate) fn pixel_count(&self) -> usize {
+        self.width * self.height * self.depth
+    }
+
+    /// Split the data into the plane of each channel.
+    #[cfg(any(feature = "nvtt", feature = "nvtt_image_integration"))]
+    fn planes(&self) -> (&[f32], &[f32], &[f32], &[f32]) {
+        let count = self.pixel_count();
+        let (r, rest) = self.data.split_at(count);
+        let (g, rest) = rest.split_at(count);
+        let (b, a) = rest.split_at(count);
+        (r, g, b, a)
+    }
+
+    /// Split the data into the plane of each channel mutably.
+    fn planes_mut(&mut self) -> (&mut [f32], &mut [f32], &mut [f32], &mut [f32]) {
+        let count = self.pixel_count();
+        let (r, rest) = self.data.split_at_mut(count);
+        let (g, rest) = rest.split_at_mut(count);
+        let (b, a) = rest.split_at_mut(count);
+        (r, g, b, a)
+    }
+
+    #[inline]
+    pub(crate) fn pixel(&self, index: usize) -> [f32; 4] {
+        let count = self.pixel_count();
+        [
+            self.data[index],
+            self.data[count + index],
+            self.data[2 * count + index],
+            self.data[3 * count + index],
+        ]
+    }
+
+    #[inline]
+    pub(crate) fn set_pixel(&mut self, index: usize, rgba: [f32; 4]) {
+        let count = self.pixel_count();
+        for (c, value) in rgba.iter().enumerate() {
+            self.data[c * count + index] = *value;
+        }
+    }
+
+    /// Rearrange the pixels in each slice of the `Surface`, so that it is `width` by
+    /// `height` pixels. `source` maps each destination pixel to the source pixel
+    /// which is copied into it.
+    fn remap<F: Fn(usize, usize) -> (usize, usize)>(
+        &mut self,
+        width: usize,
+        height: usize,
+        source: F,
+    ) {
+        let mut remapped = Self::with_depth(width, height, self.depth);
+        for z in 0..self.depth {
+            for y in 0..height {
+                for x in 0..width {
+                    let (sx, sy) = source(x, y);
+                    let src = sx + sy * self.width + z * self.width * self.height;
+                    remapped.set_pixel(x + y * width + z * width * height, self.pixel(src));
+                }
+            }
+        }
+
+        self.width = width;
+        self.height = height;
+        self.data = remapped.data;
+    }
+
+    /// Filter every channel of the `Surface` with `kernel`, without changing its size.
+    pub(crate) fn convolve(&mut self, kernel: &Kernel) {
+        let wrap = self.wrap_mode;
+        let dims = [self.width, self.height, self.depth];
+        self.data = self.map_channels(|plane| {
+            let mut plane = plane.to_vec();
+            for (axis, &len) in dims.iter().enumerate() {
+                if len > 1 {
+                    plane = resample_axis(&plane, dims, axis, len, kernel, wrap);
+                }
+            }
+            plane
+        });
+    }
+
+    /// Resample every channel of the `Surface` to the given dimensions.
+    pub(crate) fn resample(&mut self, width: usize, height: usize, depth: usize, kernel: &Kernel) {
+        let wrap = self.wrap_mode;
+        let dims = (self.width, self.height, self.depth);
+        let data = self.map_channels(|plane| {
+            let mut plane = plane.to_vec();
+            let (mut w, mut h, d) = dims;
+            if width != w {
+                plane = resample_axis(&plane, [w, h, d], 0, width, kernel, wrap);
+                w = width;
+            }
+            if height != h {
+                plane = resample_axis(&plane, [w, h, d], 1, height, kernel, wrap);
+                h = height;
+            }
+            if depth != d {
+                plane = resample_axis(&plane, [w, h, d], 2, depth, kernel, wrap);
+            }
+            plane
+        });
+
+        self.width = width;
+        self.height = height;
+        self.depth = depth;
+        self.data = data;
+    }
+
+    /// Apply `f` to the plane of each channel, and concatenate the resulting planes.
+    /// The channels are processed in parallel when the `rayon` feature is enabled.
+    fn map_channels<F>(&self, f: F) -> Vec<f32>
+    where
+        F: Fn(&[f32]) -> Vec<f32> + Sync,
+    {
+        cfg_if! {
+            if #[cfg(feature = "rayon")] {
+                use rayon::prelude::*;
+                let planes = Channel::ALL
+                    .par_iter()
+                    .map(|&channel| f(self.channel(channel)))
+                    .collect::<Vec<_>>();
+            } else {
+                let planes = Channel::ALL
+                    .iter()
+                    .map(|&channel| f(self.channel(channel)))
+                    .collect::<Vec<_>>();
+            }
+        }
+        planes.concat()
+    }
+}
+
+impl fmt::Debug for Surface {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Surface")
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("depth", &self.depth)
+            .field("wrap_mode", &self.wrap_mode)
+            .field("alpha_mode", &self.alpha_mode)
+            .field("normal_map", &self.normal_map)
+            .finish()
+    }
+}
+
+#[cfg(feature = "nvtt_image_integration")]
+impl From<&'_ image::DynamicImage> for Surface {
+    fn from(img: &'_ image::DynamicImage) -> Self {
+        let img = img.to_rgba8();
+        let (w, h) = img.dimensions();
+        let mut surface = Surface::new(w as usize, h as usize);
+        let (r, g, b, a) = surface.planes_mut();
+        simd::unorm8_to_planes(&img, r, g, b, a);
+        surface
+    }
+}
+
+#[cfg(feature = "nvtt_image_integration")]
+impl From<image::DynamicImage> for Surface {
+    #[inline]
+    fn from(img: image::DynamicImage) -> Self {
+        From::from(&img)
+    }
+}
+
+#[cfg(feature = "nvtt_image_integration")]
+impl From<&'_ Surface> for image::DynamicImage {
+    #[inline]
+    fn from(surface: &'_ Surface) -> Self {
+        surface.to_image()
+    }
+}
+
+#[cfg(feature = "nvtt")]
+impl InputOptions {
+    /// Sets the data of the given `face` and `mipmap` level to the contents of `surface`.
+    ///
+    /// The input format is set to `InputFormat::Rgba32F`. Unlike
+    /// [`InputOptions::set_image`], this does not change the texture layout, so that
+    /// each mipmap level of a texture can be set from a separate `Surface`. Use
+    /// [`Surface::texture_layout`] to set the layout from the top level `Surface`.
+    ///
+    /// # Errors
+    ///
+    /// If the dimensions of the `surface` do not match the texture layout, then this
+    /// method will fail with [`Error::Unknown`].
+    ///
+    /// [`InputOptions::set_image`]: struct.InputOptions.html#method.set_image
+    /// [`Surface::texture_layout`]: struct.Surface.html#method.texture_layout
+    /// [`Error::Unknown`]: enum.Error.html#variant.Unknown
+    pub fn set_surface(
+        &mut self,
+        surface: &Surface,
+        face: CubeFace,
+        mipmap: MipLevel,
+    ) -> Result<&mut Self, Error> {
+        self.set_surface_from(surface, face, mipmap, &mut vec![])
+    }
+
+    /// Sets the data of the given `face` and `mipmap` level to the contents of `surface`,
+    /// converting the data in the buffers of `scratch`. Once the buffers have grown to
+    /// fit the largest surface, this does not allocate. See [`InputOptions::set_surface`].
+    ///
+    /// # Errors
+    ///
+    /// See [`InputOptions::set_surface`].
+    ///
+    /// [`InputOptions::set_surface`]: struct.InputOptions.html#method.set_surface
+    pub fn set_surface_with_scratch(
+        &mut self,
+        surface: &Surface,
+        face: CubeFace,
+        mipmap: MipLevel,
+        scratch: &mut CompressScratch,
+    ) -> Result<&mut Self, Error> {
+        self.set_surface_from(surface, face, mipmap, &mut scratch.input)
+    }
+
+    fn set_surface_from(
+        &mut self,
+        surface: &Surface,
+        face: CubeFace,
+        mipmap: MipLevel,
+        bytes: &mut Vec<u8>,
+    ) -> Result<&mut Self, Error> {
+        bytes.clear();
+        bytes.resize(16 * surface.pixel_count(), 0);
+        let (r, g, b, a) = surface.planes();
+        simd::planes_to_f32(r, g, b, a, bytes);
+
+        let extent = surface.extent().ok_or(Error::InvalidInput)?;
+        self.set_format(InputFormat::Rgba32F)
+            .set_mipmap_data(bytes, extent, face, mipmap)
+    }
+}
+
+/// The reconstruction filter used when resampling a `Surface`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Kernel {
+    Box,
+    Triangle,
+    Kaiser {
+        width: f32,
+        alpha: f32,
+        stretch: f32,
+    },
+    Mitchell,
+    Gaussian {
+        sigma: f32,
+    },
+}
+
+impl Kernel {
+    /// The radius of the filter, in samples.
+    #[inline]
+    fn width(&self) -> f32 {
+        match *self {
+            Kernel::Box => 0.5,
+            Kernel::Triangle => 1.0,
+            Kernel::Kaiser { width, .. } => width,
+            Kernel::Mitchell => 2.0,
+            Kernel::Gaussian { sigma } => 3.0 * sigma,
+        }
+    }
+
+    #[inline]
+    fn evaluate(&self, x: f32) -> f32 {
+        let x = x.abs();
+        match *self {
+            Kernel::Box => {
+                if x <= 0.5 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            Kernel::Triangle => (1.0 - x).max(0.0),
+            Kernel::Kaiser {
+                width,
+                alpha,
+                stretch,
+            } => {
+                if x >= width {
+                    return 0.0;
+                }
+                let t = x / width;
+                sinc(PI * x * stretch) * bessel_i0(alpha * (1.0 - t * t).sqrt()) / bessel_i0(alpha)
+            }
+            Kernel::Mitchell => {
+                // Mitchell-Netravali with B = C = 1/3.
+                const B: f32 = 1.0 / 3.0;
+                const C: f32 = 1.0 / 3.0;
+                if x < 1.0 {
+                    ((12.0 - 9.0 * B - 6.0 * C) * x * x * x
+                        + (-18.0 + 12.0 * B + 6.0 * C) * x * x
+                        + (6.0 - 2.0 * B))
+                        / 6.0
+                } else if x < 2.0 {
+                    ((-B - 6.0 * C) * x * x * x
+                        + (6.0 * B + 30.0 * C) * x * x
+                        + (-12.0 * B - 48.0 * C) * x
+                        + (8.0 * B + 24.0 * C))
+                        / 6.0
+                } else {
+                    0.0
+                }
+            }
+            Kernel::Gaussian { sigma } => {
+                if x < 3.0 * sigma {
+                    (-x * x / (2.0 * sigma * sigma)).exp()
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
+
+impl Kernel {
+    #[inline]
+    fn kaiser(params: Option<KaiserParameters>) -> Self {
+        // These are the defaults used by nvtt.
+        let params = params.unwrap_or(KaiserParameters {
+            width: 3.0,
+            alpha: 4.0,
+            stretch: 1.0,
+        });
+        Kernel::Kaiser {
+            width: params.width,
+            alpha: params.alpha,
+            stretch: params.stretch,
+        }
+    }
+}
+
+impl From<MipmapFilter> for Kernel {
+    #[inline]
+    fn from(filter: MipmapFilter) -> Self {
+        match filter {
+            MipmapFilter::Box => Kernel::Box,
+            MipmapFilter::Triangle => Kernel::Triangle,
+            MipmapFilter::Kaiser(params) => Kernel::kaiser(params),
+        }
+    }
+}
+
+impl From<ResizeFilter> for Kernel {
+    #[inline]
+    fn from(filter: ResizeFilter) -> Self {
+        match filter {
+            ResizeFilter::Box => Kernel::Box,
+            ResizeFilter::Triangle => Kernel::Triangle,
+            ResizeFilter::Kaiser(params) => Kernel::kaiser(params),
+            ResizeFilter::Mitchell => Kernel::Mitchell,
+        }
+    }
+}
+
+/// Compute the size of an image after it is limited to `max_extent` and rounded
+/// using `round_mode`.
+pub(crate) fn target_extents(
+    (width, height, depth): (usize, usize, usize),
+    max_extent: usize,
+    round_mode: RoundMode,
+) -> (usize, usize, usize) {
+    let (mut w, mut h, mut d) = (width, height, depth);
+    let max_dim = w.max(h).max(d);
+    if max_extent > 0 && max_dim > max_extent {
+        w = (w * max_extent / max_dim).max(1);
+        h = (h * max_extent / max_dim).max(1);
+        d = (d * max_extent / max_dim).max(1);
+    }
+
+    let round = |x: usize| -> usize {
+        let next_pow2 = x.next_power_of_two();
+        let prev_pow2 = if next_pow2 == x { x } else { next_pow2 / 2 };
+        let next_mul4 = x.div_ceil(4) * 4;
+        let prev_mul4 = if x >= 4 { x / 4 * 4 } else { x };
+        match round_mode {
+            RoundMode::None => x,
+            RoundMode::ToNextPowerOfTwo => next_pow2,
+            RoundMode::ToPreviousPowerOfTwo => prev_pow2,
+            RoundMode::ToNearestPowerOfTwo => {
+                if x - prev_pow2 < next_pow2 - x {
+                    prev_pow2
+                } else {
+                    next_pow2
+                }
+            }
+            RoundMode::ToNextMultipleOfFour => next_mul4,
+            RoundMode::ToPreviousMultipleOfFour => prev_mul4,
+            RoundMode::ToNearestMultipleOfFour => {
+                if x >= 4 && x - prev_mul4 < next_mul4 - x {
+                    prev_mul4
+                } else {
+                    next_mul4
+                }
+            }
+        }
+    };
+
+    // Keep the depth of 2D images at 1.
+    let d = if depth > 1 { round(d) } else { d };
+    (round(w), round(h), d)
+}
+
+#[inline]
+fn sinc(x: f32) -> f32 {
+    if x.abs() < 1.0e-4 {
+        1.0 - x * x / 6.0
+    } else {
+        x.sin() / x
+    }
+}
+
+/// The zeroth order modified Bessel function of the first kind.
+fn bessel_i0(x: f32) -> f32 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    let mut k = 1.0;
+    while term > sum * 1.0e-8 {
+        term *= (x * x / 4.0) / (k * k);
+        sum += term;
+        k += 1.0;
+    }
+    sum
+}
+
+/// Map a normal component from the `-1..1` range into the `0..1` range.
+#[inline]
+fn pack(x: f32) -> f32 {
+    x * 0.5 + 0.5
+}
+
+/// Map a normal component from the `0..1` range into the `-1..1` range.
+#[inline]
+fn unpack(x: f32) -> f32 {
+    x * 2.0 - 1.0
+}
+
+#[inline]
+fn normalize3(v: [f32; 3]) -> [f32; 3] {
+    let len = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+    if len > 0.0 {
+        [v[0] / len, v[1] / len, v[2] / len]
+    } else {
+        [0.0, 0.0, 1.0]
+    }
+}
+
+/// Map a (possibly out of bounds) sample coordinate into the range `0..len`.
+#[inline]
+pub(crate) fn wrap_coord(i: isize, len: usize, wrap: WrapMode) -> usize {
+    let len = len as isize;
+    if len == 1 {
+        return 0;
+    }
+    let i = match wrap {
+        WrapMode::Clamp => i.max(0).min(len - 1),
+        WrapMode::Repeat => i.rem_euclid(len),
+        WrapMode::Mirror => {
+            let period = 2 * len - 2;
+            let i = i.rem_euclid(period);
+            if i >= len {
+                period - i
+            } else {
+                i
+            }
+        }
+    };
+    i as usize
+}
+
+/// Resample a single channel plane with the given `dims` along `axis` to `new_len` samples.
+fn resample_axis(
+    src: &[f32],
+    dims: [usize; 3],
+    axis: usize,
+    new_len: usize,
+    kernel: &Kernel,
+    wrap: WrapMode,
+) -> Vec<f32> {
+    let old_len = dims[axis];
+    let scale = old_len as f32 / new_len as f32;
+    let filter_scale = scale.max(1.0);
+    let support = kernel.width() * filter_scale;
+
+    // Precompute the weights for each output sample, as they are shared by every row.
+    let weights = (0..new_len)
+        .map(|i| {
+            let center = (i as f32 + 0.5) * scale - 0.5;
+            let first = (center - support).floor() as isize;
+            let last = (center + support).ceil() as isize;
+            let mut taps = (first..=last)
+                .map(|j| {
+                    let weight = kernel.evaluate((j as f32 - center) / filter_scale);
+                    (wrap_coord(j, old_len, wrap), weight)
+                })
+                .filter(|&(_, weight)| weight != 0.0)
+                .collect::<Vec<_>>();
+
+            let total = taps.iter().map(|&(_, w)| w).sum::<f32>();
+            if total != 0.0 {
+                taps.iter_mut().for_each(|(_, w)| *w /= total);
+            }
+            taps
+        })
+        .collect::<Vec<_>>();
+
+    let mut new_dims = dims;
+    new_dims[axis] = new_len;
+    let stride = match axis {
+        0 => 1,
+        1 => dims[0],
+        _ => dims[0] * dims[1],
+    };
+
+    // Each row of the output is independent, so rows can be filtered in parallel.
+    let filter_row = |row: usize, dst: &mut [f32]| {
+        let (y, z) = (row % new_dims[1], row / new_dims[1]);
+        for (x, value) in dst.iter_mut().enumerate() {
+            let pos = [x, y, z];
+            let mut src_base = pos;
+            src_base[axis] = 0;
+            let src_base = src_base[0] + src_base[1] * dims[0] + src_base[2] * dims[0] * dims[1];
+            *value = weights[pos[axis]]
+                .iter()
+                .map(|&(j, w)| src[src_base + j * stride] * w)
+                .sum();
+        }
+    };
+
+    let mut dst = vec![0.0; new_dims[0] * new_dims[1] * new_dims[2]];
+    cfg_if! {
+        if #[cfg(feature = "rayon")] {
+            use rayon::prelude::*;
+            dst.par_chunks_mut(new_dims[0])
+                .enumerate()
+                .for_each(|(row, dst)| filter_row(row, dst));
+        } else {
+            dst.chunks_mut(new_dims[0])
+                .enumerate()
+                .for_each(|(row, dst)| filter_row(row, dst));
+        }
+    }
+
+    dst
+}
+
+/// Convert a half precision float into an `f32`.
+pub(crate) fn f16_to_f32(half: u16) -> f32 {
+    let sign = u32::from(half >> 15) << 31;
+    let exponent = u32::from((half >> 10) & 0x1f);
+    let mantissa = u32::from(half & 0x3ff);
+
+    let bits = match (exponent, mantissa) {
+        (0, 0) => sign,
+        (0, _) => {
+            // Subnormal, so renormalize the value.
+            let shift = mantissa.leading_zeros() - 21;
+            let mantissa = (mantissa << shift) & 0x3ff;
+            sign | ((113 - shift) << 23) | (mantissa << 13)
+        }
+        (0x1f, _) => sign | 0x7f80_0000 | (mantissa << 13),
+        _ => sign | ((exponent + 112) << 23) | (mantissa << 13),
+    };
+    f32::from_bits(bits)
+}
+
+cfg_if! {
+    if #[cfg(nvtt_2_1)] {
+        use crate::PathConvertError;
+        use nvtt_sys::*;
+        use std::{
+            convert::TryFrom,
+            ffi::CString,
+            os::raw::{c_int, c_void},
+            ptr::NonNull,
+            slice,
+        };
+
+        /// Convert a path into a C string which can be passed to nvtt.
+        #[inline(always)]
+        fn to_c_filepath(path: &Path) -> Result<CString, PathConvertError> {
+            cfg_if! {
+                if #[cfg(target_family = "windows")] {
+                    match path.to_str() {
+                        Some(s) => {
+                            if !s.is_ascii() {
+                                return Err(PathConvertError::AsciiConvert)
+                            }
+                            CString::new(s.as_bytes()).map_err(From::from)
+                        }
+                        None => Err(PathConvertError::Utf8Convert),
+                    }
+                } else if #[cfg(target_family = "unix")] {
+                    use std::os::unix::ffi::OsStrExt;
+                    CString::new(path.as_os_str().as_bytes()).map_err(From::from)
+                } else {
+                    compile_error!("This platform is unsupported");
+                }
+            }
+        }
+
+        /// An owned `nvtt::Surface`, which is destroyed when it is dropped.
+        pub(crate) struct NativeSurface(NonNull<NvttSurface>);
+
+        impl NativeSurface {
+            fn new() -> Result<Self, Error> {
+                NonNull::new(unsafe { nvttCreateSurface() })
+                    .map(Self)
+                    .ok_or(Error::Unknown)
+            }
+
+            /// Copy the `surface` into a new `nvtt::Surface`, so that it can be
+            /// compressed with the nvtt context api.
+            pub(crate) fn from_surface(surface: &Surface) -> Result<Self, Error> {
+                let (width, height, depth) = (
+                    c_int::try_from(surface.width).map_err(|_| Error::InvalidInput)?,
+                    c_int::try_from(surface.height).map_err(|_| Error::InvalidInput)?,
+                    c_int::try_from(surface.depth).map_err(|_| Error::InvalidInput)?,
+                );
+
+                let native = Self::new()?;
+                let raw = native.as_ptr();
+                let (r, g, b, a) = surface.planes();
+
+                // @SAFETY: Each plane contains `width * height * depth` values, which
+                // nvtt copies into the new surface.
+                let set = unsafe {
+                    nvttSurfaceSetImageChannels(
+                        raw,
+                        NvttInputFormat_NVTT_InputFormat_RGBA_32F,
+                        width,
+                        height,
+                        depth,
+                        r.as_ptr() as *const c_void,
+                        g.as_ptr() as *const c_void,
+                        b.as_ptr() as *const c_void,
+                        a.as_ptr() as *const c_void,
+                    )
+                };
+                if set != true {
+                    return Err(Error::InvalidInput);
+                }
+
+                unsafe {
+                    nvttSurfaceSetWrapMode(raw, surface.wrap_mode.into());
+                    nvttSurfaceSetAlphaMode(raw, surface.alpha_mode.into());
+                    nvttSurfaceSetNormalMap(raw, surface.normal_map.into());
+                }
+
+                Ok(native)
+            }
+
+            /// Load the file at `path` with nvtt, and copy it into a `Surface`.
+            fn load(path: &Path) -> Result<Surface, Error> {
+                let c_path = to_c_filepath(path).map_err(|e| {
+                    log::error!(
+                        "Could not load surface {p}\nCaused by: {e}",
+                        p = path.display(),
+                        e = e
+                    );
+                    Error::FileOpen
+                })?;
+
+                let surface = Self::new()?;
+                let raw = surface.as_ptr();
+
+                if unsafe { nvttSurfaceLoad(raw, c_path.as_ptr()) } != true {
+                    log::error!("nvtt could not load surface {p}", p = path.display());
+                    return Err(Error::FileOpen);
+                }
+
+                let (width, height, depth) = unsafe {
+                    (
+                        nvttSurfaceWidth(raw) as usize,
+                        nvttSurfaceHeight(raw) as usize,
+                        nvttSurfaceDepth(raw) as usize,
+                    )
+                };
+                if width * height * depth == 0 {
+                    return Err(Error::FileOpen);
+                }
+
+                let mut loaded = Surface::with_depth(width, height, depth);
+                let count = loaded.pixel_count();
+                for (c, dst) in loaded.data.chunks_exact_mut(count).enumerate() {
+                    // @SAFETY: Each channel of an nvtt surface contains
+                    // `width * height * depth` values, and the surface is not modified
+                    // while the channel is borrowed.
+                    let src =
+                        unsafe { slice::from_raw_parts(nvttSurfaceChannel(raw, c as _), count) };
+                    dst.copy_from_slice(src);
+                }
+
+                unsafe {
+                    loaded.wrap_mode =
+                        WrapMode::try_from(nvttSurfaceWrapMode(raw)).unwrap_or(WrapMode::Mirror);
+                    loaded.alpha_mode =
+                        AlphaMode::try_from(nvttSurfaceAlphaMode(raw)).unwrap_or(AlphaMode::None);
+                    loaded.normal_map = nvttSurfaceIsNormalMap(raw) == true;
+                }
+
+                Ok(loaded)
+            }
+
+            #[inline]
+            pub(crate) fn as_ptr(&self) -> *mut NvttSurface {
+                self.0.as_ptr()
+            }
+        }
+
+        impl Drop for NativeSurface {
+            fn drop(&mut self) {
+                unsafe { nvttDestroySurface(self.0.as_ptr()) }
+            }
+        }
+    } else {
+        /// `nvtt::Surface` was added in nvtt 2.1, so a `NativeSurface` cannot be
+        /// created when this crate is built against an older nvtt.
+        pub(crate) enum NativeSurface {}
+
+        impl NativeSurface {
+            #[cfg(feature = "nvtt")]
+            pub(crate) fn from_surface(_surface: &Surface) -> Result<Self, Error> {
+                Err(Error::UnsupportedFeature)
+            }
+
+            fn load(path: &Path) -> Result<Surface, Error> {
+                log::error!(
+                    "Could not load surface {p}: loading requires nvtt 2.1",
+                    p = path.display()
+                );
+                Err(Error::UnsupportedFeature)
+            }
+        }
+    }
+}
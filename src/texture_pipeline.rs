@@ -0,0 +1,497 @@
+// Copyright © 2019-2020 George Burton
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::{
+    analysis, AlphaAnalysis, AlphaMode, CompressError, CompressionOutput, CompressionStage,
+    Container, Error, Format, MipmapFilter, NormalMapAnalysis, Quality, SpritePadding, SpriteRect,
+    Surface, WrapMode,
+};
+#[cfg(all(feature = "fallback-encoders", not(feature = "nvtt")))]
+use crate::{fallback::fallback_container, fallback_compress};
+#[cfg(feature = "preview")]
+use crate::{write_preview, PreviewOptions};
+#[cfg(feature = "nvtt")]
+use crate::{AlphaUsage, CompressionOptions, Context, OutputOptions};
+use cfg_if::cfg_if;
+use std::{
+    ffi::OsStr,
+    path::{Path, PathBuf},
+};
+
+/// The image which is compressed by a [`TexturePipeline`].
+///
+/// [`TexturePipeline`]: struct.TexturePipeline.html
+#[derive(Clone, Debug)]
+pub enum TextureSource {
+    /// Load the image from the file at the path with [`Surface::load`].
+    ///
+    /// [`Surface::load`]: struct.Surface.html#method.load
+    Path(PathBuf),
+    /// Compress the `Surface` directly.
+    Surface(Surface),
+}
+
+impl From<&'_ Path> for TextureSource {
+    #[inline]
+    fn from(path: &'_ Path) -> Self {
+        TextureSource::Path(path.to_path_buf())
+    }
+}
+
+impl From<PathBuf> for TextureSource {
+    #[inline]
+    fn from(path: PathBuf) -> Self {
+        TextureSource::Path(path)
+    }
+}
+
+impl From<&'_ str> for TextureSource {
+    #[inline]
+    fn from(path: &'_ str) -> Self {
+        TextureSource::Path(PathBuf::from(path))
+    }
+}
+
+impl From<Surface> for TextureSource {
+    #[inline]
+    fn from(surface: Surface) -> Self {
+        TextureSource::Surface(surface)
+    }
+}
+
+/// Compresses a single texture, bundling the input, compression and output settings
+/// together so that the [`InputOptions`], [`CompressionOptions`], [`OutputOptions`]
+/// and [`Compressor`] do not need to be set up separately.
+///
+/// ```no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use nvtt_rs::{Format, MipmapFilter, TexturePipeline};
+///
+/// TexturePipeline::new()
+///     .source("normals.png")
+///     .normal_map()
+///     .format(Format::Bc5)
+///     .mips(MipmapFilter::Kaiser(None))
+///     .to("normals.dds")?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`InputOptions`]: struct.InputOptions.html
+/// [`CompressionOptions`]: struct.CompressionOptions.html
+/// [`OutputOptions`]: struct.OutputOptions.html
+/// [`Compressor`]: struct.Compressor.html
+#[derive(Clone, Debug, Default)]
+pub struct TexturePipeline {
+    source: Option<TextureSource>,
+    format: Option<Format>,
+    quality: Quality,
+    container: Option<Container>,
+    mipmap_filter: Option<MipmapFilter>,
+    alpha_mode: Option<AlphaMode>,
+    wrap_mode: Option<WrapMode>,
+    sprites: Option<(Vec<SpriteRect>, SpritePadding)>,
+    edge_padding: usize,
+    solidify: bool,
+    #[cfg(feature = "preview")]
+    preview: Option<PreviewOptions>,
+    normal_map: bool,
+    srgb: bool,
+    cuda: bool,
+}
+
+impl TexturePipeline {
+    /// Create a new `TexturePipeline` with no source. By default, the texture is
+    /// compressed with `Quality::Normal` to the format chosen by
+    /// [`AlphaAnalysis::format`], or to `Format::Bc5` if it is a normal map, and no
+    /// mipmaps are generated.
+    ///
+    /// [`AlphaAnalysis::format`]: struct.AlphaAnalysis.html#method.format
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the image which is compressed. This can be a path, or a [`Surface`].
+    ///
+    /// [`Surface`]: struct.Surface.html
+    #[inline]
+    pub fn source<S: Into<TextureSource>>(&mut self, source: S) -> &mut Self {
+        self.source = Some(source.into());
+        self
+    }
+
+    /// Set the output format of the compressed texture. By default, `Format::Bc1` is
+    /// used for opaque images, `Format::Bc1a` for images with binary alpha, and
+    /// `Format::Bc3` otherwise. Images with binary alpha which are compressed to
+    /// `Format::Bc1a` use binary alpha quantization.
+    #[inline]
+    pub fn format(&mut self, format: Format) -> &mut Self {
+        self.format = Some(format);
+        self
+    }
+
+    /// Set the compression quality.
+    #[inline]
+    pub fn quality(&mut self, quality: Quality) -> &mut Self {
+        self.quality = quality;
+        self
+    }
+
+    /// Set the output container. By default, `Container::Ktx` is used for paths with
+    /// a `ktx` extension, `Container::Dds10` is used for `Format::Bc6` and
+    /// `Format::Bc7`, and `Container::Dds` is used otherwise.
+    #[inline]
+    pub fn container(&mut self, container: Container) -> &mut Self {
+        self.container = Some(container);
+        self
+    }
+
+    /// Generate the full mip chain of the texture using the given `filter`.
+    #[inline]
+    pub fn mips(&mut self, filter: MipmapFilter) -> &mut Self {
+        self.mipmap_filter = Some(filter);
+        self
+    }
+
+    /// Set the `AlphaMode` of the source image. By default, sources without an
+    /// `AlphaMode` use `AlphaMode::Transparency` if they are not opaque.
+    #[inline]
+    pub fn alpha_mode(&mut self, alpha_mode: AlphaMode) -> &mut Self {
+        self.alpha_mode = Some(alpha_mode);
+        self
+    }
+
+    /// Set the `WrapMode` used when filtering the source image.
+    #[inline]
+    pub fn wrap_mode(&mut self, wrap_mode: WrapMode) -> &mut Self {
+        self.wrap_mode = Some(wrap_mode);
+        self
+    }
+
+    /// Fill the color of the fully transparent pixels of the source image from their
+    /// opaque neighbours before it is compressed. See [`Surface::solidify`].
+    ///
+    /// [`Surface::solidify`]: struct.Surface.html#method.solidify
+    #[inline]
+    pub fn solidify(&mut self) -> &mut Self {
+        self.solidify = true;
+        self
+    }
+
+    /// Pad each edge of the source image by `pixels` before it is compressed, using
+    /// the `WrapMode` of the source image. See [`Surface::pad_edges`].
+    ///
+    /// [`Surface::pad_edges`]: struct.Surface.html#method.pad_edges
+    #[inline]
+    pub fn edge_padding(&mut self, pixels: usize) -> &mut Self {
+        self.edge_padding = pixels;
+        self
+    }
+
+    /// Treat the source image as a sprite sheet containing the sprites in `rects`,
+    /// and extrude each sprite by `padding` before the texture is compressed. See
+    /// [`Surface::extrude_sprites`].
+    ///
+    /// [`Surface::extrude_sprites`]: struct.Surface.html#method.extrude_sprites
+    #[inline]
+    pub fn sprites<R: Into<Vec<SpriteRect>>>(
+        &mut self,
+        rects: R,
+        padding: SpritePadding,
+    ) -> &mut Self {
+        self.sprites = Some((rects.into(), padding));
+        self
+    }
+
+    /// Mark the source image as a normal map.
+    ///
+    /// If no [`format`] is set, then normal maps, and sources which look like them, are
+    /// compressed to `Format::Bc5`. A warning is logged if they are compressed to a
+    /// color format or as srgb data. See [`NormalMapAnalysis`].
+    ///
+    /// [`format`]: struct.TexturePipeline.html#method.format
+    ///
+    /// [`NormalMapAnalysis`]: struct.NormalMapAnalysis.html
+    #[inline]
+    pub fn normal_map(&mut self) -> &mut Self {
+        self.normal_map = true;
+        self
+    }
+
+    /// Mark the compressed texture as containing srgb data.
+    #[inline]
+    pub fn srgb(&mut self) -> &mut Self {
+        self.srgb = true;
+        self
+    }
+
+    /// Set whether cuda acceleration is enabled. If cuda is not available, then the
+    /// texture is compressed on the cpu.
+    #[inline]
+    pub fn cuda(&mut self, enable: bool) -> &mut Self {
+        self.cuda = enable;
+        self
+    }
+
+    /// Write a png preview comparing the source image with the compressed texture
+    /// next to the output file, with a `preview.png` extension. The preview is only
+    /// written by [`TexturePipeline::to`], which fails if the compressed texture cannot
+    /// be decoded or the preview cannot be written. See [`write_preview`].
+    ///
+    /// # Notes
+    ///
+    /// This method requires the [`preview`] feature.
+    ///
+    /// [`TexturePipeline::to`]: struct.TexturePipeline.html#method.to
+    /// [`write_preview`]: fn.write_preview.html
+    /// [`preview`]: index.html#preview
+    #[cfg(feature = "preview")]
+    #[inline]
+    pub fn preview(&mut self, options: PreviewOptions) -> &mut Self {
+        self.preview = Some(options);
+        self
+    }
+
+    /// Compress the texture into the file at `path`.
+    ///
+    /// # Errors
+    ///
+    /// If no source has been set, or a sprite is not inside the source image, then
+    /// this method will fail with [`Error::InvalidInput`]. If the source file cannot be loaded, then this method
+    /// will fail with [`Error::FileOpen`]. Otherwise, see [`Compressor::compress`].
+    ///
+    /// [`Error::InvalidInput`]: enum.Error.html#variant.InvalidInput
+    /// [`Error::FileOpen`]: enum.Error.html#variant.FileOpen
+    /// [`Compressor::compress`]: struct.Compressor.html#method.compress
+    pub fn to<P: AsRef<Path>>(&self, path: P) -> Result<(), CompressError> {
+        let path = path.as_ref();
+        let is_ktx = path.extension() == Some(OsStr::new("ktx"));
+        self.run(Some(path), is_ktx).map(|_| ())
+    }
+
+    /// Compress the texture into memory, and return the bytes of the texture
+    /// including the container header.
+    ///
+    /// # Errors
+    ///
+    /// See [`TexturePipeline::to`].
+    ///
+    /// [`TexturePipeline::to`]: struct.TexturePipeline.html#method.to
+    pub fn to_memory(&self) -> Result<Vec<u8>, CompressError> {
+        match self.run(None, false)? {
+            CompressionOutput::Memory { data, .. } => Ok(data),
+            _ => Ok(vec![]),
+        }
+    }
+
+    fn run(&self, path: Option<&Path>, is_ktx: bool) -> Result<CompressionOutput, CompressError> {
+        let invalid = |error| {
+            CompressError::with_path(
+                error,
+                CompressionStage::InputValidation,
+                path.map(Path::to_path_buf),
+            )
+        };
+
+        let mut surface = match self.source {
+            Some(TextureSource::Path(ref source)) => Surface::load(source),
+            Some(TextureSource::Surface(ref surface)) => Ok(surface.clone()),
+            None => Err(Error::InvalidInput),
+        }
+        .map_err(invalid)?;
+        if let Some(alpha_mode) = self.alpha_mode {
+            surface.set_alpha_mode(alpha_mode);
+        }
+        if let Some(wrap_mode) = self.wrap_mode {
+            surface.set_wrap_mode(wrap_mode);
+        }
+        if self.normal_map {
+            surface.set_normal_map(true);
+        }
+        if let Some((ref rects, padding)) = self.sprites {
+            surface.extrude_sprites(rects, padding).map_err(invalid)?;
+        }
+        if self.solidify {
+            surface.solidify(0.0);
+        }
+        surface.pad_edges(self.edge_padding);
+
+        let alpha = AlphaAnalysis::analyze(&surface);
+        if self.alpha_mode.is_none() && surface.alpha_mode() == AlphaMode::None {
+            surface.set_alpha_mode(alpha.alpha_mode());
+        }
+
+        let format = match self.format {
+            Some(format) => format,
+            // The formats chosen for the alpha would compress the normals together.
+            None if surface.is_normal_map()
+                || NormalMapAnalysis::analyze(&surface).is_normal_map() =>
+            {
+                Format::Bc5
+            }
+            None => alpha.format(),
+        };
+        let name = path.map_or_else(|| "Texture".into(), |path| path.display().to_string());
+        analysis::warn_color_normal_map(&surface, format, self.srgb, &name);
+        let container = self.container.unwrap_or(match format {
+            _ if is_ktx => Container::Ktx,
+            Format::Bc6 | Format::Bc7 => Container::Dds10,
+            _ => Container::Dds,
+        });
+
+        cfg_if! {
+            if #[cfg(feature = "nvtt")] {
+                let output = self.run_nvtt(&surface, format, container, &alpha, path)?;
+            } else if #[cfg(feature = "fallback-encoders")] {
+                let output = self.run_fallback(&surface, format, container, path)?;
+            } else {
+                // Without nvtt, only the fallback encoders can compress the texture.
+                log::error!(
+                    "Cannot compress {n} to {f:?} in a {c:?} file without nvtt",
+                    n = name,
+                    f = format,
+                    c = container
+                );
+                let output: CompressionOutput = Err(invalid(Error::UnsupportedFeature))?;
+            }
+        }
+
+        #[cfg(feature = "preview")]
+        if let (Some(path), Some(ref options)) = (path, self.preview) {
+            let error = |error, stage| CompressError::with_path(error, stage, Some(path.into()));
+            let decoded = Surface::load(path).map_err(|e| error(e, CompressionStage::Decoding))?;
+            write_preview(
+                &surface,
+                &decoded,
+                path.with_extension("preview.png"),
+                options,
+            )
+            .map_err(|e| error(e, CompressionStage::Output))?;
+        }
+
+        Ok(output)
+    }
+
+    /// Compress the `surface` with nvtt.
+    #[cfg(feature = "nvtt")]
+    fn run_nvtt(
+        &self,
+        surface: &Surface,
+        format: Format,
+        container: Container,
+        alpha: &AlphaAnalysis,
+        path: Option<&Path>,
+    ) -> Result<CompressionOutput, CompressError> {
+        let invalid = |error| {
+            CompressError::with_path(
+                error,
+                CompressionStage::InputValidation,
+                path.map(Path::to_path_buf),
+            )
+        };
+
+        let mut output_options = OutputOptions::new().map_err(invalid)?;
+        if let Some(path) = path {
+            output_options
+                .set_output_location(path)
+                .map_err(|_| invalid(Error::FileOpen))?;
+        }
+        output_options
+            .set_container(container)
+            .set_srgb_flag(self.srgb);
+
+        let mut compression_options = CompressionOptions::new().map_err(invalid)?;
+        compression_options
+            .set_format(format)
+            .set_quality(self.quality);
+        if format == Format::Bc1a && alpha.usage == AlphaUsage::Binary {
+            compression_options
+                .set_quanitzation(false, false, true, 127)
+                .map_err(invalid)?;
+        }
+
+        let mut context = Context::new().map_err(invalid)?;
+        if self.cuda {
+            context.compressor_mut().enable_cuda_acceleration(true);
+        }
+
+        match self.mipmap_filter {
+            Some(filter) => context.compress_with_mipmaps(
+                surface,
+                filter,
+                &compression_options,
+                &output_options,
+            ),
+            None => context.compress_mipmaps(
+                std::slice::from_ref(surface),
+                &compression_options,
+                &output_options,
+            ),
+        }
+    }
+
+    /// Compress the `surface` with the fallback encoders, which are used when this
+    /// crate is built without nvtt.
+    #[cfg(all(feature = "fallback-encoders", not(feature = "nvtt")))]
+    fn run_fallback(
+        &self,
+        surface: &Surface,
+        format: Format,
+        container: Container,
+        path: Option<&Path>,
+    ) -> Result<CompressionOutput, CompressError> {
+        let error =
+            |error, stage| CompressError::with_path(error, stage, path.map(Path::to_path_buf));
+        if fallback_container(format) != Some(container) {
+            return Err(error(
+                Error::UnsupportedOutputFormat,
+                CompressionStage::InputValidation,
+            ));
+        }
+
+        let built;
+        let mipmaps = match self.mipmap_filter {
+            Some(filter) => {
+                built = surface.build_mipmaps(filter);
+                &built[..]
+            }
+            None => std::slice::from_ref(surface),
+        };
+        let data =
+            fallback_compress(mipmaps, format).map_err(|e| error(e, CompressionStage::Encoding))?;
+
+        match path {
+            Some(path) => {
+                std::fs::write(path, data)
+                    .map_err(|_| error(Error::FileWrite, CompressionStage::Output))?;
+                Ok(CompressionOutput::File)
+            }
+            None => Ok(CompressionOutput::Memory {
+                data,
+                width: surface.width(),
+                height: surface.height(),
+                depth: surface.depth(),
+                face: 0,
+                miplevel: 0,
+            }),
+        }
+    }
+}
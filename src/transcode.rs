@@ -0,0 +1,401 @@
+// Copyright © 2019-2020 George Burton
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::{
+    inspect::{header_len, inspect_bytes},
+    CompressError, CompressionOptions, CompressionOutput, CompressionStage, Compressor, Container,
+    CubeFace, Error, Extent3d, Format, InputFormat, InputOptions, MipLevel, OutputOptions, Surface,
+    TextureInfo, TextureLayout, TextureType,
+};
+use std::{convert::TryFrom, fs, path::Path};
+
+/// The block compressed formats which can be decoded by [`transcode`].
+///
+/// [`transcode`]: fn.transcode.html
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum BlockKind {
+    Bc1,
+    Bc2,
+    Bc3,
+    /// `Bc3` with the normal map swizzle of [`Surface::swizzle_dxt5n`] applied.
+    ///
+    /// [`Surface::swizzle_dxt5n`]: struct.Surface.html#method.swizzle_dxt5n
+    Bc3n,
+    Bc4,
+    Bc5,
+}
+
+impl BlockKind {
+    fn for_format(format: Format) -> Option<Self> {
+        match format {
+            Format::Bc1 | Format::Bc1a | Format::Dxt1 | Format::Dxt1a => Some(Self::Bc1),
+            Format::Bc2 | Format::Dxt3 => Some(Self::Bc2),
+            Format::Bc3 | Format::Dxt5 => Some(Self::Bc3),
+            Format::Bc3n | Format::Dxt5n => Some(Self::Bc3n),
+            Format::Bc4 => Some(Self::Bc4),
+            Format::Bc5 => Some(Self::Bc5),
+            _ => None,
+        }
+    }
+
+    /// The size of a `4x4` block in bytes.
+    fn block_size(self) -> usize {
+        match self {
+            Self::Bc1 | Self::Bc4 => 8,
+            Self::Bc2 | Self::Bc3 | Self::Bc3n | Self::Bc5 => 16,
+        }
+    }
+
+    /// The number of bytes in a mip level of the given `extent`.
+    fn level_size(self, extent: Extent3d) -> usize {
+        let blocks = |edge: u32| (edge as usize).div_ceil(4);
+        blocks(extent.width())
+            * blocks(extent.height())
+            * extent.depth() as usize
+            * self.block_size()
+    }
+
+    /// Decode the `block` into 16 texels in `rgba` order.
+    fn decode(self, block: &[u8]) -> [[u8; 4]; 16] {
+        match self {
+            Self::Bc1 => decode_color(block, true),
+            Self::Bc2 => {
+                let mut texels = decode_color(&block[8..], false);
+                let alpha = u64::from_le_bytes(read_block(block));
+                for (i, texel) in texels.iter_mut().enumerate() {
+                    texel[3] = ((alpha >> (4 * i)) & 0xF) as u8 * 17;
+                }
+                texels
+            }
+            Self::Bc3 => {
+                let mut texels = decode_color(&block[8..], false);
+                let alpha = decode_alpha(block);
+                for (texel, a) in texels.iter_mut().zip(alpha.iter()) {
+                    texel[3] = *a;
+                }
+                texels
+            }
+            Self::Bc3n => Self::Bc3.decode(block).map(|[_, y, _, x]| {
+                let unpack = |v: u8| f32::from(v) / 127.5 - 1.0;
+                let z = (1.0 - unpack(x).powi(2) - unpack(y).powi(2))
+                    .max(0.0)
+                    .sqrt();
+                [x, y, ((z + 1.0) * 127.5).round() as u8, 255]
+            }),
+            Self::Bc4 => decode_alpha(block).map(|r| [r, 0, 0, 255]),
+            Self::Bc5 => {
+                let red = decode_alpha(block);
+                let green = decode_alpha(&block[8..]);
+                let mut texels = [[0, 0, 0, 255]; 16];
+                for (i, texel) in texels.iter_mut().enumerate() {
+                    texel[0] = red[i];
+                    texel[1] = green[i];
+                }
+                texels
+            }
+        }
+    }
+}
+
+#[inline]
+fn read_block(bytes: &[u8]) -> [u8; 8] {
+    let mut block = [0; 8];
+    block.copy_from_slice(&bytes[..8]);
+    block
+}
+
+/// Decode a 565 color block. If `bc1` is `true`, then blocks whose first endpoint
+/// is not greater than the second use the 3 color mode with transparent black.
+fn decode_color(block: &[u8], bc1: bool) -> [[u8; 4]; 16] {
+    let expand = |c: u16| {
+        let (r, g, b) = ((c >> 11) & 0x1F, (c >> 5) & 0x3F, c & 0x1F);
+        [
+            ((r << 3) | (r >> 2)) as u32,
+            ((g << 2) | (g >> 4)) as u32,
+            ((b << 3) | (b >> 2)) as u32,
+        ]
+    };
+    let mix = |a: [u32; 3], b: [u32; 3], wa: u32, wb: u32| {
+        let c = |i: usize| ((a[i] * wa + b[i] * wb) / (wa + wb)) as u8;
+        [c(0), c(1), c(2), 255]
+    };
+
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let (e0, e1) = (expand(c0), expand(c1));
+    let palette = if !bc1 || c0 > c1 {
+        [
+            mix(e0, e1, 1, 0),
+            mix(e0, e1, 0, 1),
+            mix(e0, e1, 2, 1),
+            mix(e0, e1, 1, 2),
+        ]
+    } else {
+        [
+            mix(e0, e1, 1, 0),
+            mix(e0, e1, 0, 1),
+            mix(e0, e1, 1, 1),
+            [0; 4],
+        ]
+    };
+
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+    let mut texels = [[0; 4]; 16];
+    for (i, texel) in texels.iter_mut().enumerate() {
+        *texel = palette[((indices >> (2 * i)) & 0x3) as usize];
+    }
+    texels
+}
+
+/// Decode an interpolated 8 bit alpha block, as used by `Bc3`, `Bc4` and `Bc5`.
+fn decode_alpha(block: &[u8]) -> [u8; 16] {
+    let (a0, a1) = (u32::from(block[0]), u32::from(block[1]));
+    let mut palette = [0u8; 8];
+    palette[0] = a0 as u8;
+    palette[1] = a1 as u8;
+    if a0 > a1 {
+        for i in 1..7 {
+            palette[i + 1] = (((7 - i as u32) * a0 + i as u32 * a1) / 7) as u8;
+        }
+    } else {
+        for i in 1..5 {
+            palette[i + 1] = (((5 - i as u32) * a0 + i as u32 * a1) / 5) as u8;
+        }
+        palette[7] = 255;
+    }
+
+    let indices = u64::from_le_bytes(read_block(block)) >> 16;
+    let mut alpha = [0; 16];
+    for (i, a) in alpha.iter_mut().enumerate() {
+        *a = palette[((indices >> (3 * i)) & 0x7) as usize];
+    }
+    alpha
+}
+
+/// Decode a mip level of `extent` from `data` into `bgra` texels, which can be passed
+/// to [`InputOptions::set_mipmap_data`].
+///
+/// [`InputOptions::set_mipmap_data`]: struct.InputOptions.html#method.set_mipmap_data
+fn decode_level(kind: BlockKind, data: &[u8], extent: Extent3d) -> Vec<u8> {
+    let (width, height) = (extent.width() as usize, extent.height() as usize);
+    let (blocks_x, blocks_y) = (width.div_ceil(4), height.div_ceil(4));
+    let mut out = vec![0; extent.pixel_count() * 4];
+
+    for (index, block) in data.chunks_exact(kind.block_size()).enumerate() {
+        let slice = index / (blocks_x * blocks_y);
+        let (bx, by) = (index % blocks_x, (index / blocks_x) % blocks_y);
+        for (i, [r, g, b, a]) in kind.decode(block).iter().copied().enumerate() {
+            let (x, y) = (bx * 4 + i % 4, by * 4 + i / 4);
+            if x < width && y < height {
+                let offset = ((slice * height + y) * width + x) * 4;
+                out[offset..offset + 4].copy_from_slice(&[b, g, r, a]);
+            }
+        }
+    }
+
+    out
+}
+
+/// Decode an image of `width` by `height` pixels which was compressed with `format`
+/// into a `Surface`. Returns `None` if the format cannot be decoded, or `data` is not
+/// the size of the image.
+pub(crate) fn decode_surface(
+    format: Format,
+    data: &[u8],
+    width: usize,
+    height: usize,
+) -> Option<Surface> {
+    let kind = BlockKind::for_format(format)?;
+    let extent = Extent3d::d2(u32::try_from(width).ok()?, u32::try_from(height).ok()?)?;
+    if data.len() != kind.level_size(extent) {
+        return None;
+    }
+
+    let rgba: Vec<f32> = decode_level(kind, data, extent)
+        .chunks_exact(4)
+        .flat_map(|bgra| [bgra[2], bgra[1], bgra[0], bgra[3]])
+        .map(|value| f32::from(value) / 255.0)
+        .collect();
+    Surface::from_rgba(width, height, &rgba).ok()
+}
+
+/// Get the `TextureLayout` of the texture described by `info`.
+fn texture_layout(info: &TextureInfo) -> Result<TextureLayout, Error> {
+    let (width, height) = (info.width, info.height);
+    match info.texture_type {
+        TextureType::D2 => Ok(TextureLayout::d2(width, height)),
+        TextureType::D3 => Ok(TextureLayout::d3(width, height, info.depth)),
+        TextureType::Array => Ok(TextureLayout::array(width, height, info.array_length)),
+        TextureType::Cube if info.array_length == 1 => Ok(TextureLayout::cube(width, height)),
+        TextureType::Cube if info.container == Container::Dds10 => {
+            Ok(TextureLayout::cube_array(width, height, info.array_length))
+        }
+        _ => Err(Error::UnsupportedFeature),
+    }
+}
+
+/// Decode every face and mip level of the dds or ktx file `data` into `InputOptions`
+/// with the same layout.
+fn decode_texture(data: &[u8], info: &TextureInfo) -> Result<InputOptions, Error> {
+    let kind = info
+        .format
+        .and_then(BlockKind::for_format)
+        .ok_or(Error::UnsupportedFeature)?;
+    let layout = texture_layout(info)?;
+    let extent = layout.extent().ok_or(Error::InvalidInput)?;
+    let faces = match layout {
+        TextureLayout::Cube { .. } => CubeFace::ALL.to_vec(),
+        TextureLayout::Array { array_length, .. } => {
+            (0..array_length as u32).map(CubeFace::ArrayLayer).collect()
+        }
+        _ => vec![CubeFace::default()],
+    };
+    let mip_count = (info.mip_count as u32).min(layout.mip_count());
+
+    let mut input_options = InputOptions::new()?;
+    input_options
+        .set_texture_layout(layout)?
+        .set_format(InputFormat::Bgra8Ub)
+        .set_mipmap_generation(mip_count > 1, Some(mip_count - 1));
+
+    let mut offset = header_len(data, info).ok_or(Error::InvalidInput)?;
+    let read_level = |offset: &mut usize, mipmap: MipLevel| {
+        let size = kind.level_size(extent.mip_level(mipmap));
+        let level = data
+            .get(*offset..*offset + size)
+            .ok_or(Error::InvalidInput)?;
+        *offset += size;
+        Ok::<_, Error>(level)
+    };
+
+    // dds files store every mip level of each face in turn, and ktx files store every
+    // face of each mip level in turn, with the size of the level before it.
+    let mut levels = Vec::with_capacity(faces.len() * mip_count as usize);
+    if info.container == Container::Ktx {
+        for mipmap in (0..mip_count).map(MipLevel) {
+            offset += 4;
+            for &face in &faces {
+                levels.push((face, mipmap, read_level(&mut offset, mipmap)?));
+            }
+        }
+    } else {
+        for &face in &faces {
+            for mipmap in (0..mip_count).map(MipLevel) {
+                levels.push((face, mipmap, read_level(&mut offset, mipmap)?));
+            }
+        }
+    }
+
+    for (face, mipmap, level) in levels {
+        let mip_extent = extent.mip_level(mipmap);
+        let texels = decode_level(kind, level, mip_extent);
+        input_options.set_mipmap_data(&texels, mip_extent, face, mipmap)?;
+    }
+
+    Ok(input_options)
+}
+
+/// Recompress the existing dds or ktx texture at `path` to `target_format`.
+///
+/// Every face and mipmap level of the texture is decoded, and then encoded again
+/// with the same [`TextureLayout`], so the mipmaps are not regenerated. The container
+/// and sRGB flag of the texture are set on the `output_options`, and the output is
+/// written wherever the `output_options` direct it.
+///
+/// ```no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use nvtt_rs::{Format, OutputOptions};
+///
+/// let mut output_options = OutputOptions::new()?;
+/// output_options.set_output_location("albedo_bc7.dds")?;
+/// nvtt_rs::transcode("albedo_bc1.dds", Format::Bc7, &mut output_options)?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// If the file cannot be read, then this function will fail with [`Error::FileOpen`].
+/// See [`transcode_bytes`] for the other errors.
+///
+/// [`TextureLayout`]: enum.TextureLayout.html
+/// [`Error::FileOpen`]: enum.Error.html#variant.FileOpen
+/// [`transcode_bytes`]: fn.transcode_bytes.html
+pub fn transcode<P: AsRef<Path>>(
+    path: P,
+    target_format: Format,
+    output_options: &mut OutputOptions,
+) -> Result<CompressionOutput, CompressError> {
+    let path = path.as_ref();
+    let data = fs::read(path).map_err(|e| {
+        log::error!(
+            "Could not read texture {p}\nCaused by: {e}",
+            p = path.display(),
+            e = e
+        );
+        CompressError::with_path(
+            Error::FileOpen,
+            CompressionStage::Decoding,
+            Some(path.to_path_buf()),
+        )
+    })?;
+
+    transcode_bytes(&data, target_format, output_options)
+}
+
+/// Recompress the contents of an existing dds or ktx file to `target_format`. See
+/// [`transcode`] for more information.
+///
+/// # Errors
+///
+/// Only textures in the `Bc1` to `Bc5` formats, including `Bc3n`, can be decoded.
+/// If the texture is in another format, or is an array of cube maps in a ktx file,
+/// then this function will fail with [`Error::UnsupportedFeature`] in the
+/// [`CompressionStage::Decoding`] stage. If the data is not a dds or ktx file, or is
+/// too short for its header, then it will fail with [`Error::InvalidInput`].
+///
+/// [`transcode`]: fn.transcode.html
+/// [`Error::UnsupportedFeature`]: enum.Error.html#variant.UnsupportedFeature
+/// [`CompressionStage::Decoding`]: enum.CompressionStage.html#variant.Decoding
+/// [`Error::InvalidInput`]: enum.Error.html#variant.InvalidInput
+pub fn transcode_bytes(
+    data: &[u8],
+    target_format: Format,
+    output_options: &mut OutputOptions,
+) -> Result<CompressionOutput, CompressError> {
+    let decoding = |error| CompressError::new(error, CompressionStage::Decoding, output_options);
+    let info = inspect_bytes(data).map_err(decoding)?;
+    for diagnostic in &info.diagnostics {
+        log::warn!("Reading malformed dds texture: {}", diagnostic);
+    }
+    let input_options = decode_texture(data, &info).map_err(decoding)?;
+
+    let mut compression_options = CompressionOptions::new()
+        .map_err(|e| CompressError::new(e, CompressionStage::InputValidation, output_options))?;
+    compression_options.set_format(target_format);
+    output_options
+        .set_container(info.container)
+        .set_srgb_flag(info.srgb)
+        .set_cube_array(info.texture_type == TextureType::Cube && info.array_length > 1);
+
+    Compressor::new()
+        .map_err(|e| CompressError::new(e, CompressionStage::InputValidation, output_options))?
+        .compress(&compression_options, &input_options, output_options)
+}
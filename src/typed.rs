@@ -0,0 +1,251 @@
+// Copyright © 2019-2020 George Burton
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+use crate::{
+    AnyDestination, CompressError, CompressionOptions, Compressor, Container, Destination, Error,
+    Format, InputOptions, OutputOptions,
+};
+use std::marker::PhantomData;
+
+/// Marker types for the output formats which can be used with the typed outputs,
+/// such as [`DdsOutput`].
+///
+/// [`DdsOutput`]: ../struct.DdsOutput.html
+pub mod formats {
+    macro_rules! decl_formats {
+        ($($(#[$attr:meta])* $name:ident => $format:ident: [$($container:ident),*]),* $(,)?) => {
+            $(
+                $(#[$attr])*
+                #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+                pub enum $name {}
+
+                impl super::sealed::Sealed for $name {}
+
+                impl super::TypedFormat for $name {
+                    const FORMAT: crate::Format = crate::Format::$format;
+                }
+
+                $(
+                    impl super::$container for $name {}
+                )*
+            )*
+        };
+    }
+
+    decl_formats! {
+        /// Uncompressed rgb pixels.
+        Rgb => Rgb: [DdsFormat, Dds10Format, KtxFormat],
+        /// Uncompressed rgba pixels.
+        Rgba => Rgba: [DdsFormat, Dds10Format, KtxFormat],
+        /// The `Bc1` block compressed format.
+        Bc1 => Bc1: [DdsFormat, Dds10Format, KtxFormat],
+        /// The `Bc1` block compressed format, with 1 bit alpha.
+        Bc1a => Bc1a: [DdsFormat, Dds10Format, KtxFormat],
+        /// The `Bc2` block compressed format.
+        Bc2 => Bc2: [DdsFormat, Dds10Format, KtxFormat],
+        /// The `Bc3` block compressed format.
+        Bc3 => Bc3: [DdsFormat, Dds10Format, KtxFormat],
+        /// The `Bc3` block compressed format, swizzled for normal maps.
+        Bc3n => Bc3n: [DdsFormat],
+        /// The `Bc4` block compressed format.
+        Bc4 => Bc4: [DdsFormat, Dds10Format, KtxFormat],
+        /// The `Bc5` block compressed format.
+        Bc5 => Bc5: [DdsFormat, Dds10Format, KtxFormat],
+        /// The `Bc6h` block compressed format.
+        Bc6 => Bc6: [Dds10Format, KtxFormat],
+        /// The `Bc7` block compressed format.
+        Bc7 => Bc7: [Dds10Format, KtxFormat],
+        /// The `Etc1` block compressed format.
+        Etc1 => Etc1: [KtxFormat],
+        /// The `Etc2` block compressed format, with a single red channel.
+        Etc2R => Etc2R: [KtxFormat],
+        /// The `Etc2` block compressed format, with red and green channels.
+        Etc2Rg => Etc2Rg: [KtxFormat],
+        /// The `Etc2` block compressed format, with rgb channels.
+        Etc2Rgb => Etc2Rgb: [KtxFormat],
+        /// The `Etc2` block compressed format, with rgb channels and 1 bit alpha.
+        Etc2RgbA1 => Etc2RgbA1: [KtxFormat],
+        /// The `Etc2` block compressed format, with rgba channels.
+        Etc2Rgba => Etc2Rgba: [KtxFormat],
+    }
+}
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// An output [`Format`] which is known at compile time. This trait is sealed, and is
+/// implemented by the types in the [`formats`] module.
+///
+/// [`Format`]: enum.Format.html
+/// [`formats`]: formats/index.html
+pub trait TypedFormat: sealed::Sealed {
+    /// The format which is written.
+    const FORMAT: Format;
+}
+
+/// A [`TypedFormat`] which can be stored in a `Container::Dds` file.
+///
+/// [`TypedFormat`]: trait.TypedFormat.html
+pub trait DdsFormat: TypedFormat {}
+
+/// A [`TypedFormat`] which can be stored in a `Container::Dds10` file.
+///
+/// [`TypedFormat`]: trait.TypedFormat.html
+pub trait Dds10Format: TypedFormat {}
+
+/// A [`TypedFormat`] which can be stored in a `Container::Ktx` file.
+///
+/// [`TypedFormat`]: trait.TypedFormat.html
+pub trait KtxFormat: TypedFormat {}
+
+macro_rules! decl_typed_output {
+    ($($(#[$attr:meta])* $name:ident: $bound:ident => $container:ident),* $(,)?) => {
+        $(
+            $(#[$attr])*
+            #[derive(Debug)]
+            pub struct $name<F, D = AnyDestination> {
+                compression_options: CompressionOptions,
+                output_options: OutputOptions<D>,
+                format: PhantomData<fn() -> F>,
+            }
+
+            impl<F: $bound> $name<F> {
+                #[doc = concat!("Create a new `", stringify!($name), "` with the default options.")]
+                pub fn new() -> Result<Self, Error> {
+                    Ok(Self::from_options(CompressionOptions::new()?, OutputOptions::new()?))
+                }
+            }
+
+            impl<F: $bound, D> $name<F, D> {
+                #[doc = concat!("Create a new `", stringify!($name), "` from existing options. ")]
+                /// The format and container of the options are replaced.
+                pub fn from_options(
+                    mut compression_options: CompressionOptions,
+                    mut output_options: OutputOptions<D>,
+                ) -> Self {
+                    compression_options.set_format(F::FORMAT);
+                    output_options.set_container(Container::$container);
+                    Self {
+                        compression_options,
+                        output_options,
+                        format: PhantomData,
+                    }
+                }
+
+                /// Get the compression options.
+                #[inline]
+                pub fn compression_options(&self) -> &CompressionOptions {
+                    &self.compression_options
+                }
+
+                /// Get the compression options mutably. The format is restored before
+                /// compressing if it is changed.
+                #[inline]
+                pub fn compression_options_mut(&mut self) -> &mut CompressionOptions {
+                    &mut self.compression_options
+                }
+
+                /// Get the output options.
+                #[inline]
+                pub fn output_options(&self) -> &OutputOptions<D> {
+                    &self.output_options
+                }
+
+                /// Get the output options mutably. The container is restored before
+                /// compressing if it is changed.
+                #[inline]
+                pub fn output_options_mut(&mut self) -> &mut OutputOptions<D> {
+                    &mut self.output_options
+                }
+
+                /// Returns the compression and output options.
+                #[inline]
+                pub fn into_parts(self) -> (CompressionOptions, OutputOptions<D>) {
+                    (self.compression_options, self.output_options)
+                }
+
+                /// Compress the `input_options` with the `compressor`. See
+                /// [`Compressor::compress`].
+                ///
+                /// [`Compressor::compress`]: struct.Compressor.html#method.compress
+                pub fn compress(
+                    &mut self,
+                    compressor: &Compressor,
+                    input_options: &InputOptions,
+                ) -> Result<D::Output, CompressError>
+                where
+                    D: Destination,
+                {
+                    if self.compression_options.format() != F::FORMAT {
+                        self.compression_options.set_format(F::FORMAT);
+                    }
+                    if self.output_options.container() != Container::$container {
+                        self.output_options.set_container(Container::$container);
+                    }
+                    compressor.compress(&self.compression_options, input_options, &self.output_options)
+                }
+            }
+        )*
+    };
+}
+
+decl_typed_output! {
+    /// Options which compress a texture into a `Container::Dds` file, where the format
+    /// is checked to be supported by the container at compile time.
+    ///
+    /// The [`CompressionOptions`] and [`OutputOptions`] can be used to choose
+    /// between formats and containers at runtime instead.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use nvtt_rs::{formats::Bc3, Compressor, DdsOutput, InputOptions, OutputOptions};
+    ///
+    /// let compressor = Compressor::new()?;
+    /// let input_options = InputOptions::new()?;
+    /// let mut output = DdsOutput::<Bc3, _>::from_options(
+    ///     Default::default(),
+    ///     OutputOptions::new()?.into_file("OutFile.dds")?,
+    /// );
+    ///
+    /// output.compress(&compressor, &input_options)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`CompressionOptions`]: struct.CompressionOptions.html
+    /// [`OutputOptions`]: struct.OutputOptions.html
+    DdsOutput: DdsFormat => Dds,
+    /// Options which compress a texture into a `Container::Dds10` file, where the format
+    /// is checked to be supported by the container at compile time.
+    ///
+    /// See [`DdsOutput`] for more information.
+    ///
+    /// [`DdsOutput`]: struct.DdsOutput.html
+    Dds10Output: Dds10Format => Dds10,
+    /// Options which compress a texture into a `Container::Ktx` file, where the format
+    /// is checked to be supported by the container at compile time.
+    ///
+    /// See [`DdsOutput`] for more information.
+    ///
+    /// [`DdsOutput`]: struct.DdsOutput.html
+    KtxOutput: KtxFormat => Ktx,
+}
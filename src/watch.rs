@@ -0,0 +1,233 @@
+// Copyright © 2019-2020 George Burton
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::{Batch, BatchError, BatchResult};
+use notify::{Event, EventKind, RecursiveMode, Watcher as _};
+use std::{
+    collections::HashSet, error::Error as ErrorTrait, fmt, ops::ControlFlow, path::PathBuf,
+    sync::mpsc, time::Duration,
+};
+
+/// Watches the inputs of a [`Batch`], and recompresses each file when it changes.
+///
+/// # Example
+///
+/// ```no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use nvtt_rs::{Batch, Format, Watcher};
+/// use std::ops::ControlFlow;
+///
+/// let mut batch = Batch::new();
+/// batch
+///     .add_glob("textures/**/*.png")
+///     .output_dir("cooked/")
+///     .cache_dir("cooked/.cache")
+///     .format(Format::Bc7);
+///
+/// Watcher::new(batch).run(|result| {
+///     match result.result() {
+///         Ok(()) => println!("Compressed {}", result.input().display()),
+///         Err(e) => eprintln!("{}", e),
+///     }
+///     ControlFlow::Continue(())
+/// })?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Notes
+///
+/// This type requires the [`watch`] feature.
+///
+/// [`Batch`]: struct.Batch.html
+/// [`watch`]: index.html#watch
+#[derive(Clone, Debug)]
+pub struct Watcher {
+    batch: Batch,
+    debounce: Duration,
+    initial_run: bool,
+}
+
+impl Watcher {
+    /// Create a new `Watcher` which compresses the files matched by `batch` with its
+    /// settings.
+    #[inline]
+    pub fn new(batch: Batch) -> Self {
+        Self {
+            batch,
+            debounce: Duration::from_millis(100),
+            initial_run: true,
+        }
+    }
+
+    /// Set how long to wait for further changes after a file changes, before it is
+    /// recompressed. Editors often write a file in several steps, so this avoids
+    /// compressing partially written images. The default is 100 milliseconds.
+    #[inline]
+    pub fn debounce(&mut self, debounce: Duration) -> &mut Self {
+        self.debounce = debounce;
+        self
+    }
+
+    /// Set whether every file in the batch is compressed when the watcher starts. This
+    /// is enabled by default. With a [`Batch::cache_dir`], files which have not
+    /// changed since they were last compressed are taken from the cache.
+    ///
+    /// [`Batch::cache_dir`]: struct.Batch.html#method.cache_dir
+    #[inline]
+    pub fn initial_run(&mut self, initial_run: bool) -> &mut Self {
+        self.initial_run = initial_run;
+        self
+    }
+
+    /// Watch the inputs of the batch, calling `callback` with the result each time a
+    /// file is compressed. This blocks the current thread until `callback` returns
+    /// `ControlFlow::Break`.
+    ///
+    /// Files which are added after the watcher starts are compressed if they match a
+    /// glob of the batch.
+    ///
+    /// # Errors
+    ///
+    /// If any glob pattern is invalid, or the file system cannot be watched, then this
+    /// method will fail.
+    pub fn run<F>(&self, mut callback: F) -> Result<(), WatchError>
+    where
+        F: FnMut(&BatchResult) -> ControlFlow<()>,
+    {
+        let (sender, receiver) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender)?;
+        for (dir, recursive) in self.batch.input_dirs() {
+            let mode = if recursive {
+                RecursiveMode::Recursive
+            } else {
+                RecursiveMode::NonRecursive
+            };
+            watcher.watch(&dir, mode)?;
+        }
+
+        if self.initial_run {
+            for result in self.batch.run()? {
+                if callback(&result).is_break() {
+                    return Ok(());
+                }
+            }
+        }
+
+        let worker = self.batch.worker();
+        loop {
+            let changed = match self.wait_for_changes(&receiver)? {
+                Some(changed) => changed,
+                None => return Ok(()),
+            };
+
+            for (input, output) in self.batch.jobs()? {
+                let input_path = input.canonicalize().unwrap_or_else(|_| input.clone());
+                if !changed.contains(&input_path) {
+                    continue;
+                }
+
+                let result = self.batch.run_job(&worker, &input, &output);
+                if callback(&result).is_break() {
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /// Block until a file is created or modified, and collect every change which
+    /// happens until no change has been seen for the debounce duration. Returns
+    /// `None` if the watcher has stopped.
+    fn wait_for_changes(
+        &self,
+        receiver: &mpsc::Receiver<notify::Result<Event>>,
+    ) -> Result<Option<HashSet<PathBuf>>, WatchError> {
+        let mut changed = HashSet::new();
+        let mut add_event = |event: notify::Result<Event>| -> Result<(), WatchError> {
+            let event = event?;
+            if let EventKind::Create(_) | EventKind::Modify(_) = event.kind {
+                changed.extend(
+                    event
+                        .paths
+                        .into_iter()
+                        .map(|path| path.canonicalize().unwrap_or(path)),
+                );
+            }
+            Ok(())
+        };
+
+        match receiver.recv() {
+            Ok(event) => add_event(event)?,
+            Err(_) => return Ok(None),
+        }
+        loop {
+            match receiver.recv_timeout(self.debounce) {
+                Ok(event) => add_event(event)?,
+                Err(mpsc::RecvTimeoutError::Timeout) => return Ok(Some(changed)),
+                Err(mpsc::RecvTimeoutError::Disconnected) => return Ok(None),
+            }
+        }
+    }
+}
+
+/// An error which stops a [`Watcher`].
+///
+/// [`Watcher`]: struct.Watcher.html
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum WatchError {
+    /// The jobs of the batch could not be listed.
+    Batch(BatchError),
+    /// The file system could not be watched.
+    Notify(notify::Error),
+}
+
+impl fmt::Display for WatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            WatchError::Batch(ref e) => fmt::Display::fmt(e, f),
+            WatchError::Notify(ref e) => write!(f, "Could not watch the inputs: {}", e),
+        }
+    }
+}
+
+impl ErrorTrait for WatchError {
+    #[inline]
+    fn source(&self) -> Option<&(dyn ErrorTrait + 'static)> {
+        match *self {
+            WatchError::Batch(ref e) => Some(e),
+            WatchError::Notify(ref e) => Some(e),
+        }
+    }
+}
+
+impl From<BatchError> for WatchError {
+    #[inline]
+    fn from(e: BatchError) -> Self {
+        WatchError::Batch(e)
+    }
+}
+
+impl From<notify::Error> for WatchError {
+    #[inline]
+    fn from(e: notify::Error) -> Self {
+        WatchError::Notify(e)
+    }
+}
@@ -0,0 +1,274 @@
+// Copyright © 2019-2020 George Burton
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::{
+    inspect::{header_len, inspect_bytes},
+    Container, Error, Format, TextureInfo, TextureType,
+};
+use wgpu::{
+    Extent3d, Origin3d, Queue, TexelCopyBufferLayout, TexelCopyTextureInfo, Texture, TextureAspect,
+    TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
+};
+
+impl Format {
+    /// Get the `wgpu::TextureFormat` which can sample textures of this format, or
+    /// `None` if wgpu does not support the format.
+    ///
+    /// If `srgb` is `true`, then the sRGB variant of the format is returned, if one
+    /// exists. Uncompressed textures are written by nvtt in BGRA order, so
+    /// `Format::Rgb` and `Format::Rgba` map to `TextureFormat::Bgra8Unorm`.
+    ///
+    /// # Notes
+    ///
+    /// This method requires the [`wgpu`] feature.
+    ///
+    /// [`wgpu`]: index.html#wgpu
+    pub fn to_wgpu(self, srgb: bool) -> Option<TextureFormat> {
+        let (linear, srgb_format) = match self {
+            Format::Rgb | Format::Rgba => {
+                (TextureFormat::Bgra8Unorm, TextureFormat::Bgra8UnormSrgb)
+            }
+            Format::Bc1 | Format::Bc1a | Format::Dxt1 | Format::Dxt1a | Format::Dxt1n => {
+                (TextureFormat::Bc1RgbaUnorm, TextureFormat::Bc1RgbaUnormSrgb)
+            }
+            Format::Bc2 | Format::Dxt3 => {
+                (TextureFormat::Bc2RgbaUnorm, TextureFormat::Bc2RgbaUnormSrgb)
+            }
+            Format::Bc3 | Format::Bc3n | Format::Dxt5 | Format::Dxt5n => {
+                (TextureFormat::Bc3RgbaUnorm, TextureFormat::Bc3RgbaUnormSrgb)
+            }
+            Format::Bc4 => return Some(TextureFormat::Bc4RUnorm),
+            Format::Bc5 => return Some(TextureFormat::Bc5RgUnorm),
+            Format::Bc6 => return Some(TextureFormat::Bc6hRgbUfloat),
+            Format::Bc7 => (TextureFormat::Bc7RgbaUnorm, TextureFormat::Bc7RgbaUnormSrgb),
+            Format::Etc1 | Format::Etc2Rgb => (
+                TextureFormat::Etc2Rgb8Unorm,
+                TextureFormat::Etc2Rgb8UnormSrgb,
+            ),
+            Format::Etc2RgbA1 => (
+                TextureFormat::Etc2Rgb8A1Unorm,
+                TextureFormat::Etc2Rgb8A1UnormSrgb,
+            ),
+            Format::Etc2Rgba => (
+                TextureFormat::Etc2Rgba8Unorm,
+                TextureFormat::Etc2Rgba8UnormSrgb,
+            ),
+            Format::Etc2R => return Some(TextureFormat::EacR11Unorm),
+            Format::Etc2Rg => return Some(TextureFormat::EacRg11Unorm),
+            _ => return None,
+        };
+
+        Some(if srgb { srgb_format } else { linear })
+    }
+}
+
+/// The layout of the texture in a dds or ktx file.
+struct WgpuTexture {
+    info: TextureInfo,
+    format: TextureFormat,
+    data_offset: usize,
+}
+
+impl WgpuTexture {
+    fn new(data: &[u8]) -> Result<Self, Error> {
+        let info = inspect_bytes(data)?;
+        let format = info
+            .format
+            .and_then(|format| format.to_wgpu(info.srgb))
+            .ok_or(Error::UnsupportedOutputFormat)?;
+        let data_offset = header_len(data, &info).ok_or(Error::InvalidInput)?;
+
+        Ok(Self {
+            info,
+            format,
+            data_offset,
+        })
+    }
+
+    fn dimension(&self) -> TextureDimension {
+        match self.info.texture_type {
+            TextureType::D3 => TextureDimension::D3,
+            _ => TextureDimension::D2,
+        }
+    }
+
+    /// The number of 2d layers in the texture. Each face of a cube map is a layer.
+    fn layers(&self) -> usize {
+        match self.info.texture_type {
+            TextureType::D3 => 1,
+            TextureType::Cube => 6 * self.info.array_length,
+            _ => self.info.array_length,
+        }
+    }
+
+    fn size(&self) -> Extent3d {
+        let depth_or_array_layers = match self.info.texture_type {
+            TextureType::D3 => self.info.depth,
+            _ => self.layers(),
+        };
+
+        Extent3d {
+            width: self.info.width as u32,
+            height: self.info.height as u32,
+            depth_or_array_layers: depth_or_array_layers as u32,
+        }
+    }
+
+    /// Get the physical size of a single layer of `mip_level`, and its row pitch and
+    /// number of rows of blocks.
+    fn mip_layout(&self, mip_level: u32) -> (Extent3d, u32, u32) {
+        let mut size = self.size().mip_level_size(mip_level, self.dimension());
+        if self.dimension() == TextureDimension::D2 {
+            size.depth_or_array_layers = 1;
+        }
+        let size = size.physical_size(self.format);
+
+        let (block_width, block_height) = self.format.block_dimensions();
+        let block_size = self.format.block_copy_size(None).unwrap_or(0);
+        let bytes_per_row = (size.width / block_width) * block_size;
+        let rows = size.height / block_height;
+        (size, bytes_per_row, rows)
+    }
+}
+
+/// Create a `wgpu::TextureDescriptor` for the dds or ktx texture `data`, such as the
+/// data of a `CompressionOutput::Memory` which was written with a header.
+///
+/// The texture can be sampled, and copied into with [`write_wgpu_texture`].
+///
+/// # Errors
+///
+/// If the header of the texture cannot be read, then this function will fail with
+/// [`Error::InvalidInput`]. If wgpu does not support the format of the texture, then
+/// this function will fail with [`Error::UnsupportedOutputFormat`].
+///
+/// # Notes
+///
+/// This function requires the [`wgpu`] feature.
+///
+/// [`write_wgpu_texture`]: fn.write_wgpu_texture.html
+/// [`Error::InvalidInput`]: enum.Error.html#variant.InvalidInput
+/// [`Error::UnsupportedOutputFormat`]: enum.Error.html#variant.UnsupportedOutputFormat
+/// [`wgpu`]: index.html#wgpu
+pub fn wgpu_texture_descriptor<'a>(
+    data: &[u8],
+    label: Option<&'a str>,
+) -> Result<TextureDescriptor<'a>, Error> {
+    let texture = WgpuTexture::new(data)?;
+    Ok(TextureDescriptor {
+        label,
+        size: texture.size(),
+        mip_level_count: texture.info.mip_count as u32,
+        sample_count: 1,
+        dimension: texture.dimension(),
+        format: texture.format,
+        usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+        view_formats: &[],
+    })
+}
+
+/// Upload every layer and mipmap level of the dds or ktx texture `data` into
+/// `texture`, using `Queue::write_texture`. The `texture` should be created from
+/// the descriptor returned by [`wgpu_texture_descriptor`].
+///
+/// # Example
+///
+/// ```no_run
+/// # fn upload(device: &wgpu::Device, queue: &wgpu::Queue, output: nvtt_rs::CompressionOutput) -> Result<(), nvtt_rs::Error> {
+/// use nvtt_rs::CompressionOutput;
+///
+/// if let CompressionOutput::Memory { data, .. } = output {
+///     let descriptor = nvtt_rs::wgpu_texture_descriptor(&data, Some("albedo"))?;
+///     let texture = device.create_texture(&descriptor);
+///     nvtt_rs::write_wgpu_texture(queue, &texture, &data)?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// If the header of the texture cannot be read, or `data` is too short to contain
+/// every image, then this function will fail with [`Error::InvalidInput`]. If wgpu
+/// does not support the format of the texture, then this function will fail with
+/// [`Error::UnsupportedOutputFormat`].
+///
+/// # Notes
+///
+/// This function requires the [`wgpu`] feature.
+///
+/// [`wgpu_texture_descriptor`]: fn.wgpu_texture_descriptor.html
+/// [`Error::InvalidInput`]: enum.Error.html#variant.InvalidInput
+/// [`Error::UnsupportedOutputFormat`]: enum.Error.html#variant.UnsupportedOutputFormat
+/// [`wgpu`]: index.html#wgpu
+pub fn write_wgpu_texture(queue: &Queue, texture: &Texture, data: &[u8]) -> Result<(), Error> {
+    let layout = WgpuTexture::new(data)?;
+    let mip_count = layout.info.mip_count as u32;
+    let layers = layout.layers() as u32;
+
+    let write = |layer: u32, mip_level: u32, offset: usize| -> Result<usize, Error> {
+        let (size, bytes_per_row, rows) = layout.mip_layout(mip_level);
+        let len = (bytes_per_row * rows * size.depth_or_array_layers) as usize;
+        let image = data.get(offset..offset + len).ok_or(Error::InvalidInput)?;
+
+        queue.write_texture(
+            TexelCopyTextureInfo {
+                texture,
+                mip_level,
+                origin: Origin3d {
+                    x: 0,
+                    y: 0,
+                    z: layer,
+                },
+                aspect: TextureAspect::All,
+            },
+            image,
+            TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(bytes_per_row),
+                rows_per_image: Some(rows),
+            },
+            size,
+        );
+        Ok(len)
+    };
+
+    let mut offset = layout.data_offset;
+    if layout.info.container == Container::Ktx {
+        // Ktx files store every layer of a mipmap level together, after the size of
+        // the level. Each image is padded to 4 bytes.
+        for mip_level in 0..mip_count {
+            offset += 4;
+            for layer in 0..layers {
+                let len = write(layer, mip_level, offset)?;
+                offset += (len + 3) & !3;
+            }
+        }
+    } else {
+        // Dds files store the full mipmap chain of each layer together.
+        for layer in 0..layers {
+            for mip_level in 0..mip_count {
+                offset += write(layer, mip_level, offset)?;
+            }
+        }
+    }
+
+    Ok(())
+}